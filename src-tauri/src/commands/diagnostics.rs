@@ -0,0 +1,41 @@
+use crate::diagnostics::{DiagnosticCounts, DiagnosticsFilter, DiagnosticsStore, FileDiagnostics};
+use lsp_types::{Diagnostic, Url};
+use std::path::Path;
+use tauri::{State, command};
+
+#[command]
+pub fn diagnostics_query(
+   store: State<'_, DiagnosticsStore>,
+   filter: Option<DiagnosticsFilter>,
+) -> Vec<FileDiagnostics> {
+   store.query(&filter.unwrap_or_default())
+}
+
+#[command]
+pub fn diagnostics_counts(
+   store: State<'_, DiagnosticsStore>,
+   filter: Option<DiagnosticsFilter>,
+) -> DiagnosticCounts {
+   store.counts(&filter.unwrap_or_default())
+}
+
+/// Report diagnostics from a source outside the language servers, such as a linter or a task's
+/// output. They replace what `source` reported for the file before.
+#[command]
+pub fn diagnostics_set(
+   store: State<'_, DiagnosticsStore>,
+   source: String,
+   file_path: String,
+   diagnostics: Vec<Diagnostic>,
+) -> Result<(), String> {
+   let uri =
+      Url::from_file_path(&file_path).map_err(|_| format!("Invalid file path: {}", file_path))?;
+   store.set(uri, &source, diagnostics);
+   Ok(())
+}
+
+/// Drop what `source` reported, everywhere or below `path`.
+#[command]
+pub fn diagnostics_clear(store: State<'_, DiagnosticsStore>, source: String, path: Option<String>) {
+   store.clear(&source, path.as_deref().map(Path::new));
+}
@@ -1,8 +1,14 @@
+use crate::{
+   file_finder::{FileFinderManager, FileFinderResults},
+   frecency::{FrecencyHistory, FrecencyKind, FrecencyStore, now_secs},
+};
 use nucleo_matcher::{
    Config, Matcher, Utf32Str,
    pattern::{Atom, AtomKind, CaseMatching, Normalization},
 };
 use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
+use tauri::State;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FuzzyMatchItem {
@@ -17,20 +23,171 @@ pub struct FuzzyMatchRequest {
    pub items: Vec<String>,
    pub case_sensitive: Option<bool>,
    pub normalize: Option<bool>,
+   /// Blend in the workspace's selection history for items of this kind.
+   pub frecency: Option<FrecencyScope>,
+   pub scoring_profile: Option<ScoringProfile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FrecencyScope {
+   pub workspace_path: String,
+   pub kind: FrecencyKind,
+}
+
+/// Boost applied to completions of some kinds when the cursor is in a given context.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextMultiplier {
+   pub kinds: Vec<i32>,
+   /// Multiplier for the kind boost of completions in `kinds`.
+   pub matched: f64,
+   /// Multiplier for the kind boost of every other completion.
+   pub other: f64,
+}
+
+/// Weights added to the nucleo score when ranking. Requests may override any field; missing
+/// fields keep their defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScoringProfile {
+   /// Boost per LSP `CompletionItemKind`.
+   pub kind_boosts: HashMap<i32, i64>,
+   /// Multipliers for `kind_boosts`, keyed by completion context type.
+   pub context_multipliers: HashMap<String, ContextMultiplier>,
+   /// Boost for completions whose `sort_text` is at most `short_sort_text_len` long, which
+   /// servers use for their preferred items.
+   pub short_sort_text_boost: i64,
+   pub short_sort_text_len: usize,
+   /// Scale of the frecency bonus, `frecency_weight * ln(1 + frecency)`.
+   pub frecency_weight: f64,
+}
+
+impl Default for ScoringProfile {
+   fn default() -> Self {
+      let kind_boosts = HashMap::from([
+         (5, 100), // Field
+         (6, 150), // Variable
+         (3, 200), // Function
+         (4, 250), // Constructor
+         (8, 300), // Interface
+         (7, 300), // Class
+      ]);
+
+      let context_multipliers = HashMap::from([
+         (
+            // Boost methods and fields for member access
+            "member".to_string(),
+            ContextMultiplier {
+               kinds: vec![3, 5],
+               matched: 2.0,
+               other: 1.0,
+            },
+         ),
+         (
+            // Boost classes, interfaces and modules for type context
+            "type".to_string(),
+            ContextMultiplier {
+               kinds: vec![7, 8, 9],
+               matched: 2.0,
+               other: 0.5,
+            },
+         ),
+         (
+            // Boost modules for import context
+            "import".to_string(),
+            ContextMultiplier {
+               kinds: vec![9],
+               matched: 2.0,
+               other: 1.0,
+            },
+         ),
+      ]);
+
+      Self {
+         kind_boosts,
+         context_multipliers,
+         short_sort_text_boost: 50,
+         short_sort_text_len: 3,
+         frecency_weight: 100.0,
+      }
+   }
+}
+
+impl ScoringProfile {
+   fn kind_boost(&self, kind: i32, context_type: Option<&str>) -> i64 {
+      let base_boost = self.kind_boosts.get(&kind).copied().unwrap_or(0);
+      let multiplier = context_type
+         .and_then(|context| self.context_multipliers.get(context))
+         .map_or(1.0, |context| {
+            if context.kinds.contains(&kind) {
+               context.matched
+            } else {
+               context.other
+            }
+         });
+      (base_boost as f64 * multiplier) as i64
+   }
+
+   fn frecency_bonus(&self, frecency: f64) -> i64 {
+      (self.frecency_weight * frecency.ln_1p()) as i64
+   }
+}
+
+/// Selection history used to rank one request.
+struct FrecencyContext {
+   history: Arc<std::sync::Mutex<FrecencyHistory>>,
+   kind: FrecencyKind,
+   now: u64,
+}
+
+impl FrecencyContext {
+   fn new(store: &FrecencyStore, workspace: &str, kind: FrecencyKind) -> Self {
+      Self {
+         history: store.history(workspace),
+         kind,
+         now: now_secs(),
+      }
+   }
+
+   fn score(&self, key: &str) -> f64 {
+      self.history.lock().unwrap().score(self.kind, key, self.now)
+   }
 }
 
 #[tauri::command]
-pub fn fuzzy_match(request: FuzzyMatchRequest) -> Vec<FuzzyMatchItem> {
+pub fn fuzzy_match(
+   request: FuzzyMatchRequest,
+   frecency: State<'_, Arc<FrecencyStore>>,
+) -> Vec<FuzzyMatchItem> {
+   let context = request
+      .frecency
+      .as_ref()
+      .map(|scope| FrecencyContext::new(&frecency, &scope.workspace_path, scope.kind));
+   rank_items(request, context.as_ref())
+}
+
+fn rank_items(
+   request: FuzzyMatchRequest,
+   frecency: Option<&FrecencyContext>,
+) -> Vec<FuzzyMatchItem> {
+   let profile = request.scoring_profile.unwrap_or_default();
+   let frecency_bonus =
+      |text: &str| frecency.map_or(0, |frecency| profile.frecency_bonus(frecency.score(text)));
+
    if request.pattern.is_empty() || request.items.is_empty() {
-      return request
+      let mut items: Vec<FuzzyMatchItem> = request
          .items
          .into_iter()
          .map(|text| FuzzyMatchItem {
+            score: frecency_bonus(&text),
             text,
-            score: 0,
             indices: vec![],
          })
          .collect();
+      // Without a pattern, recently picked items come first and the rest keep their order
+      if frecency.is_some() {
+         items.sort_by_key(|item| std::cmp::Reverse(item.score));
+      }
+      return items;
    }
 
    let case_matching = if request.case_sensitive.unwrap_or(false) {
@@ -63,8 +220,8 @@ pub fn fuzzy_match(request: FuzzyMatchRequest) -> Vec<FuzzyMatchItem> {
 
       if let Some(score) = atom.indices(utf32_str, &mut matcher, &mut indices) {
          matches.push(FuzzyMatchItem {
+            score: score as i64 + frecency_bonus(&item),
             text: item,
-            score: score as i64,
             indices,
          });
       }
@@ -82,6 +239,9 @@ pub struct CompletionFilterRequest {
    pub completions: Vec<CompletionItem>,
    pub context_word: String,
    pub context_type: Option<String>,
+   /// Blend in how often completions with the same label were picked in this workspace.
+   pub workspace_path: Option<String>,
+   pub scoring_profile: Option<ScoringProfile>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -103,7 +263,21 @@ pub struct FilteredCompletion {
 }
 
 #[tauri::command]
-pub fn filter_completions(request: CompletionFilterRequest) -> Vec<FilteredCompletion> {
+pub fn filter_completions(
+   request: CompletionFilterRequest,
+   frecency: State<'_, Arc<FrecencyStore>>,
+) -> Vec<FilteredCompletion> {
+   let context = request
+      .workspace_path
+      .as_deref()
+      .map(|workspace| FrecencyContext::new(&frecency, workspace, FrecencyKind::Completion));
+   rank_completions(request, context.as_ref())
+}
+
+fn rank_completions(
+   request: CompletionFilterRequest,
+   frecency: Option<&FrecencyContext>,
+) -> Vec<FilteredCompletion> {
    if request.context_word.is_empty() {
       // Return all completions if no context word
       return request
@@ -117,6 +291,7 @@ pub fn filter_completions(request: CompletionFilterRequest) -> Vec<FilteredCompl
          .collect();
    }
 
+   let profile = request.scoring_profile.unwrap_or_default();
    let atom = Atom::new(
       &request.context_word,
       CaseMatching::Smart,
@@ -142,45 +317,21 @@ pub fn filter_completions(request: CompletionFilterRequest) -> Vec<FilteredCompl
 
          // Boost score based on completion kind and context
          if let Some(kind) = completion.kind {
-            // Base score based on kind
-            let base_boost = match kind {
-               5 => 100, // Field
-               6 => 150, // Variable
-               3 => 200, // Function
-               4 => 250, // Constructor
-               8 => 300, // Interface
-               7 => 300, // Class
-               _ => 0,
-            };
-
-            // Apply context-specific boosts
-            let context_multiplier = match request.context_type.as_deref() {
-               Some("member") => match kind {
-                  3 | 5 => 2.0, // Boost methods and fields for member access
-                  _ => 1.0,
-               },
-               Some("type") => match kind {
-                  7..=9 => 2.0, // Boost classes, interfaces, enums for type context
-                  _ => 0.5,
-               },
-               Some("import") => match kind {
-                  9 => 2.0, // Boost modules for import context
-                  _ => 1.0,
-               },
-               _ => 1.0,
-            };
-
-            final_score += (base_boost as f64 * context_multiplier) as i64;
+            final_score += profile.kind_boost(kind, request.context_type.as_deref());
          }
 
          // Consider sort_text if available
          if let Some(sort_text) = &completion.sort_text {
             // Lower sort_text should get higher score
-            if sort_text.len() <= 3 {
-               final_score += 50;
+            if sort_text.len() <= profile.short_sort_text_len {
+               final_score += profile.short_sort_text_boost;
             }
          }
 
+         if let Some(frecency) = frecency {
+            final_score += profile.frecency_bonus(frecency.score(&completion.label));
+         }
+
          filtered.push(FilteredCompletion {
             item: completion,
             score: final_score,
@@ -197,3 +348,144 @@ pub fn filter_completions(request: CompletionFilterRequest) -> Vec<FilteredCompl
 
    filtered
 }
+
+/// Remember that the user picked `key`, a workspace-relative file path or a completion label.
+#[tauri::command]
+pub fn frecency_record(
+   workspace_path: String,
+   kind: FrecencyKind,
+   key: String,
+   frecency: State<'_, Arc<FrecencyStore>>,
+) -> Result<(), String> {
+   frecency
+      .record(&workspace_path, kind, &key)
+      .map_err(|e| e.to_string())
+}
+
+/// Start enumerating a workspace for the file finder. Returns the session id to query.
+#[tauri::command]
+pub fn file_finder_open(
+   root_path: String,
+   file_finder: State<'_, Arc<FileFinderManager>>,
+) -> Result<String, String> {
+   file_finder.open(&root_path).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn file_finder_query(
+   session_id: String,
+   query: String,
+   limit: Option<usize>,
+   file_finder: State<'_, Arc<FileFinderManager>>,
+) -> Result<FileFinderResults, String> {
+   file_finder
+      .query(&session_id, &query, limit)
+      .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn file_finder_close(session_id: String, file_finder: State<'_, Arc<FileFinderManager>>) {
+   file_finder.close(&session_id);
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use std::sync::Mutex;
+
+   fn context(kind: FrecencyKind, picks: &[&str]) -> FrecencyContext {
+      let mut history = FrecencyHistory::default();
+      for key in picks {
+         history.record(kind, key, 0);
+      }
+      FrecencyContext {
+         history: Arc::new(Mutex::new(history)),
+         kind,
+         now: 0,
+      }
+   }
+
+   fn completion(label: &str, kind: i32) -> CompletionItem {
+      CompletionItem {
+         label: label.to_string(),
+         kind: Some(kind),
+         detail: None,
+         documentation: None,
+         sort_text: None,
+         filter_text: None,
+         insert_text: None,
+      }
+   }
+
+   fn completion_request(word: &str, completions: Vec<CompletionItem>) -> CompletionFilterRequest {
+      CompletionFilterRequest {
+         pattern: word.to_string(),
+         completions,
+         context_word: word.to_string(),
+         context_type: None,
+         workspace_path: None,
+         scoring_profile: None,
+      }
+   }
+
+   fn labels(filtered: &[FilteredCompletion]) -> Vec<&str> {
+      filtered.iter().map(|f| f.item.label.as_str()).collect()
+   }
+
+   #[test]
+   fn test_frecency_promotes_picked_files() {
+      let request = || FuzzyMatchRequest {
+         pattern: "main".to_string(),
+         items: vec!["src/main.rs".to_string(), "examples/main.rs".to_string()],
+         case_sensitive: None,
+         normalize: None,
+         frecency: None,
+         scoring_profile: None,
+      };
+
+      let ranked = rank_items(request(), None);
+      assert_eq!(ranked[0].text, "src/main.rs");
+
+      let picked = context(
+         FrecencyKind::File,
+         &["examples/main.rs", "examples/main.rs"],
+      );
+      let ranked = rank_items(request(), Some(&picked));
+      assert_eq!(ranked[0].text, "examples/main.rs");
+   }
+
+   #[test]
+   fn test_kind_boosts_follow_the_profile() {
+      let completions = vec![completion("value", 6), completion("valueOf", 3)];
+
+      let ranked = rank_completions(completion_request("val", completions.clone()), None);
+      assert_eq!(labels(&ranked), vec!["valueOf", "value"]);
+
+      let mut request = completion_request("val", completions);
+      request.scoring_profile = Some(ScoringProfile {
+         kind_boosts: HashMap::from([(6, 500)]),
+         ..Default::default()
+      });
+      assert_eq!(
+         labels(&rank_completions(request, None)),
+         vec!["value", "valueOf"]
+      );
+   }
+
+   #[test]
+   fn test_frecency_promotes_picked_completions() {
+      let completions = vec![completion("useMemo", 3), completion("useState", 3)];
+      let picked = context(FrecencyKind::Completion, &["useState"]);
+
+      let ranked = rank_completions(completion_request("use", completions), Some(&picked));
+      assert_eq!(labels(&ranked)[0], "useState");
+   }
+
+   #[test]
+   fn test_partial_profile_keeps_defaults() {
+      let profile: ScoringProfile = serde_json::from_str(r#"{"frecency_weight": 10}"#).unwrap();
+      assert_eq!(profile.frecency_weight, 10.0);
+      assert_eq!(profile.kind_boost(3, Some("member")), 400);
+      assert_eq!(profile.kind_boost(6, Some("type")), 75);
+   }
+}
@@ -1,16 +1,53 @@
-use crate::commands::git::{GitCommit, IntoStringError};
-use anyhow::{Context, Result};
+use crate::commands::git::{GitCommit, GitHooks, IntoStringError};
+use anyhow::{Context, Result, bail};
 use git2::{Repository, Sort};
-use tauri::command;
+use std::fs;
+use tauri::{AppHandle, command};
 
-#[command]
-pub fn git_commit(repo_path: String, message: String) -> Result<(), String> {
-   _git_commit(repo_path, message).into_string_error()
+/// Commit the index, running the repository's hooks like `git commit`. With `no_verify` the
+/// `pre-commit` and `commit-msg` hooks are skipped, as with `git commit --no-verify`.
+#[command(async)]
+pub fn git_commit(
+   app: AppHandle,
+   repo_path: String,
+   message: String,
+   no_verify: Option<bool>,
+) -> Result<(), String> {
+   _git_commit(repo_path, message, no_verify.unwrap_or(false), Some(app)).into_string_error()
 }
 
-fn _git_commit(repo_path: String, message: String) -> Result<()> {
+fn _git_commit(
+   repo_path: String,
+   message: String,
+   no_verify: bool,
+   app: Option<AppHandle>,
+) -> Result<()> {
    let repo = Repository::open(&repo_path).context("Failed to open repository")?;
+   let hooks = GitHooks::new(&repo, &repo_path, app)?;
+
+   if !no_verify {
+      hooks.run::<&str>("pre-commit", &[])?;
+   }
+
+   // Message hooks edit the message in place, like they do on the command line
+   let message_file = repo.path().join("COMMIT_EDITMSG");
+   fs::write(&message_file, &message).context("Failed to write commit message")?;
+   hooks.run(
+      "prepare-commit-msg",
+      &[message_file.as_os_str(), "message".as_ref()],
+   )?;
+   if !no_verify {
+      hooks.run("commit-msg", &[&message_file])?;
+   }
+   let message = fs::read_to_string(&message_file).context("Failed to read commit message")?;
+   let message = git2::message_prettify(message, None).context("Failed to clean up message")?;
+   if message.is_empty() {
+      bail!("Aborting commit due to empty commit message");
+   }
+
+   // Read after `pre-commit`, which may have staged changes of its own
    let mut index = repo.index().context("Failed to get index")?;
+   index.read(false).context("Failed to read index")?;
 
    let tree_id = index.write_tree().context("Failed to write tree")?;
    let tree = repo.find_tree(tree_id).context("Failed to find tree")?;
@@ -24,6 +61,11 @@ fn _git_commit(repo_path: String, message: String) -> Result<()> {
       .commit(Some("HEAD"), &sig, &sig, &message, &tree, &[&parent_commit])
       .context("Failed to create commit")?;
 
+   // The commit is made; like git, a failing `post-commit` does not undo it
+   if let Err(e) = hooks.run::<&str>("post-commit", &[]) {
+      log::warn!("{:#}", e);
+   }
+
    Ok(())
 }
 
@@ -68,3 +110,74 @@ fn _git_log(repo_path: String, limit: Option<u32>, skip: Option<u32>) -> Result<
 
    Ok(commits)
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+   use super::*;
+   use std::os::unix::fs::PermissionsExt;
+
+   fn install_hook(dir: &std::path::Path, name: &str, script: &str) {
+      let path = dir.join(name);
+      fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+      fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+   }
+
+   #[test]
+   fn test_commit_runs_hooks_from_hooks_path() {
+      let dir = tempfile::tempdir().unwrap();
+      let repo = Repository::init(dir.path()).unwrap();
+      let mut config = repo.config().unwrap();
+      config.set_str("user.name", "Test").unwrap();
+      config.set_str("user.email", "test@example.com").unwrap();
+      config.set_str("core.hooksPath", ".husky").unwrap();
+
+      let sig = repo.signature().unwrap();
+      let tree = repo
+         .find_tree(repo.index().unwrap().write_tree().unwrap())
+         .unwrap();
+      repo
+         .commit(Some("HEAD"), &sig, &sig, "Initial", &tree, &[])
+         .unwrap();
+
+      let hooks_dir = dir.path().join(".husky");
+      fs::create_dir(&hooks_dir).unwrap();
+      // `pre-commit` stages a file, `commit-msg` enforces a prefix, `prepare-commit-msg` adds a
+      // trailer and `post-commit` failing is ignored
+      install_hook(
+         &hooks_dir,
+         "pre-commit",
+         "echo linted > lint.txt && git add lint.txt",
+      );
+      install_hook(
+         &hooks_dir,
+         "commit-msg",
+         "grep -q '^feat:' \"$1\" || { echo 'message must start with feat:' >&2; exit 1; }",
+      );
+      install_hook(
+         &hooks_dir,
+         "prepare-commit-msg",
+         "[ \"$2\" = message ] && printf '\\n\\nHooked: yes' >> \"$1\"",
+      );
+      install_hook(&hooks_dir, "post-commit", "exit 3");
+      let repo_path = dir.path().to_string_lossy().to_string();
+
+      let error = _git_commit(repo_path.clone(), "fix: things".into(), false, None).unwrap_err();
+      let error = format!("{:#}", error);
+      assert!(
+         error.contains("commit-msg hook failed with exit code 1"),
+         "{}",
+         error
+      );
+      assert!(error.contains("message must start with feat:"), "{}", error);
+
+      _git_commit(repo_path.clone(), "feat: things".into(), false, None).unwrap();
+      let head = repo.head().unwrap().peel_to_commit().unwrap();
+      assert_eq!(head.message(), Some("feat: things\n\nHooked: yes\n"));
+      assert!(head.tree().unwrap().get_name("lint.txt").is_some());
+
+      // `--no-verify` skips the checks but not the message preparation
+      _git_commit(repo_path, "chore: skip".into(), true, None).unwrap();
+      let head = repo.head().unwrap().peel_to_commit().unwrap();
+      assert_eq!(head.message(), Some("chore: skip\n\nHooked: yes\n"));
+   }
+}
@@ -0,0 +1,193 @@
+use crate::commands::git::{GitHookEvent, GitHookStream};
+use anyhow::{Context, Result, bail};
+use git2::Repository;
+use std::{
+   ffi::OsStr,
+   fs,
+   io::{BufRead, BufReader, Read},
+   path::{Path, PathBuf},
+   process::{Command, Stdio},
+   sync::{Arc, Mutex},
+   thread,
+};
+use tauri::{AppHandle, Emitter};
+
+/// The hooks of a repository, run the way `git` runs them so tools that install into
+/// `core.hooksPath`, like husky and lefthook, behave as they do on the command line. Progress is
+/// sent as `git://hook` events.
+pub struct GitHooks {
+   dir: PathBuf,
+   work_dir: PathBuf,
+   index_file: PathBuf,
+   repo_path: String,
+   app: Option<AppHandle>,
+}
+
+impl GitHooks {
+   pub fn new(repo: &Repository, repo_path: &str, app: Option<AppHandle>) -> Result<Self> {
+      let work_dir = repo
+         .workdir()
+         .context("Hooks need a working directory")?
+         .to_path_buf();
+      let configured = repo
+         .config()
+         .context("Failed to read git config")?
+         .get_path("core.hooksPath")
+         .ok();
+      // Relative paths are relative to where hooks run, the top of the working directory
+      let dir = match configured {
+         Some(path) => work_dir.join(path),
+         None => common_dir(repo).join("hooks"),
+      };
+
+      Ok(Self {
+         dir,
+         index_file: repo.path().join("index"),
+         work_dir,
+         repo_path: repo_path.to_string(),
+         app,
+      })
+   }
+
+   /// Run a hook if it is installed. Fails with the hook's output when it exits unsuccessfully.
+   pub fn run<S: AsRef<OsStr>>(&self, hook: &str, args: &[S]) -> Result<()> {
+      let path = self.dir.join(hook);
+      if !path.is_file() {
+         return Ok(());
+      }
+      if !is_executable(&path) {
+         log::warn!(
+            "The '{}' hook was ignored because it is not set as executable",
+            hook
+         );
+         return Ok(());
+      }
+
+      self.emit(GitHookEvent::Started {
+         repo_path: self.repo_path.clone(),
+         hook: hook.to_string(),
+      });
+      let mut child = hook_command(&path)
+         .args(args)
+         .current_dir(&self.work_dir)
+         .env("GIT_INDEX_FILE", &self.index_file)
+         .env("GIT_EDITOR", ":")
+         .stdin(Stdio::null())
+         .stdout(Stdio::piped())
+         .stderr(Stdio::piped())
+         .spawn()
+         .with_context(|| format!("Failed to run the {} hook", hook))?;
+
+      // Both streams in the order lines arrived, for the error message
+      let output = Arc::new(Mutex::new(Vec::new()));
+      let readers = [
+         child
+            .stdout
+            .take()
+            .map(|stdout| self.forward(hook, GitHookStream::Stdout, stdout, output.clone())),
+         child
+            .stderr
+            .take()
+            .map(|stderr| self.forward(hook, GitHookStream::Stderr, stderr, output.clone())),
+      ];
+      let status = child.wait();
+      for reader in readers.into_iter().flatten() {
+         let _ = reader.join();
+      }
+      let status = status.with_context(|| format!("Failed to wait for the {} hook", hook))?;
+
+      self.emit(GitHookEvent::Finished {
+         repo_path: self.repo_path.clone(),
+         hook: hook.to_string(),
+         success: status.success(),
+         exit_code: status.code(),
+      });
+      if !status.success() {
+         let output = output.lock().unwrap().join("\n");
+         match status.code() {
+            Some(code) => bail!(
+               "The {} hook failed with exit code {}\n{}",
+               hook,
+               code,
+               output
+            ),
+            None => bail!("The {} hook was terminated\n{}", hook, output),
+         }
+      }
+      Ok(())
+   }
+
+   /// Send each line of a hook's output as an event while collecting it.
+   fn forward(
+      &self,
+      hook: &str,
+      stream: GitHookStream,
+      source: impl Read + Send + 'static,
+      output: Arc<Mutex<Vec<String>>>,
+   ) -> thread::JoinHandle<()> {
+      let app = self.app.clone();
+      let repo_path = self.repo_path.clone();
+      let hook = hook.to_string();
+      thread::spawn(move || {
+         let mut reader = BufReader::new(source);
+         let mut buffer = Vec::new();
+         while reader.read_until(b'\n', &mut buffer).unwrap_or(0) > 0 {
+            let line = String::from_utf8_lossy(&buffer)
+               .trim_end_matches(['\r', '\n'])
+               .to_string();
+            buffer.clear();
+            if let Some(app) = &app {
+               let _ = app.emit(
+                  "git://hook",
+                  GitHookEvent::Output {
+                     repo_path: repo_path.clone(),
+                     hook: hook.clone(),
+                     stream,
+                     line: line.clone(),
+                  },
+               );
+            }
+            output.lock().unwrap().push(line);
+         }
+      })
+   }
+
+   fn emit(&self, event: GitHookEvent) {
+      if let Some(app) = &self.app {
+         let _ = app.emit("git://hook", event);
+      }
+   }
+}
+
+/// The directory shared by all worktrees of a repository, where hooks live by default.
+fn common_dir(repo: &Repository) -> PathBuf {
+   let git_dir = repo.path();
+   match fs::read_to_string(git_dir.join("commondir")) {
+      Ok(common) => git_dir.join(common.trim()),
+      Err(_) => git_dir.to_path_buf(),
+   }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+   use std::os::unix::fs::PermissionsExt;
+   fs::metadata(path).is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+   true
+}
+
+#[cfg(unix)]
+fn hook_command(path: &Path) -> Command {
+   Command::new(path)
+}
+
+/// Hooks are shell scripts, which Windows cannot run directly; Git for Windows provides `sh`.
+#[cfg(not(unix))]
+fn hook_command(path: &Path) -> Command {
+   let mut command = Command::new("sh");
+   command.arg(path);
+   command
+}
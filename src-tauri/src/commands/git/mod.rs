@@ -2,6 +2,7 @@ mod blame;
 mod branch;
 mod commit;
 mod diff;
+mod hooks;
 mod hunk;
 mod remote;
 mod staging;
@@ -15,6 +16,7 @@ pub use blame::*;
 pub use branch::*;
 pub use commit::*;
 pub use diff::*;
+pub use hooks::*;
 pub use hunk::*;
 pub use remote::*;
 pub use staging::*;
@@ -1,9 +1,14 @@
-use crate::commands::git::{
-   FileStatus, GitFile, GitStatus, IntoStringError, get_ahead_behind_counts,
+use crate::{
+   commands::git::{
+      FileStatus, GitFile, GitStatus, IntoStringError, WorkspaceRootGitStatus,
+      get_ahead_behind_counts,
+   },
+   workspace::WorkspaceManager,
 };
 use anyhow::{Context, Result};
 use git2::Repository;
-use tauri::command;
+use std::sync::Arc;
+use tauri::{State, command};
 
 #[command]
 pub fn git_status(repo_path: String) -> Result<GitStatus, String> {
@@ -110,6 +115,37 @@ fn _git_status(repo_path: String) -> Result<GitStatus> {
    })
 }
 
+/// Status of the repository containing each workspace root.
+#[command]
+pub fn git_workspace_status(
+   workspace: State<'_, Arc<WorkspaceManager>>,
+) -> Vec<WorkspaceRootGitStatus> {
+   workspace
+      .roots()
+      .into_iter()
+      .map(|root| {
+         let repo_path = Repository::discover(&root.path)
+            .ok()
+            .and_then(|repo| repo.workdir().map(|dir| dir.to_string_lossy().to_string()));
+         let (status, error) = match &repo_path {
+            Some(repo_path) => match _git_status(repo_path.clone()) {
+               Ok(status) => (Some(status), None),
+               Err(e) => (None, Some(e.to_string())),
+            },
+            None => (None, None),
+         };
+
+         WorkspaceRootGitStatus {
+            root_name: root.name,
+            root_path: root.path,
+            repo_path,
+            status,
+            error,
+         }
+      })
+      .collect()
+}
+
 #[command]
 pub fn git_init(repo_path: String) -> Result<(), String> {
    _git_init(repo_path).into_string_error()
@@ -8,6 +8,17 @@ pub struct GitStatus {
    pub files: Vec<GitFile>,
 }
 
+/// Git status of one root of a multi-root workspace.
+#[derive(Serialize)]
+pub struct WorkspaceRootGitStatus {
+   pub root_name: String,
+   pub root_path: String,
+   /// Working directory of the repository containing the root, if it is inside one.
+   pub repo_path: Option<String>,
+   pub status: Option<GitStatus>,
+   pub error: Option<String>,
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub enum FileStatus {
@@ -42,7 +53,7 @@ pub enum DiffLineType {
    Header,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct GitDiffLine {
    pub line_type: DiffLineType,
    pub content: String,
@@ -108,3 +119,32 @@ pub struct GitHunk {
    pub file_path: String,
    pub lines: Vec<GitDiffLine>,
 }
+
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum GitHookStream {
+   Stdout,
+   Stderr,
+}
+
+/// Payload of `git://hook`, sent while the hooks of a commit made from the app run.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum GitHookEvent {
+   Started {
+      repo_path: String,
+      hook: String,
+   },
+   Output {
+      repo_path: String,
+      hook: String,
+      stream: GitHookStream,
+      line: String,
+   },
+   Finished {
+      repo_path: String,
+      hook: String,
+      success: bool,
+      exit_code: Option<i32>,
+   },
+}
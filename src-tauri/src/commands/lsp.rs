@@ -1,5 +1,24 @@
-use crate::lsp::{LspManager, types::LspResult};
-use lsp_types::{CompletionItem, Hover};
+use crate::lsp::{
+   LspManager,
+   code_action::LspCodeAction,
+   config::LspServerConfig,
+   hierarchy::{HierarchyDirection, HierarchyItem, HierarchyNode},
+   installer::{InstalledServer, LspServerUpdate},
+   location::LspLocation,
+   manager::{GotoKind, PrepareRenameResult},
+   semantic_tokens::LspSemanticToken,
+   snippet::{self, ExpandedSnippet, SnippetContext},
+   symbols::{LspWorkspaceSymbol, OutlineSymbol},
+   traffic::TrafficEntry,
+   types::LspResult,
+   workspace_edit::AppliedWorkspaceEdit,
+};
+use lsp_types::{
+   CodeLens, Command, CompletionItem, Diagnostic, DocumentHighlight, FoldingRange, Hover,
+   InlayHint, MessageActionItem, Position, Range, SelectionRange, SignatureHelp,
+   TextDocumentContentChangeEvent,
+};
+use serde_json::Value;
 use std::path::PathBuf;
 use tauri::State;
 
@@ -91,6 +110,24 @@ pub async fn lsp_get_completions(
    result
 }
 
+#[tauri::command]
+pub async fn lsp_resolve_completion(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   item: CompletionItem,
+) -> LspResult<CompletionItem> {
+   lsp_manager
+      .resolve_completion(&file_path, item)
+      .await
+      .map_err(Into::into)
+}
+
+/// Expand an LSP snippet, e.g. a completion's `insert_text` when its format is `Snippet`.
+#[tauri::command]
+pub fn lsp_expand_snippet(snippet: String, context: Option<SnippetContext>) -> ExpandedSnippet {
+   snippet::expand(&snippet, &context.unwrap_or_default())
+}
+
 #[tauri::command]
 pub async fn lsp_get_hover(
    lsp_manager: State<'_, LspManager>,
@@ -104,6 +141,370 @@ pub async fn lsp_get_hover(
       .map_err(Into::into)
 }
 
+#[tauri::command]
+pub async fn lsp_goto_definition(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   line: u32,
+   character: u32,
+) -> LspResult<Vec<LspLocation>> {
+   lsp_manager
+      .goto(GotoKind::Definition, &file_path, line, character)
+      .await
+      .map_err(Into::into)
+}
+
+#[tauri::command]
+pub async fn lsp_goto_type_definition(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   line: u32,
+   character: u32,
+) -> LspResult<Vec<LspLocation>> {
+   lsp_manager
+      .goto(GotoKind::TypeDefinition, &file_path, line, character)
+      .await
+      .map_err(Into::into)
+}
+
+#[tauri::command]
+pub async fn lsp_goto_implementation(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   line: u32,
+   character: u32,
+) -> LspResult<Vec<LspLocation>> {
+   lsp_manager
+      .goto(GotoKind::Implementation, &file_path, line, character)
+      .await
+      .map_err(Into::into)
+}
+
+#[tauri::command]
+pub async fn lsp_goto_declaration(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   line: u32,
+   character: u32,
+) -> LspResult<Vec<LspLocation>> {
+   lsp_manager
+      .goto(GotoKind::Declaration, &file_path, line, character)
+      .await
+      .map_err(Into::into)
+}
+
+#[tauri::command]
+pub async fn lsp_find_references(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   line: u32,
+   character: u32,
+   include_declaration: Option<bool>,
+) -> LspResult<Vec<LspLocation>> {
+   lsp_manager
+      .get_references(
+         &file_path,
+         line,
+         character,
+         include_declaration.unwrap_or(true),
+      )
+      .await
+      .map_err(Into::into)
+}
+
+#[tauri::command]
+pub async fn lsp_prepare_rename(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   line: u32,
+   character: u32,
+) -> LspResult<Option<PrepareRenameResult>> {
+   lsp_manager
+      .prepare_rename(&file_path, line, character)
+      .await
+      .map_err(Into::into)
+}
+
+#[tauri::command]
+pub async fn lsp_rename(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   line: u32,
+   character: u32,
+   new_name: String,
+   open_files: Vec<String>,
+) -> LspResult<AppliedWorkspaceEdit> {
+   lsp_manager
+      .rename(
+         &file_path,
+         line,
+         character,
+         new_name,
+         open_files.into_iter().map(PathBuf::from).collect(),
+      )
+      .await
+      .map_err(|e| {
+         log::error!("Failed to rename symbol: {}", e);
+         e.into()
+      })
+}
+
+#[tauri::command]
+pub async fn lsp_get_code_actions(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   range: Range,
+   only: Option<Vec<String>>,
+) -> LspResult<Vec<LspCodeAction>> {
+   lsp_manager
+      .get_code_actions(&file_path, range, only)
+      .await
+      .map_err(Into::into)
+}
+
+#[tauri::command]
+pub async fn lsp_resolve_code_action(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   action: LspCodeAction,
+) -> LspResult<LspCodeAction> {
+   lsp_manager
+      .resolve_code_action(&file_path, action)
+      .await
+      .map_err(Into::into)
+}
+
+#[tauri::command]
+pub async fn lsp_apply_code_action(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   action: LspCodeAction,
+   open_files: Vec<String>,
+) -> LspResult<AppliedWorkspaceEdit> {
+   lsp_manager
+      .apply_code_action(
+         &file_path,
+         action,
+         open_files.into_iter().map(PathBuf::from).collect(),
+      )
+      .await
+      .map_err(|e| {
+         log::error!("Failed to apply code action: {}", e);
+         e.into()
+      })
+}
+
+#[tauri::command]
+pub async fn lsp_execute_command(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   command: Command,
+) -> LspResult<Option<Value>> {
+   lsp_manager
+      .execute_command(&file_path, command)
+      .await
+      .map_err(Into::into)
+}
+
+#[tauri::command]
+pub async fn lsp_signature_help(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   line: u32,
+   character: u32,
+   trigger_character: Option<String>,
+) -> LspResult<Option<SignatureHelp>> {
+   lsp_manager
+      .signature_help(&file_path, line, character, trigger_character)
+      .await
+      .map_err(Into::into)
+}
+
+#[tauri::command]
+pub async fn lsp_inlay_hints(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   range: Range,
+) -> LspResult<Vec<InlayHint>> {
+   lsp_manager
+      .inlay_hints(&file_path, range)
+      .await
+      .map_err(Into::into)
+}
+
+/// Semantic tokens classified like `get_tokens`, to overlay on the tree-sitter highlighting.
+#[tauri::command]
+pub async fn lsp_semantic_tokens(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   range: Option<Range>,
+) -> LspResult<Vec<LspSemanticToken>> {
+   lsp_manager
+      .semantic_tokens(&file_path, range)
+      .await
+      .map_err(Into::into)
+}
+
+/// Outline of a file. Falls back to the syntax tree when no language server provides one.
+#[tauri::command]
+pub async fn lsp_document_symbols(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   content: Option<String>,
+) -> LspResult<Vec<OutlineSymbol>> {
+   lsp_manager
+      .document_symbols(&file_path, content)
+      .await
+      .map_err(Into::into)
+}
+
+#[tauri::command]
+pub async fn lsp_workspace_symbols(
+   lsp_manager: State<'_, LspManager>,
+   query: String,
+) -> LspResult<Vec<LspWorkspaceSymbol>> {
+   lsp_manager
+      .workspace_symbols(&query)
+      .await
+      .map_err(Into::into)
+}
+
+#[tauri::command]
+pub async fn lsp_code_lenses(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+) -> LspResult<Vec<CodeLens>> {
+   lsp_manager
+      .code_lenses(&file_path)
+      .await
+      .map_err(Into::into)
+}
+
+/// Fill in the command of a lens; lenses that already have one are returned as they are.
+#[tauri::command]
+pub async fn lsp_resolve_code_lens(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   lens: CodeLens,
+) -> LspResult<CodeLens> {
+   lsp_manager
+      .resolve_code_lens(&file_path, lens)
+      .await
+      .map_err(Into::into)
+}
+
+#[tauri::command]
+pub async fn lsp_document_highlights(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   line: u32,
+   character: u32,
+) -> LspResult<Vec<DocumentHighlight>> {
+   lsp_manager
+      .document_highlights(&file_path, line, character)
+      .await
+      .map_err(Into::into)
+}
+
+/// Folding ranges of a file. Falls back to the syntax tree when no language server provides them.
+#[tauri::command]
+pub async fn lsp_folding_ranges(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   content: Option<String>,
+) -> LspResult<Vec<FoldingRange>> {
+   lsp_manager
+      .folding_ranges(&file_path, content)
+      .await
+      .map_err(Into::into)
+}
+
+/// Expand-selection ranges for each position, with the same fallback as `lsp_folding_ranges`.
+#[tauri::command]
+pub async fn lsp_selection_ranges(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   positions: Vec<Position>,
+   content: Option<String>,
+) -> LspResult<Vec<SelectionRange>> {
+   lsp_manager
+      .selection_ranges(&file_path, positions, content)
+      .await
+      .map_err(Into::into)
+}
+
+/// Call or type hierarchy at a position. `depth` levels are expanded up front (one by
+/// default); deeper nodes are fetched with `lsp_expand_hierarchy`.
+#[tauri::command]
+pub async fn lsp_prepare_hierarchy(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   line: u32,
+   character: u32,
+   direction: HierarchyDirection,
+   depth: Option<usize>,
+) -> LspResult<Vec<HierarchyNode>> {
+   lsp_manager
+      .prepare_hierarchy(&file_path, line, character, direction, depth.unwrap_or(1))
+      .await
+      .map_err(Into::into)
+}
+
+#[tauri::command]
+pub async fn lsp_expand_hierarchy(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+   item: HierarchyItem,
+   direction: HierarchyDirection,
+   depth: Option<usize>,
+   ancestors: Option<Vec<HierarchyItem>>,
+) -> LspResult<Vec<HierarchyNode>> {
+   lsp_manager
+      .expand_hierarchy(
+         &file_path,
+         item,
+         direction,
+         depth.unwrap_or(1),
+         ancestors.unwrap_or_default(),
+      )
+      .await
+      .map_err(Into::into)
+}
+
+/// Pull a file's diagnostics from servers that support it. Servers that only publish them
+/// return what they last published.
+#[tauri::command]
+pub async fn lsp_pull_diagnostics(
+   lsp_manager: State<'_, LspManager>,
+   file_path: String,
+) -> LspResult<Vec<Diagnostic>> {
+   lsp_manager
+      .pull_diagnostics(&file_path)
+      .await
+      .map_err(Into::into)
+}
+
+/// Pull workspace diagnostics into the store; query them with `diagnostics_query`.
+#[tauri::command]
+pub async fn lsp_workspace_diagnostics(lsp_manager: State<'_, LspManager>) -> LspResult<()> {
+   lsp_manager
+      .workspace_diagnostics()
+      .await
+      .map_err(Into::into)
+}
+
+/// Answer a `lsp://show-message-request` event. `action` is `None` when the user dismissed it.
+#[tauri::command]
+pub fn lsp_respond_message_request(
+   lsp_manager: State<'_, LspManager>,
+   request_id: String,
+   action: Option<MessageActionItem>,
+) -> LspResult<()> {
+   lsp_manager
+      .respond_to_message_request(&request_id, action)
+      .map_err(Into::into)
+}
+
 #[tauri::command]
 pub fn lsp_document_open(
    lsp_manager: State<'_, LspManager>,
@@ -116,14 +517,26 @@ pub fn lsp_document_open(
 }
 
 #[tauri::command]
+/// Either `content` replaces the whole document or `changes` edits ranges of it, so large files
+/// do not have to be resent on every keystroke.
 pub fn lsp_document_change(
    lsp_manager: State<'_, LspManager>,
    file_path: String,
-   content: String,
+   content: Option<String>,
+   changes: Option<Vec<TextDocumentContentChangeEvent>>,
    version: i32,
 ) -> LspResult<()> {
+   let changes = match (content, changes) {
+      (_, Some(changes)) => changes,
+      (Some(text), None) => vec![TextDocumentContentChangeEvent {
+         range: None,
+         range_length: None,
+         text,
+      }],
+      (None, None) => return Err(anyhow::anyhow!("No content or changes given").into()),
+   };
    lsp_manager
-      .notify_document_change(&file_path, content, version)
+      .notify_document_change(&file_path, changes, version)
       .map_err(Into::into)
 }
 
@@ -135,13 +548,101 @@ pub fn lsp_document_close(lsp_manager: State<'_, LspManager>, file_path: String)
 }
 
 #[tauri::command]
-pub fn lsp_is_language_supported(file_path: String) -> bool {
-   let path = PathBuf::from(file_path);
-   let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+pub fn lsp_is_language_supported(lsp_manager: State<'_, LspManager>, file_path: String) -> bool {
+   lsp_manager.is_file_supported(&PathBuf::from(file_path))
+}
+
+/// Built-in and user-configured language servers, in order of preference.
+#[tauri::command]
+pub fn lsp_list_servers(lsp_manager: State<'_, LspManager>) -> Vec<LspServerConfig> {
+   lsp_manager.servers()
+}
+
+/// Re-read the user's `lsp.json` and return the resulting servers.
+#[tauri::command]
+pub fn lsp_reload_config(lsp_manager: State<'_, LspManager>) -> LspResult<Vec<LspServerConfig>> {
+   lsp_manager.reload_config()?;
+   Ok(lsp_manager.servers())
+}
+
+#[tauri::command]
+pub fn lsp_get_config_path(lsp_manager: State<'_, LspManager>) -> LspResult<String> {
+   let path = lsp_manager.config_path()?;
+   Ok(path.to_string_lossy().to_string())
+}
+
+/// Install or update a server into the app data directory. Progress is reported through
+/// `lsp://install-progress` events.
+#[tauri::command]
+pub async fn lsp_install_server(
+   lsp_manager: State<'_, LspManager>,
+   server_name: String,
+) -> LspResult<InstalledServer> {
+   lsp_manager
+      .install_server(&server_name)
+      .await
+      .map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn lsp_uninstall_server(
+   lsp_manager: State<'_, LspManager>,
+   server_name: String,
+) -> LspResult<()> {
+   lsp_manager
+      .uninstall_server(&server_name)
+      .map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn lsp_list_installed_servers(
+   lsp_manager: State<'_, LspManager>,
+) -> LspResult<Vec<InstalledServer>> {
+   lsp_manager.installed_servers().map_err(Into::into)
+}
+
+#[tauri::command]
+pub async fn lsp_check_server_updates(
+   lsp_manager: State<'_, LspManager>,
+) -> LspResult<Vec<LspServerUpdate>> {
+   lsp_manager.check_server_updates().await.map_err(Into::into)
+}
+
+/// Messages recently exchanged with a server, or with all of them, oldest first. Message bodies
+/// are left out unless `include_messages` is set.
+#[tauri::command]
+pub fn lsp_get_traffic(
+   lsp_manager: State<'_, LspManager>,
+   server_name: Option<String>,
+   include_messages: Option<bool>,
+) -> Vec<TrafficEntry> {
+   lsp_manager
+      .traffic()
+      .entries(server_name.as_deref(), include_messages.unwrap_or(false))
+}
+
+#[tauri::command]
+pub fn lsp_clear_traffic(lsp_manager: State<'_, LspManager>, server_name: Option<String>) {
+   lsp_manager.traffic().clear(server_name.as_deref());
+}
 
-   // Support TypeScript, JavaScript, and related files
-   matches!(
-      extension,
-      "ts" | "tsx" | "js" | "jsx" | "mjs" | "cjs" | "json"
-   )
+/// Start or stop sending each message as an `lsp://traffic` event.
+#[tauri::command]
+pub fn lsp_set_traffic_streaming(lsp_manager: State<'_, LspManager>, enabled: bool) {
+   lsp_manager.traffic().set_streaming(enabled);
+}
+
+/// Write the recorded messages to `output_path` as JSON lines that LSP trace viewers can load.
+/// Returns how many messages were written.
+#[tauri::command]
+pub fn lsp_export_traffic(
+   lsp_manager: State<'_, LspManager>,
+   server_name: Option<String>,
+   output_path: String,
+) -> LspResult<usize> {
+   let traffic = lsp_manager.traffic();
+   let lines = traffic.export_jsonl(server_name.as_deref());
+   std::fs::write(&output_path, &lines)
+      .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", output_path, e))?;
+   Ok(lines.lines().count())
 }
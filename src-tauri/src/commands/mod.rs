@@ -1,6 +1,7 @@
 pub mod ai_tokens;
 pub mod claude;
 pub mod cli;
+pub mod diagnostics;
 pub mod extensions;
 pub mod font;
 pub mod format;
@@ -9,16 +10,19 @@ pub mod fuzzy;
 pub mod git;
 pub mod github;
 pub mod lsp;
+pub mod replace;
 pub mod search;
 pub mod sqlite;
 pub mod theme;
 pub mod tokens;
 pub mod watcher;
 pub mod window;
+pub mod workspace;
 
 pub use ai_tokens::*;
 pub use claude::*;
 pub use cli::*;
+pub use diagnostics::*;
 pub use extensions::*;
 pub use font::*;
 pub use format::*;
@@ -27,9 +31,11 @@ pub use fuzzy::*;
 pub use git::*;
 pub use github::*;
 pub use lsp::*;
+pub use replace::*;
 pub use search::*;
 pub use sqlite::*;
 pub use theme::*;
 pub use tokens::*;
 pub use watcher::*;
 pub use window::*;
+pub use workspace::*;
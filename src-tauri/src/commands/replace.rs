@@ -0,0 +1,521 @@
+use crate::{
+   commands::{
+      git::{DiffLineType, GitDiffLine},
+      search::{SearchFilesRequest, SearchLimits, SearchMatcher, search_candidates},
+   },
+   workspace::WorkspaceManager,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+   fs,
+   path::{Path, PathBuf},
+   sync::Arc,
+};
+use tauri::State;
+
+#[derive(Debug, Deserialize)]
+pub struct ReplacePlanRequest {
+   #[serde(flatten)]
+   pub search: SearchFilesRequest,
+   /// Replacement text. In regex mode `$1`, `${name}` and `$$` are expanded from the match.
+   pub replacement: String,
+}
+
+/// A single replacement. `start`/`end` are byte offsets into the file content the plan was
+/// computed from; line and columns are for display.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReplaceEdit {
+   pub start: usize,
+   pub end: usize,
+   pub line_number: usize,
+   pub column_start: usize,
+   pub column_end: usize,
+   pub original: String,
+   pub replacement: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileReplacePlan {
+   pub file_path: String,
+   /// SHA-256 of the content the edits were computed against.
+   pub content_hash: String,
+   pub edits: Vec<ReplaceEdit>,
+   pub preview: Vec<GitDiffLine>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplacePlan {
+   pub files: Vec<FileReplacePlan>,
+   pub total_edits: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApplyReplaceRequest {
+   pub files: Vec<FileReplaceSelection>,
+}
+
+/// The subset of a file's planned edits the user chose to apply.
+#[derive(Debug, Deserialize)]
+pub struct FileReplaceSelection {
+   pub file_path: String,
+   pub content_hash: String,
+   pub edits: Vec<ReplaceEdit>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApplyReplaceResult {
+   pub files_changed: usize,
+   pub edits_applied: usize,
+}
+
+/// Compute the edits for one file's content. Matches never span lines, mirroring search.
+fn plan_edits(
+   content: &str,
+   matcher: &SearchMatcher,
+   use_regex: bool,
+   replacement: &str,
+) -> Vec<ReplaceEdit> {
+   let mut edits = Vec::new();
+   let mut line_offset = 0;
+
+   for (line_idx, raw_line) in content.split_inclusive('\n').enumerate() {
+      let line = raw_line
+         .strip_suffix('\n')
+         .map(|l| l.strip_suffix('\r').unwrap_or(l))
+         .unwrap_or(raw_line);
+
+      for captures in matcher.regex().captures_iter(line) {
+         let Some(found) = captures.get(0) else {
+            continue;
+         };
+         // Empty matches are never reported by search, so they are never replaced either
+         if found.is_empty() {
+            continue;
+         }
+
+         let replaced = if use_regex {
+            let mut expanded = String::new();
+            captures.expand(replacement, &mut expanded);
+            expanded
+         } else {
+            replacement.to_string()
+         };
+
+         if replaced == found.as_str() {
+            continue;
+         }
+
+         edits.push(ReplaceEdit {
+            start: line_offset + found.start(),
+            end: line_offset + found.end(),
+            line_number: line_idx + 1,
+            column_start: found.start(),
+            column_end: found.end(),
+            original: found.as_str().to_string(),
+            replacement: replaced,
+         });
+      }
+
+      line_offset += raw_line.len();
+   }
+
+   edits
+}
+
+/// Apply edits to `content`, verifying each one still matches the text it was planned against.
+fn apply_edits(content: &str, edits: &[ReplaceEdit]) -> Result<String, String> {
+   let mut sorted: Vec<&ReplaceEdit> = edits.iter().collect();
+   sorted.sort_by_key(|edit| edit.start);
+
+   let mut result = String::with_capacity(content.len());
+   let mut cursor = 0;
+   for edit in sorted {
+      if edit.start < cursor {
+         return Err(format!("Overlapping edits at line {}", edit.line_number));
+      }
+      if content.get(edit.start..edit.end) != Some(edit.original.as_str()) {
+         return Err(format!(
+            "Edit at line {} no longer matches the file content",
+            edit.line_number
+         ));
+      }
+
+      result.push_str(&content[cursor..edit.start]);
+      result.push_str(&edit.replacement);
+      cursor = edit.end;
+   }
+   result.push_str(&content[cursor..]);
+
+   Ok(result)
+}
+
+/// Removed/added line pairs for every line touched by `edits`, in the `GitDiffLine` shape used
+/// by the diff viewer.
+fn preview_lines(content: &str, edits: &[ReplaceEdit]) -> Vec<GitDiffLine> {
+   let lines: Vec<&str> = content.lines().collect();
+   let mut preview = Vec::new();
+   // Replacements containing newlines shift the numbering of every following line
+   let mut line_delta: i64 = 0;
+
+   let mut index = 0;
+   while index < edits.len() {
+      let line_number = edits[index].line_number;
+      let line_edits: Vec<&ReplaceEdit> = edits[index..]
+         .iter()
+         .take_while(|edit| edit.line_number == line_number)
+         .collect();
+      index += line_edits.len();
+
+      let old_line = lines.get(line_number - 1).copied().unwrap_or_default();
+      let mut new_line = String::with_capacity(old_line.len());
+      let mut cursor = 0;
+      for edit in &line_edits {
+         new_line.push_str(&old_line[cursor..edit.column_start]);
+         new_line.push_str(&edit.replacement);
+         cursor = edit.column_end;
+      }
+      new_line.push_str(&old_line[cursor..]);
+
+      let new_lines: Vec<&str> = new_line.split('\n').collect();
+      let new_start = (line_number as i64 + line_delta) as u32;
+
+      preview.push(GitDiffLine {
+         line_type: DiffLineType::Header,
+         content: format!(
+            "@@ -{},1 +{},{} @@",
+            line_number,
+            new_start,
+            new_lines.len()
+         ),
+         old_line_number: None,
+         new_line_number: None,
+      });
+      preview.push(GitDiffLine {
+         line_type: DiffLineType::Removed,
+         content: old_line.to_string(),
+         old_line_number: Some(line_number as u32),
+         new_line_number: None,
+      });
+      for (offset, added) in new_lines.iter().enumerate() {
+         preview.push(GitDiffLine {
+            line_type: DiffLineType::Added,
+            content: added.trim_end_matches('\r').to_string(),
+            old_line_number: None,
+            new_line_number: Some(new_start + offset as u32),
+         });
+      }
+
+      line_delta += new_lines.len() as i64 - 1;
+   }
+
+   preview
+}
+
+/// Write `content` next to `path` and rename it into place so readers never see a partial file.
+pub(crate) fn write_atomically(path: &Path, content: &[u8]) -> std::io::Result<()> {
+   let dir = path.parent().unwrap_or_else(|| Path::new("."));
+   let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+   std::io::Write::write_all(&mut temp, content)?;
+   if let Ok(metadata) = fs::metadata(path) {
+      let _ = fs::set_permissions(temp.path(), metadata.permissions());
+   }
+   temp.persist(path).map_err(|e| e.error)?;
+   Ok(())
+}
+
+/// Write each `(path, original, updated)` entry, restoring the originals of files already
+/// written if any write fails.
+fn write_all_or_rollback<W>(pending: &[(PathBuf, String, String)], write: W) -> Result<(), String>
+where
+   W: Fn(&Path, &str) -> std::io::Result<()>,
+{
+   for (index, (path, _, updated)) in pending.iter().enumerate() {
+      if let Err(e) = write(path, updated) {
+         for (written, original, _) in &pending[..index] {
+            if let Err(restore_err) = write(written, original) {
+               log::error!(
+                  "Failed to restore {} after aborted replace: {}",
+                  written.display(),
+                  restore_err
+               );
+            }
+         }
+         return Err(format!(
+            "Failed to write {}: {}. Changes to other files were rolled back",
+            path.display(),
+            e
+         ));
+      }
+   }
+
+   Ok(())
+}
+
+#[tauri::command]
+pub fn replace_plan(
+   mut request: ReplacePlanRequest,
+   workspace: State<'_, Arc<WorkspaceManager>>,
+) -> Result<ReplacePlan, String> {
+   request.search = workspace.scope_search(request.search);
+   plan_replace(&request)
+}
+
+/// Plan replacements across every root of the request.
+pub fn plan_replace(request: &ReplacePlanRequest) -> Result<ReplacePlan, String> {
+   if request.search.query.is_empty() {
+      return Ok(ReplacePlan {
+         files: Vec::new(),
+         total_edits: 0,
+      });
+   }
+
+   let matcher = SearchMatcher::from_request(&request.search)?;
+   let use_regex = request.search.use_regex.unwrap_or(false);
+   let max_files = request.search.max_results.unwrap_or(usize::MAX);
+   let limits = SearchLimits::from_request(&request.search);
+   let mut files = Vec::new();
+
+   let mut candidates = Vec::new();
+   for root_request in request.search.per_root() {
+      candidates.push(search_candidates(&root_request)?);
+   }
+
+   for path in candidates.into_iter().flatten() {
+      if files.len() >= max_files {
+         break;
+      }
+
+      if fs::metadata(&path).is_ok_and(|metadata| metadata.len() > limits.max_file_size) {
+         continue;
+      }
+
+      let Ok(content) = fs::read_to_string(&path) else {
+         continue;
+      };
+      if content.contains('\0') {
+         continue;
+      }
+
+      let edits = plan_edits(&content, &matcher, use_regex, &request.replacement);
+      if edits.is_empty() {
+         continue;
+      }
+
+      files.push(FileReplacePlan {
+         file_path: path.to_string_lossy().to_string(),
+         content_hash: sha256::digest(content.as_str()),
+         preview: preview_lines(&content, &edits),
+         edits,
+      });
+   }
+
+   let total_edits = files.iter().map(|file| file.edits.len()).sum();
+   Ok(ReplacePlan { files, total_edits })
+}
+
+/// Apply the selected edits. Every file is validated before anything is written, and files
+/// already written are restored if a later write fails.
+#[tauri::command]
+pub fn replace_apply(request: ApplyReplaceRequest) -> Result<ApplyReplaceResult, String> {
+   let mut pending: Vec<(PathBuf, String, String)> = Vec::new();
+   let mut edits_applied = 0;
+
+   for file in &request.files {
+      if file.edits.is_empty() {
+         continue;
+      }
+
+      let path = PathBuf::from(&file.file_path);
+      let original = fs::read_to_string(&path)
+         .map_err(|e| format!("Failed to read {}: {}", file.file_path, e))?;
+      if sha256::digest(original.as_str()) != file.content_hash {
+         return Err(format!(
+            "{} changed since the replacement was previewed",
+            file.file_path
+         ));
+      }
+
+      let updated =
+         apply_edits(&original, &file.edits).map_err(|e| format!("{}: {}", file.file_path, e))?;
+      edits_applied += file.edits.len();
+      pending.push((path, original, updated));
+   }
+
+   write_all_or_rollback(&pending, |path, content| {
+      write_atomically(path, content.as_bytes())
+   })?;
+
+   Ok(ApplyReplaceResult {
+      files_changed: pending.len(),
+      edits_applied,
+   })
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::test_utils::create_tree;
+   use tempfile::TempDir;
+
+   fn plan_request(
+      dir: &TempDir,
+      query: &str,
+      replacement: &str,
+      regex: bool,
+   ) -> ReplacePlanRequest {
+      ReplacePlanRequest {
+         search: SearchFilesRequest {
+            root_path: dir.path().to_string_lossy().to_string(),
+            root_paths: None,
+            query: query.to_string(),
+            case_sensitive: Some(true),
+            max_results: None,
+            use_regex: Some(regex),
+            whole_word: None,
+            include_globs: None,
+            exclude_globs: None,
+            include_ignored: None,
+            max_matches_per_file: None,
+            max_file_size: None,
+         },
+         replacement: replacement.to_string(),
+      }
+   }
+
+   fn select_all(plan: ReplacePlan) -> ApplyReplaceRequest {
+      ApplyReplaceRequest {
+         files: plan
+            .files
+            .into_iter()
+            .map(|file| FileReplaceSelection {
+               file_path: file.file_path,
+               content_hash: file.content_hash,
+               edits: file.edits,
+            })
+            .collect(),
+      }
+   }
+
+   #[test]
+   fn test_regex_replace_expands_capture_groups() {
+      let dir = create_tree(&[("a.rs", "let old_name = 1;\r\nprint(old_name, old_other);\n")]);
+
+      let plan = plan_replace(&plan_request(&dir, r"old_(\w+)", "new_${1}_v$$", true)).unwrap();
+      assert_eq!(plan.total_edits, 3);
+      assert_eq!(plan.files[0].edits[1].start, 25);
+      assert_eq!(plan.files[0].edits[1].replacement, "new_name_v$");
+
+      let preview = &plan.files[0].preview;
+      assert!(matches!(preview[0].line_type, DiffLineType::Header));
+      assert_eq!(preview[1].content, "let old_name = 1;");
+      assert_eq!(preview[2].content, "let new_name_v$ = 1;");
+      assert_eq!(preview[5].content, "print(new_name_v$, new_other_v$);");
+      assert_eq!(preview[5].new_line_number, Some(2));
+
+      let result = replace_apply(select_all(plan)).unwrap();
+      assert_eq!(result.files_changed, 1);
+      assert_eq!(result.edits_applied, 3);
+      assert_eq!(
+         fs::read_to_string(dir.path().join("a.rs")).unwrap(),
+         "let new_name_v$ = 1;\r\nprint(new_name_v$, new_other_v$);\n"
+      );
+   }
+
+   #[test]
+   fn test_literal_replace_does_not_expand_dollars() {
+      let dir = create_tree(&[("a.txt", "price: (x)")]);
+
+      let plan = plan_replace(&plan_request(&dir, "(x)", "$1", false)).unwrap();
+      replace_apply(select_all(plan)).unwrap();
+      assert_eq!(
+         fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+         "price: $1"
+      );
+   }
+
+   #[test]
+   fn test_apply_selected_subset() {
+      let dir = create_tree(&[("a.txt", "foo foo\nfoo\n"), ("b.txt", "foo\n")]);
+
+      let plan = plan_replace(&plan_request(&dir, "foo", "bar", false)).unwrap();
+      let mut request = select_all(plan);
+      request
+         .files
+         .retain(|file| file.file_path.ends_with("a.txt"));
+      request.files[0].edits.remove(1);
+
+      let result = replace_apply(request).unwrap();
+      assert_eq!(result.edits_applied, 2);
+      assert_eq!(
+         fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+         "bar foo\nbar\n"
+      );
+      assert_eq!(
+         fs::read_to_string(dir.path().join("b.txt")).unwrap(),
+         "foo\n"
+      );
+   }
+
+   #[test]
+   fn test_multiline_replacement_preview_numbers() {
+      let dir = create_tree(&[("a.txt", "a;b\nc;d\n")]);
+
+      let plan = plan_replace(&plan_request(&dir, ";", "\n", false)).unwrap();
+      let added: Vec<(String, Option<u32>)> = plan.files[0]
+         .preview
+         .iter()
+         .filter(|line| matches!(line.line_type, DiffLineType::Added))
+         .map(|line| (line.content.clone(), line.new_line_number))
+         .collect();
+      assert_eq!(
+         added,
+         vec![
+            ("a".to_string(), Some(1)),
+            ("b".to_string(), Some(2)),
+            ("c".to_string(), Some(3)),
+            ("d".to_string(), Some(4)),
+         ]
+      );
+   }
+
+   #[test]
+   fn test_stale_plan_is_rejected_without_writing() {
+      let dir = create_tree(&[("a.txt", "foo\n"), ("b.txt", "foo\n")]);
+
+      let plan = plan_replace(&plan_request(&dir, "foo", "bar", false)).unwrap();
+      fs::write(dir.path().join("b.txt"), "foo changed\n").unwrap();
+
+      assert!(replace_apply(select_all(plan)).is_err());
+      assert_eq!(
+         fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+         "foo\n"
+      );
+   }
+
+   #[test]
+   fn test_failed_write_rolls_back_written_files() {
+      let dir = create_tree(&[("a.txt", "foo\n"), ("b.txt", "foo\n"), ("c.txt", "foo\n")]);
+      let pending: Vec<(PathBuf, String, String)> = ["a.txt", "b.txt", "c.txt"]
+         .iter()
+         .map(|name| {
+            (
+               dir.path().join(name),
+               "foo\n".to_string(),
+               "bar\n".to_string(),
+            )
+         })
+         .collect();
+
+      let result = write_all_or_rollback(&pending, |path, content| {
+         if path.ends_with("c.txt") && content == "bar\n" {
+            return Err(std::io::Error::other("disk full"));
+         }
+         write_atomically(path, content.as_bytes())
+      });
+
+      assert!(result.unwrap_err().contains("disk full"));
+      for name in ["a.txt", "b.txt", "c.txt"] {
+         assert_eq!(fs::read_to_string(dir.path().join(name)).unwrap(), "foo\n");
+      }
+   }
+}
@@ -1,6 +1,27 @@
+use crate::{
+   search_index::{SearchIndexManager, SearchIndexStatus},
+   search_session::SearchSessionManager,
+   workspace::WorkspaceManager,
+   workspace_ignore::WorkspaceIgnore,
+};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use regex::{Regex, RegexBuilder};
 use serde::{Deserialize, Serialize};
-use std::{fs, path::Path};
-use walkdir::WalkDir;
+use std::{
+   fs,
+   path::{Path, PathBuf},
+   sync::Arc,
+};
+use tauri::State;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CaptureSpan {
+   pub index: usize,
+   pub name: Option<String>,
+   pub column_start: usize,
+   pub column_end: usize,
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SearchMatch {
@@ -8,241 +29,675 @@ pub struct SearchMatch {
    pub line_content: String,
    pub column_start: usize,
    pub column_end: usize,
+   #[serde(default)]
+   pub captures: Vec<CaptureSpan>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FileSearchResult {
    pub file_path: String,
    pub matches: Vec<SearchMatch>,
    pub total_matches: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchFilesRequest {
    pub root_path: String,
+   /// Roots of a multi-root workspace. When set, every listed root is searched instead of
+   /// `root_path`.
+   pub root_paths: Option<Vec<String>>,
    pub query: String,
    pub case_sensitive: Option<bool>,
    pub max_results: Option<usize>,
+   pub use_regex: Option<bool>,
+   pub whole_word: Option<bool>,
+   pub include_globs: Option<Vec<String>>,
+   pub exclude_globs: Option<Vec<String>>,
+   pub include_ignored: Option<bool>,
+   pub max_matches_per_file: Option<usize>,
+   pub max_file_size: Option<u64>,
+}
+
+impl SearchFilesRequest {
+   /// One request per root to search, each with `root_path` set to that root.
+   pub fn per_root(&self) -> Vec<SearchFilesRequest> {
+      let roots = match &self.root_paths {
+         Some(roots) if !roots.is_empty() => roots.clone(),
+         _ => vec![self.root_path.clone()],
+      };
+      roots
+         .into_iter()
+         .map(|root_path| SearchFilesRequest {
+            root_path,
+            root_paths: None,
+            ..self.clone()
+         })
+         .collect()
+   }
+}
+
+pub const DEFAULT_MAX_MATCHES_PER_FILE: usize = 50;
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 1_000_000;
+
+/// Per-file limits from a `SearchFilesRequest`.
+#[derive(Debug, Clone, Copy)]
+pub struct SearchLimits {
+   pub max_matches_per_file: usize,
+   /// Files larger than this many bytes are skipped.
+   pub max_file_size: u64,
+}
+
+impl SearchLimits {
+   pub fn from_request(request: &SearchFilesRequest) -> Self {
+      Self {
+         max_matches_per_file: request
+            .max_matches_per_file
+            .unwrap_or(DEFAULT_MAX_MATCHES_PER_FILE)
+            .max(1),
+         max_file_size: request.max_file_size.unwrap_or(DEFAULT_MAX_FILE_SIZE),
+      }
+   }
+}
+
+pub enum FileSearchOutcome {
+   Matched(FileSearchResult),
+   NoMatches,
+   /// Too large, binary or unreadable.
+   Skipped,
+}
+
+/// Compiled form of the query options in a `SearchFilesRequest`.
+///
+/// Literal queries are escaped and run through the same regex engine so that case folding,
+/// whole-word boundaries and column offsets behave identically in both modes.
+pub struct SearchMatcher {
+   regex: Regex,
 }
 
-fn should_ignore_file(path: &Path) -> bool {
-   let ignored_dirs = [
-      "node_modules",
-      ".git",
-      ".next",
-      ".nuxt",
-      "dist",
-      "build",
-      "target",
-      ".cache",
-      ".vscode",
-      ".idea",
-      "__pycache__",
-      "vendor",
-      "coverage",
-      ".nyc_output",
-      ".pytest_cache",
-      ".turbo",
-      "out",
-      ".vercel",
-      ".DS_Store",
-   ];
-
-   let ignored_extensions = [
-      ".png",
-      ".jpg",
-      ".jpeg",
-      ".gif",
-      ".bmp",
-      ".ico",
-      ".svg",
-      ".mp4",
-      ".mp3",
-      ".wav",
-      ".avi",
-      ".mov",
-      ".pdf",
-      ".zip",
-      ".tar",
-      ".gz",
-      ".rar",
-      ".7z",
-      ".exe",
-      ".dll",
-      ".so",
-      ".dylib",
-      ".lock",
-      ".min.js",
-      ".min.css",
-      ".map",
-      ".log",
-      ".tmp",
-      ".temp",
-      ".swp",
-      ".swo",
-      ".bak",
-      ".cache",
-      ".pid",
-      ".seed",
-      ".pid.lock",
-      ".dat",
-      ".db",
-      ".sqlite",
-      ".wasm",
-   ];
-
-   let ignored_filenames = [
-      ".DS_Store",
-      "Thumbs.db",
-      "desktop.ini",
-      ".gitignore",
-      ".gitattributes",
-      ".eslintcache",
-      ".prettierignore",
-      ".npmrc",
-      ".yarnrc",
-      "npm-debug.log",
-      "yarn-error.log",
-      "yarn-debug.log",
-   ];
-
-   // Check if any component of the path contains an ignored directory
-   for component in path.components() {
-      if let Some(comp_str) = component.as_os_str().to_str() {
-         // Ignore hidden directories (starting with .)
-         if comp_str.starts_with('.') && ignored_dirs.contains(&comp_str) {
-            return true;
+impl SearchMatcher {
+   pub fn new(
+      query: &str,
+      use_regex: bool,
+      case_sensitive: bool,
+      whole_word: bool,
+   ) -> Result<Self, String> {
+      let pattern = if use_regex {
+         query.to_string()
+      } else {
+         regex::escape(query)
+      };
+
+      // Half word boundaries only look outward, so queries that begin or end with punctuation
+      // still match as whole words
+      let pattern = if whole_word {
+         format!(r"\b{{start-half}}(?:{})\b{{end-half}}", pattern)
+      } else {
+         pattern
+      };
+
+      let regex = RegexBuilder::new(&pattern)
+         .case_insensitive(!case_sensitive)
+         .build()
+         .map_err(|e| format!("Invalid search pattern: {}", e))?;
+
+      Ok(Self { regex })
+   }
+
+   pub fn regex(&self) -> &Regex {
+      &self.regex
+   }
+
+   pub fn from_request(request: &SearchFilesRequest) -> Result<Self, String> {
+      Self::new(
+         &request.query,
+         request.use_regex.unwrap_or(false),
+         request.case_sensitive.unwrap_or(false),
+         request.whole_word.unwrap_or(false),
+      )
+   }
+
+   /// Find all non-empty matches in a single line. Column offsets are byte offsets into `line`.
+   pub fn find_in_line(&self, line: &str, line_number: usize, limit: usize) -> Vec<SearchMatch> {
+      let mut matches = Vec::new();
+      let names: Vec<Option<&str>> = self.regex.capture_names().collect();
+
+      for captures in self.regex.captures_iter(line) {
+         if matches.len() >= limit {
+            break;
          }
-         if ignored_dirs.contains(&comp_str) {
-            return true;
+
+         let Some(whole) = captures.get(0) else {
+            continue;
+         };
+
+         // Patterns like `a*` can match the empty string at every position
+         if whole.is_empty() {
+            continue;
          }
+
+         let capture_spans = captures
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter_map(|(index, group)| {
+               group.map(|m| CaptureSpan {
+                  index,
+                  name: names.get(index).copied().flatten().map(String::from),
+                  column_start: m.start(),
+                  column_end: m.end(),
+               })
+            })
+            .collect();
+
+         matches.push(SearchMatch {
+            line_number,
+            line_content: line.to_string(),
+            column_start: whole.start(),
+            column_end: whole.end(),
+            captures: capture_spans,
+         });
       }
+
+      matches
    }
+}
 
-   // Check filename
-   if let Some(file_name) = path.file_name()
-      && let Some(name_str) = file_name.to_str()
-   {
-      // Ignore hidden files (starting with .)
-      if name_str.starts_with('.') {
-         return true;
-      }
-      if ignored_filenames.contains(&name_str) {
-         return true;
+/// Include/exclude glob lists from a `SearchFilesRequest`, matched against paths relative to
+/// the search root.
+pub struct PathFilter {
+   include: Option<GlobSet>,
+   exclude: Option<GlobSet>,
+}
+
+impl PathFilter {
+   pub fn new(include: &[String], exclude: &[String]) -> Result<Self, String> {
+      Ok(Self {
+         include: build_glob_set(include)?,
+         exclude: build_glob_set(exclude)?,
+      })
+   }
+
+   pub fn from_request(request: &SearchFilesRequest) -> Result<Self, String> {
+      Self::new(
+         request.include_globs.as_deref().unwrap_or_default(),
+         request.exclude_globs.as_deref().unwrap_or_default(),
+      )
+   }
+
+   pub fn is_excluded(&self, relative_path: &Path) -> bool {
+      self
+         .exclude
+         .as_ref()
+         .is_some_and(|set| set.is_match(relative_path))
+   }
+
+   pub fn is_included(&self, relative_path: &Path) -> bool {
+      if self.is_excluded(relative_path) {
+         return false;
       }
+      self
+         .include
+         .as_ref()
+         .is_none_or(|set| set.is_match(relative_path))
+   }
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<Option<GlobSet>, String> {
+   let patterns: Vec<&str> = patterns
+      .iter()
+      .map(|p| p.trim())
+      .filter(|p| !p.is_empty())
+      .collect();
+   if patterns.is_empty() {
+      return Ok(None);
    }
 
-   // Check file extension
-   if let Some(ext) = path.extension()
-      && let Some(ext_str) = ext.to_str()
+   let mut builder = GlobSetBuilder::new();
+   for pattern in patterns {
+      // A pattern without a separator (e.g. `*.rs`) applies at any depth
+      let pattern = pattern.trim_start_matches("./");
+      let normalized = if pattern.contains('/') {
+         pattern.trim_start_matches('/').to_string()
+      } else {
+         format!("**/{}", pattern)
+      };
+
+      let glob = GlobBuilder::new(&normalized)
+         .literal_separator(true)
+         .build()
+         .map_err(|e| format!("Invalid glob pattern '{}': {}", pattern, e))?;
+      builder.add(glob);
+   }
+
+   builder
+      .build()
+      .map(Some)
+      .map_err(|e| format!("Failed to build glob set: {}", e))
+}
+
+/// Search a single file within the given limits.
+pub fn search_file(
+   path: &Path,
+   matcher: &SearchMatcher,
+   limits: SearchLimits,
+) -> FileSearchOutcome {
+   if let Ok(metadata) = fs::metadata(path)
+      && metadata.len() > limits.max_file_size
    {
-      let ext_with_dot = format!(".{}", ext_str);
-      if ignored_extensions.contains(&ext_with_dot.as_str()) {
-         return true;
+      return FileSearchOutcome::Skipped;
+   }
+
+   // Skip binary files or files we can't read
+   let Ok(content) = fs::read_to_string(path) else {
+      return FileSearchOutcome::Skipped;
+   };
+   if content.contains('\0') {
+      return FileSearchOutcome::Skipped;
+   }
+
+   let limit = limits.max_matches_per_file;
+   let mut file_matches: Vec<SearchMatch> = Vec::new();
+
+   // Search through each line, limiting matches per file
+   for (line_idx, line) in content.lines().enumerate() {
+      let remaining = limit - file_matches.len();
+      file_matches.extend(matcher.find_in_line(line, line_idx + 1, remaining));
+
+      if file_matches.len() >= limit {
+         break;
       }
    }
 
-   false
+   if file_matches.is_empty() {
+      return FileSearchOutcome::NoMatches;
+   }
+
+   let total_matches = file_matches.len();
+   FileSearchOutcome::Matched(FileSearchResult {
+      file_path: path.to_string_lossy().to_string(),
+      matches: file_matches,
+      total_matches,
+   })
 }
 
-#[tauri::command]
-pub fn search_files_content(request: SearchFilesRequest) -> Result<Vec<FileSearchResult>, String> {
-   if request.query.is_empty() {
-      return Ok(Vec::new());
+/// Include globs are checked per file, after the walker has pruned ignored and excluded paths.
+pub struct CandidateFilter {
+   root: PathBuf,
+   path_filter: Arc<PathFilter>,
+}
+
+impl CandidateFilter {
+   pub fn from_request(request: &SearchFilesRequest) -> Result<Self, String> {
+      Ok(Self {
+         root: PathBuf::from(&request.root_path),
+         path_filter: Arc::new(PathFilter::from_request(request)?),
+      })
    }
 
-   let root = Path::new(&request.root_path);
-   if !root.exists() {
-      return Err("Root path does not exist".to_string());
+   pub fn accepts(&self, path: &Path) -> bool {
+      let relative = path.strip_prefix(&self.root).unwrap_or(path);
+      self.path_filter.is_included(relative)
    }
 
-   let case_sensitive = request.case_sensitive.unwrap_or(false);
-   let max_results = request.max_results.unwrap_or(100);
-   let mut results: Vec<FileSearchResult> = Vec::new();
+   /// Like `accepts`, for paths that did not come from the walker and so may sit inside an
+   /// excluded directory.
+   pub fn accepts_unwalked(&self, path: &Path) -> bool {
+      let relative = path.strip_prefix(&self.root).unwrap_or(path);
+      let excluded_parent = relative
+         .ancestors()
+         .skip(1)
+         .filter(|ancestor| !ancestor.as_os_str().is_empty())
+         .any(|ancestor| self.path_filter.is_excluded(ancestor));
+      !excluded_parent && self.path_filter.is_included(relative)
+   }
+}
 
-   let query_lower = if case_sensitive {
-      request.query.clone()
-   } else {
-      request.query.to_lowercase()
+/// Walker over the request root that applies its ignore rules and exclude globs, together with
+/// the filter for the files it yields.
+pub fn search_walker(
+   request: &SearchFilesRequest,
+) -> Result<(WalkBuilder, CandidateFilter), String> {
+   let filter = CandidateFilter::from_request(request)?;
+   if !filter.root.exists() {
+      return Err("Root path does not exist".to_string());
+   }
+
+   let ignore = WorkspaceIgnore::new(&filter.root, request.include_ignored.unwrap_or(false));
+   let mut builder = {
+      let path_filter = filter.path_filter.clone();
+      let root = filter.root.clone();
+      ignore.walk_builder(move |e| {
+         // Prune excluded directories early; include globs only apply to files
+         let relative = e.path().strip_prefix(&root).unwrap_or(e.path());
+         e.depth() == 0 || !path_filter.is_excluded(relative)
+      })
    };
+   builder.max_depth(Some(20));
 
-   for entry in WalkDir::new(root)
-      .max_depth(20)
-      .follow_links(false)
-      .into_iter()
-      .filter_entry(|e| !should_ignore_file(e.path()))
-   {
+   Ok((builder, filter))
+}
+
+/// Files under the request root that pass its ignore rules and glob filters.
+pub fn search_candidates(
+   request: &SearchFilesRequest,
+) -> Result<impl Iterator<Item = PathBuf> + use<>, String> {
+   let (builder, filter) = search_walker(request)?;
+
+   Ok(builder
+      .build()
+      .flatten()
+      .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+      .map(|entry| entry.into_path())
+      .filter(move |path| filter.accepts(path)))
+}
+
+/// Search every root of the request, using the trigram index of each root to narrow the files
+/// to scan when one is ready.
+pub fn search_workspace(
+   request: &SearchFilesRequest,
+   search_index: Option<&SearchIndexManager>,
+) -> Result<Vec<FileSearchResult>, String> {
+   if request.query.is_empty() {
+      return Ok(Vec::new());
+   }
+
+   let matcher = SearchMatcher::from_request(request)?;
+   let limits = SearchLimits::from_request(request);
+   let max_results = request.max_results.unwrap_or(100);
+
+   let mut results = Vec::new();
+   for root_request in request.per_root() {
       if results.len() >= max_results {
          break;
       }
+      let candidates: Box<dyn Iterator<Item = PathBuf>> =
+         match search_index.and_then(|index| index.candidates(&root_request)) {
+            Some(paths) => {
+               let filter = CandidateFilter::from_request(&root_request)?;
+               Box::new(
+                  paths
+                     .into_iter()
+                     .filter(move |path| filter.accepts_unwalked(path)),
+               )
+            }
+            None => Box::new(search_candidates(&root_request)?),
+         };
 
-      let entry = match entry {
-         Ok(e) => e,
-         Err(_) => continue,
-      };
+      results.extend(
+         candidates
+            .filter_map(|path| match search_file(&path, &matcher, limits) {
+               FileSearchOutcome::Matched(result) => Some(result),
+               _ => None,
+            })
+            .take(max_results - results.len()),
+      );
+   }
 
-      let path = entry.path();
+   Ok(results)
+}
 
-      // Skip directories
-      if path.is_dir() {
-         continue;
-      }
+#[tauri::command]
+pub fn search_files_content(
+   request: SearchFilesRequest,
+   search_index: State<'_, Arc<SearchIndexManager>>,
+   workspace: State<'_, Arc<WorkspaceManager>>,
+) -> Result<Vec<FileSearchResult>, String> {
+   search_workspace(&workspace.scope_search(request), Some(&search_index))
+}
 
-      // Skip files larger than 1MB
-      if let Ok(metadata) = fs::metadata(path)
-         && metadata.len() > 1_000_000
-      {
-         continue;
-      }
+/// Start a streaming search session. Results arrive as `search://results` events tagged with
+/// the returned session id.
+#[tauri::command]
+pub fn search_start(
+   request: SearchFilesRequest,
+   search_sessions: State<'_, Arc<SearchSessionManager>>,
+   workspace: State<'_, Arc<WorkspaceManager>>,
+) -> Result<String, String> {
+   search_sessions
+      .start(workspace.scope_search(request))
+      .map_err(|e| e.to_string())
+}
 
-      // Read file content
-      let content = match fs::read_to_string(path) {
-         Ok(c) => c,
-         Err(_) => continue, // Skip binary files or files we can't read
-      };
+#[tauri::command]
+pub fn search_cancel(session_id: String, search_sessions: State<'_, Arc<SearchSessionManager>>) {
+   search_sessions.cancel(&session_id);
+}
 
-      let mut file_matches: Vec<SearchMatch> = Vec::new();
+/// Build or load the trigram index for a workspace root. Progress is reported through
+/// `search-index://status` events.
+#[tauri::command]
+pub fn search_index_enable(
+   root_path: String,
+   search_index: State<'_, Arc<SearchIndexManager>>,
+) -> Result<SearchIndexStatus, String> {
+   search_index.enable(&root_path).map_err(|e| e.to_string())
+}
 
-      // Search through each line
-      for (line_idx, line) in content.lines().enumerate() {
-         let search_line = if case_sensitive {
-            line.to_string()
-         } else {
-            line.to_lowercase()
-         };
+#[tauri::command]
+pub fn search_index_disable(
+   root_path: String,
+   delete: Option<bool>,
+   search_index: State<'_, Arc<SearchIndexManager>>,
+) -> Result<(), String> {
+   search_index
+      .disable(&root_path, delete.unwrap_or(false))
+      .map_err(|e| e.to_string())
+}
 
-         // Find all occurrences in the line
-         let mut start_pos = 0;
-         while let Some(pos) = search_line[start_pos..].find(&query_lower) {
-            let actual_pos = start_pos + pos;
-            file_matches.push(SearchMatch {
-               line_number: line_idx + 1,
-               line_content: line.to_string(),
-               column_start: actual_pos,
-               column_end: actual_pos + request.query.len(),
-            });
-
-            start_pos = actual_pos + 1;
-
-            // Limit matches per file
-            if file_matches.len() >= 50 {
-               break;
-            }
-         }
+#[tauri::command]
+pub fn search_index_status(
+   root_path: String,
+   search_index: State<'_, Arc<SearchIndexManager>>,
+) -> SearchIndexStatus {
+   search_index.status(&root_path)
+}
 
-         if file_matches.len() >= 50 {
-            break;
-         }
-      }
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::test_utils::create_tree;
+   use tempfile::TempDir;
 
-      if !file_matches.is_empty() {
-         results.push(FileSearchResult {
-            file_path: path.to_string_lossy().to_string(),
-            matches: file_matches.clone(),
-            total_matches: file_matches.len(),
-         });
+   fn search(request: SearchFilesRequest) -> Result<Vec<FileSearchResult>, String> {
+      search_workspace(&request, None)
+   }
+
+   fn request(dir: &TempDir, query: &str) -> SearchFilesRequest {
+      SearchFilesRequest {
+         root_path: dir.path().to_string_lossy().to_string(),
+         root_paths: None,
+         query: query.to_string(),
+         case_sensitive: None,
+         max_results: None,
+         use_regex: None,
+         whole_word: None,
+         include_globs: None,
+         exclude_globs: None,
+         include_ignored: None,
+         max_matches_per_file: None,
+         max_file_size: None,
       }
    }
 
-   Ok(results)
+   fn matched_files(dir: &TempDir, results: &[FileSearchResult]) -> Vec<String> {
+      let mut files: Vec<String> = results
+         .iter()
+         .map(|r| {
+            Path::new(&r.file_path)
+               .strip_prefix(dir.path())
+               .unwrap()
+               .to_string_lossy()
+               .replace('\\', "/")
+         })
+         .collect();
+      files.sort();
+      files
+   }
+
+   #[test]
+   fn test_literal_search_escapes_regex_characters() {
+      let dir = create_tree(&[("a.txt", "call foo(bar) and Foo(bar)\nfoo.bar")]);
+
+      let results = search(request(&dir, "foo(bar)")).unwrap();
+      assert_eq!(results.len(), 1);
+      assert_eq!(results[0].total_matches, 2);
+      assert_eq!(results[0].matches[0].column_start, 5);
+      assert_eq!(results[0].matches[0].column_end, 13);
+
+      let mut req = request(&dir, "foo(bar)");
+      req.case_sensitive = Some(true);
+      let results = search(req).unwrap();
+      assert_eq!(results[0].total_matches, 1);
+   }
+
+   #[test]
+   fn test_regex_search_reports_capture_spans() {
+      let dir = create_tree(&[("lib.rs", "fn alpha() {}\nlet x = 1;\nfn beta_two() {}")]);
+
+      let mut req = request(&dir, r"fn (?P<name>\w+)\((\w*)\)");
+      req.use_regex = Some(true);
+      let results = search(req).unwrap();
+
+      assert_eq!(results.len(), 1);
+      let matches = &results[0].matches;
+      assert_eq!(matches.len(), 2);
+      assert_eq!(matches[0].line_number, 1);
+      assert_eq!(matches[1].line_number, 3);
+
+      let name = &matches[1].captures[0];
+      assert_eq!(name.index, 1);
+      assert_eq!(name.name.as_deref(), Some("name"));
+      assert_eq!(
+         &matches[1].line_content[name.column_start..name.column_end],
+         "beta_two"
+      );
+
+      let args = &matches[1].captures[1];
+      assert_eq!(args.index, 2);
+      assert_eq!(args.name, None);
+      assert_eq!(args.column_start, args.column_end);
+   }
+
+   #[test]
+   fn test_invalid_regex_is_an_error() {
+      let dir = create_tree(&[("a.txt", "text")]);
+      let mut req = request(&dir, "(unclosed");
+      req.use_regex = Some(true);
+      assert!(search(req).is_err());
+   }
+
+   #[test]
+   fn test_whole_word_search() {
+      let dir = create_tree(&[("a.txt", "count counter recount count_all (count)")]);
+
+      let mut req = request(&dir, "count");
+      req.whole_word = Some(true);
+      let results = search(req).unwrap();
+      let columns: Vec<usize> = results[0].matches.iter().map(|m| m.column_start).collect();
+      assert_eq!(columns, vec![0, 33]);
+
+      // Boundaries are only checked on the outside of the query
+      let mut req = request(&dir, "(count)");
+      req.whole_word = Some(true);
+      let results = search(req).unwrap();
+      assert_eq!(results[0].total_matches, 1);
+
+      let mut req = request(&dir, r"count\w*");
+      req.use_regex = Some(true);
+      req.whole_word = Some(true);
+      let results = search(req).unwrap();
+      assert_eq!(results[0].total_matches, 4);
+   }
+
+   #[test]
+   fn test_include_and_exclude_globs() {
+      let dir = create_tree(&[
+         ("src/main.rs", "needle"),
+         ("src/nested/mod.rs", "needle"),
+         ("src/fixtures/sample.rs", "needle"),
+         ("src/notes.md", "needle"),
+         ("tests/it.rs", "needle"),
+      ]);
+
+      let mut req = request(&dir, "needle");
+      req.include_globs = Some(vec!["src/**/*.rs".to_string()]);
+      req.exclude_globs = Some(vec!["**/fixtures".to_string()]);
+      let results = search(req).unwrap();
+      assert_eq!(
+         matched_files(&dir, &results),
+         vec!["src/main.rs", "src/nested/mod.rs"]
+      );
+
+      // Patterns without a separator match at any depth
+      let mut req = request(&dir, "needle");
+      req.include_globs = Some(vec!["*.rs".to_string()]);
+      req.exclude_globs = Some(vec!["src/fixtures/**".to_string()]);
+      let results = search(req).unwrap();
+      assert_eq!(
+         matched_files(&dir, &results),
+         vec!["src/main.rs", "src/nested/mod.rs", "tests/it.rs"]
+      );
+   }
+
+   #[test]
+   fn test_all_modes_combine() {
+      let dir = create_tree(&[
+         (
+            "src/a.ts",
+            "const userId = getUser(id);\nconst userIdentity = 1;",
+         ),
+         ("src/b.js", "const userId = 2;"),
+         ("fixtures/c.ts", "const userId = 3;"),
+      ]);
+
+      let mut req = request(&dir, r"(user)Id");
+      req.use_regex = Some(true);
+      req.whole_word = Some(true);
+      req.case_sensitive = Some(true);
+      req.include_globs = Some(vec!["*.ts".to_string()]);
+      req.exclude_globs = Some(vec!["fixtures/**".to_string()]);
+      let results = search(req).unwrap();
+
+      assert_eq!(matched_files(&dir, &results), vec!["src/a.ts"]);
+      assert_eq!(results[0].total_matches, 1);
+      let capture = &results[0].matches[0].captures[0];
+      assert_eq!((capture.column_start, capture.column_end), (6, 10));
+   }
+
+   #[test]
+   fn test_gitignored_files_are_skipped_unless_requested() {
+      let dir = create_tree(&[
+         (".gitignore", "dist/\n"),
+         (".env.example", "needle"),
+         ("dist/app.js", "needle"),
+         ("src/app.ts", "needle"),
+      ]);
+
+      let results = search(request(&dir, "needle")).unwrap();
+      assert_eq!(
+         matched_files(&dir, &results),
+         vec![".env.example", "src/app.ts"]
+      );
+
+      let mut req = request(&dir, "needle");
+      req.include_ignored = Some(true);
+      let results = search(req).unwrap();
+      assert_eq!(
+         matched_files(&dir, &results),
+         vec![".env.example", "dist/app.js", "src/app.ts"]
+      );
+   }
+
+   #[test]
+   fn test_request_limits() {
+      let dir = create_tree(&[("small.txt", "x x x x"), ("large.txt", &"x ".repeat(100))]);
+
+      let mut req = request(&dir, "x");
+      req.max_matches_per_file = Some(3);
+      req.max_file_size = Some(50);
+      let results = search(req).unwrap();
+
+      assert_eq!(matched_files(&dir, &results), vec!["small.txt"]);
+      assert_eq!(results[0].total_matches, 3);
+   }
 }
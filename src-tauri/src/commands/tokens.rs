@@ -1,5 +1,6 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use tree_sitter::Language;
 use tree_sitter_highlight::{HighlightConfiguration, HighlightEvent, Highlighter};
 
 // Import new language parsers
@@ -376,7 +377,7 @@ fn get_language_config(language_name: &str) -> Result<HighlightConfiguration> {
    }
 }
 
-fn map_highlight_to_class(highlight_name: &str) -> (&str, &str) {
+pub fn map_highlight_to_class(highlight_name: &str) -> (&str, &str) {
    match highlight_name {
       "keyword" | "keyword.control" | "keyword.function" | "keyword.operator"
       | "keyword.return" => ("keyword", "token-keyword"),
@@ -415,10 +416,9 @@ fn map_highlight_to_class(highlight_name: &str) -> (&str, &str) {
    }
 }
 
-#[tauri::command]
-pub async fn get_tokens(content: String, file_extension: String) -> Result<Vec<Token>, String> {
-   // Determine language from extension
-   let language = match file_extension.as_str() {
+/// Grammar name for a file extension, as accepted by [`get_language`] and [`tokenize_content`].
+pub fn language_for_extension(file_extension: &str) -> Option<&'static str> {
+   let language = match file_extension {
       "js" | "jsx" => "javascript",
       "ts" => "typescript",
       "tsx" => "tsx",
@@ -446,8 +446,42 @@ pub async fn get_tokens(content: String, file_extension: String) -> Result<Vec<T
       // "ex" | "exs" => "elixir",
       // "dart" => "dart",
       // "sql" => "sql",
-      _ => return Err(format!("Unsupported file extension: {}", file_extension)),
+      _ => return None,
+   };
+   Some(language)
+}
+
+/// The compiled grammar for a language, for callers that need the syntax tree itself.
+pub fn get_language(language_name: &str) -> Result<Language> {
+   let language = match language_name {
+      "javascript" | "js" => tree_sitter_javascript::LANGUAGE.into(),
+      "typescript" | "ts" => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+      "tsx" => tree_sitter_typescript::LANGUAGE_TSX.into(),
+      "json" => tree_sitter_json::LANGUAGE.into(),
+      "yaml" | "yml" => tree_sitter_yaml::LANGUAGE.into(),
+      "go" => tree_sitter_go::LANGUAGE.into(),
+      "ruby" | "rb" => tree_sitter_ruby::LANGUAGE.into(),
+      "rust" | "rs" => tree_sitter_rust::LANGUAGE.into(),
+      "erb" | "html.erb" => tree_sitter_embedded_template::LANGUAGE.into(),
+      "python" | "py" => tree_sitter_python::LANGUAGE.into(),
+      "html" => tree_sitter_html::LANGUAGE.into(),
+      "css" => tree_sitter_css::LANGUAGE.into(),
+      "markdown" | "md" => tree_sitter_md::LANGUAGE.into(),
+      "bash" | "sh" => tree_sitter_bash::LANGUAGE.into(),
+      "toml" => tree_sitter_toml_ng::LANGUAGE.into(),
+      "java" => tree_sitter_java::LANGUAGE.into(),
+      "c" => tree_sitter_c::LANGUAGE.into(),
+      "cpp" | "cxx" | "cc" => tree_sitter_cpp::LANGUAGE.into(),
+      "php" => tree_sitter_php::LANGUAGE_PHP.into(),
+      _ => anyhow::bail!("Unsupported language: {}", language_name),
    };
+   Ok(language)
+}
+
+#[tauri::command]
+pub async fn get_tokens(content: String, file_extension: String) -> Result<Vec<Token>, String> {
+   let language = language_for_extension(&file_extension)
+      .ok_or_else(|| format!("Unsupported file extension: {}", file_extension))?;
 
    tokenize_content(&content, language).map_err(|e| format!("Failed to tokenize: {e}"))
 }
@@ -502,28 +536,8 @@ pub async fn get_tokens_range(
    start_line: usize,
    end_line: usize,
 ) -> Result<Vec<Token>, String> {
-   let language = match file_extension.as_str() {
-      "js" | "jsx" => "javascript",
-      "ts" => "typescript",
-      "tsx" => "tsx",
-      "json" => "json",
-      "yml" | "yaml" => "yaml",
-      "go" => "go",
-      "rb" | "ruby" => "ruby",
-      "rs" => "rust",
-      "erb" | "html.erb" => "erb",
-      "py" => "python",
-      "html" | "htm" => "html",
-      "css" => "css",
-      "md" | "markdown" => "markdown",
-      "sh" | "bash" => "bash",
-      "toml" => "toml",
-      "java" => "java",
-      "c" => "c",
-      "cpp" | "cxx" | "cc" | "c++" | "hpp" | "hxx" | "h++" => "cpp",
-      "php" => "php",
-      _ => return Err(format!("Unsupported file extension: {}", file_extension)),
-   };
+   let language = language_for_extension(&file_extension)
+      .ok_or_else(|| format!("Unsupported file extension: {}", file_extension))?;
 
    // Split content into lines
    let lines: Vec<&str> = content.lines().collect();
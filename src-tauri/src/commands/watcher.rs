@@ -1,6 +1,11 @@
-use crate::file_watcher::FileWatcher;
+use crate::{
+   commands::workspace::apply_roots_change,
+   file_watcher::FileWatcher,
+   lsp::LspManager,
+   workspace::{WorkspaceManager, WorkspaceRoot},
+};
 use std::sync::Arc;
-use tauri::command;
+use tauri::{AppHandle, command};
 
 #[command]
 pub async fn start_watching(
@@ -21,12 +26,21 @@ pub async fn stop_watching(
    file_watcher.stop_watching(path).map_err(|e| e.to_string())
 }
 
+/// Open `path` as a single-root workspace and start watching it recursively.
 #[command]
 pub async fn set_project_root(
    path: String,
+   app_handle: AppHandle,
    file_watcher: tauri::State<'_, Arc<FileWatcher>>,
+   workspace: tauri::State<'_, Arc<WorkspaceManager>>,
+   lsp_manager: tauri::State<'_, LspManager>,
 ) -> Result<(), String> {
-   // Start watching the project root recursively
+   let change = workspace
+      .set_roots(vec![WorkspaceRoot::from_path(&path)])
+      .map_err(|e| e.to_string())?;
+   apply_roots_change(&change, &app_handle, &file_watcher, &lsp_manager).await;
+
+   // The root may already be in the workspace but not yet watched
    file_watcher
       .watch_path(path)
       .await
@@ -0,0 +1,80 @@
+use crate::{
+   file_watcher::FileWatcher,
+   lsp::LspManager,
+   workspace::{WorkspaceManager, WorkspaceRoot, WorkspaceRootsChange},
+};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, State, command};
+
+#[command]
+pub fn workspace_get_roots(workspace: State<'_, Arc<WorkspaceManager>>) -> Vec<WorkspaceRoot> {
+   workspace.roots()
+}
+
+#[command]
+pub async fn workspace_set_roots(
+   roots: Vec<WorkspaceRoot>,
+   app_handle: AppHandle,
+   workspace: State<'_, Arc<WorkspaceManager>>,
+   file_watcher: State<'_, Arc<FileWatcher>>,
+   lsp_manager: State<'_, LspManager>,
+) -> Result<WorkspaceRootsChange, String> {
+   let change = workspace.set_roots(roots).map_err(|e| e.to_string())?;
+   apply_roots_change(&change, &app_handle, &file_watcher, &lsp_manager).await;
+   Ok(change)
+}
+
+/// Add a folder to the workspace, named after the folder unless `name` is given.
+#[command]
+pub async fn workspace_add_root(
+   path: String,
+   name: Option<String>,
+   app_handle: AppHandle,
+   workspace: State<'_, Arc<WorkspaceManager>>,
+   file_watcher: State<'_, Arc<FileWatcher>>,
+   lsp_manager: State<'_, LspManager>,
+) -> Result<WorkspaceRootsChange, String> {
+   let mut root = WorkspaceRoot::from_path(&path);
+   if let Some(name) = name {
+      root.name = name;
+   }
+   let change = workspace.add_root(root).map_err(|e| e.to_string())?;
+   apply_roots_change(&change, &app_handle, &file_watcher, &lsp_manager).await;
+   Ok(change)
+}
+
+#[command]
+pub async fn workspace_remove_root(
+   name: String,
+   app_handle: AppHandle,
+   workspace: State<'_, Arc<WorkspaceManager>>,
+   file_watcher: State<'_, Arc<FileWatcher>>,
+   lsp_manager: State<'_, LspManager>,
+) -> Result<WorkspaceRootsChange, String> {
+   let change = workspace.remove_root(&name).map_err(|e| e.to_string())?;
+   apply_roots_change(&change, &app_handle, &file_watcher, &lsp_manager).await;
+   Ok(change)
+}
+
+/// Watch added roots, stop watching removed ones, update running language servers and emit
+/// `workspace://roots-changed`.
+pub async fn apply_roots_change(
+   change: &WorkspaceRootsChange,
+   app_handle: &AppHandle,
+   file_watcher: &FileWatcher,
+   lsp_manager: &LspManager,
+) {
+   for root in &change.removed {
+      if let Err(e) = file_watcher.stop_watching(root.path.clone()) {
+         log::debug!("[Workspace] Root {} was not watched: {}", root.path, e);
+      }
+   }
+   for root in &change.added {
+      if let Err(e) = file_watcher.watch_path(root.path.clone()).await {
+         log::warn!("[Workspace] Failed to watch root {}: {}", root.path, e);
+      }
+   }
+
+   lsp_manager.update_workspace_folders(change);
+   let _ = app_handle.emit("workspace://roots-changed", change);
+}
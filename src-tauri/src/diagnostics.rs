@@ -0,0 +1,356 @@
+use crate::lsp::transport::editor_path;
+use lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Url};
+use serde::{Deserialize, Serialize};
+use std::{
+   collections::HashMap,
+   path::{Path, PathBuf},
+   sync::{Arc, Mutex},
+};
+use tauri::{AppHandle, Emitter};
+
+type Entries = HashMap<Url, HashMap<String, Vec<Diagnostic>>>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct DiagnosticCounts {
+   pub errors: usize,
+   pub warnings: usize,
+   pub information: usize,
+   pub hints: usize,
+}
+
+impl DiagnosticCounts {
+   fn add(&mut self, diagnostic: &Diagnostic) {
+      match severity_rank(diagnostic.severity) {
+         1 => self.errors += 1,
+         2 => self.warnings += 1,
+         3 => self.information += 1,
+         _ => self.hints += 1,
+      }
+   }
+
+   fn merge(&mut self, other: DiagnosticCounts) {
+      self.errors += other.errors;
+      self.warnings += other.warnings;
+      self.information += other.information;
+      self.hints += other.hints;
+   }
+}
+
+/// Narrows a query. Every criterion left out matches everything.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DiagnosticsFilter {
+   /// A file, or a directory to match every file below.
+   pub path: Option<PathBuf>,
+   /// The least severe level to include, e.g. warnings to get errors and warnings.
+   pub min_severity: Option<DiagnosticSeverity>,
+   /// Producers, such as a language server name or `eslint`.
+   pub sources: Option<Vec<String>>,
+   pub code: Option<String>,
+   /// Case-insensitive text the message must contain.
+   pub message: Option<String>,
+}
+
+impl DiagnosticsFilter {
+   fn matches_file(&self, source: &str, file_path: Option<&Path>) -> bool {
+      if let Some(sources) = &self.sources
+         && !sources.iter().any(|s| s == source)
+      {
+         return false;
+      }
+      match (&self.path, file_path) {
+         (Some(path), Some(file_path)) => file_path.starts_with(path),
+         (Some(_), None) => false,
+         (None, _) => true,
+      }
+   }
+
+   fn matches(&self, diagnostic: &Diagnostic) -> bool {
+      if let Some(min_severity) = self.min_severity
+         && severity_rank(diagnostic.severity) > severity_rank(Some(min_severity))
+      {
+         return false;
+      }
+      if let Some(code) = &self.code
+         && diagnostic.code.as_ref().map(code_string).as_deref() != Some(code.as_str())
+      {
+         return false;
+      }
+      if let Some(message) = &self.message
+         && !diagnostic
+            .message
+            .to_lowercase()
+            .contains(&message.to_lowercase())
+      {
+         return false;
+      }
+      true
+   }
+}
+
+/// The diagnostics one source reported for one file.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileDiagnostics {
+   pub uri: Url,
+   pub file_path: Option<PathBuf>,
+   pub source: String,
+   pub diagnostics: Vec<Diagnostic>,
+   pub counts: DiagnosticCounts,
+}
+
+/// Payload of `diagnostics://changed`, sent whenever a source reports new diagnostics for a file.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticsChangedEvent {
+   pub uri: Url,
+   pub file_path: Option<PathBuf>,
+   pub source: String,
+   pub counts: DiagnosticCounts,
+   /// Counts across the whole store, for the status bar.
+   pub totals: DiagnosticCounts,
+}
+
+/// Every diagnostic known to the backend, by file and by the source that reported it. Each
+/// report from a source replaces what that source said about the file before.
+#[derive(Clone)]
+pub struct DiagnosticsStore {
+   entries: Arc<Mutex<Entries>>,
+   app_handle: Option<AppHandle>,
+}
+
+impl DiagnosticsStore {
+   pub fn new(app_handle: Option<AppHandle>) -> Self {
+      Self {
+         entries: Arc::default(),
+         app_handle,
+      }
+   }
+
+   pub fn set(&self, uri: Url, source: &str, diagnostics: Vec<Diagnostic>) {
+      let mut counts = DiagnosticCounts::default();
+      diagnostics.iter().for_each(|d| counts.add(d));
+
+      let totals = {
+         let mut entries = self.entries.lock().unwrap();
+         let by_source = entries.entry(uri.clone()).or_default();
+         let unchanged = diagnostics.is_empty() && !by_source.contains_key(source);
+         if diagnostics.is_empty() {
+            by_source.remove(source);
+         } else {
+            by_source.insert(source.to_string(), diagnostics);
+         }
+         if by_source.is_empty() {
+            entries.remove(&uri);
+         }
+         if unchanged {
+            return;
+         }
+         totals(&entries)
+      };
+
+      if let Some(app_handle) = &self.app_handle {
+         let _ = app_handle.emit(
+            "diagnostics://changed",
+            &DiagnosticsChangedEvent {
+               file_path: editor_path(&uri),
+               uri,
+               source: source.to_string(),
+               counts,
+               totals,
+            },
+         );
+      }
+   }
+
+   /// Diagnostics for a file, from one source or from all of them.
+   pub fn get(&self, uri: &Url, source: Option<&str>) -> Vec<Diagnostic> {
+      let entries = self.entries.lock().unwrap();
+      let Some(by_source) = entries.get(uri) else {
+         return Vec::new();
+      };
+      match source {
+         Some(source) => by_source.get(source).cloned().unwrap_or_default(),
+         None => by_source.values().flatten().cloned().collect(),
+      }
+   }
+
+   /// Drop everything a source reported, or only what it reported below `path`.
+   pub fn clear(&self, source: &str, path: Option<&Path>) {
+      let cleared: Vec<Url> = {
+         let entries = self.entries.lock().unwrap();
+         entries
+            .iter()
+            .filter(|(uri, by_source)| {
+               by_source.contains_key(source)
+                  && path
+                     .is_none_or(|path| editor_path(uri).is_some_and(|file| file.starts_with(path)))
+            })
+            .map(|(uri, _)| uri.clone())
+            .collect()
+      };
+      for uri in cleared {
+         self.set(uri, source, Vec::new());
+      }
+   }
+
+   /// Matching diagnostics, sorted by file and position.
+   pub fn query(&self, filter: &DiagnosticsFilter) -> Vec<FileDiagnostics> {
+      let entries = self.entries.lock().unwrap();
+      let mut results = Vec::new();
+      for (uri, by_source) in entries.iter() {
+         let file_path = editor_path(uri);
+         for (source, diagnostics) in by_source {
+            if !filter.matches_file(source, file_path.as_deref()) {
+               continue;
+            }
+            let mut diagnostics: Vec<Diagnostic> = diagnostics
+               .iter()
+               .filter(|d| filter.matches(d))
+               .cloned()
+               .collect();
+            if diagnostics.is_empty() {
+               continue;
+            }
+            diagnostics.sort_by_key(|d| (d.range.start.line, d.range.start.character));
+            let mut counts = DiagnosticCounts::default();
+            diagnostics.iter().for_each(|d| counts.add(d));
+            results.push(FileDiagnostics {
+               uri: uri.clone(),
+               file_path: file_path.clone(),
+               source: source.clone(),
+               diagnostics,
+               counts,
+            });
+         }
+      }
+      results.sort_by(|a, b| (a.uri.as_str(), &a.source).cmp(&(b.uri.as_str(), &b.source)));
+      results
+   }
+
+   pub fn counts(&self, filter: &DiagnosticsFilter) -> DiagnosticCounts {
+      let mut counts = DiagnosticCounts::default();
+      for file in self.query(filter) {
+         counts.merge(file.counts);
+      }
+      counts
+   }
+}
+
+fn totals(entries: &Entries) -> DiagnosticCounts {
+   let mut counts = DiagnosticCounts::default();
+   entries
+      .values()
+      .flat_map(|by_source| by_source.values().flatten())
+      .for_each(|d| counts.add(d));
+   counts
+}
+
+/// 1 for errors up to 4 for hints. Diagnostics without a severity are treated as errors.
+fn severity_rank(severity: Option<DiagnosticSeverity>) -> u8 {
+   match severity {
+      Some(DiagnosticSeverity::WARNING) => 2,
+      Some(DiagnosticSeverity::INFORMATION) => 3,
+      Some(DiagnosticSeverity::HINT) => 4,
+      _ => 1,
+   }
+}
+
+fn code_string(code: &NumberOrString) -> String {
+   match code {
+      NumberOrString::Number(number) => number.to_string(),
+      NumberOrString::String(string) => string.clone(),
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use lsp_types::{Position, Range};
+
+   fn diagnostic(line: u32, severity: DiagnosticSeverity, code: &str, message: &str) -> Diagnostic {
+      Diagnostic {
+         range: Range::new(Position::new(line, 0), Position::new(line, 1)),
+         severity: Some(severity),
+         code: Some(NumberOrString::String(code.to_string())),
+         message: message.to_string(),
+         ..Default::default()
+      }
+   }
+
+   fn uri(path: &str) -> Url {
+      Url::from_file_path(path).unwrap()
+   }
+
+   #[test]
+   fn test_query_and_count_by_source_path_and_severity() {
+      let store = DiagnosticsStore::new(None);
+      store.set(
+         uri("/project/src/main.rs"),
+         "rust-analyzer",
+         vec![
+            diagnostic(
+               9,
+               DiagnosticSeverity::WARNING,
+               "unused_variables",
+               "unused variable",
+            ),
+            diagnostic(2, DiagnosticSeverity::ERROR, "E0308", "mismatched types"),
+         ],
+      );
+      store.set(
+         uri("/project/src/main.rs"),
+         "clippy",
+         vec![diagnostic(
+            4,
+            DiagnosticSeverity::HINT,
+            "needless_return",
+            "Unneeded return",
+         )],
+      );
+      store.set(
+         uri("/project/web/app.ts"),
+         "eslint",
+         vec![diagnostic(
+            0,
+            DiagnosticSeverity::ERROR,
+            "no-undef",
+            "x is not defined",
+         )],
+      );
+
+      let all = store.counts(&DiagnosticsFilter::default());
+      assert_eq!((all.errors, all.warnings, all.hints), (2, 1, 1));
+      assert_eq!(store.get(&uri("/project/src/main.rs"), None).len(), 3);
+
+      let files = store.query(&DiagnosticsFilter {
+         path: Some(PathBuf::from("/project/src")),
+         min_severity: Some(DiagnosticSeverity::WARNING),
+         ..Default::default()
+      });
+      assert_eq!(files.len(), 1);
+      assert_eq!(files[0].source, "rust-analyzer");
+      let lines: Vec<_> = files[0]
+         .diagnostics
+         .iter()
+         .map(|d| d.range.start.line)
+         .collect();
+      assert_eq!(lines, [2, 9]);
+
+      let by_code = store.query(&DiagnosticsFilter {
+         code: Some("no-undef".to_string()),
+         message: Some("NOT DEFINED".to_string()),
+         ..Default::default()
+      });
+      assert_eq!(by_code.len(), 1);
+      assert_eq!(
+         by_code[0].file_path,
+         Some(PathBuf::from("/project/web/app.ts"))
+      );
+
+      // A new report replaces the old one and clearing only affects its source
+      store.set(uri("/project/src/main.rs"), "rust-analyzer", Vec::new());
+      store.clear("eslint", Some(Path::new("/project")));
+      let remaining = store.query(&DiagnosticsFilter::default());
+      assert_eq!(remaining.len(), 1);
+      assert_eq!(remaining[0].source, "clippy");
+   }
+}
@@ -0,0 +1,510 @@
+use crate::workspace_ignore::WorkspaceIgnore;
+use anyhow::{Result, bail};
+use nucleo::{
+   Config, Matcher, Nucleo, Utf32Str,
+   pattern::{CaseMatching, Normalization, Pattern},
+};
+use serde::Serialize;
+use std::{
+   collections::{HashMap, HashSet},
+   path::{MAIN_SEPARATOR, Path, PathBuf},
+   sync::{
+      Arc, Mutex,
+      atomic::{AtomicBool, Ordering},
+   },
+   thread,
+};
+use tauri::{AppHandle, Emitter};
+use uuid::Uuid;
+
+/// Paths are injected into the matcher in batches of this size while the workspace is walked.
+const WALK_BATCH_SIZE: usize = 1000;
+/// How long a query waits on each matcher tick before checking whether it has finished.
+const TICK_TIMEOUT_MS: u64 = 10;
+const DEFAULT_LIMIT: usize = 50;
+/// Matcher results considered for path-aware re-ranking, as a multiple of the limit.
+const RERANK_FACTOR: usize = 4;
+
+/// Adjustments applied on top of the raw nucleo score of the relative path.
+struct PathScoring {
+   /// Multiplier for the score of the query against the file name alone.
+   basename_weight: i64,
+   /// Subtracted once per directory between the root and the file.
+   depth_penalty: i64,
+   /// Subtracted when the path passes through a vendored or generated directory.
+   vendor_penalty: i64,
+}
+
+const PATH_SCORING: PathScoring = PathScoring {
+   basename_weight: 1,
+   depth_penalty: 2,
+   vendor_penalty: 80,
+};
+
+/// Directories whose contents are rarely what the user is looking for, even when not ignored.
+const VENDOR_DIRS: &[&str] = &[
+   "node_modules",
+   "vendor",
+   "third_party",
+   "bower_components",
+   "Pods",
+   "target",
+   "dist",
+   "build",
+   "out",
+   ".venv",
+   "venv",
+   "__pycache__",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileFinderMatch {
+   pub path: String,
+   pub relative_path: String,
+   pub score: i64,
+   /// Character indices into `relative_path` to highlight.
+   pub indices: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileFinderResults {
+   pub matches: Vec<FileFinderMatch>,
+   pub total_matches: usize,
+   pub total_files: usize,
+   /// Whether the initial walk is still running, so more files may match later.
+   pub indexing: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileFinderUpdatedEvent {
+   pub session_id: String,
+   pub total_files: usize,
+   pub indexing: bool,
+}
+
+struct FinderState {
+   nucleo: Nucleo<String>,
+   /// Relative paths of every file in the workspace, the source of truth for the matcher items.
+   files: HashSet<String>,
+   query: String,
+   /// Set when files were removed; nucleo cannot drop items, so the matcher is refilled before
+   /// the next query.
+   needs_restart: bool,
+}
+
+impl FinderState {
+   fn inject(&self, paths: impl IntoIterator<Item = String>) {
+      let injector = self.nucleo.injector();
+      for path in paths {
+         injector.push(path, |path, columns| columns[0] = path.as_str().into());
+      }
+   }
+
+   fn add(&mut self, paths: Vec<String>) -> bool {
+      let added: Vec<String> = paths
+         .into_iter()
+         .filter(|path| self.files.insert(path.clone()))
+         .collect();
+      let changed = !added.is_empty();
+      if !self.needs_restart {
+         self.inject(added);
+      }
+      changed
+   }
+
+   fn remove(&mut self, relative: &str) -> bool {
+      let prefix = format!("{relative}{MAIN_SEPARATOR}");
+      let before = self.files.len();
+      self
+         .files
+         .retain(|path| path != relative && !path.starts_with(&prefix));
+      let changed = self.files.len() != before;
+      self.needs_restart |= changed;
+      changed
+   }
+}
+
+/// A workspace file list held in a nucleo matcher. The list is built by walking the root once
+/// with its ignore rules and then kept current from file watcher events.
+pub struct FileFinderSession {
+   id: String,
+   root: PathBuf,
+   ignore: WorkspaceIgnore,
+   state: Mutex<FinderState>,
+   indexing: AtomicBool,
+   closed: AtomicBool,
+}
+
+impl FileFinderSession {
+   fn new(id: String, root: PathBuf) -> Self {
+      let mut config = Config::DEFAULT;
+      config.set_match_paths();
+
+      Self {
+         id,
+         ignore: WorkspaceIgnore::new(root.clone(), false),
+         root,
+         state: Mutex::new(FinderState {
+            nucleo: Nucleo::new(config, Arc::new(|| {}), None, 1),
+            files: HashSet::new(),
+            query: String::new(),
+            needs_restart: false,
+         }),
+         indexing: AtomicBool::new(true),
+         closed: AtomicBool::new(false),
+      }
+   }
+
+   /// Walk the root and feed every file into the matcher, calling `on_batch` after each batch.
+   fn index(&self, mut on_batch: impl FnMut()) {
+      let mut batch = Vec::with_capacity(WALK_BATCH_SIZE);
+      for entry in self.ignore.walk_builder(|_| true).build().flatten() {
+         if self.closed.load(Ordering::Relaxed) {
+            return;
+         }
+         if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+         }
+         if let Some(relative) = self.relative(entry.path()) {
+            batch.push(relative);
+         }
+         if batch.len() >= WALK_BATCH_SIZE {
+            self.state.lock().unwrap().add(std::mem::take(&mut batch));
+            on_batch();
+         }
+      }
+
+      self.state.lock().unwrap().add(batch);
+      self.indexing.store(false, Ordering::Relaxed);
+      on_batch();
+   }
+
+   /// Apply a created, modified or deleted path. Returns whether the file list changed.
+   pub fn handle_change(&self, path: &Path) -> bool {
+      let Some(relative) = self.relative(path) else {
+         return false;
+      };
+      if relative.is_empty() {
+         return false;
+      }
+      self.ignore.invalidate(path);
+
+      if path.is_file() {
+         if self.ignore.is_ignored(path, false) {
+            return self.state.lock().unwrap().remove(&relative);
+         }
+         self.state.lock().unwrap().add(vec![relative])
+      } else if path.is_dir() {
+         // A directory moved into place may not report events for its children
+         let files: Vec<String> = self
+            .ignore
+            .walk_builder_from(path, |_| true)
+            .build()
+            .flatten()
+            .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+            .filter_map(|entry| self.relative(entry.path()))
+            .collect();
+         self.state.lock().unwrap().add(files)
+      } else {
+         self.state.lock().unwrap().remove(&relative)
+      }
+   }
+
+   /// Score every file against `query` and return the best `limit` matches.
+   pub fn query(&self, query: &str, limit: usize) -> FileFinderResults {
+      let mut state = self.state.lock().unwrap();
+      let state = &mut *state;
+
+      if state.needs_restart {
+         state.nucleo.restart(true);
+         state.inject(state.files.iter().cloned());
+         state.needs_restart = false;
+         state.query.clear();
+      }
+
+      let total_files = state.files.len();
+      let indexing = self.indexing.load(Ordering::Relaxed);
+
+      if query.is_empty() {
+         let mut paths: Vec<&String> = state.files.iter().collect();
+         paths.sort_by_cached_key(|path| (-path_adjustment(path), path.to_string()));
+         return FileFinderResults {
+            matches: paths
+               .into_iter()
+               .take(limit)
+               .map(|relative| self.result(relative, 0, Vec::new()))
+               .collect(),
+            total_matches: total_files,
+            total_files,
+            indexing,
+         };
+      }
+
+      // Typing more characters only narrows the previous matches
+      let append = !state.query.is_empty() && query.starts_with(state.query.as_str());
+      state
+         .nucleo
+         .pattern
+         .reparse(0, query, CaseMatching::Smart, Normalization::Smart, append);
+      state.query = query.to_string();
+      while state.nucleo.tick(TICK_TIMEOUT_MS).running {}
+
+      let snapshot = state.nucleo.snapshot();
+      let pattern = snapshot.pattern().column_pattern(0);
+      let total_matches = snapshot.matched_item_count() as usize;
+      let considered = (limit * RERANK_FACTOR).min(total_matches) as u32;
+
+      let mut config = Config::DEFAULT;
+      config.set_match_paths();
+      let mut matcher = Matcher::new(config);
+      let mut scored: Vec<(i64, &String)> = snapshot
+         .matched_items(..considered)
+         .filter_map(|item| {
+            let score = path_score(pattern, item.data, &mut matcher)?;
+            Some((score, item.data))
+         })
+         .collect();
+      scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+      scored.truncate(limit);
+
+      let matches = scored
+         .into_iter()
+         .map(|(score, relative)| {
+            let mut buf = Vec::new();
+            let mut indices = Vec::new();
+            pattern.indices(
+               Utf32Str::new(relative, &mut buf),
+               &mut matcher,
+               &mut indices,
+            );
+            indices.sort_unstable();
+            indices.dedup();
+            self.result(relative, score, indices)
+         })
+         .collect();
+
+      FileFinderResults {
+         matches,
+         total_matches,
+         total_files,
+         indexing,
+      }
+   }
+
+   fn result(&self, relative: &str, score: i64, indices: Vec<u32>) -> FileFinderMatch {
+      FileFinderMatch {
+         path: self.root.join(relative).to_string_lossy().to_string(),
+         relative_path: relative.to_string(),
+         score,
+         indices,
+      }
+   }
+
+   fn relative(&self, path: &Path) -> Option<String> {
+      path
+         .strip_prefix(&self.root)
+         .ok()
+         .map(|relative| relative.to_string_lossy().to_string())
+   }
+}
+
+/// Score of `pattern` against a relative path, favoring matches in the file name and
+/// penalizing deep or vendored paths.
+fn path_score(pattern: &Pattern, relative: &str, matcher: &mut Matcher) -> Option<i64> {
+   let mut buf = Vec::new();
+   let score = pattern.score(Utf32Str::new(relative, &mut buf), matcher)? as i64;
+
+   let basename = relative.rsplit(['/', '\\']).next().unwrap_or(relative);
+   let basename_score = pattern
+      .score(Utf32Str::new(basename, &mut buf), matcher)
+      .map_or(0, |score| score as i64 * PATH_SCORING.basename_weight);
+
+   Some(score + basename_score + path_adjustment(relative))
+}
+
+fn path_adjustment(relative: &str) -> i64 {
+   let directories: Vec<&str> = relative.split(['/', '\\']).collect();
+   let directories = &directories[..directories.len().saturating_sub(1)];
+
+   let mut adjustment = -(directories.len() as i64) * PATH_SCORING.depth_penalty;
+   if directories.iter().any(|dir| VENDOR_DIRS.contains(dir)) {
+      adjustment -= PATH_SCORING.vendor_penalty;
+   }
+   adjustment
+}
+
+/// Open file finder sessions. Walk progress and watcher updates are announced with
+/// `file-finder://updated` so the frontend can re-run its current query.
+pub struct FileFinderManager {
+   app_handle: AppHandle,
+   sessions: Arc<Mutex<HashMap<String, Arc<FileFinderSession>>>>,
+}
+
+impl FileFinderManager {
+   pub fn new(app_handle: AppHandle) -> Self {
+      Self {
+         app_handle,
+         sessions: Arc::new(Mutex::new(HashMap::new())),
+      }
+   }
+
+   /// Start enumerating `root` in the background. Returns the session id to query.
+   pub fn open(&self, root: &str) -> Result<String> {
+      let root = PathBuf::from(root);
+      if !root.is_dir() {
+         bail!("Root path does not exist");
+      }
+
+      let session_id = Uuid::new_v4().to_string();
+      let session = Arc::new(FileFinderSession::new(session_id.clone(), root));
+      self
+         .sessions
+         .lock()
+         .unwrap()
+         .insert(session_id.clone(), session.clone());
+
+      let app_handle = self.app_handle.clone();
+      thread::spawn(move || {
+         session.index(|| emit_updated(&app_handle, &session));
+      });
+
+      Ok(session_id)
+   }
+
+   pub fn query(
+      &self,
+      session_id: &str,
+      query: &str,
+      limit: Option<usize>,
+   ) -> Result<FileFinderResults> {
+      let Some(session) = self.sessions.lock().unwrap().get(session_id).cloned() else {
+         bail!("Unknown file finder session: {}", session_id);
+      };
+      Ok(session.query(query, limit.unwrap_or(DEFAULT_LIMIT)))
+   }
+
+   pub fn close(&self, session_id: &str) {
+      if let Some(session) = self.sessions.lock().unwrap().remove(session_id) {
+         session.closed.store(true, Ordering::Relaxed);
+      }
+   }
+
+   /// Feed a file watcher event into every session whose root contains `path`.
+   pub fn handle_file_change(&self, path: &Path) {
+      let sessions: Vec<Arc<FileFinderSession>> =
+         self.sessions.lock().unwrap().values().cloned().collect();
+
+      for session in sessions {
+         if session.handle_change(path) {
+            emit_updated(&self.app_handle, &session);
+         }
+      }
+   }
+}
+
+fn emit_updated(app_handle: &AppHandle, session: &FileFinderSession) {
+   let event = FileFinderUpdatedEvent {
+      session_id: session.id.clone(),
+      total_files: session.state.lock().unwrap().files.len(),
+      indexing: session.indexing.load(Ordering::Relaxed),
+   };
+   let _ = app_handle.emit("file-finder://updated", event);
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::test_utils::create_tree;
+   use std::fs;
+
+   fn create_session(files: &[(&str, &str)]) -> (tempfile::TempDir, FileFinderSession) {
+      let dir = create_tree(files);
+      let session = FileFinderSession::new("test".to_string(), dir.path().to_path_buf());
+      session.index(|| {});
+      (dir, session)
+   }
+
+   fn paths(results: &FileFinderResults) -> Vec<String> {
+      results
+         .matches
+         .iter()
+         .map(|m| m.relative_path.replace('\\', "/"))
+         .collect()
+   }
+
+   #[test]
+   fn test_walk_respects_ignore_rules() {
+      let (_dir, session) = create_session(&[
+         (".gitignore", "out/\n"),
+         ("src/main.rs", ""),
+         ("out/main.js", ""),
+      ]);
+
+      let results = session.query("main", 10);
+      assert_eq!(paths(&results), vec!["src/main.rs"]);
+      assert_eq!(results.total_files, 2);
+      assert!(!results.indexing);
+   }
+
+   #[test]
+   fn test_basename_and_vendor_scoring() {
+      let (_dir, session) = create_session(&[
+         ("node_modules/button/index.js", ""),
+         ("src/components/button.tsx", ""),
+         ("src/button_utils/helpers/index.ts", ""),
+      ]);
+
+      let results = session.query("button", 10);
+      assert_eq!(paths(&results)[0], "src/components/button.tsx");
+      assert_eq!(
+         paths(&results).last().unwrap(),
+         "node_modules/button/index.js"
+      );
+
+      let top = &results.matches[0];
+      let highlighted: String = top
+         .indices
+         .iter()
+         .map(|&i| top.relative_path.chars().nth(i as usize).unwrap())
+         .collect();
+      assert_eq!(highlighted.to_lowercase(), "button");
+   }
+
+   #[test]
+   fn test_limit_and_incremental_queries() {
+      let files: Vec<String> = (0..30).map(|i| format!("file_{i}.rs")).collect();
+      let refs: Vec<(&str, &str)> = files.iter().map(|f| (f.as_str(), "")).collect();
+      let (_dir, session) = create_session(&refs);
+
+      let results = session.query("file", 5);
+      assert_eq!(results.matches.len(), 5);
+      assert_eq!(results.total_matches, 30);
+
+      let results = session.query("file_2", 50);
+      // Fuzzy matching also accepts a later `2`, as in `file_12.rs`
+      assert_eq!(results.total_matches, 12);
+      let results = session.query("file_", 50);
+      assert_eq!(results.total_matches, 30);
+   }
+
+   #[test]
+   fn test_watcher_changes_update_the_list() {
+      let (dir, session) = create_session(&[("a.rs", ""), ("lib/b.rs", "")]);
+
+      fs::write(dir.path().join("c.rs"), "").unwrap();
+      assert!(session.handle_change(&dir.path().join("c.rs")));
+      fs::create_dir_all(dir.path().join("moved/deep")).unwrap();
+      fs::write(dir.path().join("moved/deep/d.rs"), "").unwrap();
+      assert!(session.handle_change(&dir.path().join("moved")));
+      assert_eq!(session.query("rs", 10).total_matches, 4);
+
+      fs::remove_dir_all(dir.path().join("lib")).unwrap();
+      assert!(session.handle_change(&dir.path().join("lib")));
+      assert!(!session.handle_change(&dir.path().join("lib")));
+
+      let mut results = paths(&session.query("rs", 10));
+      results.sort();
+      assert_eq!(results, vec!["a.rs", "c.rs", "moved/deep/d.rs"]);
+      assert_eq!(session.query("", 10).matches[0].relative_path, "a.rs");
+   }
+}
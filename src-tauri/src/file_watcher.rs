@@ -1,3 +1,7 @@
+use crate::{
+   file_finder::FileFinderManager, search_index::SearchIndexManager,
+   workspace_ignore::WorkspaceIgnore,
+};
 use anyhow::{Context, Result, bail};
 use notify::RecursiveMode;
 use notify_debouncer_mini::{DebounceEventResult, Debouncer, new_debouncer};
@@ -7,7 +11,7 @@ use std::{
    sync::{Arc, Mutex},
    time::{Duration, SystemTime},
 };
-use tauri::{AppHandle, Emitter};
+use tauri::{AppHandle, Emitter, Manager};
 
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct FileChangeEvent {
@@ -29,6 +33,7 @@ pub struct FileWatcher {
    watched_paths: Arc<Mutex<HashSet<PathBuf>>>,
    watched_directories: Arc<Mutex<HashSet<PathBuf>>>,
    known_files: Arc<Mutex<HashMap<PathBuf, SystemTime>>>,
+   ignores: Arc<Mutex<HashMap<PathBuf, Arc<WorkspaceIgnore>>>>,
 }
 
 impl FileWatcher {
@@ -39,6 +44,7 @@ impl FileWatcher {
          watched_paths: Arc::new(Mutex::new(HashSet::new())),
          watched_directories: Arc::new(Mutex::new(HashSet::new())),
          known_files: Arc::new(Mutex::new(HashMap::new())),
+         ignores: Arc::new(Mutex::new(HashMap::new())),
       }
    }
 
@@ -87,6 +93,7 @@ impl FileWatcher {
       let watched_paths = self.watched_paths.clone();
       let watched_directories = self.watched_directories.clone();
       let known_files = self.known_files.clone();
+      let ignores = self.ignores.clone();
 
       Ok(new_debouncer(
          Duration::from_millis(300),
@@ -98,6 +105,7 @@ impl FileWatcher {
                   &watched_paths,
                   &watched_directories,
                   &known_files,
+                  &ignores,
                );
             }
          },
@@ -110,15 +118,21 @@ impl FileWatcher {
       watched_paths: &Arc<Mutex<HashSet<PathBuf>>>,
       watched_directories: &Arc<Mutex<HashSet<PathBuf>>>,
       known_files: &Arc<Mutex<HashMap<PathBuf, SystemTime>>>,
+      ignores: &Arc<Mutex<HashMap<PathBuf, Arc<WorkspaceIgnore>>>>,
    ) {
       let watched_paths = watched_paths.lock().unwrap();
       let watched_dirs = watched_directories.lock().unwrap();
+      let ignores = ignores.lock().unwrap();
 
       for event in events {
          if !Self::is_path_watched(&event.path, &watched_paths, &watched_dirs) {
             continue;
          }
 
+         if Self::is_path_ignored(&event.path, &watched_paths, &ignores) {
+            continue;
+         }
+
          let event_type = Self::determine_event_type(&event.path, known_files);
 
          // Only emit event if it's not a metadata-only change
@@ -134,6 +148,13 @@ impl FileWatcher {
                change_event.event_type
             );
             let _ = app_handle.emit("file-changed", &change_event);
+
+            if let Some(search_index) = app_handle.try_state::<Arc<SearchIndexManager>>() {
+               search_index.handle_file_change(&event.path);
+            }
+            if let Some(file_finder) = app_handle.try_state::<Arc<FileFinderManager>>() {
+               file_finder.handle_file_change(&event.path);
+            }
          }
       }
    }
@@ -146,6 +167,27 @@ impl FileWatcher {
       watched_paths.contains(path) || watched_dirs.iter().any(|dir| path.starts_with(dir))
    }
 
+   /// Changes under a watched directory are dropped when its ignore rules exclude them, unless
+   /// the file itself was explicitly watched. Edits to ignore files refresh the cached rules.
+   fn is_path_ignored(
+      path: &PathBuf,
+      watched_paths: &HashSet<PathBuf>,
+      ignores: &HashMap<PathBuf, Arc<WorkspaceIgnore>>,
+   ) -> bool {
+      for ignore in ignores.values() {
+         ignore.invalidate(path);
+      }
+
+      if watched_paths.contains(path) {
+         return false;
+      }
+
+      ignores
+         .iter()
+         .filter(|(dir, _)| path.starts_with(dir))
+         .any(|(_, ignore)| ignore.is_ignored(path, path.is_dir()))
+   }
+
    fn determine_event_type(
       path: &PathBuf,
       known_files: &Arc<Mutex<HashMap<PathBuf, SystemTime>>>,
@@ -241,6 +283,10 @@ impl FileWatcher {
          .lock()
          .unwrap()
          .insert(path_buf.clone());
+      self.ignores.lock().unwrap().insert(
+         path_buf.clone(),
+         Arc::new(WorkspaceIgnore::new(path_buf.clone(), false)),
+      );
 
       let entries = std::fs::read_dir(path_buf)?;
       let mut known_files = self.known_files.lock().unwrap();
@@ -281,6 +327,7 @@ impl FileWatcher {
       if path_buf.is_dir() {
          let mut watched_dirs = self.watched_directories.lock().unwrap();
          watched_dirs.remove(&path_buf);
+         self.ignores.lock().unwrap().remove(&path_buf);
       }
 
       // Remove from known files tracking
@@ -310,6 +357,7 @@ impl FileWatcher {
 
       watched_paths.clear();
       self.known_files.lock().unwrap().clear();
+      self.ignores.lock().unwrap().clear();
       *debouncer_guard = None;
    }
 }
@@ -0,0 +1,190 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+   collections::HashMap,
+   fs,
+   path::PathBuf,
+   sync::{Arc, Mutex},
+   time::{SystemTime, UNIX_EPOCH},
+};
+use tauri::{AppHandle, Manager};
+
+/// Time for an entry's score to decay to half its value.
+const HALF_LIFE_SECS: f64 = 7.0 * 24.0 * 60.0 * 60.0;
+/// Entries whose decayed score falls below this are dropped when the history is saved.
+const MIN_SCORE: f64 = 0.05;
+/// Upper bound on remembered entries per kind, keeping the highest scores.
+const MAX_ENTRIES: usize = 2000;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum FrecencyKind {
+   File,
+   Completion,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct FrecencyEntry {
+   /// Score as of `last_used`, before decay.
+   score: f64,
+   /// Seconds since the Unix epoch.
+   last_used: u64,
+}
+
+impl FrecencyEntry {
+   fn score_at(&self, now: u64) -> f64 {
+      let elapsed = now.saturating_sub(self.last_used) as f64;
+      self.score * 0.5f64.powf(elapsed / HALF_LIFE_SECS)
+   }
+}
+
+/// Selection history for one workspace.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FrecencyHistory {
+   entries: HashMap<FrecencyKind, HashMap<String, FrecencyEntry>>,
+}
+
+impl FrecencyHistory {
+   pub fn record(&mut self, kind: FrecencyKind, key: &str, now: u64) {
+      let entry = self
+         .entries
+         .entry(kind)
+         .or_default()
+         .entry(key.to_string())
+         .or_insert(FrecencyEntry {
+            score: 0.0,
+            last_used: now,
+         });
+      entry.score = entry.score_at(now) + 1.0;
+      entry.last_used = now;
+   }
+
+   /// Decayed score of `key`; roughly the number of recent selections.
+   pub fn score(&self, kind: FrecencyKind, key: &str, now: u64) -> f64 {
+      self
+         .entries
+         .get(&kind)
+         .and_then(|entries| entries.get(key))
+         .map_or(0.0, |entry| entry.score_at(now))
+   }
+
+   /// Drop entries that have decayed away and cap the size of each kind.
+   fn prune(&mut self, now: u64) {
+      for entries in self.entries.values_mut() {
+         entries.retain(|_, entry| entry.score_at(now) >= MIN_SCORE);
+         if entries.len() > MAX_ENTRIES {
+            let mut scores: Vec<f64> = entries.values().map(|e| e.score_at(now)).collect();
+            scores.sort_by(|a, b| b.total_cmp(a));
+            let cutoff = scores[MAX_ENTRIES - 1];
+            entries.retain(|_, entry| entry.score_at(now) >= cutoff);
+         }
+      }
+   }
+}
+
+pub fn now_secs() -> u64 {
+   SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|d| d.as_secs())
+      .unwrap_or_default()
+}
+
+/// Per-workspace histories of picked files and completions, persisted as JSON under the app
+/// data directory and loaded on first use.
+pub struct FrecencyStore {
+   app_handle: AppHandle,
+   histories: Arc<Mutex<HashMap<String, Arc<Mutex<FrecencyHistory>>>>>,
+}
+
+impl FrecencyStore {
+   pub fn new(app_handle: AppHandle) -> Self {
+      Self {
+         app_handle,
+         histories: Arc::new(Mutex::new(HashMap::new())),
+      }
+   }
+
+   pub fn record(&self, workspace: &str, kind: FrecencyKind, key: &str) -> Result<()> {
+      let history = self.history(workspace);
+      let mut history = history.lock().unwrap();
+      let now = now_secs();
+      history.record(kind, key, now);
+      history.prune(now);
+
+      let path = self.history_path(workspace)?;
+      if let Some(parent) = path.parent() {
+         fs::create_dir_all(parent)?;
+      }
+      fs::write(path, serde_json::to_string(&*history)?)?;
+      Ok(())
+   }
+
+   pub fn history(&self, workspace: &str) -> Arc<Mutex<FrecencyHistory>> {
+      let mut histories = self.histories.lock().unwrap();
+      if let Some(history) = histories.get(workspace) {
+         return history.clone();
+      }
+
+      let history = self
+         .history_path(workspace)
+         .ok()
+         .and_then(|path| fs::read_to_string(path).ok())
+         .and_then(|json| serde_json::from_str(&json).ok())
+         .unwrap_or_default();
+      let history = Arc::new(Mutex::new(history));
+      histories.insert(workspace.to_string(), history.clone());
+      history
+   }
+
+   fn history_path(&self, workspace: &str) -> Result<PathBuf> {
+      let app_data_dir = self
+         .app_handle
+         .path()
+         .app_data_dir()
+         .context("Failed to get app data directory")?;
+      let file_name = format!("{}.json", sha256::digest(workspace.as_bytes()));
+      Ok(app_data_dir.join("frecency").join(file_name))
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_scores_accumulate_and_decay() {
+      let mut history = FrecencyHistory::default();
+      let day = 24 * 60 * 60;
+
+      history.record(FrecencyKind::File, "src/main.rs", 0);
+      history.record(FrecencyKind::File, "src/main.rs", 0);
+      assert_eq!(history.score(FrecencyKind::File, "src/main.rs", 0), 2.0);
+      assert_eq!(
+         history.score(FrecencyKind::Completion, "src/main.rs", 0),
+         0.0
+      );
+
+      let week_later = history.score(FrecencyKind::File, "src/main.rs", 7 * day);
+      assert!((week_later - 1.0).abs() < 1e-9);
+
+      history.record(FrecencyKind::File, "src/lib.rs", 7 * day);
+      history.prune(70 * day);
+      assert_eq!(
+         history.score(FrecencyKind::File, "src/main.rs", 70 * day),
+         0.0
+      );
+   }
+
+   #[test]
+   fn test_history_roundtrips_through_json() {
+      let mut history = FrecencyHistory::default();
+      history.record(FrecencyKind::Completion, "useState", 100);
+
+      let json = serde_json::to_string(&history).unwrap();
+      let restored: FrecencyHistory = serde_json::from_str(&json).unwrap();
+      assert_eq!(
+         restored.score(FrecencyKind::Completion, "useState", 100),
+         1.0
+      );
+   }
+}
@@ -0,0 +1,284 @@
+use lsp_types::*;
+
+/// Everything the backend can consume, declared in `initialize` so servers do not fall back to
+/// their most basic behavior.
+pub fn client_capabilities() -> ClientCapabilities {
+   ClientCapabilities {
+      workspace: Some(workspace_capabilities()),
+      text_document: Some(text_document_capabilities()),
+      window: Some(WindowClientCapabilities {
+         work_done_progress: Some(true),
+         show_message: Some(ShowMessageRequestClientCapabilities {
+            message_action_item: Some(MessageActionItemCapabilities {
+               additional_properties_support: Some(true),
+            }),
+         }),
+         ..Default::default()
+      }),
+      general: Some(GeneralClientCapabilities {
+         // Positions are converted with UTF-16 offsets throughout
+         position_encodings: Some(vec![PositionEncodingKind::UTF16]),
+         markdown: Some(MarkdownClientCapabilities {
+            parser: "marked".to_string(),
+            version: None,
+            allowed_tags: None,
+         }),
+         ..Default::default()
+      }),
+      ..Default::default()
+   }
+}
+
+fn workspace_capabilities() -> WorkspaceClientCapabilities {
+   WorkspaceClientCapabilities {
+      apply_edit: Some(true),
+      workspace_edit: Some(WorkspaceEditClientCapabilities {
+         document_changes: Some(true),
+         resource_operations: Some(vec![
+            ResourceOperationKind::Create,
+            ResourceOperationKind::Rename,
+            ResourceOperationKind::Delete,
+         ]),
+         failure_handling: Some(FailureHandlingKind::Transactional),
+         normalizes_line_endings: Some(false),
+         change_annotation_support: None,
+      }),
+      did_change_configuration: Some(DynamicRegistrationClientCapabilities {
+         dynamic_registration: Some(false),
+      }),
+      execute_command: Some(DynamicRegistrationClientCapabilities {
+         dynamic_registration: Some(false),
+      }),
+      symbol: Some(WorkspaceSymbolClientCapabilities {
+         dynamic_registration: Some(false),
+         symbol_kind: Some(SymbolKindCapability {
+            value_set: Some((1..=26).map(symbol_kind).collect()),
+         }),
+         tag_support: None,
+         resolve_support: None,
+      }),
+      workspace_folders: Some(true),
+      configuration: Some(true),
+      diagnostic: Some(DiagnosticWorkspaceClientCapabilities {
+         refresh_support: Some(true),
+      }),
+      ..Default::default()
+   }
+}
+
+fn text_document_capabilities() -> TextDocumentClientCapabilities {
+   let markup = || Some(vec![MarkupKind::Markdown, MarkupKind::PlainText]);
+   let goto = || {
+      Some(GotoCapability {
+         dynamic_registration: Some(false),
+         link_support: Some(true),
+      })
+   };
+
+   TextDocumentClientCapabilities {
+      synchronization: Some(TextDocumentSyncClientCapabilities {
+         dynamic_registration: Some(false),
+         will_save: Some(false),
+         will_save_wait_until: Some(false),
+         did_save: Some(false),
+      }),
+      completion: Some(CompletionClientCapabilities {
+         completion_item: Some(CompletionItemCapability {
+            // Expanded with `snippet::expand`
+            snippet_support: Some(true),
+            commit_characters_support: Some(true),
+            documentation_format: markup(),
+            deprecated_support: Some(true),
+            preselect_support: Some(true),
+            tag_support: Some(TagSupport {
+               value_set: vec![CompletionItemTag::DEPRECATED],
+            }),
+            insert_replace_support: Some(false),
+            resolve_support: Some(CompletionItemCapabilityResolveSupport {
+               properties: ["documentation", "detail", "additionalTextEdits"]
+                  .iter()
+                  .map(|property| property.to_string())
+                  .collect(),
+            }),
+            label_details_support: Some(true),
+            ..Default::default()
+         }),
+         completion_item_kind: Some(CompletionItemKindCapability {
+            value_set: Some((1..=25).map(completion_item_kind).collect()),
+         }),
+         context_support: Some(true),
+         ..Default::default()
+      }),
+      hover: Some(HoverClientCapabilities {
+         dynamic_registration: Some(false),
+         content_format: markup(),
+      }),
+      signature_help: Some(SignatureHelpClientCapabilities {
+         dynamic_registration: Some(false),
+         signature_information: Some(SignatureInformationSettings {
+            documentation_format: markup(),
+            parameter_information: Some(ParameterInformationSettings {
+               label_offset_support: Some(true),
+            }),
+            active_parameter_support: Some(true),
+         }),
+         context_support: Some(true),
+      }),
+      inlay_hint: Some(InlayHintClientCapabilities {
+         dynamic_registration: Some(false),
+         resolve_support: None,
+      }),
+      semantic_tokens: Some(SemanticTokensClientCapabilities {
+         dynamic_registration: Some(false),
+         requests: SemanticTokensClientCapabilitiesRequests {
+            range: Some(true),
+            full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
+         },
+         token_types: [
+            SemanticTokenType::NAMESPACE,
+            SemanticTokenType::TYPE,
+            SemanticTokenType::CLASS,
+            SemanticTokenType::ENUM,
+            SemanticTokenType::INTERFACE,
+            SemanticTokenType::STRUCT,
+            SemanticTokenType::TYPE_PARAMETER,
+            SemanticTokenType::PARAMETER,
+            SemanticTokenType::VARIABLE,
+            SemanticTokenType::PROPERTY,
+            SemanticTokenType::ENUM_MEMBER,
+            SemanticTokenType::EVENT,
+            SemanticTokenType::FUNCTION,
+            SemanticTokenType::METHOD,
+            SemanticTokenType::MACRO,
+            SemanticTokenType::KEYWORD,
+            SemanticTokenType::MODIFIER,
+            SemanticTokenType::COMMENT,
+            SemanticTokenType::STRING,
+            SemanticTokenType::NUMBER,
+            SemanticTokenType::REGEXP,
+            SemanticTokenType::OPERATOR,
+            SemanticTokenType::DECORATOR,
+         ]
+         .to_vec(),
+         token_modifiers: [
+            SemanticTokenModifier::DECLARATION,
+            SemanticTokenModifier::DEFINITION,
+            SemanticTokenModifier::READONLY,
+            SemanticTokenModifier::STATIC,
+            SemanticTokenModifier::DEPRECATED,
+            SemanticTokenModifier::ABSTRACT,
+            SemanticTokenModifier::ASYNC,
+            SemanticTokenModifier::MODIFICATION,
+            SemanticTokenModifier::DOCUMENTATION,
+            SemanticTokenModifier::DEFAULT_LIBRARY,
+         ]
+         .to_vec(),
+         formats: vec![TokenFormat::RELATIVE],
+         overlapping_token_support: Some(false),
+         multiline_token_support: Some(false),
+         server_cancel_support: Some(true),
+         augments_syntax_tokens: Some(true),
+      }),
+      diagnostic: Some(DiagnosticClientCapabilities {
+         dynamic_registration: Some(false),
+         related_document_support: Some(true),
+      }),
+      call_hierarchy: Some(CallHierarchyClientCapabilities {
+         dynamic_registration: Some(false),
+      }),
+      type_hierarchy: Some(TypeHierarchyClientCapabilities {
+         dynamic_registration: Some(false),
+      }),
+      references: Some(DynamicRegistrationClientCapabilities {
+         dynamic_registration: Some(false),
+      }),
+      definition: goto(),
+      type_definition: goto(),
+      implementation: goto(),
+      declaration: goto(),
+      document_symbol: Some(DocumentSymbolClientCapabilities {
+         dynamic_registration: Some(false),
+         symbol_kind: Some(SymbolKindCapability {
+            value_set: Some((1..=26).map(symbol_kind).collect()),
+         }),
+         hierarchical_document_symbol_support: Some(true),
+         tag_support: None,
+      }),
+      document_highlight: Some(DynamicRegistrationClientCapabilities {
+         dynamic_registration: Some(false),
+      }),
+      code_lens: Some(DynamicRegistrationClientCapabilities {
+         dynamic_registration: Some(false),
+      }),
+      folding_range: Some(FoldingRangeClientCapabilities {
+         dynamic_registration: Some(false),
+         range_limit: None,
+         // The editor folds whole lines
+         line_folding_only: Some(true),
+         folding_range_kind: Some(FoldingRangeKindCapability {
+            value_set: Some(vec![
+               FoldingRangeKind::Comment,
+               FoldingRangeKind::Imports,
+               FoldingRangeKind::Region,
+            ]),
+         }),
+         folding_range: Some(FoldingRangeCapability {
+            collapsed_text: Some(false),
+         }),
+      }),
+      selection_range: Some(SelectionRangeClientCapabilities {
+         dynamic_registration: Some(false),
+      }),
+      rename: Some(RenameClientCapabilities {
+         dynamic_registration: Some(false),
+         prepare_support: Some(true),
+         prepare_support_default_behavior: Some(PrepareSupportDefaultBehavior::IDENTIFIER),
+         honors_change_annotations: Some(false),
+      }),
+      code_action: Some(CodeActionClientCapabilities {
+         code_action_literal_support: Some(CodeActionLiteralSupport {
+            code_action_kind: CodeActionKindLiteralSupport {
+               value_set: [
+                  CodeActionKind::EMPTY,
+                  CodeActionKind::QUICKFIX,
+                  CodeActionKind::REFACTOR,
+                  CodeActionKind::REFACTOR_EXTRACT,
+                  CodeActionKind::REFACTOR_INLINE,
+                  CodeActionKind::REFACTOR_REWRITE,
+                  CodeActionKind::SOURCE,
+                  CodeActionKind::SOURCE_ORGANIZE_IMPORTS,
+                  CodeActionKind::SOURCE_FIX_ALL,
+               ]
+               .iter()
+               .map(|kind| kind.as_str().to_string())
+               .collect(),
+            },
+         }),
+         is_preferred_support: Some(true),
+         disabled_support: Some(true),
+         data_support: Some(true),
+         resolve_support: Some(CodeActionCapabilityResolveSupport {
+            properties: vec!["edit".to_string()],
+         }),
+         ..Default::default()
+      }),
+      publish_diagnostics: Some(PublishDiagnosticsClientCapabilities {
+         related_information: Some(true),
+         tag_support: Some(TagSupport {
+            value_set: vec![DiagnosticTag::UNNECESSARY, DiagnosticTag::DEPRECATED],
+         }),
+         version_support: Some(true),
+         code_description_support: Some(true),
+         data_support: Some(true),
+      }),
+      ..Default::default()
+   }
+}
+
+fn completion_item_kind(value: i32) -> CompletionItemKind {
+   serde_json::from_value(value.into()).unwrap_or(CompletionItemKind::TEXT)
+}
+
+fn symbol_kind(value: i32) -> SymbolKind {
+   serde_json::from_value(value.into()).unwrap_or(SymbolKind::NULL)
+}
@@ -1,22 +1,36 @@
-use anyhow::{Context, Result};
+use super::{
+   capabilities::client_capabilities,
+   config::LspServerConfig,
+   dispatcher::{
+      Dispatcher, MessageRequests, PendingRequests, request_id, send_message, send_result,
+   },
+   semantic_tokens,
+   traffic::{TrafficDirection, TrafficRecorder},
+   transport::{self, LspTransport, RemoteUris, ServerHandle},
+   workspace_edit::apply_content_changes,
+};
+use crate::diagnostics::DiagnosticsStore;
+use anyhow::{Context, Result, bail};
 use crossbeam_channel::{Sender, bounded};
 use lsp_types::*;
 use serde_json::{Value, json};
 use std::{
-   collections::HashMap,
+   collections::{HashMap, HashSet},
    io::{BufRead, BufReader, Read, Write},
-   path::PathBuf,
-   process::{Child, Command, Stdio},
+   path::{Path, PathBuf},
    sync::{
       Arc, Mutex,
       atomic::{AtomicU64, Ordering},
    },
    thread,
+   time::Duration,
 };
-use tauri::{AppHandle, Emitter};
-use tokio::sync::oneshot;
+use tauri::AppHandle;
+use tokio::sync::{oneshot, watch};
 
-type PendingRequests = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<Value>>>>>;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Servers index the workspace before answering `initialize`, which can take a while.
+const INITIALIZE_TIMEOUT: Duration = Duration::from_secs(120);
 
 #[derive(Clone)]
 pub struct LspClient {
@@ -24,34 +38,81 @@ pub struct LspClient {
    stdin_tx: Sender<String>,
    pending_requests: PendingRequests,
    capabilities: Arc<Mutex<Option<ServerCapabilities>>>,
+   server_name: String,
+   /// Shared with the other servers; this one's diagnostics are filed under its name.
+   diagnostics: DiagnosticsStore,
+   /// `resultId` of the last pulled diagnostics per document, so unchanged ones are not resent.
+   diagnostic_result_ids: Arc<Mutex<HashMap<Url, String>>>,
+   message_requests: MessageRequests,
+   open_documents: Arc<Mutex<HashSet<PathBuf>>>,
+   /// Each open document as the server last saw it, so range changes can be expanded for
+   /// servers that only accept full syncs and documents can be reopened after a restart.
+   documents: Arc<Mutex<HashMap<Url, TextDocumentItem>>>,
+   workspace_folders: Arc<Mutex<Vec<WorkspaceFolder>>>,
+   settings: Value,
+   /// Last full semantic tokens per document, which `full/delta` responses are applied to.
+   semantic_tokens: Arc<Mutex<HashMap<Url, SemanticTokens>>>,
+   /// The in-flight completion request, cancelled when a newer one supersedes it.
+   completion_request: Arc<Mutex<Option<u64>>>,
+   /// Flips to true once the server's output closes.
+   exited: watch::Receiver<bool>,
+   /// Set for servers on another host, whose messages carry the host's own file URIs.
+   remote_uris: Option<RemoteUris>,
 }
 
 impl LspClient {
+   /// Start `server` from `server_path` through `transport`. The diagnostics it reports are
+   /// recorded in `diagnostics` and every message exchanged with it in `traffic`.
    pub fn start(
-      server_path: PathBuf,
-      args: Vec<String>,
-      _root_uri: Url,
+      server: &LspServerConfig,
+      server_path: &Path,
+      transport: &LspTransport,
+      diagnostics: DiagnosticsStore,
+      traffic: TrafficRecorder,
       app_handle: Option<AppHandle>,
-   ) -> Result<(Self, Child)> {
-      log::info!("Starting language server: {:?} {:?}", server_path, args);
-      let mut child = Command::new(server_path)
-         .args(args)
-         .stdin(Stdio::piped())
-         .stdout(Stdio::piped())
-         .stderr(Stdio::piped())
-         .spawn()
-         .context("Failed to spawn LSP server")?;
-
-      log::info!("Language server process started with PID: {:?}", child.id());
-
-      let stdin = child.stdin.take().context("Failed to get stdin")?;
-      let stdout = child.stdout.take().context("Failed to get stdout")?;
-      let stderr = child.stderr.take().context("Failed to get stderr")?;
+   ) -> Result<(Self, ServerHandle)> {
+      log::info!(
+         "Starting language server: {:?} {:?}",
+         server_path,
+         server.args
+      );
+      let server_name = server.name.as_str();
+      let settings = server.settings.clone().unwrap_or(Value::Null);
+      let process = transport.spawn(server_path, &server.args)?;
+      let (stdin, stdout, stderr) = (process.stdin, process.stdout, process.stderr);
+      let remote_uris = transport.remote_uris();
 
       let (stdin_tx, stdin_rx) = bounded::<String>(100);
-      let pending_requests = Arc::new(Mutex::new(HashMap::new()));
-      let pending_requests_clone = Arc::clone(&pending_requests);
-      let app_handle_clone = app_handle.clone();
+      let (exited_tx, exited) = watch::channel(false);
+      let client = Self {
+         request_counter: Arc::new(AtomicU64::new(1)),
+         stdin_tx,
+         pending_requests: Arc::new(Mutex::new(HashMap::new())),
+         capabilities: Arc::new(Mutex::new(None)),
+         server_name: server_name.to_string(),
+         diagnostics,
+         diagnostic_result_ids: Arc::new(Mutex::new(HashMap::new())),
+         message_requests: Arc::new(Mutex::new(HashMap::new())),
+         open_documents: Arc::new(Mutex::new(HashSet::new())),
+         documents: Arc::new(Mutex::new(HashMap::new())),
+         workspace_folders: Arc::new(Mutex::new(Vec::new())),
+         settings,
+         semantic_tokens: Arc::new(Mutex::new(HashMap::new())),
+         completion_request: Arc::new(Mutex::new(None)),
+         exited,
+         remote_uris: remote_uris.clone(),
+      };
+      let dispatcher = Dispatcher {
+         server_name: server_name.to_string(),
+         app_handle,
+         stdin_tx: client.stdin_tx.clone(),
+         pending_requests: Arc::clone(&client.pending_requests),
+         diagnostics: client.diagnostics.clone(),
+         message_requests: Arc::clone(&client.message_requests),
+         settings: client.settings.clone(),
+         open_documents: Arc::clone(&client.open_documents),
+         workspace_folders: Arc::clone(&client.workspace_folders),
+      };
 
       // Stderr reader thread
       thread::spawn(move || {
@@ -75,9 +136,17 @@ impl LspClient {
       });
 
       // Stdin writer thread
+      let outgoing_uris = remote_uris.clone();
+      let outgoing_traffic = traffic.clone();
       thread::spawn(move || {
          let mut stdin = stdin;
-         while let Ok(msg) = stdin_rx.recv() {
+         while let Ok(mut msg) = stdin_rx.recv() {
+            if let Some(uris) = &outgoing_uris {
+               msg = uris.framed_to_server(msg);
+            }
+            if let Some((_, body)) = msg.split_once("\r\n\r\n") {
+               outgoing_traffic.record(TrafficDirection::Send, body);
+            }
             if stdin.write_all(msg.as_bytes()).is_err() {
                break;
             }
@@ -90,15 +159,16 @@ impl LspClient {
       // Stdout reader thread
       thread::spawn(move || {
          let mut reader = BufReader::new(stdout);
-         loop {
+         'messages: loop {
             let mut headers: HashMap<String, String> = HashMap::new();
             let mut line = String::new();
 
             // Read headers
             loop {
                line.clear();
-               if reader.read_line(&mut line).is_err() {
-                  return;
+               match reader.read_line(&mut line) {
+                  Ok(0) | Err(_) => break 'messages, // EOF
+                  Ok(_) => {}
                }
 
                if line == "\r\n" || line == "\n" {
@@ -123,50 +193,54 @@ impl LspClient {
             // Read content
             let mut content = vec![0u8; content_length];
             if reader.read_exact(&mut content).is_err() {
-               return;
+               break;
             }
 
-            if let Ok(content_str) = String::from_utf8(content)
-               && let Ok(message) = serde_json::from_str::<Value>(&content_str)
-            {
-               log::debug!("LSP Message: {}", content_str);
-
-               // Check if this is a response (has id) or notification (no id)
-               if message.get("id").is_some() {
-                  Self::handle_response(message, &pending_requests_clone);
-               } else if message.get("method").is_some() {
-                  Self::handle_notification(message, &app_handle_clone);
+            let Ok(content_str) = String::from_utf8(content) else {
+               continue;
+            };
+            traffic.record(TrafficDirection::Receive, &content_str);
+            if let Ok(mut message) = serde_json::from_str::<Value>(&content_str) {
+               if let Some(uris) = &remote_uris {
+                  uris.to_editor(&mut message);
                }
+               dispatcher.dispatch(message);
             }
          }
-      });
 
-      let client = Self {
-         request_counter: Arc::new(AtomicU64::new(1)),
-         stdin_tx,
-         pending_requests,
-         capabilities: Arc::new(Mutex::new(None)),
-      };
+         dispatcher.fail_pending("Language server exited");
+         let _ = exited_tx.send(true);
+      });
 
       // Don't initialize here - we'll do it separately to avoid runtime issues
       log::info!("LSP client created, initialization will happen separately");
 
-      Ok((client, child))
+      Ok((client, process.handle))
    }
 
-   pub async fn initialize(&self, root_uri: Url) -> Result<()> {
+   pub async fn initialize(
+      &self,
+      root_uri: Url,
+      workspace_folders: Vec<WorkspaceFolder>,
+      initialization_options: Option<Value>,
+   ) -> Result<()> {
       log::info!("Initializing LSP server with root_uri: {}", root_uri);
+      *self.workspace_folders.lock().unwrap() = workspace_folders.clone();
 
       let init_params = InitializeParams {
-         process_id: Some(std::process::id()),
+         // A remote server would look for this process on its own host and exit
+         process_id: self.remote_uris.is_none().then(std::process::id),
          #[allow(deprecated)]
          root_uri: Some(root_uri),
-         capabilities: ClientCapabilities::default(),
+         workspace_folders: Some(workspace_folders.clone()),
+         initialization_options,
+         capabilities: client_capabilities(),
          ..Default::default()
       };
 
-      let initialize_result: InitializeResult =
-         self.request::<request::Initialize>(init_params).await?;
+      let initialize_result: InitializeResult = self
+         .request_with_timeout::<request::Initialize>(init_params, INITIALIZE_TIMEOUT)
+         .await?;
       log::info!("LSP initialized successfully");
 
       if let Some(caps) = initialize_result.capabilities.into() {
@@ -176,51 +250,274 @@ impl LspClient {
       // Send initialized notification
       self.notify::<notification::Initialized>(InitializedParams {})?;
 
+      // Servers that pull settings expect a nudge before asking for them
+      if !self.settings.is_null() {
+         self.notify::<notification::DidChangeConfiguration>(DidChangeConfigurationParams {
+            settings: self.settings.clone(),
+         })?;
+      }
+
       Ok(())
    }
 
-   fn handle_response(response: Value, pending: &PendingRequests) {
-      if let Some(id) = response.get("id").and_then(|id| id.as_u64())
-         && let Some(tx) = pending.lock().unwrap().remove(&id)
-      {
-         if let Some(error) = response.get("error") {
-            let _ = tx.send(Err(anyhow::anyhow!("LSP error: {:?}", error)));
-         } else if let Some(result) = response.get("result") {
-            let _ = tx.send(Ok(result.clone()));
+   /// Whether the server asked for `workspace/didChangeWorkspaceFolders` notifications.
+   pub fn supports_workspace_folder_changes(&self) -> bool {
+      self
+         .capabilities
+         .lock()
+         .unwrap()
+         .as_ref()
+         .and_then(|caps| caps.workspace.as_ref())
+         .and_then(|workspace| workspace.workspace_folders.as_ref())
+         .and_then(|folders| folders.change_notifications.as_ref())
+         .is_some_and(|notifications| match notifications {
+            OneOf::Left(enabled) => *enabled,
+            OneOf::Right(_) => true,
+         })
+   }
+
+   /// How the server wants document changes sent. Servers that declare nothing get none.
+   pub fn text_document_sync_kind(&self) -> TextDocumentSyncKind {
+      match self.text_document_sync() {
+         Some(TextDocumentSyncCapability::Kind(kind)) => kind,
+         Some(TextDocumentSyncCapability::Options(options)) => {
+            options.change.unwrap_or(TextDocumentSyncKind::NONE)
          }
+         None => TextDocumentSyncKind::NONE,
       }
    }
 
-   fn handle_notification(notification: Value, app_handle: &Option<AppHandle>) {
-      let method = notification.get("method").and_then(|m| m.as_str());
-      let params = notification.get("params");
+   /// Whether the server wants `didOpen` and `didClose`. Only an explicit opt-out disables them,
+   /// since servers that only declare a sync kind still expect them.
+   fn wants_open_close(&self) -> bool {
+      match self.text_document_sync() {
+         Some(TextDocumentSyncCapability::Options(options)) => options.open_close != Some(false),
+         _ => true,
+      }
+   }
 
-      match method {
-         Some("textDocument/publishDiagnostics") => {
-            if let Some(params) = params {
-               log::debug!("Received diagnostics: {:?}", params);
+   fn text_document_sync(&self) -> Option<TextDocumentSyncCapability> {
+      self
+         .capabilities
+         .lock()
+         .unwrap()
+         .as_ref()
+         .and_then(|caps| caps.text_document_sync.clone())
+   }
 
-               // Parse diagnostics
-               if let Ok(diagnostic_params) =
-                  serde_json::from_value::<PublishDiagnosticsParams>(params.clone())
-               {
-                  // Emit event to frontend
-                  if let Some(app) = app_handle {
-                     let _ = app.emit("lsp://diagnostics", &diagnostic_params);
-                     log::info!("Emitted diagnostics for file: {}", diagnostic_params.uri);
-                  }
-               } else {
-                  log::error!("Failed to parse diagnostics params");
-               }
+   /// Whether the server answers `textDocument/prepareRename`.
+   pub fn supports_prepare_rename(&self) -> bool {
+      self
+         .capabilities
+         .lock()
+         .unwrap()
+         .as_ref()
+         .and_then(|caps| caps.rename_provider.as_ref())
+         .is_some_and(|provider| match provider {
+            OneOf::Left(_) => false,
+            OneOf::Right(options) => options.prepare_provider == Some(true),
+         })
+   }
+
+   /// Whether the server fills in code actions lazily through `codeAction/resolve`.
+   pub fn supports_code_action_resolve(&self) -> bool {
+      self
+         .capabilities
+         .lock()
+         .unwrap()
+         .as_ref()
+         .and_then(|caps| caps.code_action_provider.as_ref())
+         .is_some_and(|provider| match provider {
+            CodeActionProviderCapability::Simple(_) => false,
+            CodeActionProviderCapability::Options(options) => {
+               options.resolve_provider == Some(true)
             }
-         }
-         Some(method_name) => {
-            log::debug!("Unhandled LSP notification: {}", method_name);
-         }
-         None => {
-            log::warn!("Received notification without method");
-         }
-      }
+         })
+   }
+
+   /// Whether the server answers `textDocument/documentSymbol`.
+   pub fn supports_document_symbols(&self) -> bool {
+      self
+         .capabilities
+         .lock()
+         .unwrap()
+         .as_ref()
+         .and_then(|caps| caps.document_symbol_provider.as_ref())
+         .is_some_and(|provider| match provider {
+            OneOf::Left(enabled) => *enabled,
+            OneOf::Right(_) => true,
+         })
+   }
+
+   /// Whether the server answers `workspace/symbol`.
+   pub fn supports_workspace_symbols(&self) -> bool {
+      self
+         .capabilities
+         .lock()
+         .unwrap()
+         .as_ref()
+         .and_then(|caps| caps.workspace_symbol_provider.as_ref())
+         .is_some_and(|provider| match provider {
+            OneOf::Left(enabled) => *enabled,
+            OneOf::Right(_) => true,
+         })
+   }
+
+   pub fn supports_signature_help(&self) -> bool {
+      self
+         .capabilities
+         .lock()
+         .unwrap()
+         .as_ref()
+         .is_some_and(|caps| caps.signature_help_provider.is_some())
+   }
+
+   pub fn supports_inlay_hints(&self) -> bool {
+      self
+         .capabilities
+         .lock()
+         .unwrap()
+         .as_ref()
+         .and_then(|caps| caps.inlay_hint_provider.as_ref())
+         .is_some_and(|provider| match provider {
+            OneOf::Left(enabled) => *enabled,
+            OneOf::Right(_) => true,
+         })
+   }
+
+   /// Whether the server fills in completion details lazily through `completionItem/resolve`.
+   pub fn supports_completion_resolve(&self) -> bool {
+      self
+         .capabilities
+         .lock()
+         .unwrap()
+         .as_ref()
+         .and_then(|caps| caps.completion_provider.as_ref())
+         .is_some_and(|options| options.resolve_provider == Some(true))
+   }
+
+   /// Whether the server answers `textDocument/codeLens`.
+   pub fn supports_code_lens(&self) -> bool {
+      self
+         .capabilities
+         .lock()
+         .unwrap()
+         .as_ref()
+         .is_some_and(|caps| caps.code_lens_provider.is_some())
+   }
+
+   /// Whether the server fills in lens commands lazily through `codeLens/resolve`.
+   pub fn supports_code_lens_resolve(&self) -> bool {
+      self
+         .capabilities
+         .lock()
+         .unwrap()
+         .as_ref()
+         .and_then(|caps| caps.code_lens_provider.as_ref())
+         .is_some_and(|options| options.resolve_provider == Some(true))
+   }
+
+   pub fn supports_document_highlight(&self) -> bool {
+      self
+         .capabilities
+         .lock()
+         .unwrap()
+         .as_ref()
+         .and_then(|caps| caps.document_highlight_provider.as_ref())
+         .is_some_and(|provider| match provider {
+            OneOf::Left(enabled) => *enabled,
+            OneOf::Right(_) => true,
+         })
+   }
+
+   pub fn supports_folding_range(&self) -> bool {
+      self
+         .capabilities
+         .lock()
+         .unwrap()
+         .as_ref()
+         .and_then(|caps| caps.folding_range_provider.as_ref())
+         .is_some_and(|provider| match provider {
+            FoldingRangeProviderCapability::Simple(enabled) => *enabled,
+            _ => true,
+         })
+   }
+
+   pub fn supports_selection_range(&self) -> bool {
+      self
+         .capabilities
+         .lock()
+         .unwrap()
+         .as_ref()
+         .and_then(|caps| caps.selection_range_provider.as_ref())
+         .is_some_and(|provider| match provider {
+            SelectionRangeProviderCapability::Simple(enabled) => *enabled,
+            _ => true,
+         })
+   }
+
+   pub fn supports_call_hierarchy(&self) -> bool {
+      self
+         .capabilities
+         .lock()
+         .unwrap()
+         .as_ref()
+         .and_then(|caps| caps.call_hierarchy_provider.as_ref())
+         .is_some_and(|provider| match provider {
+            CallHierarchyServerCapability::Simple(enabled) => *enabled,
+            CallHierarchyServerCapability::Options(_) => true,
+         })
+   }
+
+   /// The server's semantic token options, including the legend needed to decode its tokens.
+   pub fn semantic_tokens_options(&self) -> Option<SemanticTokensOptions> {
+      self
+         .capabilities
+         .lock()
+         .unwrap()
+         .as_ref()
+         .and_then(|caps| caps.semantic_tokens_provider.clone())
+         .map(|provider| match provider {
+            SemanticTokensServerCapabilities::SemanticTokensOptions(options) => options,
+            SemanticTokensServerCapabilities::SemanticTokensRegistrationOptions(options) => {
+               options.semantic_tokens_options
+            }
+         })
+   }
+
+   /// Diagnostics the server last reported for `uri`, pushed or pulled.
+   pub fn diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+      self.diagnostics.get(uri, Some(&self.server_name))
+   }
+
+   /// Set when the server supports pulling diagnostics.
+   pub fn diagnostic_options(&self) -> Option<DiagnosticOptions> {
+      self
+         .capabilities
+         .lock()
+         .unwrap()
+         .as_ref()
+         .and_then(|caps| caps.diagnostic_provider.clone())
+         .map(|provider| match provider {
+            DiagnosticServerCapabilities::Options(options) => options,
+            DiagnosticServerCapabilities::RegistrationOptions(options) => {
+               options.diagnostic_options
+            }
+         })
+   }
+
+   /// Answer a `window/showMessageRequest` forwarded to the frontend. Returns false when no
+   /// such request is waiting on this server.
+   pub fn respond_to_message_request(
+      &self,
+      request_id: &str,
+      action: Option<MessageActionItem>,
+   ) -> Result<bool> {
+      let Some(id) = self.message_requests.lock().unwrap().remove(request_id) else {
+         return Ok(false);
+      };
+      send_result(&self.stdin_tx, id, json!(action))?;
+      Ok(true)
    }
 
    pub async fn request<R>(&self, params: R::Params) -> Result<R::Result>
@@ -229,6 +526,35 @@ impl LspClient {
       R::Params: serde::Serialize,
       R::Result: serde::de::DeserializeOwned,
    {
+      self
+         .request_with_timeout::<R>(params, REQUEST_TIMEOUT)
+         .await
+   }
+
+   async fn request_with_timeout<R>(
+      &self,
+      params: R::Params,
+      timeout: Duration,
+   ) -> Result<R::Result>
+   where
+      R: lsp_types::request::Request,
+      R::Params: serde::Serialize,
+      R::Result: serde::de::DeserializeOwned,
+   {
+      let (id, rx) = self.send_request::<R>(params)?;
+      let response = self.wait_for_response(id, rx, R::METHOD, timeout).await?;
+      serde_json::from_value(response).context("Failed to deserialize response")
+   }
+
+   fn send_request<R>(&self, params: R::Params) -> Result<(u64, oneshot::Receiver<Result<Value>>)>
+   where
+      R: lsp_types::request::Request,
+      R::Params: serde::Serialize,
+   {
+      if self.has_exited() {
+         bail!("Language server exited");
+      }
+
       let id = self.request_counter.fetch_add(1, Ordering::SeqCst);
       let (tx, rx) = oneshot::channel();
 
@@ -236,23 +562,62 @@ impl LspClient {
 
       let request = json!({
           "jsonrpc": "2.0",
-          "id": id,
+          "id": request_id(id),
           "method": R::METHOD,
           "params": params,
       });
 
       log::debug!("LSP Request {}: {}", id, R::METHOD);
 
-      let msg = format!(
-         "Content-Length: {}\r\n\r\n{}",
-         request.to_string().len(),
-         request
-      );
+      if let Err(e) = send_message(&self.stdin_tx, &request) {
+         self.pending_requests.lock().unwrap().remove(&id);
+         return Err(e.context("Failed to send request"));
+      }
+      Ok((id, rx))
+   }
 
-      self.stdin_tx.send(msg).context("Failed to send request")?;
+   async fn wait_for_response(
+      &self,
+      id: u64,
+      rx: oneshot::Receiver<Result<Value>>,
+      method: &str,
+      timeout: Duration,
+   ) -> Result<Value> {
+      match tokio::time::timeout(timeout, rx).await {
+         Ok(response) => response.context("Request cancelled")?,
+         Err(_) => {
+            self.cancel_request(id);
+            bail!("{} timed out after {}s", method, timeout.as_secs())
+         }
+      }
+   }
 
-      let response = rx.await.context("Request cancelled")??;
-      serde_json::from_value(response).context("Failed to deserialize response")
+   /// Stop waiting for a request and tell the server it can drop it.
+   pub fn cancel_request(&self, id: u64) {
+      if self.pending_requests.lock().unwrap().remove(&id).is_some() {
+         log::debug!("Cancelling LSP request {}", id);
+         let _ = self.notify::<notification::Cancel>(CancelParams { id: request_id(id) });
+      }
+   }
+
+   pub fn has_exited(&self) -> bool {
+      *self.exited.borrow()
+   }
+
+   /// Resolves once the server's output closes, whether it crashed or was stopped.
+   pub async fn wait_for_exit(&self) {
+      let mut exited = self.exited.clone();
+      let _ = exited.wait_for(|exited| *exited).await;
+   }
+
+   /// Whether both handles talk to the same server process.
+   pub fn same_server(&self, other: &LspClient) -> bool {
+      Arc::ptr_eq(&self.pending_requests, &other.pending_requests)
+   }
+
+   /// Documents currently open on the server, with their latest text and version.
+   pub fn open_documents(&self) -> Vec<TextDocumentItem> {
+      self.documents.lock().unwrap().values().cloned().collect()
    }
 
    pub fn notify<N>(&self, params: N::Params) -> Result<()>
@@ -266,17 +631,7 @@ impl LspClient {
           "params": params,
       });
 
-      let msg = format!(
-         "Content-Length: {}\r\n\r\n{}",
-         notification.to_string().len(),
-         notification
-      );
-
-      self
-         .stdin_tx
-         .send(msg)
-         .context("Failed to send notification")?;
-      Ok(())
+      send_message(&self.stdin_tx, &notification).context("Failed to send notification")
    }
 
    pub async fn text_document_completion(
@@ -287,7 +642,33 @@ impl LspClient {
          "Sending completion request to LSP server: {:?}",
          params.text_document_position.position
       );
-      let result = self.request::<request::Completion>(params).await;
+      let (id, rx) = self.send_request::<request::Completion>(params)?;
+      let superseded = self.completion_request.lock().unwrap().replace(id);
+      if let Some(previous) = superseded {
+         self.cancel_request(previous);
+      }
+
+      let response = self
+         .wait_for_response(
+            id,
+            rx,
+            <request::Completion as request::Request>::METHOD,
+            REQUEST_TIMEOUT,
+         )
+         .await;
+      {
+         let mut current = self.completion_request.lock().unwrap();
+         if *current != Some(id) {
+            // A newer request took over; its results are the ones that matter
+            return Ok(None);
+         }
+         *current = None;
+      }
+
+      let result = response.and_then(|response| {
+         serde_json::from_value::<Option<CompletionResponse>>(response)
+            .context("Failed to deserialize response")
+      });
       match &result {
          Ok(Some(response)) => {
             let count = match response {
@@ -302,19 +683,439 @@ impl LspClient {
       result
    }
 
+   pub async fn completion_item_resolve(&self, item: CompletionItem) -> Result<CompletionItem> {
+      self.request::<request::ResolveCompletionItem>(item).await
+   }
+
    pub async fn text_document_hover(&self, params: HoverParams) -> Result<Option<Hover>> {
       self.request::<request::HoverRequest>(params).await
    }
 
+   pub async fn text_document_definition(
+      &self,
+      params: GotoDefinitionParams,
+   ) -> Result<Option<GotoDefinitionResponse>> {
+      self.request::<request::GotoDefinition>(params).await
+   }
+
+   pub async fn text_document_type_definition(
+      &self,
+      params: request::GotoTypeDefinitionParams,
+   ) -> Result<Option<GotoDefinitionResponse>> {
+      self.request::<request::GotoTypeDefinition>(params).await
+   }
+
+   pub async fn text_document_implementation(
+      &self,
+      params: request::GotoImplementationParams,
+   ) -> Result<Option<GotoDefinitionResponse>> {
+      self.request::<request::GotoImplementation>(params).await
+   }
+
+   pub async fn text_document_declaration(
+      &self,
+      params: request::GotoDeclarationParams,
+   ) -> Result<Option<GotoDefinitionResponse>> {
+      self.request::<request::GotoDeclaration>(params).await
+   }
+
+   pub async fn text_document_references(
+      &self,
+      params: ReferenceParams,
+   ) -> Result<Option<Vec<Location>>> {
+      self.request::<request::References>(params).await
+   }
+
+   pub async fn text_document_prepare_rename(
+      &self,
+      params: TextDocumentPositionParams,
+   ) -> Result<Option<PrepareRenameResponse>> {
+      self.request::<request::PrepareRenameRequest>(params).await
+   }
+
+   pub async fn text_document_rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+      self.request::<request::Rename>(params).await
+   }
+
+   pub async fn text_document_code_action(
+      &self,
+      params: CodeActionParams,
+   ) -> Result<Option<CodeActionResponse>> {
+      self.request::<request::CodeActionRequest>(params).await
+   }
+
+   pub async fn code_action_resolve(&self, action: CodeAction) -> Result<CodeAction> {
+      self
+         .request::<request::CodeActionResolveRequest>(action)
+         .await
+   }
+
+   pub async fn text_document_document_symbol(
+      &self,
+      params: DocumentSymbolParams,
+   ) -> Result<Option<DocumentSymbolResponse>> {
+      self.request::<request::DocumentSymbolRequest>(params).await
+   }
+
+   pub async fn workspace_symbol(
+      &self,
+      params: WorkspaceSymbolParams,
+   ) -> Result<Option<WorkspaceSymbolResponse>> {
+      self
+         .request::<request::WorkspaceSymbolRequest>(params)
+         .await
+   }
+
+   pub async fn text_document_signature_help(
+      &self,
+      params: SignatureHelpParams,
+   ) -> Result<Option<SignatureHelp>> {
+      self.request::<request::SignatureHelpRequest>(params).await
+   }
+
+   pub async fn text_document_inlay_hint(
+      &self,
+      params: InlayHintParams,
+   ) -> Result<Option<Vec<InlayHint>>> {
+      self.request::<request::InlayHintRequest>(params).await
+   }
+
+   /// Semantic tokens for a whole document. With `delta` the server is asked only for what
+   /// changed since the last result, which is patched locally.
+   pub async fn text_document_semantic_tokens_full(
+      &self,
+      uri: Url,
+      delta: bool,
+   ) -> Result<Vec<SemanticToken>> {
+      let previous = self.semantic_tokens.lock().unwrap().get(&uri).cloned();
+      let tokens = match previous.filter(|previous| delta && previous.result_id.is_some()) {
+         Some(previous) => {
+            let params = SemanticTokensDeltaParams {
+               text_document: TextDocumentIdentifier { uri: uri.clone() },
+               previous_result_id: previous.result_id.clone().unwrap_or_default(),
+               work_done_progress_params: Default::default(),
+               partial_result_params: Default::default(),
+            };
+            match self
+               .request::<request::SemanticTokensFullDeltaRequest>(params)
+               .await?
+            {
+               Some(SemanticTokensFullDeltaResult::Tokens(tokens)) => tokens,
+               Some(SemanticTokensFullDeltaResult::TokensDelta(delta)) => SemanticTokens {
+                  result_id: delta.result_id,
+                  data: semantic_tokens::apply_edits(&previous.data, delta.edits),
+               },
+               Some(SemanticTokensFullDeltaResult::PartialTokensDelta { edits }) => {
+                  SemanticTokens {
+                     result_id: None,
+                     data: semantic_tokens::apply_edits(&previous.data, edits),
+                  }
+               }
+               None => SemanticTokens::default(),
+            }
+         }
+         None => {
+            let params = SemanticTokensParams {
+               text_document: TextDocumentIdentifier { uri: uri.clone() },
+               work_done_progress_params: Default::default(),
+               partial_result_params: Default::default(),
+            };
+            match self
+               .request::<request::SemanticTokensFullRequest>(params)
+               .await?
+            {
+               Some(SemanticTokensResult::Tokens(tokens)) => tokens,
+               Some(SemanticTokensResult::Partial(partial)) => SemanticTokens {
+                  result_id: None,
+                  data: partial.data,
+               },
+               None => SemanticTokens::default(),
+            }
+         }
+      };
+
+      let data = tokens.data.clone();
+      self.semantic_tokens.lock().unwrap().insert(uri, tokens);
+      Ok(data)
+   }
+
+   pub async fn text_document_semantic_tokens_range(
+      &self,
+      params: SemanticTokensRangeParams,
+   ) -> Result<Vec<SemanticToken>> {
+      let tokens = match self
+         .request::<request::SemanticTokensRangeRequest>(params)
+         .await?
+      {
+         Some(SemanticTokensRangeResult::Tokens(tokens)) => tokens.data,
+         Some(SemanticTokensRangeResult::Partial(partial)) => partial.data,
+         None => Vec::new(),
+      };
+      Ok(tokens)
+   }
+
+   pub async fn text_document_code_lens(
+      &self,
+      params: CodeLensParams,
+   ) -> Result<Option<Vec<CodeLens>>> {
+      self.request::<request::CodeLensRequest>(params).await
+   }
+
+   pub async fn code_lens_resolve(&self, lens: CodeLens) -> Result<CodeLens> {
+      self.request::<request::CodeLensResolve>(lens).await
+   }
+
+   pub async fn text_document_document_highlight(
+      &self,
+      params: DocumentHighlightParams,
+   ) -> Result<Option<Vec<DocumentHighlight>>> {
+      self
+         .request::<request::DocumentHighlightRequest>(params)
+         .await
+   }
+
+   pub async fn text_document_folding_range(
+      &self,
+      params: FoldingRangeParams,
+   ) -> Result<Option<Vec<FoldingRange>>> {
+      self.request::<request::FoldingRangeRequest>(params).await
+   }
+
+   pub async fn text_document_selection_range(
+      &self,
+      params: SelectionRangeParams,
+   ) -> Result<Option<Vec<SelectionRange>>> {
+      self.request::<request::SelectionRangeRequest>(params).await
+   }
+
+   pub async fn text_document_prepare_call_hierarchy(
+      &self,
+      params: CallHierarchyPrepareParams,
+   ) -> Result<Option<Vec<CallHierarchyItem>>> {
+      self.request::<request::CallHierarchyPrepare>(params).await
+   }
+
+   pub async fn call_hierarchy_incoming_calls(
+      &self,
+      params: CallHierarchyIncomingCallsParams,
+   ) -> Result<Option<Vec<CallHierarchyIncomingCall>>> {
+      self
+         .request::<request::CallHierarchyIncomingCalls>(params)
+         .await
+   }
+
+   pub async fn call_hierarchy_outgoing_calls(
+      &self,
+      params: CallHierarchyOutgoingCallsParams,
+   ) -> Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+      self
+         .request::<request::CallHierarchyOutgoingCalls>(params)
+         .await
+   }
+
+   /// `lsp_types` does not model the `typeHierarchyProvider` capability, so there is no
+   /// matching `supports_` check; servers without it answer with an error.
+   pub async fn text_document_prepare_type_hierarchy(
+      &self,
+      params: TypeHierarchyPrepareParams,
+   ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+      self.request::<request::TypeHierarchyPrepare>(params).await
+   }
+
+   pub async fn type_hierarchy_supertypes(
+      &self,
+      params: TypeHierarchySupertypesParams,
+   ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+      self
+         .request::<request::TypeHierarchySupertypes>(params)
+         .await
+   }
+
+   pub async fn type_hierarchy_subtypes(
+      &self,
+      params: TypeHierarchySubtypesParams,
+   ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+      self.request::<request::TypeHierarchySubtypes>(params).await
+   }
+
+   /// Pull diagnostics for a document into the store.
+   pub async fn text_document_diagnostic(&self, uri: Url) -> Result<()> {
+      let params = DocumentDiagnosticParams {
+         text_document: TextDocumentIdentifier { uri: uri.clone() },
+         identifier: self
+            .diagnostic_options()
+            .and_then(|options| options.identifier),
+         previous_result_id: self
+            .diagnostic_result_ids
+            .lock()
+            .unwrap()
+            .get(&uri)
+            .cloned(),
+         work_done_progress_params: Default::default(),
+         partial_result_params: Default::default(),
+      };
+      let (report, related) = match self
+         .request::<request::DocumentDiagnosticRequest>(params)
+         .await?
+      {
+         DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(report)) => (
+            Some(DocumentDiagnosticReportKind::Full(
+               report.full_document_diagnostic_report,
+            )),
+            report.related_documents,
+         ),
+         DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Unchanged(report)) => (
+            Some(DocumentDiagnosticReportKind::Unchanged(
+               report.unchanged_document_diagnostic_report,
+            )),
+            report.related_documents,
+         ),
+         DocumentDiagnosticReportResult::Partial(partial) => (None, partial.related_documents),
+      };
+
+      if let Some(report) = report {
+         self.store_diagnostic_report(uri, report);
+      }
+      for (uri, report) in related.unwrap_or_default() {
+         self.store_diagnostic_report(uri, report);
+      }
+      Ok(())
+   }
+
+   /// Pull diagnostics for the whole workspace into the store.
+   pub async fn workspace_diagnostic(&self) -> Result<()> {
+      let previous_result_ids = self
+         .diagnostic_result_ids
+         .lock()
+         .unwrap()
+         .iter()
+         .map(|(uri, value)| PreviousResultId {
+            uri: uri.clone(),
+            value: value.clone(),
+         })
+         .collect();
+      let params = WorkspaceDiagnosticParams {
+         identifier: self
+            .diagnostic_options()
+            .and_then(|options| options.identifier),
+         previous_result_ids,
+         work_done_progress_params: Default::default(),
+         partial_result_params: Default::default(),
+      };
+      let items = match self
+         .request::<request::WorkspaceDiagnosticRequest>(params)
+         .await?
+      {
+         WorkspaceDiagnosticReportResult::Report(report) => report.items,
+         WorkspaceDiagnosticReportResult::Partial(partial) => partial.items,
+      };
+
+      for item in items {
+         match item {
+            WorkspaceDocumentDiagnosticReport::Full(report) => self.store_diagnostic_report(
+               report.uri,
+               DocumentDiagnosticReportKind::Full(report.full_document_diagnostic_report),
+            ),
+            WorkspaceDocumentDiagnosticReport::Unchanged(report) => self.store_diagnostic_report(
+               report.uri,
+               DocumentDiagnosticReportKind::Unchanged(report.unchanged_document_diagnostic_report),
+            ),
+         }
+      }
+      Ok(())
+   }
+
+   fn store_diagnostic_report(&self, uri: Url, report: DocumentDiagnosticReportKind) {
+      let mut result_ids = self.diagnostic_result_ids.lock().unwrap();
+      match report {
+         DocumentDiagnosticReportKind::Full(report) => {
+            match report.result_id {
+               Some(result_id) => result_ids.insert(uri.clone(), result_id),
+               None => result_ids.remove(&uri),
+            };
+            self.diagnostics.set(uri, &self.server_name, report.items);
+         }
+         DocumentDiagnosticReportKind::Unchanged(report) => {
+            result_ids.insert(uri, report.result_id);
+         }
+      }
+   }
+
+   pub async fn workspace_execute_command(
+      &self,
+      params: ExecuteCommandParams,
+   ) -> Result<Option<Value>> {
+      self.request::<request::ExecuteCommand>(params).await
+   }
+
    pub fn text_document_did_open(&self, params: DidOpenTextDocumentParams) -> Result<()> {
+      let uri = &params.text_document.uri;
+      if let Some(path) = transport::editor_path(uri) {
+         self.open_documents.lock().unwrap().insert(path);
+      }
+      self
+         .documents
+         .lock()
+         .unwrap()
+         .insert(uri.clone(), params.text_document.clone());
+
+      if !self.wants_open_close() {
+         return Ok(());
+      }
       self.notify::<notification::DidOpenTextDocument>(params)
    }
 
-   pub fn text_document_did_change(&self, params: DidChangeTextDocumentParams) -> Result<()> {
+   /// Send changes in the form the server asked for. Range changes are collapsed into the full
+   /// text for servers that sync fully, and nothing is sent to servers that do not sync.
+   pub fn text_document_did_change(&self, mut params: DidChangeTextDocumentParams) -> Result<()> {
+      let text = {
+         let mut documents = self.documents.lock().unwrap();
+         let Some(document) = documents.get_mut(&params.text_document.uri) else {
+            bail!("Document {} is not open", params.text_document.uri);
+         };
+         document.text = apply_content_changes(&document.text, &params.content_changes);
+         document.version = params.text_document.version;
+         document.text.clone()
+      };
+
+      match self.text_document_sync_kind() {
+         TextDocumentSyncKind::NONE => return Ok(()),
+         TextDocumentSyncKind::INCREMENTAL => {}
+         _ => {
+            params.content_changes = vec![TextDocumentContentChangeEvent {
+               range: None,
+               range_length: None,
+               text,
+            }];
+         }
+      }
       self.notify::<notification::DidChangeTextDocument>(params)
    }
 
+   pub fn workspace_did_change_workspace_folders(
+      &self,
+      params: DidChangeWorkspaceFoldersParams,
+   ) -> Result<()> {
+      {
+         let mut folders = self.workspace_folders.lock().unwrap();
+         folders.retain(|folder| !params.event.removed.contains(folder));
+         folders.extend(params.event.added.iter().cloned());
+      }
+      self.notify::<notification::DidChangeWorkspaceFolders>(params)
+   }
+
    pub fn text_document_did_close(&self, params: DidCloseTextDocumentParams) -> Result<()> {
+      let uri = &params.text_document.uri;
+      if let Some(path) = transport::editor_path(uri) {
+         self.open_documents.lock().unwrap().remove(&path);
+      }
+      self.documents.lock().unwrap().remove(uri);
+      self.semantic_tokens.lock().unwrap().remove(uri);
+      self.diagnostic_result_ids.lock().unwrap().remove(uri);
+
+      if !self.wants_open_close() {
+         return Ok(());
+      }
       self.notify::<notification::DidCloseTextDocument>(params)
    }
 }
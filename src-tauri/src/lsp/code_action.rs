@@ -0,0 +1,138 @@
+use lsp_types::{
+   CodeAction, CodeActionDisabled, CodeActionKind, CodeActionOrCommand, Command, Diagnostic, Range,
+   WorkspaceEdit,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A code action or bare command in one shape. Sent back unchanged to resolve or apply it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LspCodeAction {
+   pub title: String,
+   pub kind: Option<String>,
+   #[serde(default)]
+   pub is_preferred: bool,
+   /// Why the action cannot be applied right now, if the server disabled it.
+   pub disabled_reason: Option<String>,
+   #[serde(default)]
+   pub diagnostics: Vec<Diagnostic>,
+   pub edit: Option<WorkspaceEdit>,
+   pub command: Option<Command>,
+   /// Opaque server data, preserved for `codeAction/resolve`.
+   pub data: Option<Value>,
+}
+
+impl LspCodeAction {
+   /// Whether `codeAction/resolve` may fill in the edit.
+   pub fn needs_resolve(&self) -> bool {
+      self.edit.is_none() && self.data.is_some()
+   }
+}
+
+impl From<CodeActionOrCommand> for LspCodeAction {
+   fn from(action: CodeActionOrCommand) -> Self {
+      match action {
+         CodeActionOrCommand::Command(command) => Self {
+            title: command.title.clone(),
+            kind: None,
+            is_preferred: false,
+            disabled_reason: None,
+            diagnostics: Vec::new(),
+            edit: None,
+            command: Some(command),
+            data: None,
+         },
+         CodeActionOrCommand::CodeAction(action) => action.into(),
+      }
+   }
+}
+
+impl From<CodeAction> for LspCodeAction {
+   fn from(action: CodeAction) -> Self {
+      Self {
+         title: action.title,
+         kind: action.kind.map(|kind| kind.as_str().to_string()),
+         is_preferred: action.is_preferred.unwrap_or(false),
+         disabled_reason: action.disabled.map(|disabled| disabled.reason),
+         diagnostics: action.diagnostics.unwrap_or_default(),
+         edit: action.edit,
+         command: action.command,
+         data: action.data,
+      }
+   }
+}
+
+impl From<LspCodeAction> for CodeAction {
+   fn from(action: LspCodeAction) -> Self {
+      Self {
+         title: action.title,
+         kind: action.kind.map(CodeActionKind::from),
+         diagnostics: (!action.diagnostics.is_empty()).then_some(action.diagnostics),
+         edit: action.edit,
+         command: action.command,
+         is_preferred: action.is_preferred.then_some(true),
+         disabled: action
+            .disabled_reason
+            .map(|reason| CodeActionDisabled { reason }),
+         data: action.data,
+      }
+   }
+}
+
+/// Diagnostics touching `range`, which the server needs to offer quick fixes for them.
+pub fn diagnostics_in_range(diagnostics: &[Diagnostic], range: Range) -> Vec<Diagnostic> {
+   diagnostics
+      .iter()
+      .filter(|diagnostic| {
+         diagnostic.range.start <= range.end && range.start <= diagnostic.range.end
+      })
+      .cloned()
+      .collect()
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use lsp_types::Position;
+
+   fn range(start: u32, end: u32) -> Range {
+      Range::new(Position::new(start, 0), Position::new(end, 0))
+   }
+
+   #[test]
+   fn test_actions_roundtrip_for_resolve() {
+      let response: Vec<CodeActionOrCommand> = serde_json::from_value(serde_json::json!([
+         { "title": "Organize imports", "command": "organize", "arguments": [] },
+         {
+            "title": "Add missing import",
+            "kind": "quickfix",
+            "isPreferred": true,
+            "data": { "id": 7 }
+         }
+      ]))
+      .unwrap();
+      let actions: Vec<LspCodeAction> = response.into_iter().map(Into::into).collect();
+
+      assert_eq!(actions[0].title, "Organize imports");
+      assert!(actions[0].command.is_some() && !actions[0].needs_resolve());
+      assert_eq!(actions[1].kind.as_deref(), Some("quickfix"));
+      assert!(actions[1].is_preferred && actions[1].needs_resolve());
+
+      let action: CodeAction = actions[1].clone().into();
+      assert_eq!(action.data, Some(serde_json::json!({ "id": 7 })));
+      assert_eq!(LspCodeAction::from(action), actions[1]);
+   }
+
+   #[test]
+   fn test_diagnostics_in_range() {
+      let diagnostic = |start, end| Diagnostic {
+         range: range(start, end),
+         ..Default::default()
+      };
+      let diagnostics = vec![diagnostic(1, 2), diagnostic(5, 6), diagnostic(9, 9)];
+
+      let found = diagnostics_in_range(&diagnostics, range(2, 5));
+      assert_eq!(found, diagnostics[..2]);
+      assert!(diagnostics_in_range(&diagnostics, range(7, 8)).is_empty());
+   }
+}
@@ -1,5 +1,12 @@
+use super::installer::{LspInstallSource, LspInstallSources};
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::path::{Path, PathBuf};
+use serde_json::Value;
+use std::{
+   collections::HashMap,
+   fs,
+   path::{Path, PathBuf},
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LspSettings {
@@ -21,107 +28,487 @@ pub struct LspServerConfig {
    pub command: PathBuf,
    pub args: Vec<String>,
    pub file_extensions: Vec<String>,
+   /// Files or directories whose presence in the workspace root selects this server.
+   #[serde(default)]
+   pub root_markers: Vec<String>,
+   /// Sent as `initializationOptions` in the `initialize` request.
+   #[serde(default)]
+   pub initialization_options: Option<Value>,
+   /// Answers the server's `workspace/configuration` requests.
+   #[serde(default)]
+   pub settings: Option<Value>,
+   /// Shown when the server binary cannot be found.
+   #[serde(default)]
+   pub install_hint: Option<String>,
+   /// Lets the editor install the server itself when it is missing.
+   #[serde(default)]
+   pub install: Option<LspInstallSource>,
+}
+
+impl LspServerConfig {
+   fn builtin(
+      name: &str,
+      language_id: &str,
+      command: &str,
+      args: &[&str],
+      file_extensions: &[&str],
+      root_markers: &[&str],
+      install_hint: &str,
+   ) -> Self {
+      let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect();
+      Self {
+         name: name.to_string(),
+         language_id: language_id.to_string(),
+         command: PathBuf::from(command),
+         args: strings(args),
+         file_extensions: strings(file_extensions),
+         root_markers: strings(root_markers),
+         initialization_options: None,
+         settings: None,
+         install_hint: Some(install_hint.to_string()),
+         install: None,
+      }
+   }
+
+   fn installed_from(mut self, source: LspInstallSource) -> Self {
+      self.install = Some(source);
+      self
+   }
+
+   /// A server given by path, outside the registry.
+   pub fn custom(path: &str, args: Vec<String>) -> Self {
+      Self {
+         name: path.split('/').next_back().unwrap_or("custom").to_string(),
+         language_id: String::new(),
+         command: PathBuf::from(path),
+         args,
+         file_extensions: Vec::new(),
+         root_markers: Vec::new(),
+         initialization_options: None,
+         settings: None,
+         install_hint: None,
+         install: None,
+      }
+   }
+
+   fn matches_workspace(&self, workspace: &Path) -> bool {
+      self
+         .root_markers
+         .iter()
+         .any(|marker| workspace.join(marker).exists())
+   }
+}
+
+/// Changes to one server from the user's `lsp.json`. Fields left out keep the built-in values;
+/// servers that are not built in need at least `command`, `language_id` and `file_extensions`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LspServerOverride {
+   pub language_id: Option<String>,
+   pub command: Option<PathBuf>,
+   pub args: Option<Vec<String>>,
+   pub file_extensions: Option<Vec<String>>,
+   pub root_markers: Option<Vec<String>>,
+   pub initialization_options: Option<Value>,
+   pub settings: Option<Value>,
+   pub install: Option<LspInstallSource>,
+   #[serde(default)]
+   pub disabled: bool,
+}
+
+/// Contents of the user-editable `lsp.json` in the app data directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LspUserConfig {
+   #[serde(default)]
+   pub servers: HashMap<String, LspServerOverride>,
+   #[serde(default)]
+   pub install_sources: LspInstallSources,
+}
+
+impl LspUserConfig {
+   pub fn load(path: &Path) -> Result<Self> {
+      if !path.exists() {
+         return Ok(Self::default());
+      }
+      let json = fs::read_to_string(path)
+         .with_context(|| format!("Failed to read LSP config {}", path.display()))?;
+      serde_json::from_str(&json)
+         .with_context(|| format!("Failed to parse LSP config {}", path.display()))
+   }
 }
 
 pub struct LspRegistry {
    servers: Vec<LspServerConfig>,
+   install_sources: LspInstallSources,
 }
 
 impl LspRegistry {
    pub fn new() -> Self {
+      Self {
+         servers: builtin_servers(),
+         install_sources: LspInstallSources::default(),
+      }
+   }
+
+   /// Built-in servers with the user's overrides and additions applied. User-defined servers
+   /// take precedence over built-in ones for the same files.
+   pub fn with_user_config(config: LspUserConfig) -> Self {
       let mut registry = Self {
-         servers: Vec::new(),
+         install_sources: config.install_sources,
+         ..Self::new()
       };
+      let mut added = Vec::new();
 
-      // Register TypeScript server
-      registry.register_typescript();
+      let mut entries: Vec<_> = config.servers.into_iter().collect();
+      entries.sort_by(|a, b| a.0.cmp(&b.0));
+      for (name, entry) in entries {
+         if entry.disabled {
+            registry.servers.retain(|server| server.name != name);
+            continue;
+         }
 
+         if let Some(server) = registry.servers.iter_mut().find(|s| s.name == name) {
+            apply_override(server, entry);
+            continue;
+         }
+
+         let (Some(language_id), Some(command), Some(file_extensions)) = (
+            entry.language_id.clone(),
+            entry.command.clone(),
+            entry.file_extensions.clone(),
+         ) else {
+            log::warn!(
+               "[LSP] Ignoring server '{}' from config: command, language_id and file_extensions \
+                are required",
+               name
+            );
+            continue;
+         };
+         let mut server = LspServerConfig {
+            name,
+            language_id,
+            command,
+            args: Vec::new(),
+            file_extensions,
+            root_markers: Vec::new(),
+            initialization_options: None,
+            settings: None,
+            install_hint: None,
+            install: None,
+         };
+         apply_override(&mut server, entry);
+         added.push(server);
+      }
+
+      added.append(&mut registry.servers);
+      registry.servers = added;
       registry
    }
 
-   fn register_typescript(&mut self) {
-      self.servers.push(LspServerConfig {
-         name: "typescript".to_string(),
-         language_id: "typescript".to_string(),
-         command: PathBuf::from("typescript-language-server"),
-         args: vec!["--stdio".to_string()],
-         file_extensions: vec![
-            "ts".to_string(),
-            "tsx".to_string(),
-            "js".to_string(),
-            "jsx".to_string(),
-         ],
-      });
+   pub fn servers(&self) -> &[LspServerConfig] {
+      &self.servers
+   }
+
+   pub fn install_sources(&self) -> &LspInstallSources {
+      &self.install_sources
    }
 
    pub fn find_server_for_file(&self, file_path: &Path) -> Option<&LspServerConfig> {
-      // Get file extension
-      let extension = file_path.extension().and_then(|e| e.to_str()).unwrap_or("");
+      self.servers_for_file(file_path).next()
+   }
 
-      // Find server that handles this extension
+   /// Servers able to handle `file_path`, in order of preference.
+   pub fn servers_for_file<'a>(
+      &'a self,
+      file_path: &Path,
+   ) -> impl Iterator<Item = &'a LspServerConfig> + use<'a> {
+      let extension = file_path
+         .extension()
+         .and_then(|e| e.to_str())
+         .unwrap_or("")
+         .to_string();
       self
          .servers
          .iter()
-         .find(|s| s.file_extensions.contains(&extension.to_string()))
+         .filter(move |server| server.file_extensions.contains(&extension))
    }
 
-   pub fn find_server_for_workspace(&self, workspace: &Path) -> Option<&LspServerConfig> {
-      // Always try TypeScript server for JS/TS projects - it handles both
-      if self.is_javascript_or_typescript_project(workspace) {
-         self.servers.iter().find(|s| s.name == "typescript")
-      } else {
-         // For now, default to TypeScript if no other server is found
-         // This ensures LSP functionality for most common file types
-         self.servers.iter().find(|s| s.name == "typescript")
-      }
-   }
-
-   fn is_javascript_or_typescript_project(&self, workspace: &Path) -> bool {
-      // Check for TypeScript/JavaScript project indicators
-      let config_indicators = ["tsconfig.json", "package.json", "jsconfig.json"];
-
-      let file_extensions = [".ts", ".tsx", ".js", ".jsx", ".mjs", ".cjs"];
-
-      // Check in workspace root for config files
-      for indicator in &config_indicators {
-         if workspace.join(indicator).exists() {
-            return true;
-         }
+   /// Servers for a workspace, in order of preference: those whose root markers are present,
+   /// then those handling source files at the top of the workspace.
+   pub fn servers_for_workspace(&self, workspace: &Path) -> Vec<&LspServerConfig> {
+      let mut servers: Vec<_> = self
+         .servers
+         .iter()
+         .filter(|server| server.matches_workspace(workspace))
+         .collect();
+      if !servers.is_empty() {
+         return servers;
       }
 
-      // Check for source files in common directories and root
-      let source_dirs = [
-         "src",
-         "lib",
-         "app",
-         "pages",
-         "components",
-         "javascript",
-         "js",
-         ".",
-      ];
-      for dir in &source_dirs {
-         let dir_path = if *dir == "." {
-            workspace.to_path_buf()
-         } else {
-            workspace.join(dir)
+      let source_dirs = [".", "src", "lib", "app"];
+      for dir in source_dirs {
+         let Ok(entries) = fs::read_dir(workspace.join(dir)) else {
+            continue;
          };
-         if dir_path.exists() && dir_path.is_dir() {
-            // Walk through the directory looking for TS/JS files
-            if let Ok(entries) = std::fs::read_dir(&dir_path) {
-               for entry in entries.flatten() {
-                  if let Some(ext) = entry.path().extension() {
-                     let ext_str = format!(".{}", ext.to_str().unwrap_or(""));
-                     if file_extensions.contains(&ext_str.as_str()) {
-                        return true;
-                     }
-                  }
+         for path in entries.flatten().map(|entry| entry.path()) {
+            for server in self.servers_for_file(&path) {
+               if !servers.iter().any(|s| s.name == server.name) {
+                  servers.push(server);
                }
             }
          }
       }
+      servers
+   }
+
+   pub fn is_file_supported(&self, file_path: &Path) -> bool {
+      self.find_server_for_file(file_path).is_some()
+   }
+}
+
+fn apply_override(server: &mut LspServerConfig, entry: LspServerOverride) {
+   if let Some(language_id) = entry.language_id {
+      server.language_id = language_id;
+   }
+   if let Some(command) = entry.command {
+      server.command = command;
+   }
+   if let Some(args) = entry.args {
+      server.args = args;
+   }
+   if let Some(file_extensions) = entry.file_extensions {
+      server.file_extensions = file_extensions;
+   }
+   if let Some(root_markers) = entry.root_markers {
+      server.root_markers = root_markers;
+   }
+   if entry.initialization_options.is_some() {
+      server.initialization_options = entry.initialization_options;
+   }
+   if entry.settings.is_some() {
+      server.settings = entry.settings;
+   }
+   if entry.install.is_some() {
+      server.install = entry.install;
+   }
+}
+
+fn builtin_servers() -> Vec<LspServerConfig> {
+   vec![
+      LspServerConfig::builtin(
+         "typescript",
+         "typescript",
+         "typescript-language-server",
+         &["--stdio"],
+         &["ts", "tsx", "js", "jsx", "mjs", "cjs", "mts", "cts"],
+         &["tsconfig.json", "jsconfig.json", "package.json"],
+         "bun add -g typescript-language-server typescript",
+      )
+      .installed_from(LspInstallSource::Npm {
+         packages: vec![
+            "typescript-language-server".to_string(),
+            "typescript".to_string(),
+         ],
+         bin: "typescript-language-server".to_string(),
+      }),
+      LspServerConfig::builtin(
+         "rust-analyzer",
+         "rust",
+         "rust-analyzer",
+         &[],
+         &["rs"],
+         &["Cargo.toml", "rust-project.json"],
+         "rustup component add rust-analyzer",
+      )
+      .installed_from(LspInstallSource::GithubRelease {
+         repo: "rust-lang/rust-analyzer".to_string(),
+         asset: "rust-analyzer-{target}.gz".to_string(),
+         binary: "rust-analyzer".to_string(),
+      }),
+      LspServerConfig::builtin(
+         "pyright",
+         "python",
+         "pyright-langserver",
+         &["--stdio"],
+         &["py", "pyi"],
+         &[
+            "pyproject.toml",
+            "pyrightconfig.json",
+            "setup.py",
+            "setup.cfg",
+            "requirements.txt",
+            "Pipfile",
+         ],
+         "bun add -g pyright",
+      )
+      .installed_from(LspInstallSource::Npm {
+         packages: vec!["pyright".to_string()],
+         bin: "pyright-langserver".to_string(),
+      }),
+      LspServerConfig::builtin(
+         "pylsp",
+         "python",
+         "pylsp",
+         &[],
+         &["py", "pyi"],
+         &[
+            "pyproject.toml",
+            "setup.py",
+            "setup.cfg",
+            "requirements.txt",
+            "Pipfile",
+         ],
+         "pip install python-lsp-server",
+      ),
+      LspServerConfig::builtin(
+         "gopls",
+         "go",
+         "gopls",
+         &[],
+         &["go"],
+         &["go.mod", "go.work"],
+         "go install golang.org/x/tools/gopls@latest",
+      )
+      .installed_from(LspInstallSource::Go {
+         module: "golang.org/x/tools/gopls".to_string(),
+         bin: "gopls".to_string(),
+      }),
+      LspServerConfig::builtin(
+         "clangd",
+         "cpp",
+         "clangd",
+         &[],
+         &["c", "h", "cc", "cpp", "cxx", "hh", "hpp", "hxx", "m", "mm"],
+         &[
+            "compile_commands.json",
+            "compile_flags.txt",
+            ".clangd",
+            "CMakeLists.txt",
+         ],
+         "install clangd from your system package manager or LLVM",
+      ),
+      LspServerConfig::builtin(
+         "lua-language-server",
+         "lua",
+         "lua-language-server",
+         &[],
+         &["lua"],
+         &[".luarc.json", ".luarc.jsonc"],
+         "install lua-language-server from your system package manager",
+      ),
+      LspServerConfig::builtin(
+         "zls",
+         "zig",
+         "zls",
+         &[],
+         &["zig", "zon"],
+         &["build.zig"],
+         "install zls from https://github.com/zigtools/zls/releases",
+      ),
+      LspServerConfig::builtin(
+         "bash-language-server",
+         "shellscript",
+         "bash-language-server",
+         &["start"],
+         &["sh", "bash"],
+         &[],
+         "bun add -g bash-language-server",
+      )
+      .installed_from(LspInstallSource::Npm {
+         packages: vec!["bash-language-server".to_string()],
+         bin: "bash-language-server".to_string(),
+      }),
+      LspServerConfig::builtin(
+         "json",
+         "json",
+         "vscode-json-language-server",
+         &["--stdio"],
+         &["json", "jsonc"],
+         &[],
+         "bun add -g vscode-langservers-extracted",
+      )
+      .installed_from(LspInstallSource::Npm {
+         packages: vec!["vscode-langservers-extracted".to_string()],
+         bin: "vscode-json-language-server".to_string(),
+      }),
+      LspServerConfig::builtin(
+         "yaml",
+         "yaml",
+         "yaml-language-server",
+         &["--stdio"],
+         &["yaml", "yml"],
+         &[],
+         "bun add -g yaml-language-server",
+      )
+      .installed_from(LspInstallSource::Npm {
+         packages: vec!["yaml-language-server".to_string()],
+         bin: "yaml-language-server".to_string(),
+      }),
+   ]
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_workspace_servers_follow_root_markers() {
+      let dir = tempfile::tempdir().unwrap();
+      fs::write(dir.path().join("Cargo.toml"), "[package]").unwrap();
+      let registry = LspRegistry::new();
+
+      let names: Vec<_> = registry
+         .servers_for_workspace(dir.path())
+         .iter()
+         .map(|s| s.name.as_str())
+         .collect();
+      assert_eq!(names, ["rust-analyzer"]);
+
+      fs::remove_file(dir.path().join("Cargo.toml")).unwrap();
+      fs::write(dir.path().join("main.py"), "").unwrap();
+      let names: Vec<_> = registry
+         .servers_for_workspace(dir.path())
+         .iter()
+         .map(|s| s.name.as_str())
+         .collect();
+      assert_eq!(names, ["pyright", "pylsp"]);
+   }
+
+   #[test]
+   fn test_user_config_overrides_and_adds_servers() {
+      let config: LspUserConfig = serde_json::from_value(serde_json::json!({
+         "servers": {
+            "rust-analyzer": {
+               "command": "/opt/rust-analyzer",
+               "initialization_options": { "cargo": { "features": "all" } }
+            },
+            "pyright": { "disabled": true },
+            "nil": {
+               "language_id": "nix",
+               "command": "nil",
+               "file_extensions": ["nix"]
+            },
+            "broken": { "command": "broken" }
+         }
+      }))
+      .unwrap();
+      let registry = LspRegistry::with_user_config(config);
 
-      // If we found any JS/TS files anywhere, consider it a JS/TS project
-      true // Default to yes for broader compatibility
+      let find = |name: &str| registry.servers().iter().find(|s| s.name == name);
+      let rust = find("rust-analyzer").unwrap();
+      assert_eq!(rust.command, PathBuf::from("/opt/rust-analyzer"));
+      assert!(rust.initialization_options.is_some());
+      assert_eq!(
+         registry
+            .find_server_for_file(Path::new("main.py"))
+            .map(|s| s.name.as_str()),
+         Some("pylsp")
+      );
+      assert_eq!(
+         registry
+            .find_server_for_file(Path::new("flake.nix"))
+            .map(|s| s.name.as_str()),
+         Some("nil")
+      );
+      assert!(find("broken").is_none());
    }
 }
@@ -0,0 +1,473 @@
+use super::workspace_edit::{AppliedWorkspaceEdit, WorkspaceEditApplier};
+use crate::diagnostics::DiagnosticsStore;
+use anyhow::{Context, Result};
+use crossbeam_channel::Sender;
+use lsp_types::*;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::{
+   collections::{HashMap, HashSet},
+   path::PathBuf,
+   sync::{Arc, Mutex},
+};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::oneshot;
+
+pub(super) type PendingRequests = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<Value>>>>>;
+/// `window/showMessageRequest`s waiting for the user, by the id sent to the frontend.
+pub(super) type MessageRequests = Arc<Mutex<HashMap<String, Value>>>;
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Payload of the events forwarded to the frontend, tagged with the server that sent them.
+#[derive(Serialize)]
+struct ServerEvent<'a, T: Serialize> {
+   server_name: &'a str,
+   #[serde(flatten)]
+   params: T,
+}
+
+#[derive(Serialize)]
+struct MessageRequestEvent {
+   request_id: String,
+   #[serde(flatten)]
+   params: ShowMessageRequestParams,
+}
+
+#[derive(Serialize)]
+struct ApplyEditEvent {
+   label: Option<String>,
+   result: AppliedWorkspaceEdit,
+}
+
+struct ResponseError {
+   code: i64,
+   message: String,
+}
+
+/// Routes messages read from a server: responses complete our pending requests, requests from
+/// the server are answered and notifications are forwarded to the frontend.
+pub(super) struct Dispatcher {
+   pub server_name: String,
+   pub app_handle: Option<AppHandle>,
+   pub stdin_tx: Sender<String>,
+   pub pending_requests: PendingRequests,
+   /// Published diagnostics are recorded here under the server's name.
+   pub diagnostics: DiagnosticsStore,
+   pub message_requests: MessageRequests,
+   /// Answers `workspace/configuration`.
+   pub settings: Value,
+   /// Documents open in the editor, whose edits go to their buffers rather than to disk.
+   pub open_documents: Arc<Mutex<HashSet<PathBuf>>>,
+   pub workspace_folders: Arc<Mutex<Vec<WorkspaceFolder>>>,
+}
+
+impl Dispatcher {
+   pub fn dispatch(&self, message: Value) {
+      let method = message.get("method").and_then(|m| m.as_str());
+      match (message.get("id"), method) {
+         (Some(id), Some(method)) => {
+            let params = message.get("params").cloned().unwrap_or(Value::Null);
+            self.handle_request(id.clone(), method, params);
+         }
+         (Some(_), None) => self.handle_response(message),
+         (None, Some(method)) => {
+            let params = message.get("params").cloned().unwrap_or(Value::Null);
+            self.handle_notification(method, params);
+         }
+         (None, None) => log::warn!("Received LSP message without id or method"),
+      }
+   }
+
+   fn handle_response(&self, response: Value) {
+      let id = response.get("id").and_then(|id| match id {
+         Value::String(id) => id.parse().ok(),
+         id => id.as_u64(),
+      });
+      if let Some(id) = id
+         && let Some(tx) = self.pending_requests.lock().unwrap().remove(&id)
+      {
+         if let Some(error) = response.get("error") {
+            let _ = tx.send(Err(anyhow::anyhow!("LSP error: {:?}", error)));
+         } else {
+            let result = response.get("result").cloned().unwrap_or(Value::Null);
+            let _ = tx.send(Ok(result));
+         }
+      }
+   }
+
+   /// Fail every request still waiting for an answer, e.g. once the server has exited.
+   pub fn fail_pending(&self, reason: &str) {
+      let pending: Vec<_> = self.pending_requests.lock().unwrap().drain().collect();
+      if !pending.is_empty() {
+         log::warn!(
+            "[LSP] Failing {} pending requests to '{}': {}",
+            pending.len(),
+            self.server_name,
+            reason
+         );
+      }
+      for (_, tx) in pending {
+         let _ = tx.send(Err(anyhow::anyhow!("{}", reason)));
+      }
+   }
+
+   fn handle_request(&self, id: Value, method: &str, params: Value) {
+      log::debug!("LSP server request {}: {}", id, method);
+
+      let result = match method {
+         "workspace/configuration" => parse::<ConfigurationParams>(params).map(|params| {
+            let items: Vec<Value> = params
+               .items
+               .iter()
+               .map(|item| configuration_section(&self.settings, item.section.as_deref()))
+               .collect();
+            json!(items)
+         }),
+         "client/registerCapability"
+         | "client/unregisterCapability"
+         | "window/workDoneProgress/create"
+         | "workspace/semanticTokens/refresh"
+         | "workspace/inlayHint/refresh"
+         | "workspace/codeLens/refresh" => Ok(Value::Null),
+         "workspace/diagnostic/refresh" => {
+            // The frontend pulls diagnostics again for the documents it shows
+            self.forward::<Value>("lsp://diagnostics-refresh", json!({}));
+            Ok(Value::Null)
+         }
+         "workspace/workspaceFolders" => Ok(json!(*self.workspace_folders.lock().unwrap())),
+         "window/showMessageRequest" => match parse::<ShowMessageRequestParams>(params) {
+            Ok(params) => {
+               self.forward_message_request(id, params);
+               return;
+            }
+            Err(e) => Err(e),
+         },
+         "workspace/applyEdit" => {
+            parse::<ApplyWorkspaceEditParams>(params).map(|params| json!(self.apply_edit(params)))
+         }
+         _ => Err(ResponseError {
+            code: METHOD_NOT_FOUND,
+            message: format!("Unhandled method {}", method),
+         }),
+      };
+
+      if let Err(e) = send_response(&self.stdin_tx, id, result) {
+         log::error!("Failed to answer LSP request {}: {}", method, e);
+      }
+   }
+
+   /// Ask the user through the frontend; the answer arrives later through
+   /// [`super::client::LspClient::respond_to_message_request`].
+   fn forward_message_request(&self, id: Value, params: ShowMessageRequestParams) {
+      let Some(app) = &self.app_handle else {
+         let _ = send_response(&self.stdin_tx, id, Ok(Value::Null));
+         return;
+      };
+
+      let request_id = uuid::Uuid::new_v4().to_string();
+      self
+         .message_requests
+         .lock()
+         .unwrap()
+         .insert(request_id.clone(), id);
+      self.emit(
+         app,
+         "lsp://show-message-request",
+         MessageRequestEvent { request_id, params },
+      );
+   }
+
+   fn apply_edit(&self, params: ApplyWorkspaceEditParams) -> ApplyWorkspaceEditResponse {
+      let open_documents = self.open_documents.lock().unwrap().clone();
+      match WorkspaceEditApplier::new(open_documents).apply(params.edit) {
+         Ok(result) => {
+            if let Some(app) = &self.app_handle {
+               let event = ApplyEditEvent {
+                  label: params.label,
+                  result,
+               };
+               self.emit(app, "lsp://apply-edit", event);
+            }
+            ApplyWorkspaceEditResponse {
+               applied: true,
+               failure_reason: None,
+               failed_change: None,
+            }
+         }
+         Err(e) => {
+            log::warn!(
+               "[LSP] Failed to apply edit from '{}': {}",
+               self.server_name,
+               e
+            );
+            ApplyWorkspaceEditResponse {
+               applied: false,
+               failure_reason: Some(e.to_string()),
+               failed_change: None,
+            }
+         }
+      }
+   }
+
+   fn handle_notification(&self, method: &str, params: Value) {
+      match method {
+         "textDocument/publishDiagnostics" => {
+            log::debug!("Received diagnostics: {:?}", params);
+
+            // Parse diagnostics
+            if let Ok(diagnostic_params) =
+               serde_json::from_value::<PublishDiagnosticsParams>(params)
+            {
+               self.diagnostics.set(
+                  diagnostic_params.uri.clone(),
+                  &self.server_name,
+                  diagnostic_params.diagnostics.clone(),
+               );
+
+               // Emit event to frontend
+               if let Some(app) = &self.app_handle {
+                  let _ = app.emit("lsp://diagnostics", &diagnostic_params);
+                  log::info!("Emitted diagnostics for file: {}", diagnostic_params.uri);
+               }
+            } else {
+               log::error!("Failed to parse diagnostics params");
+            }
+         }
+         "$/progress" => self.forward::<ProgressParams>("lsp://progress", params),
+         "window/showMessage" => self.forward::<ShowMessageParams>("lsp://show-message", params),
+         "window/logMessage" => {
+            if let Some(message) = params.get("message").and_then(|m| m.as_str()) {
+               log::debug!("[LSP] {}: {}", self.server_name, message);
+            }
+            self.forward::<LogMessageParams>("lsp://log-message", params);
+         }
+         method_name => {
+            log::debug!("Unhandled LSP notification: {}", method_name);
+         }
+      }
+   }
+
+   fn forward<T: serde::de::DeserializeOwned + Serialize>(&self, event: &str, params: Value) {
+      let Some(app) = &self.app_handle else {
+         return;
+      };
+      match serde_json::from_value::<T>(params) {
+         Ok(params) => self.emit(app, event, params),
+         Err(e) => log::warn!("Failed to parse params for {}: {}", event, e),
+      }
+   }
+
+   fn emit<T: Serialize>(&self, app: &AppHandle, event: &str, params: T) {
+      let payload = ServerEvent {
+         server_name: &self.server_name,
+         params,
+      };
+      let _ = app.emit(event, &payload);
+   }
+}
+
+fn parse<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, ResponseError> {
+   serde_json::from_value(params).map_err(|e| ResponseError {
+      code: INVALID_PARAMS,
+      message: e.to_string(),
+   })
+}
+
+/// Frame a JSON-RPC message for the server's stdin.
+/// The id a request goes out with. The protocol only allows 32-bit integer ids, so larger ones
+/// are sent as strings.
+pub(super) fn request_id(id: u64) -> NumberOrString {
+   i32::try_from(id).map_or_else(
+      |_| NumberOrString::String(id.to_string()),
+      NumberOrString::Number,
+   )
+}
+
+pub(super) fn send_message(stdin_tx: &Sender<String>, message: &Value) -> Result<()> {
+   let message = message.to_string();
+   let msg = format!("Content-Length: {}\r\n\r\n{}", message.len(), message);
+   stdin_tx.send(msg).context("Language server is not running")
+}
+
+fn send_response(
+   stdin_tx: &Sender<String>,
+   id: Value,
+   result: Result<Value, ResponseError>,
+) -> Result<()> {
+   let response = match result {
+      Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+      Err(error) => json!({
+         "jsonrpc": "2.0",
+         "id": id,
+         "error": { "code": error.code, "message": error.message },
+      }),
+   };
+   send_message(stdin_tx, &response)
+}
+
+pub(super) fn send_result(stdin_tx: &Sender<String>, id: Value, result: Value) -> Result<()> {
+   send_response(stdin_tx, id, Ok(result))
+}
+
+/// The part of `settings` a `workspace/configuration` item asks for. Sections are dotted paths,
+/// e.g. `python.analysis`; missing sections are `null`.
+pub fn configuration_section(settings: &Value, section: Option<&str>) -> Value {
+   let Some(section) = section.filter(|s| !s.is_empty()) else {
+      return settings.clone();
+   };
+   section
+      .split('.')
+      .try_fold(settings, |value, key| value.get(key))
+      .cloned()
+      .unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crossbeam_channel::unbounded;
+
+   fn dispatcher(stdin_tx: Sender<String>) -> Dispatcher {
+      Dispatcher {
+         server_name: "test".to_string(),
+         app_handle: None,
+         stdin_tx,
+         pending_requests: Arc::new(Mutex::new(HashMap::new())),
+         diagnostics: DiagnosticsStore::new(None),
+         message_requests: Arc::new(Mutex::new(HashMap::new())),
+         settings: json!({ "python": { "analysis": { "typeCheckingMode": "strict" } } }),
+         open_documents: Arc::new(Mutex::new(HashSet::new())),
+         workspace_folders: Arc::new(Mutex::new(Vec::new())),
+      }
+   }
+
+   fn body(message: String) -> Value {
+      let (_, body) = message.split_once("\r\n\r\n").unwrap();
+      serde_json::from_str(body).unwrap()
+   }
+
+   #[test]
+   fn test_configuration_sections() {
+      let settings = json!({ "python": { "analysis": { "strict": true } } });
+      assert_eq!(configuration_section(&settings, None), settings);
+      assert_eq!(
+         configuration_section(&settings, Some("python.analysis")),
+         json!({ "strict": true })
+      );
+      assert_eq!(
+         configuration_section(&settings, Some("rust-analyzer")),
+         Value::Null
+      );
+   }
+
+   #[test]
+   fn test_server_requests_are_answered() {
+      let (tx, rx) = unbounded();
+      let dispatcher = dispatcher(tx);
+
+      dispatcher.dispatch(json!({
+         "jsonrpc": "2.0",
+         "id": 1,
+         "method": "workspace/configuration",
+         "params": { "items": [{ "section": "python.analysis" }, { "section": "missing" }] }
+      }));
+      assert_eq!(
+         body(rx.try_recv().unwrap()),
+         json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": [{ "typeCheckingMode": "strict" }, null]
+         })
+      );
+
+      dispatcher.dispatch(json!({
+         "jsonrpc": "2.0",
+         "id": "progress-1",
+         "method": "window/workDoneProgress/create",
+         "params": { "token": "indexing" }
+      }));
+      assert_eq!(body(rx.try_recv().unwrap())["id"], "progress-1");
+
+      dispatcher.dispatch(json!({ "jsonrpc": "2.0", "id": 3, "method": "custom/unknown" }));
+      assert_eq!(
+         body(rx.try_recv().unwrap())["error"]["code"],
+         METHOD_NOT_FOUND
+      );
+   }
+
+   #[test]
+   fn test_apply_edit_requests_write_closed_files() {
+      let dir = tempfile::tempdir().unwrap();
+      let file = dir.path().join("main.py");
+      std::fs::write(&file, "import os").unwrap();
+      let (tx, rx) = unbounded();
+      let dispatcher = dispatcher(tx);
+
+      dispatcher.dispatch(json!({
+         "jsonrpc": "2.0",
+         "id": 4,
+         "method": "workspace/applyEdit",
+         "params": {
+            "edit": {
+               "changes": {
+                  Url::from_file_path(&file).unwrap().as_str(): [{
+                     "range": {
+                        "start": { "line": 0, "character": 7 },
+                        "end": { "line": 0, "character": 9 }
+                     },
+                     "newText": "sys"
+                  }]
+               }
+            }
+         }
+      }));
+
+      assert_eq!(body(rx.try_recv().unwrap())["result"]["applied"], true);
+      assert_eq!(std::fs::read_to_string(&file).unwrap(), "import sys");
+   }
+
+   #[test]
+   fn test_pending_requests_fail_when_server_exits() {
+      let (tx, _rx) = unbounded();
+      let dispatcher = dispatcher(tx);
+      let (answered_tx, answered_rx) = oneshot::channel();
+      let (waiting_tx, mut waiting_rx) = oneshot::channel();
+      dispatcher
+         .pending_requests
+         .lock()
+         .unwrap()
+         .insert(1, answered_tx);
+      dispatcher
+         .pending_requests
+         .lock()
+         .unwrap()
+         .insert(2, waiting_tx);
+
+      dispatcher.dispatch(json!({ "jsonrpc": "2.0", "id": 1, "result": "done" }));
+
+      // Ids past the 32-bit range go out as strings and come back the same way
+      let large_id = u64::from(u32::MAX);
+      assert_eq!(
+         request_id(large_id),
+         NumberOrString::String(large_id.to_string())
+      );
+      assert_eq!(request_id(7), NumberOrString::Number(7));
+      let (large_tx, large_rx) = oneshot::channel();
+      dispatcher
+         .pending_requests
+         .lock()
+         .unwrap()
+         .insert(large_id, large_tx);
+      dispatcher.dispatch(json!({ "jsonrpc": "2.0", "id": large_id.to_string(), "result": 1 }));
+      assert_eq!(large_rx.blocking_recv().unwrap().unwrap(), 1);
+
+      dispatcher.fail_pending("Language server exited");
+
+      assert_eq!(answered_rx.blocking_recv().unwrap().unwrap(), "done");
+      let error = waiting_rx.try_recv().unwrap().unwrap_err();
+      assert_eq!(error.to_string(), "Language server exited");
+      assert!(dispatcher.pending_requests.lock().unwrap().is_empty());
+   }
+}
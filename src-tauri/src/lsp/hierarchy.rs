@@ -0,0 +1,281 @@
+use anyhow::Result;
+use lsp_types::{
+   CallHierarchyIncomingCall, CallHierarchyItem, CallHierarchyOutgoingCall, Range, SymbolKind,
+   SymbolTag, TypeHierarchyItem, Url,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashSet, future::Future};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HierarchyDirection {
+   /// Callers of the item.
+   IncomingCalls,
+   /// Functions the item calls.
+   OutgoingCalls,
+   Supertypes,
+   Subtypes,
+}
+
+impl HierarchyDirection {
+   pub fn is_call(self) -> bool {
+      matches!(self, Self::IncomingCalls | Self::OutgoingCalls)
+   }
+}
+
+/// A call or type hierarchy item. Sent back unchanged to expand it further.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HierarchyItem {
+   pub name: String,
+   pub kind: SymbolKind,
+   #[serde(default)]
+   pub tags: Vec<SymbolTag>,
+   pub detail: Option<String>,
+   pub uri: Url,
+   pub range: Range,
+   pub selection_range: Range,
+   /// Opaque server data, preserved between the prepare and expand requests.
+   pub data: Option<Value>,
+}
+
+impl HierarchyItem {
+   /// Identity used to notice an item showing up again, e.g. in recursive calls.
+   fn key(&self) -> (Url, Range) {
+      (self.uri.clone(), self.selection_range)
+   }
+}
+
+impl From<CallHierarchyItem> for HierarchyItem {
+   fn from(item: CallHierarchyItem) -> Self {
+      Self {
+         name: item.name,
+         kind: item.kind,
+         tags: item.tags.unwrap_or_default(),
+         detail: item.detail,
+         uri: item.uri,
+         range: item.range,
+         selection_range: item.selection_range,
+         data: item.data,
+      }
+   }
+}
+
+impl From<HierarchyItem> for CallHierarchyItem {
+   fn from(item: HierarchyItem) -> Self {
+      Self {
+         name: item.name,
+         kind: item.kind,
+         tags: (!item.tags.is_empty()).then_some(item.tags),
+         detail: item.detail,
+         uri: item.uri,
+         range: item.range,
+         selection_range: item.selection_range,
+         data: item.data,
+      }
+   }
+}
+
+impl From<TypeHierarchyItem> for HierarchyItem {
+   fn from(item: TypeHierarchyItem) -> Self {
+      Self {
+         name: item.name,
+         kind: item.kind,
+         tags: item.tags.into_iter().collect(),
+         detail: item.detail,
+         uri: item.uri,
+         range: item.range,
+         selection_range: item.selection_range,
+         data: item.data,
+      }
+   }
+}
+
+impl From<HierarchyItem> for TypeHierarchyItem {
+   fn from(item: HierarchyItem) -> Self {
+      Self {
+         name: item.name,
+         kind: item.kind,
+         tags: item.tags.into_iter().next(),
+         detail: item.detail,
+         uri: item.uri,
+         range: item.range,
+         selection_range: item.selection_range,
+         data: item.data,
+      }
+   }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HierarchyNode {
+   pub item: HierarchyItem,
+   /// Where the calls happen: in the caller for incoming calls, in the expanded item for
+   /// outgoing ones. Empty for type hierarchies.
+   pub call_ranges: Vec<Range>,
+   /// `None` until the node is expanded, which happens lazily past the requested depth.
+   pub children: Option<Vec<HierarchyNode>>,
+   /// The item already appears elsewhere in the tree, so it is not expanded again. This is
+   /// what stops recursion from expanding forever.
+   pub repeated: bool,
+}
+
+impl HierarchyNode {
+   pub fn new(item: HierarchyItem) -> Self {
+      Self {
+         item,
+         call_ranges: Vec::new(),
+         children: None,
+         repeated: false,
+      }
+   }
+}
+
+impl From<CallHierarchyIncomingCall> for HierarchyNode {
+   fn from(call: CallHierarchyIncomingCall) -> Self {
+      Self {
+         call_ranges: call.from_ranges,
+         ..Self::new(call.from.into())
+      }
+   }
+}
+
+impl From<CallHierarchyOutgoingCall> for HierarchyNode {
+   fn from(call: CallHierarchyOutgoingCall) -> Self {
+      Self {
+         call_ranges: call.from_ranges,
+         ..Self::new(call.to.into())
+      }
+   }
+}
+
+/// Expand `roots` breadth first down to `depth` levels, fetching each node's children with
+/// `fetch`. Items already in the tree, or among `ancestors` from an earlier expansion, are
+/// marked as repeated and left unexpanded.
+pub async fn expand<F, Fut>(
+   roots: &mut [HierarchyNode],
+   ancestors: &[HierarchyItem],
+   depth: usize,
+   mut fetch: F,
+) -> Result<()>
+where
+   F: FnMut(HierarchyItem) -> Fut,
+   Fut: Future<Output = Result<Vec<HierarchyNode>>>,
+{
+   let mut seen: HashSet<_> = ancestors.iter().map(HierarchyItem::key).collect();
+   let mut level = Vec::new();
+   for (index, root) in roots.iter_mut().enumerate() {
+      root.repeated = !seen.insert(root.item.key());
+      if !root.repeated {
+         level.push(vec![index]);
+      }
+   }
+
+   for _ in 0..depth {
+      let mut next_level = Vec::new();
+      for path in level {
+         let node = node_at(roots, &path);
+         let mut children = fetch(node.item.clone()).await?;
+         for (index, child) in children.iter_mut().enumerate() {
+            child.repeated = !seen.insert(child.item.key());
+            if !child.repeated {
+               next_level.push([path.as_slice(), &[index]].concat());
+            }
+         }
+         node.children = Some(children);
+      }
+      if next_level.is_empty() {
+         break;
+      }
+      level = next_level;
+   }
+   Ok(())
+}
+
+fn node_at<'a>(roots: &'a mut [HierarchyNode], path: &[usize]) -> &'a mut HierarchyNode {
+   let mut node = &mut roots[path[0]];
+   for &index in &path[1..] {
+      node = &mut node.children.as_mut().expect("expanded parent")[index];
+   }
+   node
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use lsp_types::Position;
+   use std::collections::HashMap;
+
+   fn item(name: &str) -> HierarchyItem {
+      let line = name.as_bytes()[0] as u32;
+      let range = Range::new(Position::new(line, 0), Position::new(line, 1));
+      HierarchyItem {
+         name: name.to_string(),
+         kind: SymbolKind::FUNCTION,
+         tags: Vec::new(),
+         detail: None,
+         uri: Url::parse("file:///lib.rs").unwrap(),
+         range,
+         selection_range: range,
+         data: None,
+      }
+   }
+
+   fn names(nodes: &[HierarchyNode]) -> Vec<(&str, bool)> {
+      nodes
+         .iter()
+         .map(|node| (node.item.name.as_str(), node.repeated))
+         .collect()
+   }
+
+   #[tokio::test]
+   async fn test_expand_stops_at_cycles_and_depth() {
+      // a -> b -> c -> a, and a -> c
+      let graph: HashMap<&str, Vec<&str>> = HashMap::from([
+         ("a", vec!["b", "c"]),
+         ("b", vec!["c"]),
+         ("c", vec!["a", "d"]),
+         ("d", vec!["e"]),
+      ]);
+      let mut fetched = Vec::new();
+      let mut roots = vec![HierarchyNode::new(item("a"))];
+
+      expand(&mut roots, &[], 2, |parent| {
+         fetched.push(parent.name.clone());
+         let children = graph[parent.name.as_str()]
+            .iter()
+            .map(|name| HierarchyNode::new(item(name)))
+            .collect();
+         async move { Ok(children) }
+      })
+      .await
+      .unwrap();
+
+      assert_eq!(fetched, vec!["a", "b", "c"]);
+      let children = roots[0].children.as_ref().unwrap();
+      assert_eq!(names(children), vec![("b", false), ("c", false)]);
+      assert_eq!(
+         names(children[0].children.as_ref().unwrap()),
+         vec![("c", true)]
+      );
+      let grandchildren = children[1].children.as_ref().unwrap();
+      assert_eq!(names(grandchildren), vec![("a", true), ("d", false)]);
+      // Past the requested depth nodes are left for lazy expansion
+      assert_eq!(grandchildren[1].children, None);
+
+      // Expanding later with the path so far still recognizes the cycle
+      let mut roots = vec![HierarchyNode::new(item("c"))];
+      expand(&mut roots, &[item("a")], 1, |parent| {
+         let children = graph[parent.name.as_str()]
+            .iter()
+            .map(|name| HierarchyNode::new(item(name)))
+            .collect();
+         async move { Ok(children) }
+      })
+      .await
+      .unwrap();
+      assert_eq!(
+         names(roots[0].children.as_ref().unwrap()),
+         vec![("a", true), ("d", false)]
+      );
+   }
+}
@@ -0,0 +1,1142 @@
+use super::utils::PackageManager;
+use anyhow::{Context, Result, bail};
+use base64::{Engine as _, engine::general_purpose};
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use sha2::{Digest, Sha256, Sha512};
+use std::{
+   collections::HashMap,
+   fs,
+   io::Read,
+   path::{Component, Path, PathBuf},
+};
+use tauri::{AppHandle, Emitter};
+
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+/// Share of the progress bar taken by downloading; verifying and unpacking fill the rest.
+const DOWNLOAD_SHARE: f32 = 0.8;
+const METADATA_FILE: &str = "installed.json";
+
+/// Where managed servers are downloaded from. Overridable in `lsp.json`, e.g. to use a mirror.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LspInstallSources {
+   pub npm_registry: String,
+   pub github_api: String,
+   pub go_proxy: String,
+}
+
+impl Default for LspInstallSources {
+   fn default() -> Self {
+      Self {
+         npm_registry: "https://registry.npmjs.org".to_string(),
+         github_api: "https://api.github.com".to_string(),
+         go_proxy: "https://proxy.golang.org".to_string(),
+      }
+   }
+}
+
+/// How a server can be installed into the app data directory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LspInstallSource {
+   /// Tarballs from the npm registry. `bin` comes from the first package; the others are
+   /// installed next to it, e.g. `typescript` for its language server.
+   Npm { packages: Vec<String>, bin: String },
+   /// An asset of the latest GitHub release. `asset` and `binary`, the executable's path once
+   /// unpacked, may use `{version}`, `{os}`, `{arch}` and `{target}` (the Rust target triple).
+   GithubRelease {
+      repo: String,
+      asset: String,
+      binary: String,
+   },
+   /// `go install` of the module's latest version, which produces `bin`. The go command checks
+   /// the module against the checksum database itself.
+   Go { module: String, bin: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledServer {
+   pub name: String,
+   pub version: String,
+   pub source: LspInstallSource,
+   /// The executable, relative to the server's directory.
+   pub binary: PathBuf,
+   pub installed_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LspServerUpdate {
+   pub name: String,
+   pub installed_version: String,
+   pub latest_version: String,
+}
+
+/// Payload of `lsp://install-progress`.
+#[derive(Debug, Clone, Serialize)]
+pub struct LspInstallProgress {
+   pub server_name: String,
+   pub status: LspInstallStatus,
+   pub progress: f32, // 0.0 to 1.0
+   pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum LspInstallStatus {
+   Resolving,
+   Downloading,
+   Verifying,
+   Extracting,
+   Installing,
+   Completed,
+   Failed { error: String },
+}
+
+#[derive(Debug, Deserialize)]
+struct NpmManifest {
+   name: String,
+   version: String,
+   dist: NpmDist,
+   bin: Option<Value>,
+   #[serde(default)]
+   dependencies: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NpmDist {
+   tarball: String,
+   integrity: Option<String>,
+}
+
+impl NpmManifest {
+   /// Path of the executable named `bin` inside the package.
+   fn bin_path(&self, bin: &str) -> Option<&str> {
+      match self.bin.as_ref()? {
+         Value::String(path) => {
+            let unscoped = self.name.rsplit('/').next().unwrap_or(&self.name);
+            (unscoped == bin).then_some(path.as_str())
+         }
+         Value::Object(bins) => bins.get(bin)?.as_str(),
+         _ => None,
+      }
+   }
+}
+
+#[derive(Debug, Deserialize)]
+struct GithubRelease {
+   tag_name: String,
+   assets: Vec<GithubAsset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GithubAsset {
+   name: String,
+   browser_download_url: String,
+   /// `sha256:<hex>`, published for release assets by GitHub.
+   digest: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GoModuleVersion {
+   #[serde(rename = "Version")]
+   version: String,
+}
+
+enum Checksum {
+   /// Lowercase hex.
+   Sha256(String),
+   /// Base64, as in npm's `integrity` field.
+   Sha512(String),
+}
+
+impl Checksum {
+   fn verify(&self, bytes: &[u8]) -> Result<()> {
+      let (expected, computed) = match self {
+         Checksum::Sha256(hex) => (hex.to_lowercase(), format!("{:x}", Sha256::digest(bytes))),
+         Checksum::Sha512(base64) => (
+            base64.clone(),
+            general_purpose::STANDARD.encode(Sha512::digest(bytes)),
+         ),
+      };
+      if computed != expected {
+         bail!("Checksum mismatch: expected {}, got {}", expected, computed);
+      }
+      Ok(())
+   }
+}
+
+/// Installs language servers into `<app data>/lsp-servers/<name>`, one directory per server
+/// with an `installed.json` recording what is there.
+pub struct LspInstaller {
+   servers_dir: PathBuf,
+   sources: LspInstallSources,
+   http: reqwest::Client,
+   app_handle: Option<AppHandle>,
+}
+
+impl LspInstaller {
+   pub fn new(
+      servers_dir: PathBuf,
+      sources: LspInstallSources,
+      app_handle: Option<AppHandle>,
+   ) -> Result<Self> {
+      fs::create_dir_all(&servers_dir)?;
+      let http = reqwest::Client::builder()
+         .user_agent(USER_AGENT)
+         .build()
+         .context("Failed to create HTTP client")?;
+      Ok(Self {
+         servers_dir,
+         sources,
+         http,
+         app_handle,
+      })
+   }
+
+   /// Install the latest version of a server, replacing any installed one once the new
+   /// version is complete.
+   pub async fn install(&self, name: &str, source: &LspInstallSource) -> Result<InstalledServer> {
+      validate_name(name)?;
+      log::info!("[LSP] Installing server '{}'", name);
+
+      let staging = self.servers_dir.join(format!(".{}.partial", name));
+      if staging.exists() {
+         fs::remove_dir_all(&staging)?;
+      }
+      fs::create_dir_all(&staging)?;
+
+      let result = match source {
+         LspInstallSource::Npm { packages, bin } => {
+            self.install_npm(name, packages, bin, &staging).await
+         }
+         LspInstallSource::GithubRelease {
+            repo,
+            asset,
+            binary,
+         } => {
+            self
+               .install_github(name, repo, asset, binary, &staging)
+               .await
+         }
+         LspInstallSource::Go { module, bin } => self.install_go(name, module, bin, &staging).await,
+      };
+      let installed = result.and_then(|(version, binary)| {
+         let installed = InstalledServer {
+            name: name.to_string(),
+            version,
+            source: source.clone(),
+            binary,
+            installed_at: chrono::Utc::now().to_rfc3339(),
+         };
+         fs::write(
+            staging.join(METADATA_FILE),
+            serde_json::to_string_pretty(&installed)?,
+         )?;
+
+         let server_dir = self.server_dir(name);
+         if server_dir.exists() {
+            fs::remove_dir_all(&server_dir)?;
+         }
+         fs::rename(&staging, &server_dir)?;
+         Ok(installed)
+      });
+
+      match installed {
+         Ok(installed) => {
+            log::info!("[LSP] Installed server '{}' {}", name, installed.version);
+            self.progress(
+               name,
+               LspInstallStatus::Completed,
+               1.0,
+               format!("Installed {}", installed.version),
+            );
+            Ok(installed)
+         }
+         Err(e) => {
+            let _ = fs::remove_dir_all(&staging);
+            log::error!("[LSP] Failed to install server '{}': {:#}", name, e);
+            self.progress(
+               name,
+               LspInstallStatus::Failed {
+                  error: format!("{:#}", e),
+               },
+               1.0,
+               "Installation failed".to_string(),
+            );
+            Err(e)
+         }
+      }
+   }
+
+   pub fn uninstall(&self, name: &str) -> Result<()> {
+      validate_name(name)?;
+      let server_dir = self.server_dir(name);
+      if server_dir.exists() {
+         fs::remove_dir_all(&server_dir)?;
+         log::info!("[LSP] Uninstalled server '{}'", name);
+      } else {
+         log::warn!("[LSP] Server '{}' is not installed", name);
+      }
+      Ok(())
+   }
+
+   pub fn installed(&self, name: &str) -> Option<InstalledServer> {
+      validate_name(name).ok()?;
+      let json = fs::read_to_string(self.server_dir(name).join(METADATA_FILE)).ok()?;
+      serde_json::from_str(&json).ok()
+   }
+
+   pub fn list_installed(&self) -> Result<Vec<InstalledServer>> {
+      let mut servers = Vec::new();
+      for entry in fs::read_dir(&self.servers_dir)? {
+         let name = entry?.file_name().to_string_lossy().to_string();
+         if let Some(server) = self.installed(&name) {
+            servers.push(server);
+         }
+      }
+      servers.sort_by(|a, b| a.name.cmp(&b.name));
+      Ok(servers)
+   }
+
+   /// The executable of an installed server, if it is still there.
+   pub fn binary_path(&self, name: &str) -> Option<PathBuf> {
+      let installed = self.installed(name)?;
+      let path = self.server_dir(name).join(installed.binary);
+      path.is_file().then_some(path)
+   }
+
+   pub async fn latest_version(&self, source: &LspInstallSource) -> Result<String> {
+      let version = match source {
+         LspInstallSource::Npm { packages, .. } => {
+            let package = packages.first().context("No npm package to install")?;
+            self.npm_manifest(package).await?.version
+         }
+         LspInstallSource::GithubRelease { repo, .. } => {
+            self.github_latest_release(repo).await?.tag_name
+         }
+         LspInstallSource::Go { module, .. } => self.go_latest_version(module).await?,
+      };
+      Ok(normalize_version(&version))
+   }
+
+   /// A newer version of an installed server, if there is one.
+   pub async fn check_update(&self, name: &str) -> Result<Option<LspServerUpdate>> {
+      let installed = self
+         .installed(name)
+         .with_context(|| format!("Server '{}' is not installed", name))?;
+      let latest_version = self.latest_version(&installed.source).await?;
+      Ok(
+         (latest_version != installed.version).then_some(LspServerUpdate {
+            name: installed.name,
+            installed_version: installed.version,
+            latest_version,
+         }),
+      )
+   }
+
+   fn server_dir(&self, name: &str) -> PathBuf {
+      self.servers_dir.join(name)
+   }
+
+   async fn install_npm(
+      &self,
+      name: &str,
+      packages: &[String],
+      bin: &str,
+      staging: &Path,
+   ) -> Result<(String, PathBuf)> {
+      if packages.is_empty() {
+         bail!("No npm package to install");
+      }
+
+      let mut manifests = Vec::new();
+      for package in packages {
+         self.progress(
+            name,
+            LspInstallStatus::Resolving,
+            0.0,
+            format!("Resolving {}", package),
+         );
+         manifests.push(self.npm_manifest(package).await?);
+      }
+
+      for (index, manifest) in manifests.iter().enumerate() {
+         let bytes = self
+            .download(name, &manifest.dist.tarball, index, manifests.len())
+            .await?;
+
+         self.progress(
+            name,
+            LspInstallStatus::Verifying,
+            DOWNLOAD_SHARE,
+            format!("Verifying {}", manifest.name),
+         );
+         let integrity = manifest.dist.integrity.as_deref().unwrap_or_default();
+         let Some(sha512) = integrity
+            .split_whitespace()
+            .find_map(|hash| hash.strip_prefix("sha512-"))
+         else {
+            bail!("No sha512 integrity published for {}", manifest.name);
+         };
+         Checksum::Sha512(sha512.to_string())
+            .verify(&bytes)
+            .with_context(|| format!("Failed to verify {}", manifest.name))?;
+
+         self.progress(
+            name,
+            LspInstallStatus::Extracting,
+            0.85,
+            format!("Extracting {}", manifest.name),
+         );
+         let package_dir = staging.join("node_modules").join(&manifest.name);
+         unpack_npm_tarball(&bytes, &package_dir)
+            .with_context(|| format!("Failed to extract {}", manifest.name))?;
+      }
+
+      for manifest in manifests.iter().filter(|m| !m.dependencies.is_empty()) {
+         self.progress(
+            name,
+            LspInstallStatus::Installing,
+            0.9,
+            format!("Installing dependencies of {}", manifest.name),
+         );
+         self
+            .install_npm_dependencies(&staging.join("node_modules").join(&manifest.name))
+            .await?;
+      }
+
+      let main = &manifests[0];
+      let bin_path = main
+         .bin_path(bin)
+         .with_context(|| format!("{} has no executable named {}", main.name, bin))?;
+      let binary = Path::new("node_modules").join(&main.name).join(bin_path);
+      ensure_relative(&binary)?;
+      make_executable(&staging.join(&binary))?;
+      Ok((normalize_version(&main.version), binary))
+   }
+
+   /// Dependencies go into the package's own `node_modules`, leaving the verified package as is.
+   async fn install_npm_dependencies(&self, package_dir: &Path) -> Result<()> {
+      let (program, args): (&str, &[&str]) = match PackageManager::detect() {
+         Some(PackageManager::Bun) => ("bun", &["install", "--production", "--ignore-scripts"]),
+         Some(PackageManager::Node) => (
+            "npm",
+            &[
+               "install",
+               "--omit=dev",
+               "--ignore-scripts",
+               "--no-package-lock",
+            ],
+         ),
+         None => bail!("Installing npm dependencies requires bun or node"),
+      };
+
+      let output = tokio::process::Command::new(program)
+         .args(args)
+         .current_dir(package_dir)
+         .env("NPM_CONFIG_REGISTRY", &self.sources.npm_registry)
+         .output()
+         .await
+         .with_context(|| format!("Failed to run {}", program))?;
+      if !output.status.success() {
+         bail!(
+            "{} install failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+         );
+      }
+      Ok(())
+   }
+
+   async fn install_github(
+      &self,
+      name: &str,
+      repo: &str,
+      asset: &str,
+      binary: &str,
+      staging: &Path,
+   ) -> Result<(String, PathBuf)> {
+      self.progress(
+         name,
+         LspInstallStatus::Resolving,
+         0.0,
+         format!("Resolving the latest release of {}", repo),
+      );
+      let release = self.github_latest_release(repo).await?;
+      let version = normalize_version(&release.tag_name);
+      let asset_name = expand_template(asset, &version);
+      let Some(asset) = release.assets.iter().find(|a| a.name == asset_name) else {
+         bail!(
+            "Release {} of {} has no asset {}",
+            release.tag_name,
+            repo,
+            asset_name
+         );
+      };
+      let checksum = match &asset.digest {
+         Some(digest) => match digest.split_once(':') {
+            Some(("sha256", hex)) => Checksum::Sha256(hex.to_string()),
+            _ => bail!("Unsupported digest for {}: {}", asset_name, digest),
+         },
+         None => self.github_sibling_checksum(&release, &asset_name).await?,
+      };
+
+      let bytes = self
+         .download(name, &asset.browser_download_url, 0, 1)
+         .await?;
+      self.progress(
+         name,
+         LspInstallStatus::Verifying,
+         DOWNLOAD_SHARE,
+         format!("Verifying {}", asset_name),
+      );
+      checksum
+         .verify(&bytes)
+         .with_context(|| format!("Failed to verify {}", asset_name))?;
+
+      self.progress(
+         name,
+         LspInstallStatus::Extracting,
+         0.9,
+         format!("Extracting {}", asset_name),
+      );
+      let binary = PathBuf::from(expand_template(binary, &version));
+      ensure_relative(&binary)?;
+      unpack_asset(&asset_name, &bytes, staging, &binary)
+         .with_context(|| format!("Failed to extract {}", asset_name))?;
+      if !staging.join(&binary).is_file() {
+         bail!("{} does not contain {}", asset_name, binary.display());
+      }
+      make_executable(&staging.join(&binary))?;
+      Ok((version, binary))
+   }
+
+   /// Checksum from a `<asset>.sha256` file published next to the asset.
+   async fn github_sibling_checksum(
+      &self,
+      release: &GithubRelease,
+      asset_name: &str,
+   ) -> Result<Checksum> {
+      let Some(checksum_asset) = release.assets.iter().find(|a| {
+         a.name == format!("{}.sha256", asset_name) || a.name == format!("{}.sha256sum", asset_name)
+      }) else {
+         bail!("No checksum published for {}", asset_name);
+      };
+      let text = self.get_text(&checksum_asset.browser_download_url).await?;
+      let hex = text
+         .split_whitespace()
+         .next()
+         .with_context(|| format!("Empty checksum file for {}", asset_name))?;
+      Ok(Checksum::Sha256(hex.to_string()))
+   }
+
+   async fn install_go(
+      &self,
+      name: &str,
+      module: &str,
+      bin: &str,
+      staging: &Path,
+   ) -> Result<(String, PathBuf)> {
+      self.progress(
+         name,
+         LspInstallStatus::Resolving,
+         0.0,
+         format!("Resolving {}", module),
+      );
+      let version = self.go_latest_version(module).await?;
+
+      self.progress(
+         name,
+         LspInstallStatus::Installing,
+         0.5,
+         format!("Building {}@{}", module, version),
+      );
+      let output = tokio::process::Command::new("go")
+         .arg("install")
+         .arg(format!("{}@{}", module, version))
+         .env("GOBIN", staging.join("bin"))
+         .env("GOPROXY", &self.sources.go_proxy)
+         .output()
+         .await
+         .context("Failed to run go, which is needed to install this server")?;
+      if !output.status.success() {
+         bail!(
+            "go install failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+         );
+      }
+
+      let binary = Path::new("bin").join(format!("{}{}", bin, std::env::consts::EXE_SUFFIX));
+      if !staging.join(&binary).is_file() {
+         bail!("go install did not produce {}", binary.display());
+      }
+      Ok((normalize_version(&version), binary))
+   }
+
+   async fn npm_manifest(&self, package: &str) -> Result<NpmManifest> {
+      let url = format!(
+         "{}/{}/latest",
+         self.sources.npm_registry.trim_end_matches('/'),
+         package.replace('/', "%2F")
+      );
+      self
+         .get_json(&url)
+         .await
+         .with_context(|| format!("Failed to resolve npm package {}", package))
+   }
+
+   async fn github_latest_release(&self, repo: &str) -> Result<GithubRelease> {
+      let url = format!(
+         "{}/repos/{}/releases/latest",
+         self.sources.github_api.trim_end_matches('/'),
+         repo
+      );
+      self
+         .get_json(&url)
+         .await
+         .with_context(|| format!("Failed to find the latest release of {}", repo))
+   }
+
+   async fn go_latest_version(&self, module: &str) -> Result<String> {
+      let url = format!(
+         "{}/{}/@latest",
+         self.sources.go_proxy.trim_end_matches('/'),
+         escape_module_path(module)
+      );
+      let latest: GoModuleVersion = self
+         .get_json(&url)
+         .await
+         .with_context(|| format!("Failed to resolve Go module {}", module))?;
+      Ok(latest.version)
+   }
+
+   async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+      let bytes = self
+         .http
+         .get(url)
+         .send()
+         .await?
+         .error_for_status()?
+         .bytes()
+         .await?;
+      serde_json::from_slice(&bytes).with_context(|| format!("Unexpected response from {}", url))
+   }
+
+   async fn get_text(&self, url: &str) -> Result<String> {
+      Ok(self
+         .http
+         .get(url)
+         .send()
+         .await?
+         .error_for_status()?
+         .text()
+         .await?)
+   }
+
+   /// Download the `index`th of `count` files, reporting progress as it arrives.
+   async fn download(&self, name: &str, url: &str, index: usize, count: usize) -> Result<Vec<u8>> {
+      log::info!("[LSP] Downloading {}", url);
+      let file_name = url.rsplit('/').next().unwrap_or(url);
+      let mut response = self
+         .http
+         .get(url)
+         .send()
+         .await
+         .and_then(|response| response.error_for_status())
+         .with_context(|| format!("Failed to download {}", url))?;
+
+      let total = response.content_length();
+      let mut bytes = Vec::with_capacity(total.unwrap_or(0) as usize);
+      let mut reported = -1.0;
+      while let Some(chunk) = response.chunk().await? {
+         bytes.extend_from_slice(&chunk);
+         let fraction = total.map_or(0.0, |total| {
+            (bytes.len() as f32 / total.max(1) as f32).min(1.0)
+         });
+         let progress = (index as f32 + fraction) / count as f32 * DOWNLOAD_SHARE;
+         if progress - reported >= 0.01 {
+            reported = progress;
+            self.progress(
+               name,
+               LspInstallStatus::Downloading,
+               progress,
+               format!("Downloading {} ({} KB)", file_name, bytes.len() / 1024),
+            );
+         }
+      }
+      Ok(bytes)
+   }
+
+   fn progress(&self, name: &str, status: LspInstallStatus, progress: f32, message: String) {
+      if let Some(app_handle) = &self.app_handle {
+         let _ = app_handle.emit(
+            "lsp://install-progress",
+            &LspInstallProgress {
+               server_name: name.to_string(),
+               status,
+               progress,
+               message,
+            },
+         );
+      }
+   }
+}
+
+/// Names become directory names, so they must stay a single path component.
+fn validate_name(name: &str) -> Result<()> {
+   let mut components = Path::new(name).components();
+   match (components.next(), components.next()) {
+      (Some(Component::Normal(_)), None) if !name.starts_with('.') => Ok(()),
+      _ => bail!("Invalid server name: {}", name),
+   }
+}
+
+/// Reject paths from manifests and templates that would escape the server's directory.
+fn ensure_relative(path: &Path) -> Result<()> {
+   if path
+      .components()
+      .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+   {
+      Ok(())
+   } else {
+      bail!("Refusing to install to {}", path.display())
+   }
+}
+
+fn normalize_version(version: &str) -> String {
+   version.trim().trim_start_matches('v').to_string()
+}
+
+fn expand_template(template: &str, version: &str) -> String {
+   template
+      .replace("{version}", version)
+      .replace("{os}", std::env::consts::OS)
+      .replace("{arch}", std::env::consts::ARCH)
+      .replace("{target}", target_triple())
+}
+
+fn target_triple() -> &'static str {
+   match (std::env::consts::OS, std::env::consts::ARCH) {
+      ("linux", "x86_64") => "x86_64-unknown-linux-gnu",
+      ("linux", "aarch64") => "aarch64-unknown-linux-gnu",
+      ("macos", "x86_64") => "x86_64-apple-darwin",
+      ("macos", "aarch64") => "aarch64-apple-darwin",
+      ("windows", "x86_64") => "x86_64-pc-windows-msvc",
+      ("windows", "aarch64") => "aarch64-pc-windows-msvc",
+      _ => "unknown",
+   }
+}
+
+/// The Go module proxy protocol escapes upper case letters as `!` plus the lower case letter.
+fn escape_module_path(module: &str) -> String {
+   let mut escaped = String::with_capacity(module.len());
+   for c in module.chars() {
+      if c.is_ascii_uppercase() {
+         escaped.push('!');
+         escaped.push(c.to_ascii_lowercase());
+      } else {
+         escaped.push(c);
+      }
+   }
+   escaped
+}
+
+/// npm tarballs keep everything under a top-level `package/` directory, which is dropped.
+fn unpack_npm_tarball(bytes: &[u8], package_dir: &Path) -> Result<()> {
+   unpack_tarball(bytes, package_dir, 1)
+}
+
+/// Unpack a gzipped tarball into `dest`, dropping the first `strip` components of each path.
+/// Links could point outside `dest`, so only files and directories are unpacked.
+fn unpack_tarball(bytes: &[u8], dest: &Path, strip: usize) -> Result<()> {
+   fs::create_dir_all(dest)?;
+   let mut archive = tar::Archive::new(GzDecoder::new(bytes));
+   for entry in archive.entries()? {
+      let mut entry = entry?;
+      if !matches!(
+         entry.header().entry_type(),
+         tar::EntryType::Regular | tar::EntryType::Directory
+      ) {
+         continue;
+      }
+      let path: PathBuf = entry.path()?.components().skip(strip).collect();
+      if path.as_os_str().is_empty() || ensure_relative(&path).is_err() {
+         continue;
+      }
+      let target = dest.join(path);
+      if let Some(parent) = target.parent() {
+         fs::create_dir_all(parent)?;
+      }
+      entry.unpack(&target)?;
+   }
+   Ok(())
+}
+
+/// Unpack a zip archive into `dest`, skipping links and paths that would leave it.
+fn unpack_zip(bytes: &[u8], dest: &Path) -> Result<()> {
+   let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes))?;
+   for index in 0..archive.len() {
+      let mut file = archive.by_index(index)?;
+      if file.is_symlink() {
+         continue;
+      }
+      let Some(path) = file.enclosed_name() else {
+         continue;
+      };
+      let target = dest.join(path);
+      if file.is_dir() {
+         fs::create_dir_all(&target)?;
+         continue;
+      }
+      if let Some(parent) = target.parent() {
+         fs::create_dir_all(parent)?;
+      }
+      let mut contents = Vec::new();
+      file.read_to_end(&mut contents)?;
+      fs::write(&target, contents)?;
+      #[cfg(unix)]
+      if let Some(mode) = file.unix_mode() {
+         use std::os::unix::fs::PermissionsExt;
+         fs::set_permissions(&target, fs::Permissions::from_mode(mode & 0o777))?;
+      }
+   }
+   Ok(())
+}
+
+/// Unpack a release asset by its extension. Single compressed files and bare executables are
+/// written to `binary`.
+fn unpack_asset(asset_name: &str, bytes: &[u8], staging: &Path, binary: &Path) -> Result<()> {
+   if asset_name.ends_with(".tar.gz") || asset_name.ends_with(".tgz") {
+      return unpack_tarball(bytes, staging, 0);
+   }
+   if asset_name.ends_with(".zip") {
+      return unpack_zip(bytes, staging);
+   }
+   if asset_name.ends_with(".tar.xz") {
+      bail!("Unsupported archive format: {}", asset_name);
+   }
+
+   let contents = if asset_name.ends_with(".gz") {
+      let mut contents = Vec::new();
+      GzDecoder::new(bytes).read_to_end(&mut contents)?;
+      contents
+   } else {
+      bytes.to_vec()
+   };
+   let target = staging.join(binary);
+   if let Some(parent) = target.parent() {
+      fs::create_dir_all(parent)?;
+   }
+   fs::write(target, contents)?;
+   Ok(())
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+   use std::os::unix::fs::PermissionsExt;
+
+   let mut permissions = fs::metadata(path)
+      .with_context(|| format!("Missing executable {}", path.display()))?
+      .permissions();
+   permissions.set_mode(permissions.mode() | 0o755);
+   fs::set_permissions(path, permissions)?;
+   Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(path: &Path) -> Result<()> {
+   if !path.is_file() {
+      bail!("Missing executable {}", path.display());
+   }
+   Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use flate2::{Compression, write::GzEncoder};
+   use serde_json::json;
+   use std::{
+      io::{BufRead, BufReader, Write},
+      net::TcpListener,
+      sync::{Arc, Mutex},
+   };
+
+   type Routes = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+   /// A minimal HTTP server answering GET requests from `routes`.
+   fn serve() -> (String, Routes) {
+      let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+      let base = format!("http://{}", listener.local_addr().unwrap());
+      let routes: Routes = Arc::default();
+      let served = Arc::clone(&routes);
+      std::thread::spawn(move || {
+         for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut header = String::new();
+            while reader.read_line(&mut header).unwrap() > 2 {
+               header.clear();
+            }
+
+            let path = request_line.split(' ').nth(1).unwrap_or("/").to_string();
+            let body = served.lock().unwrap().get(&path).cloned();
+            let (status, body) = match body {
+               Some(body) => ("200 OK", body),
+               None => ("404 Not Found", Vec::new()),
+            };
+            let _ = write!(
+               stream,
+               "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+               status,
+               body.len()
+            );
+            let _ = stream.write_all(&body);
+         }
+      });
+      (base, routes)
+   }
+
+   fn sources(base: &str) -> LspInstallSources {
+      LspInstallSources {
+         npm_registry: format!("{}/npm", base),
+         github_api: format!("{}/github", base),
+         go_proxy: format!("{}/go", base),
+      }
+   }
+
+   fn gzip(bytes: &[u8]) -> Vec<u8> {
+      let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+      encoder.write_all(bytes).unwrap();
+      encoder.finish().unwrap()
+   }
+
+   fn npm_tarball(files: &[(&str, &str)]) -> Vec<u8> {
+      let mut builder = tar::Builder::new(Vec::new());
+      for (path, contents) in files {
+         let mut header = tar::Header::new_gnu();
+         header.set_size(contents.len() as u64);
+         header.set_mode(0o644);
+         header.set_cksum();
+         builder
+            .append_data(
+               &mut header,
+               format!("package/{}", path),
+               contents.as_bytes(),
+            )
+            .unwrap();
+      }
+      gzip(&builder.into_inner().unwrap())
+   }
+
+   fn publish_npm(base: &str, routes: &Routes, version: &str, tarball: &[u8], integrity: &str) {
+      let tarball_path = format!("/npm/fake-ls/-/fake-ls-{}.tgz", version);
+      let manifest = json!({
+         "name": "fake-ls",
+         "version": version,
+         "bin": { "fake-ls": "bin/server.js" },
+         "dist": {
+            "tarball": format!("{}{}", base, tarball_path),
+            "integrity": integrity,
+         },
+      });
+      let mut routes = routes.lock().unwrap();
+      routes.insert(tarball_path, tarball.to_vec());
+      routes.insert(
+         "/npm/fake-ls/latest".to_string(),
+         manifest.to_string().into_bytes(),
+      );
+   }
+
+   fn npm_source() -> LspInstallSource {
+      LspInstallSource::Npm {
+         packages: vec!["fake-ls".to_string()],
+         bin: "fake-ls".to_string(),
+      }
+   }
+
+   #[tokio::test]
+   async fn test_install_update_and_uninstall_npm_server() {
+      let (base, routes) = serve();
+      let dir = tempfile::tempdir().unwrap();
+      let installer = LspInstaller::new(dir.path().to_path_buf(), sources(&base), None).unwrap();
+
+      let tarball = npm_tarball(&[
+         ("package.json", r#"{"name":"fake-ls"}"#),
+         ("bin/server.js", "#!/usr/bin/env node\n"),
+      ]);
+      let integrity = format!(
+         "sha512-{}",
+         general_purpose::STANDARD.encode(Sha512::digest(&tarball))
+      );
+      publish_npm(&base, &routes, "1.0.0", &tarball, &integrity);
+
+      let installed = installer.install("fake", &npm_source()).await.unwrap();
+      assert_eq!(installed.version, "1.0.0");
+      let binary = installer.binary_path("fake").unwrap();
+      assert!(binary.ends_with("node_modules/fake-ls/bin/server.js"));
+      assert_eq!(
+         fs::read_to_string(&binary).unwrap(),
+         "#!/usr/bin/env node\n"
+      );
+      assert!(installer.check_update("fake").await.unwrap().is_none());
+
+      publish_npm(&base, &routes, "1.1.0", &tarball, &integrity);
+      let update = installer.check_update("fake").await.unwrap().unwrap();
+      assert_eq!(
+         (
+            update.installed_version.as_str(),
+            update.latest_version.as_str()
+         ),
+         ("1.0.0", "1.1.0")
+      );
+      installer.install("fake", &npm_source()).await.unwrap();
+      let names: Vec<_> = installer
+         .list_installed()
+         .unwrap()
+         .into_iter()
+         .map(|server| (server.name, server.version))
+         .collect();
+      assert_eq!(names, [("fake".to_string(), "1.1.0".to_string())]);
+
+      installer.uninstall("fake").unwrap();
+      assert!(installer.installed("fake").is_none());
+      assert!(installer.binary_path("fake").is_none());
+   }
+
+   #[tokio::test]
+   async fn test_checksum_mismatch_keeps_previous_install() {
+      let (base, routes) = serve();
+      let dir = tempfile::tempdir().unwrap();
+      let installer = LspInstaller::new(dir.path().to_path_buf(), sources(&base), None).unwrap();
+
+      let tarball = npm_tarball(&[("bin/server.js", "good")]);
+      let integrity = format!(
+         "sha512-{}",
+         general_purpose::STANDARD.encode(Sha512::digest(&tarball))
+      );
+      publish_npm(&base, &routes, "1.0.0", &tarball, &integrity);
+      installer.install("fake", &npm_source()).await.unwrap();
+
+      let tampered = npm_tarball(&[("bin/server.js", "evil")]);
+      publish_npm(&base, &routes, "2.0.0", &tampered, &integrity);
+      let error = installer.install("fake", &npm_source()).await.unwrap_err();
+      assert!(format!("{:#}", error).contains("Checksum mismatch"));
+
+      assert_eq!(installer.installed("fake").unwrap().version, "1.0.0");
+      let binary = installer.binary_path("fake").unwrap();
+      assert_eq!(fs::read_to_string(binary).unwrap(), "good");
+      assert!(!dir.path().join(".fake.partial").exists());
+   }
+
+   #[tokio::test]
+   async fn test_install_github_release_asset() {
+      let (base, routes) = serve();
+      let dir = tempfile::tempdir().unwrap();
+      let installer = LspInstaller::new(dir.path().to_path_buf(), sources(&base), None).unwrap();
+
+      let asset = gzip(b"binary");
+      let asset_name = format!("fake-ls-{}.gz", target_triple());
+      let release = json!({
+         "tag_name": "v2.3.0",
+         "assets": [{
+            "name": asset_name,
+            "browser_download_url": format!("{}/download/{}", base, asset_name),
+            "digest": format!("sha256:{:x}", Sha256::digest(&asset)),
+         }],
+      });
+      {
+         let mut routes = routes.lock().unwrap();
+         routes.insert(
+            "/github/repos/owner/fake-ls/releases/latest".to_string(),
+            release.to_string().into_bytes(),
+         );
+         routes.insert(format!("/download/{}", asset_name), asset);
+      }
+
+      let source = LspInstallSource::GithubRelease {
+         repo: "owner/fake-ls".to_string(),
+         asset: "fake-ls-{target}.gz".to_string(),
+         binary: "fake-ls-{version}".to_string(),
+      };
+      let installed = installer.install("fake", &source).await.unwrap();
+      assert_eq!(installed.version, "2.3.0");
+      assert_eq!(installed.binary, PathBuf::from("fake-ls-2.3.0"));
+      let binary = installer.binary_path("fake").unwrap();
+      assert_eq!(fs::read(binary).unwrap(), b"binary");
+   }
+
+   #[test]
+   fn test_names_and_paths_stay_inside_the_servers_dir() {
+      assert!(validate_name("rust-analyzer").is_ok());
+      assert!(validate_name("../evil").is_err());
+      assert!(validate_name(".hidden").is_err());
+      assert!(ensure_relative(Path::new("bin/server")).is_ok());
+      assert!(ensure_relative(Path::new("../server")).is_err());
+
+      let mut builder = tar::Builder::new(Vec::new());
+      let mut header = tar::Header::new_gnu();
+      header.set_entry_type(tar::EntryType::Symlink);
+      header.set_size(0);
+      header.set_cksum();
+      builder
+         .append_link(&mut header, "package/escape", "/")
+         .unwrap();
+      let dir = tempfile::tempdir().unwrap();
+      unpack_npm_tarball(&gzip(&builder.into_inner().unwrap()), dir.path()).unwrap();
+      assert!(fs::symlink_metadata(dir.path().join("escape")).is_err());
+      assert_eq!(
+         escape_module_path("github.com/BurntSushi/toml"),
+         "github.com/!burnt!sushi/toml"
+      );
+   }
+
+   #[test]
+   fn test_release_archives_unpack_files_but_not_links() {
+      let mut builder = tar::Builder::new(Vec::new());
+      let mut header = tar::Header::new_gnu();
+      header.set_size(6);
+      header.set_mode(0o755);
+      header.set_cksum();
+      builder
+         .append_data(&mut header, "bin/fake-ls", &b"binary"[..])
+         .unwrap();
+      let mut header = tar::Header::new_gnu();
+      header.set_entry_type(tar::EntryType::Symlink);
+      header.set_size(0);
+      header.set_cksum();
+      builder.append_link(&mut header, "escape", "/").unwrap();
+      let dir = tempfile::tempdir().unwrap();
+      let tarball = gzip(&builder.into_inner().unwrap());
+      unpack_asset(
+         "fake-ls.tar.gz",
+         &tarball,
+         dir.path(),
+         Path::new("bin/fake-ls"),
+      )
+      .unwrap();
+      assert_eq!(fs::read(dir.path().join("bin/fake-ls")).unwrap(), b"binary");
+      assert!(fs::symlink_metadata(dir.path().join("escape")).is_err());
+
+      let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+      let options = zip::write::SimpleFileOptions::default().unix_permissions(0o755);
+      writer.start_file("bin/fake-ls", options).unwrap();
+      writer.write_all(b"zipped").unwrap();
+      writer.add_symlink("escape", "/", options).unwrap();
+      writer.start_file("../outside", options).unwrap();
+      writer.write_all(b"outside").unwrap();
+      let archive = writer.finish().unwrap().into_inner();
+      let dir = tempfile::tempdir().unwrap();
+      let staging = dir.path().join("staging");
+      unpack_asset("fake-ls.zip", &archive, &staging, Path::new("bin/fake-ls")).unwrap();
+      assert_eq!(fs::read(staging.join("bin/fake-ls")).unwrap(), b"zipped");
+      assert!(fs::symlink_metadata(staging.join("escape")).is_err());
+      assert!(!dir.path().join("outside").exists());
+
+      assert!(unpack_asset("fake-ls.tar.xz", b"", dir.path(), Path::new("fake-ls")).is_err());
+   }
+}
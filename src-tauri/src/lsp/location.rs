@@ -0,0 +1,130 @@
+use super::transport;
+use lsp_types::{GotoDefinitionResponse, Location, Range, Url};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Longest preview line sent to the frontend, in characters.
+const MAX_PREVIEW_CHARS: usize = 200;
+
+/// A navigation target with the line it points at, ready for a peek or results list.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct LspLocation {
+   pub uri: Url,
+   /// Editor path of `uri`, when it is a `file://` or remote URI.
+   pub file_path: Option<String>,
+   /// Range to select, e.g. the symbol name for a definition.
+   pub range: Range,
+   /// Full range of the target when the server reported one, e.g. the whole function.
+   pub target_range: Option<Range>,
+   /// Text of the first line of `range`, read from disk or from the remote host.
+   pub preview: Option<String>,
+}
+
+impl LspLocation {
+   pub(super) fn new(uri: Url, range: Range, target_range: Option<Range>) -> Self {
+      let file_path = transport::editor_path(&uri).map(|path| path.to_string_lossy().to_string());
+      Self {
+         uri,
+         file_path,
+         range,
+         target_range,
+         preview: None,
+      }
+   }
+}
+
+/// Flatten the three shapes of a goto response into one list.
+pub fn from_goto_response(response: Option<GotoDefinitionResponse>) -> Vec<LspLocation> {
+   match response {
+      None => Vec::new(),
+      Some(GotoDefinitionResponse::Scalar(location)) => from_locations(vec![location]),
+      Some(GotoDefinitionResponse::Array(locations)) => from_locations(locations),
+      Some(GotoDefinitionResponse::Link(links)) => links
+         .into_iter()
+         .map(|link| {
+            LspLocation::new(
+               link.target_uri,
+               link.target_selection_range,
+               Some(link.target_range),
+            )
+         })
+         .collect(),
+   }
+}
+
+pub fn from_locations(locations: Vec<Location>) -> Vec<LspLocation> {
+   locations
+      .into_iter()
+      .map(|location| LspLocation::new(location.uri, location.range, None))
+      .collect()
+}
+
+/// Fill in `preview` for every location, reading each file once.
+pub fn attach_previews(locations: &mut [LspLocation]) {
+   let mut files: HashMap<String, Option<Vec<String>>> = HashMap::new();
+
+   for location in locations.iter_mut() {
+      let Some(file_path) = &location.file_path else {
+         continue;
+      };
+      let lines = files.entry(file_path.clone()).or_insert_with(|| {
+         transport::read_editor_file(file_path)
+            .ok()
+            .map(|content| content.lines().map(str::to_string).collect())
+      });
+
+      location.preview = lines
+         .as_ref()
+         .and_then(|lines| lines.get(location.range.start.line as usize))
+         .map(|line| line.trim_end().chars().take(MAX_PREVIEW_CHARS).collect());
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use lsp_types::{LocationLink, Position};
+   use std::fs;
+
+   fn range(line: u32, start: u32, end: u32) -> Range {
+      Range::new(Position::new(line, start), Position::new(line, end))
+   }
+
+   #[test]
+   fn test_goto_responses_are_normalized_with_previews() {
+      let dir = tempfile::tempdir().unwrap();
+      let file = dir.path().join("lib.rs");
+      fs::write(&file, "mod a;\npub fn greet() {\n   println!(\"hi\");\n}\n").unwrap();
+      let uri = Url::from_file_path(&file).unwrap();
+
+      let link = LocationLink {
+         origin_selection_range: None,
+         target_uri: uri.clone(),
+         target_range: Range::new(Position::new(1, 0), Position::new(3, 1)),
+         target_selection_range: range(1, 7, 12),
+      };
+      let mut locations = from_goto_response(Some(GotoDefinitionResponse::Link(vec![link])));
+      locations.extend(from_goto_response(Some(GotoDefinitionResponse::Scalar(
+         Location::new(uri.clone(), range(2, 3, 11)),
+      ))));
+      locations.extend(from_goto_response(Some(GotoDefinitionResponse::Array(
+         vec![Location::new(uri.clone(), range(9, 0, 1))],
+      ))));
+      attach_previews(&mut locations);
+
+      assert_eq!(locations.len(), 3);
+      assert_eq!(locations[0].range, range(1, 7, 12));
+      assert!(locations[0].target_range.is_some());
+      assert_eq!(locations[0].preview.as_deref(), Some("pub fn greet() {"));
+      assert_eq!(
+         locations[1].file_path.as_deref(),
+         Some(file.to_string_lossy().as_ref())
+      );
+      assert_eq!(
+         locations[1].preview.as_deref(),
+         Some("   println!(\"hi\");")
+      );
+      assert_eq!(locations[2].preview, None);
+      assert!(from_goto_response(None).is_empty());
+   }
+}
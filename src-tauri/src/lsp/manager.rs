@@ -1,92 +1,302 @@
 use super::{
    client::LspClient,
-   config::{LspRegistry, LspSettings},
+   code_action::{self, LspCodeAction},
+   config::{LspRegistry, LspServerConfig, LspSettings, LspUserConfig},
+   hierarchy::{self, HierarchyDirection, HierarchyItem, HierarchyNode},
+   installer::{InstalledServer, LspInstaller, LspServerUpdate},
+   location::{self, LspLocation},
+   ranges,
+   semantic_tokens::{self, LspSemanticToken},
+   supervisor::{LspServerStatus, LspStatusEvent, RestartPolicy},
+   symbols::{self, LspWorkspaceSymbol, OutlineSymbol},
+   traffic::LspTraffic,
+   transport::{self, LspTransport, ServerHandle},
    utils,
+   workspace_edit::{AppliedWorkspaceEdit, WorkspaceEditApplier},
+};
+use crate::{
+   diagnostics::DiagnosticsStore,
+   workspace::{WorkspaceManager, WorkspaceRoot, WorkspaceRootsChange},
 };
 use anyhow::{Context, Result, bail};
 use lsp_types::*;
+use serde_json::Value;
 use std::{
-   collections::HashMap,
-   path::PathBuf,
-   process::Child,
+   collections::{HashMap, HashSet},
+   path::{Path, PathBuf},
    sync::{Arc, Mutex},
    time::Instant,
 };
-use tauri::{AppHandle, Manager as TauriManager};
+use tauri::{AppHandle, Emitter, Manager as TauriManager};
 
 struct LspInstance {
    client: LspClient,
-   child: Child,
+   process: ServerHandle,
    server_name: String,
+   /// How the server was started, to restart it the same way after a crash.
+   server: LspServerConfig,
+   server_path: PathBuf,
+   restarts: RestartPolicy,
    ref_count: usize,
    files: Vec<PathBuf>,
+   /// Workspace folders the server was told about.
+   folders: Vec<PathBuf>,
+   /// Whether the server was started for a workspace root and so follows root changes.
+   follows_workspace: bool,
+}
+
+impl LspInstance {
+   /// Length of the innermost folder containing `path`, if any.
+   fn folder_match(&self, path: &Path) -> Option<usize> {
+      self
+         .folders
+         .iter()
+         .filter(|folder| path.starts_with(folder))
+         .map(|folder| folder.as_os_str().len())
+         .max()
+   }
+}
+
+/// Where a rename may happen. Without a `range` the frontend renames the word at the cursor.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PrepareRenameResult {
+   pub range: Option<Range>,
+   pub placeholder: Option<String>,
+}
+
+/// The goto requests that share `GotoDefinitionResponse`.
+#[derive(Debug, Clone, Copy)]
+pub enum GotoKind {
+   Definition,
+   TypeDefinition,
+   Implementation,
+   Declaration,
 }
 
-type WorkspaceClients = Arc<Mutex<HashMap<(PathBuf, String), LspInstance>>>;
+/// (workspace path, server name)
+type InstanceKey = (PathBuf, String);
+type WorkspaceClients = Arc<Mutex<HashMap<InstanceKey, LspInstance>>>;
 
 pub struct LspManager {
    // Map (workspace path, language) to their LSP clients with reference counting
    workspace_clients: WorkspaceClients,
-   registry: LspRegistry,
+   registry: Mutex<LspRegistry>,
    app_handle: AppHandle,
    settings: LspSettings,
+   diagnostics: DiagnosticsStore,
+   /// Messages recently exchanged with each server.
+   traffic: LspTraffic,
+   /// Servers currently being installed, so the same one is not installed twice at once.
+   installing: Mutex<HashSet<String>>,
 }
 
 impl LspManager {
-   pub fn new(app_handle: AppHandle) -> Self {
-      Self {
+   pub fn new(app_handle: AppHandle, diagnostics: DiagnosticsStore) -> Self {
+      let traffic = LspTraffic::new(Some(app_handle.clone()));
+      let manager = Self {
          workspace_clients: Arc::new(Mutex::new(HashMap::new())),
-         registry: LspRegistry::new(),
+         registry: Mutex::new(LspRegistry::new()),
          app_handle,
          settings: LspSettings::default(),
+         diagnostics,
+         traffic,
+         installing: Mutex::new(HashSet::new()),
+      };
+      if let Err(e) = manager.reload_config() {
+         log::error!("[LSP] {:#}", e);
       }
+      manager
    }
 
-   pub fn get_server_path(&self, server_name: &str) -> Result<PathBuf> {
-      // For TypeScript, try multiple detection strategies
-      if server_name == "typescript" {
-         // First try: globally installed server via package managers
-         if let Some(path) = utils::find_global_binary("typescript-language-server") {
-            log::info!("Using global TypeScript server: {:?}", path);
-            return Ok(path);
-         }
+   pub fn traffic(&self) -> &LspTraffic {
+      &self.traffic
+   }
 
-         // Second try: check if it's in PATH
-         if let Some(path) = utils::find_in_path("typescript-language-server") {
-            log::info!("Using TypeScript server from PATH: {:?}", path);
-            return Ok(path);
-         }
+   /// Path of the user-editable server configuration.
+   pub fn config_path(&self) -> Result<PathBuf> {
+      let app_dir = self
+         .app_handle
+         .path()
+         .app_data_dir()
+         .context("Failed to get app dir")?;
+      Ok(app_dir.join("lsp.json"))
+   }
+
+   /// Re-read `lsp.json`. Running servers keep their configuration until restarted.
+   pub fn reload_config(&self) -> Result<()> {
+      let config = LspUserConfig::load(&self.config_path()?)?;
+      *self.registry.lock().unwrap() = LspRegistry::with_user_config(config);
+      Ok(())
+   }
+
+   pub fn servers(&self) -> Vec<LspServerConfig> {
+      self.registry.lock().unwrap().servers().to_vec()
+   }
 
-         // Third try: local node_modules in current working directory
-         let local_path = std::env::current_dir()
-            .context("Failed to get current directory")?
-            .join("node_modules/.bin/typescript-language-server");
+   pub fn is_file_supported(&self, file_path: &Path) -> bool {
+      self.registry.lock().unwrap().is_file_supported(file_path)
+   }
 
-         if local_path.exists() {
-            log::info!("Using local TypeScript server: {:?}", local_path);
-            return Ok(local_path);
+   /// Locate the binary for `server`. Commands given as paths are used as-is; bare names are
+   /// looked up in the package manager's global bin, `PATH`, the workspace's `node_modules`,
+   /// common tool directories, the app data directory and finally the servers the editor
+   /// installed itself.
+   pub fn get_server_path(&self, server: &LspServerConfig, workspace: &Path) -> Result<PathBuf> {
+      if server.command.components().count() > 1 {
+         if server.command.exists() {
+            return Ok(server.command.clone());
          }
+         bail!(
+            "Language server '{}' not found at {}",
+            server.name,
+            server.command.display()
+         );
       }
 
-      // Look for bundled executable
-      let app_dir = self
+      let binary = server.command.to_string_lossy();
+      if let Some(path) = utils::find_global_binary(&binary) {
+         log::info!("Using global language server '{}': {:?}", server.name, path);
+         return Ok(path);
+      }
+
+      if let Some(path) = utils::find_in_path(&binary) {
+         log::info!(
+            "Using language server '{}' from PATH: {:?}",
+            server.name,
+            path
+         );
+         return Ok(path);
+      }
+
+      let mut dirs = vec![workspace.join("node_modules/.bin")];
+      if let Ok(current_dir) = std::env::current_dir() {
+         dirs.push(current_dir.join("node_modules/.bin"));
+      }
+      dirs.extend(utils::tool_dirs());
+      if let Ok(app_dir) = self.app_handle.path().app_data_dir() {
+         dirs.push(app_dir);
+      }
+
+      if let Some(path) = utils::find_in_dirs(&binary, &dirs) {
+         log::info!("Using language server '{}': {:?}", server.name, path);
+         return Ok(path);
+      }
+
+      if let Some(path) = self
+         .installer()
+         .ok()
+         .and_then(|installer| installer.binary_path(&server.name))
+      {
+         log::info!(
+            "Using installed language server '{}': {:?}",
+            server.name,
+            path
+         );
+         return Ok(path);
+      }
+
+      match &server.install_hint {
+         Some(hint) if server.install.is_some() => bail!(
+            "Language server '{}' not found. Install it from the editor or with: {}",
+            server.name,
+            hint
+         ),
+         Some(hint) => bail!(
+            "Language server '{}' not found. Install it with: {}",
+            server.name,
+            hint
+         ),
+         None => bail!(
+            "Language server '{}' not found: {}",
+            server.name,
+            server.command.display()
+         ),
+      }
+   }
+
+   fn installer(&self) -> Result<LspInstaller> {
+      let servers_dir = self
          .app_handle
          .path()
          .app_data_dir()
-         .context("Failed to get app dir")?;
-
-      let bundled_path = app_dir.join(format!("{}-language-server", server_name));
+         .context("Failed to get app dir")?
+         .join("lsp-servers");
+      let sources = self.registry.lock().unwrap().install_sources().clone();
+      LspInstaller::new(servers_dir, sources, Some(self.app_handle.clone()))
+   }
 
-      if bundled_path.exists() {
-         log::info!("Using bundled language server: {:?}", bundled_path);
-         Ok(bundled_path)
-      } else {
+   /// Install the latest version of a server that has an install source, or update it.
+   /// Running instances keep the old binary until they are restarted.
+   pub async fn install_server(&self, server_name: &str) -> Result<InstalledServer> {
+      let server = self
+         .servers()
+         .into_iter()
+         .find(|server| server.name == server_name)
+         .with_context(|| format!("Unknown language server '{}'", server_name))?;
+      let source = server
+         .install
+         .with_context(|| format!("Language server '{}' cannot be installed", server_name))?;
+
+      let Some(_installing) = InstallingGuard::new(&self.installing, server_name) else {
          bail!(
-            "Language server '{}' not found. Please install it globally using: bun add -g \
-             typescript-language-server",
+            "Language server '{}' is already being installed",
             server_name
-         )
+         );
+      };
+      self.installer()?.install(server_name, &source).await
+   }
+
+   pub fn uninstall_server(&self, server_name: &str) -> Result<()> {
+      self.installer()?.uninstall(server_name)
+   }
+
+   pub fn installed_servers(&self) -> Result<Vec<InstalledServer>> {
+      self.installer()?.list_installed()
+   }
+
+   /// Installed servers with a newer version available.
+   pub async fn check_server_updates(&self) -> Result<Vec<LspServerUpdate>> {
+      let installer = self.installer()?;
+      let mut updates = Vec::new();
+      for server in installer.list_installed()? {
+         match installer.check_update(&server.name).await {
+            Ok(Some(update)) => updates.push(update),
+            Ok(None) => {}
+            Err(e) => log::warn!(
+               "[LSP] Failed to check for updates of '{}': {:#}",
+               server.name,
+               e
+            ),
+         }
       }
+      Ok(updates)
+   }
+
+   /// The first of `candidates` whose binary is installed. When none are, the error for the
+   /// preferred one is returned.
+   fn resolve_server(
+      &self,
+      candidates: Vec<LspServerConfig>,
+      workspace: &Path,
+   ) -> Result<(LspServerConfig, PathBuf)> {
+      // Remote servers are looked up on the host's PATH when they start
+      if transport::is_remote(workspace)
+         && let Some(server) = candidates.first()
+      {
+         return Ok((server.clone(), server.command.clone()));
+      }
+
+      let mut first_error = None;
+      for server in candidates {
+         match self.get_server_path(&server, workspace) {
+            Ok(path) => return Ok((server, path)),
+            Err(e) => {
+               log::debug!("[LSP] {}", e);
+               first_error.get_or_insert(e);
+            }
+         }
+      }
+      Err(first_error.unwrap_or_else(|| anyhow::anyhow!("No LSP server found")))
    }
 
    pub async fn start_lsp_for_workspace(
@@ -98,10 +308,8 @@ impl LspManager {
       log::info!("Starting LSP for workspace: {:?}", workspace_path);
 
       // Use provided server path or find appropriate LSP server for workspace
-      let (server_path, server_args, server_name) = if let Some(path) = server_path_override {
+      let (server, server_path) = if let Some(path) = server_path_override {
          log::info!("Using provided server path override: {}", path);
-         let args = server_args_override.unwrap_or_default();
-         let name = path.split('/').next_back().unwrap_or("custom").to_string();
 
          // Use the path directly - it should already be absolute from the frontend
          let resolved_path = PathBuf::from(&path);
@@ -109,63 +317,81 @@ impl LspManager {
          log::info!("Resolved LSP server path: {:?}", resolved_path);
          log::info!("Path exists: {}", resolved_path.exists());
 
-         (resolved_path, args, name)
+         let args = server_args_override.unwrap_or_default();
+         (LspServerConfig::custom(&path, args), resolved_path)
       } else {
          // Fallback to registry-based detection
-         let server_config = self
+         let candidates: Vec<_> = self
             .registry
-            .find_server_for_workspace(&workspace_path)
-            .context("No LSP server found for workspace")?;
-
-         log::info!("Using LSP server '{}' for workspace", server_config.name);
+            .lock()
+            .unwrap()
+            .servers_for_workspace(&workspace_path)
+            .into_iter()
+            .cloned()
+            .collect();
+         if candidates.is_empty() {
+            bail!("No LSP server found for workspace");
+         }
+         let (server, server_path) = self.resolve_server(candidates, &workspace_path)?;
 
-         let server_path = self.get_server_path(&server_config.name)?;
-         (
-            server_path,
-            server_config.args.clone(),
-            server_config.name.clone(),
-         )
+         log::info!("Using LSP server '{}' for workspace", server.name);
+         (server, server_path)
       };
-
-      let root_uri = Url::from_file_path(&workspace_path)
-         .map_err(|_| anyhow::anyhow!("Invalid workspace path"))?;
-
-      let (client, child) = LspClient::start(
-         server_path,
-         server_args,
-         root_uri.clone(),
-         Some(self.app_handle.clone()),
-      )?;
-
-      // Initialize the client
-      client.initialize(root_uri).await?;
+      let server_name = server.name.clone();
 
       // Check if LSP already running for this workspace+language
       let workspace_key = (workspace_path.clone(), server_name.clone());
-      if self
-         .workspace_clients
-         .lock()
-         .unwrap()
-         .contains_key(&workspace_key)
-      {
-         log::info!(
-            "LSP '{}' already running for workspace: {:?}",
-            server_name,
-            workspace_path
-         );
+      let already_running = |clients: &HashMap<InstanceKey, LspInstance>| {
+         let running = clients.contains_key(&workspace_key);
+         if running {
+            log::info!(
+               "LSP '{}' already running for workspace: {:?}",
+               server_name,
+               workspace_path
+            );
+         }
+         running
+      };
+      if already_running(&self.workspace_clients.lock().unwrap()) {
          return Ok(());
       }
 
-      self.workspace_clients.lock().unwrap().insert(
-         workspace_key,
+      let (folders, follows_workspace) = self.workspace_folders_for(&workspace_path);
+      let (client, mut process) = self
+         .spawn_server(
+            &server,
+            &server_path,
+            &workspace_path,
+            to_workspace_folders(&folders),
+         )
+         .await?;
+
+      // Another start for the same workspace may have finished while this one initialized
+      let mut clients = self.workspace_clients.lock().unwrap();
+      if already_running(&clients) {
+         let _ = process.kill();
+         return Ok(());
+      }
+      clients.insert(
+         workspace_key.clone(),
          LspInstance {
-            client,
-            child,
+            client: client.clone(),
+            process,
             server_name: server_name.clone(),
+            server,
+            server_path,
+            restarts: RestartPolicy::default(),
             ref_count: 0,
             files: Vec::new(),
+            folders: folders
+               .iter()
+               .map(|root| PathBuf::from(&root.path))
+               .collect(),
+            follows_workspace,
          },
       );
+      drop(clients);
+      self.supervise(workspace_key, client);
 
       log::info!("LSP '{}' started and initialized successfully", server_name);
       Ok(())
@@ -183,34 +409,54 @@ impl LspManager {
    ) -> Result<()> {
       log::info!("Starting LSP for file: {:?}", file_path);
 
+      // A remote file is served from its own host, so a workspace elsewhere does not apply
+      let workspace_path = match transport::connection_id(&file_path) {
+         Some(id) if transport::connection_id(&workspace_path) != Some(id) => {
+            file_path.parent().map_or(workspace_path, Path::to_path_buf)
+         }
+         _ => workspace_path,
+      };
+
       // Find appropriate LSP server for this file
-      let (server_path, server_args, server_name) = if let Some(path) = server_path_override {
+      let (server, server_path) = if let Some(path) = server_path_override {
          log::info!("Using provided server path override: {}", path);
          let args = server_args_override.unwrap_or_default();
-         let name = path.split('/').next_back().unwrap_or("custom").to_string();
-         let resolved_path = PathBuf::from(&path);
-         (resolved_path, args, name)
+         (LspServerConfig::custom(&path, args), PathBuf::from(&path))
       } else {
-         let server_config = self
+         let candidates: Vec<_> = self
             .registry
-            .find_server_for_file(&file_path)
-            .context("No LSP server found for file")?;
+            .lock()
+            .unwrap()
+            .servers_for_file(&file_path)
+            .cloned()
+            .collect();
+         if candidates.is_empty() {
+            bail!("No LSP server found for file");
+         }
+         let (server, server_path) = self.resolve_server(candidates, &workspace_path)?;
 
-         log::info!("Using LSP server '{}' for file", server_config.name);
-         let server_path = self.get_server_path(&server_config.name)?;
-         (
-            server_path,
-            server_config.args.clone(),
-            server_config.name.clone(),
-         )
+         log::info!("Using LSP server '{}' for file", server.name);
+         (server, server_path)
       };
+      let server_name = server.name.clone();
 
       let workspace_key = (workspace_path.clone(), server_name.clone());
 
-      // Check if LSP already running for this workspace+language
+      // Check if LSP already running for this workspace+language, or for another root of a
+      // multi-root workspace that contains the file
       {
          let mut clients = self.workspace_clients.lock().unwrap();
-         if let Some(instance) = clients.get_mut(&workspace_key) {
+         let existing_key = if clients.contains_key(&workspace_key) {
+            Some(workspace_key.clone())
+         } else {
+            clients
+               .iter()
+               .filter(|((_, name), _)| name == &server_name)
+               .filter_map(|(key, instance)| Some((key, instance.folder_match(&file_path)?)))
+               .max_by_key(|(_, len)| *len)
+               .map(|(key, _)| key.clone())
+         };
+         if let Some(instance) = existing_key.and_then(|key| clients.get_mut(&key)) {
             // Increment ref count and add file to tracking
             instance.ref_count += 1;
             if !instance.files.contains(&file_path) {
@@ -225,35 +471,243 @@ impl LspManager {
          }
       } // Lock is automatically dropped here
 
-      let root_uri = Url::from_file_path(&workspace_path)
-         .map_err(|_| anyhow::anyhow!("Invalid workspace path"))?;
-
-      let (client, child) = LspClient::start(
-         server_path,
-         server_args,
-         root_uri.clone(),
-         Some(self.app_handle.clone()),
-      )?;
-
-      // Initialize the client
-      client.initialize(root_uri).await?;
+      let (folders, follows_workspace) = self.workspace_folders_for(&workspace_path);
+      let (client, process) = self
+         .spawn_server(
+            &server,
+            &server_path,
+            &workspace_path,
+            to_workspace_folders(&folders),
+         )
+         .await?;
 
       // Store the new instance
       self.workspace_clients.lock().unwrap().insert(
-         workspace_key,
+         workspace_key.clone(),
          LspInstance {
-            client,
-            child,
+            client: client.clone(),
+            process,
             server_name: server_name.clone(),
+            server,
+            server_path,
+            restarts: RestartPolicy::default(),
             ref_count: 1,
             files: vec![file_path],
+            folders: folders
+               .iter()
+               .map(|root| PathBuf::from(&root.path))
+               .collect(),
+            follows_workspace,
          },
       );
+      self.supervise(workspace_key, client);
 
       log::info!("LSP '{}' started successfully for file", server_name);
       Ok(())
    }
 
+   /// Start and initialize a server process. The process is killed if initialization fails.
+   async fn spawn_server(
+      &self,
+      server: &LspServerConfig,
+      server_path: &Path,
+      workspace_path: &Path,
+      workspace_folders: Vec<WorkspaceFolder>,
+   ) -> Result<(LspClient, ServerHandle)> {
+      let root_uri = transport::editor_uri(workspace_path)?;
+
+      let (client, mut process) = LspClient::start(
+         server,
+         server_path,
+         &LspTransport::for_workspace(workspace_path),
+         self.diagnostics.clone(),
+         self.traffic.recorder(workspace_path, &server.name),
+         Some(self.app_handle.clone()),
+      )?;
+
+      // Initialize the client
+      if let Err(e) = client
+         .initialize(
+            root_uri,
+            workspace_folders,
+            server.initialization_options.clone(),
+         )
+         .await
+      {
+         let _ = process.kill();
+         return Err(e);
+      }
+
+      self.emit_status(
+         workspace_path,
+         &server.name,
+         LspServerStatus::Running,
+         0,
+         None,
+      );
+      Ok((client, process))
+   }
+
+   /// Restart the server behind `client` if its process exits while it is still in use.
+   fn supervise(&self, key: InstanceKey, client: LspClient) {
+      let app_handle = self.app_handle.clone();
+      tauri::async_runtime::spawn(async move {
+         client.wait_for_exit().await;
+         if let Some(manager) = app_handle.try_state::<LspManager>() {
+            manager.restart_after_exit(key, client).await;
+         }
+      });
+   }
+
+   async fn restart_after_exit(&self, key: InstanceKey, exited: LspClient) {
+      let mut message = {
+         let mut clients = self.workspace_clients.lock().unwrap();
+         // Servers that were stopped on purpose are no longer tracked
+         let Some(instance) = clients
+            .get_mut(&key)
+            .filter(|instance| instance.client.same_server(&exited))
+         else {
+            return;
+         };
+         match instance.process.exit_status() {
+            Some(status) => format!("Language server exited with {}", status),
+            None => "Language server closed its output".to_string(),
+         }
+      };
+      log::warn!("[LSP] '{}' stopped unexpectedly: {}", key.1, message);
+      self.emit_status(
+         &key.0,
+         &key.1,
+         LspServerStatus::Crashed,
+         0,
+         Some(message.clone()),
+      );
+
+      loop {
+         let (server, server_path, folders, delay, attempt) = {
+            let mut clients = self.workspace_clients.lock().unwrap();
+            let Some(instance) = clients
+               .get_mut(&key)
+               .filter(|instance| instance.client.same_server(&exited))
+            else {
+               return;
+            };
+            let Some(delay) = instance.restarts.next_delay() else {
+               let attempts = instance.restarts.attempts();
+               clients.remove(&key);
+               log::error!("[LSP] Giving up on '{}' after {} restarts", key.1, attempts);
+               self.emit_status(
+                  &key.0,
+                  &key.1,
+                  LspServerStatus::Failed,
+                  attempts,
+                  Some(message),
+               );
+               return;
+            };
+            (
+               instance.server.clone(),
+               instance.server_path.clone(),
+               instance.folders.clone(),
+               delay,
+               instance.restarts.attempts(),
+            )
+         };
+
+         self.emit_status(&key.0, &key.1, LspServerStatus::Restarting, attempt, None);
+         tokio::time::sleep(delay).await;
+
+         let roots: Vec<_> = folders
+            .iter()
+            .map(|folder| WorkspaceRoot::from_path(&folder.to_string_lossy()))
+            .collect();
+         match self
+            .spawn_server(&server, &server_path, &key.0, to_workspace_folders(&roots))
+            .await
+         {
+            Ok((client, process)) => {
+               if self.replace_instance_client(&key, &exited, client.clone(), process) {
+                  log::info!("[LSP] Restarted '{}' (attempt {})", key.1, attempt);
+                  self.supervise(key, client);
+               }
+               return;
+            }
+            Err(e) => {
+               log::warn!("[LSP] Failed to restart '{}': {:#}", key.1, e);
+               message = format!("{:#}", e);
+            }
+         }
+      }
+   }
+
+   /// Swap a restarted server into its instance and reopen the documents the old one had open.
+   /// Returns false, killing the new server, if the instance was stopped in the meantime.
+   fn replace_instance_client(
+      &self,
+      key: &InstanceKey,
+      exited: &LspClient,
+      client: LspClient,
+      mut process: ServerHandle,
+   ) -> bool {
+      let mut clients = self.workspace_clients.lock().unwrap();
+      let Some(instance) = clients
+         .get_mut(key)
+         .filter(|instance| instance.client.same_server(exited))
+      else {
+         let _ = process.kill();
+         return false;
+      };
+
+      let mut documents = exited.open_documents();
+      for file in &instance.files {
+         let Ok(uri) = transport::editor_uri(file) else {
+            continue;
+         };
+         if documents.iter().any(|document| document.uri == uri) {
+            continue;
+         }
+         match transport::read_editor_file(file) {
+            Ok(text) => documents.push(TextDocumentItem {
+               uri,
+               language_id: self.get_language_id_for_file(&file.to_string_lossy()),
+               version: 1,
+               text,
+            }),
+            Err(e) => log::warn!("[LSP] Failed to reopen {:?}: {}", file, e),
+         }
+      }
+      for text_document in documents {
+         if let Err(e) = client.text_document_did_open(DidOpenTextDocumentParams { text_document })
+         {
+            log::warn!("[LSP] Failed to reopen document: {}", e);
+         }
+      }
+
+      let mut old_process = std::mem::replace(&mut instance.process, process);
+      let _ = old_process.exit_status();
+      instance.client = client;
+      instance.restarts.started();
+      true
+   }
+
+   fn emit_status(
+      &self,
+      workspace_path: &Path,
+      server_name: &str,
+      status: LspServerStatus,
+      restart_attempt: u32,
+      message: Option<String>,
+   ) {
+      let event = LspStatusEvent {
+         server_name: server_name.to_string(),
+         workspace_path: workspace_path.to_string_lossy().into_owned(),
+         status,
+         restart_attempt,
+         message,
+      };
+      let _ = self.app_handle.emit("lsp://status", &event);
+   }
+
    /// Stop LSP server for a specific file (buffer-scoped)
    /// This will decrement the reference count and shutdown the server if it reaches 0
    pub fn stop_lsp_for_file(&self, file_path: &PathBuf) -> Result<()> {
@@ -294,7 +748,8 @@ impl LspManager {
          && let Some(mut instance) = clients.remove(&key)
       {
          log::info!("Shutting down LSP '{}'", instance.server_name);
-         let _ = instance.child.kill();
+         let _ = instance.process.kill();
+         self.clear_diagnostics(&instance);
       }
 
       Ok(())
@@ -304,17 +759,85 @@ impl LspManager {
       let path = PathBuf::from(file_path);
       let clients = self.workspace_clients.lock().unwrap();
 
-      // Find the right language server for this file
-      let server_config = self.registry.find_server_for_file(&path)?;
+      // Find the language servers able to handle this file
+      let registry = self.registry.lock().unwrap();
+      let server_names: Vec<&str> = registry
+         .servers_for_file(&path)
+         .map(|server| server.name.as_str())
+         .collect();
+
+      // Find the innermost workspace folder that contains this file
+      clients
+         .iter()
+         .filter(|((_, server_name), _)| server_names.contains(&server_name.as_str()))
+         .filter_map(|(_, instance)| Some((instance, instance.folder_match(&path)?)))
+         .max_by_key(|(_, len)| *len)
+         .map(|(instance, _)| instance.client.clone())
+   }
+
+   /// Tell servers started for the workspace about roots that were added or removed.
+   pub fn update_workspace_folders(&self, change: &WorkspaceRootsChange) {
+      if change.added.is_empty() && change.removed.is_empty() {
+         return;
+      }
 
-      // Find workspace that contains this file
-      for ((workspace_path, server_name), instance) in clients.iter() {
-         if path.starts_with(workspace_path) && server_name == &server_config.name {
-            return Some(instance.client.clone());
+      let mut clients = self.workspace_clients.lock().unwrap();
+      for instance in clients.values_mut().filter(|i| i.follows_workspace) {
+         instance.folders.retain(|folder| {
+            !change
+               .removed
+               .iter()
+               .any(|root| folder == Path::new(&root.path))
+         });
+         instance
+            .folders
+            .extend(change.added.iter().map(|root| PathBuf::from(&root.path)));
+
+         if !instance.client.supports_workspace_folder_changes() {
+            log::info!(
+               "LSP '{}' does not accept workspace folder changes",
+               instance.server_name
+            );
+            continue;
+         }
+
+         let params = DidChangeWorkspaceFoldersParams {
+            event: WorkspaceFoldersChangeEvent {
+               added: to_workspace_folders(&change.added),
+               removed: to_workspace_folders(&change.removed),
+            },
+         };
+         if let Err(e) = instance
+            .client
+            .workspace_did_change_workspace_folders(params)
+         {
+            log::warn!(
+               "Failed to update workspace folders for LSP '{}': {}",
+               instance.server_name,
+               e
+            );
          }
       }
+   }
+
+   /// Folders to report for a server started at `workspace_path`: every workspace root when it
+   /// is one of them, otherwise just that folder.
+   fn workspace_folders_for(&self, workspace_path: &Path) -> (Vec<WorkspaceRoot>, bool) {
+      let roots = self
+         .app_handle
+         .try_state::<Arc<WorkspaceManager>>()
+         .map(|workspace| workspace.roots())
+         .unwrap_or_default();
 
-      None
+      if roots
+         .iter()
+         .any(|root| Path::new(&root.path) == workspace_path)
+      {
+         (roots, true)
+      } else {
+         let path = workspace_path.to_string_lossy();
+         (vec![WorkspaceRoot::from_path(&path)], false)
+      }
    }
 
    pub async fn get_completions(
@@ -332,8 +855,7 @@ impl LspManager {
       let params = CompletionParams {
          text_document_position: TextDocumentPositionParams {
             text_document: TextDocumentIdentifier {
-               uri: Url::from_file_path(file_path)
-                  .map_err(|_| anyhow::anyhow!("Invalid file path"))?,
+               uri: transport::editor_uri(file_path)?,
             },
             position: Position { line, character },
          },
@@ -355,6 +877,13 @@ impl LspManager {
       };
 
       if items.len() > max_completions {
+         // Keep the items the server ranks first
+         items.sort_by_cached_key(|item| {
+            (
+               item.preselect != Some(true),
+               item.sort_text.clone().unwrap_or_else(|| item.label.clone()),
+            )
+         });
          log::debug!(
             "LSP returned {} completions, limiting to {}",
             items.len(),
@@ -373,6 +902,23 @@ impl LspManager {
       Ok(items)
    }
 
+   /// Fill in what the server left out of a completion item, such as its documentation or the
+   /// import it needs as `additional_text_edits`.
+   pub async fn resolve_completion(
+      &self,
+      file_path: &str,
+      item: CompletionItem,
+   ) -> Result<CompletionItem> {
+      let client = self
+         .get_client_for_file(file_path)
+         .context("No LSP client for this file")?;
+
+      if !client.supports_completion_resolve() {
+         return Ok(item);
+      }
+      client.completion_item_resolve(item).await
+   }
+
    pub async fn get_hover(
       &self,
       file_path: &str,
@@ -384,7 +930,7 @@ impl LspManager {
          .context("No LSP client for this file")?;
 
       let text_document = TextDocumentIdentifier {
-         uri: Url::from_file_path(file_path).map_err(|_| anyhow::anyhow!("Invalid file path"))?,
+         uri: transport::editor_uri(file_path)?,
       };
 
       let params = HoverParams {
@@ -398,6 +944,604 @@ impl LspManager {
       client.text_document_hover(params).await
    }
 
+   /// Signatures for the call at a position. `trigger_character` is the character just typed
+   /// when the request was triggered automatically, e.g. `(` or `,`.
+   pub async fn signature_help(
+      &self,
+      file_path: &str,
+      line: u32,
+      character: u32,
+      trigger_character: Option<String>,
+   ) -> Result<Option<SignatureHelp>> {
+      let client = self
+         .get_client_for_file(file_path)
+         .context("No LSP client for this file")?;
+      if !client.supports_signature_help() {
+         return Ok(None);
+      }
+
+      let params = SignatureHelpParams {
+         context: Some(SignatureHelpContext {
+            trigger_kind: if trigger_character.is_some() {
+               SignatureHelpTriggerKind::TRIGGER_CHARACTER
+            } else {
+               SignatureHelpTriggerKind::INVOKED
+            },
+            trigger_character,
+            is_retrigger: false,
+            active_signature_help: None,
+         }),
+         text_document_position_params: position_params(file_path, line, character)?,
+         work_done_progress_params: Default::default(),
+      };
+      client.text_document_signature_help(params).await
+   }
+
+   pub async fn inlay_hints(&self, file_path: &str, range: Range) -> Result<Vec<InlayHint>> {
+      let client = self
+         .get_client_for_file(file_path)
+         .context("No LSP client for this file")?;
+      if !client.supports_inlay_hints() {
+         return Ok(Vec::new());
+      }
+
+      let params = InlayHintParams {
+         text_document: TextDocumentIdentifier {
+            uri: transport::editor_uri(file_path)?,
+         },
+         range,
+         work_done_progress_params: Default::default(),
+      };
+      Ok(client
+         .text_document_inlay_hint(params)
+         .await?
+         .unwrap_or_default())
+   }
+
+   /// Semantic tokens for the whole document, or for `range` when the server supports it.
+   /// Full requests reuse the previous result through deltas where possible.
+   pub async fn semantic_tokens(
+      &self,
+      file_path: &str,
+      range: Option<Range>,
+   ) -> Result<Vec<LspSemanticToken>> {
+      let client = self
+         .get_client_for_file(file_path)
+         .context("No LSP client for this file")?;
+      let Some(options) = client.semantic_tokens_options() else {
+         return Ok(Vec::new());
+      };
+      let uri = transport::editor_uri(file_path)?;
+
+      let supports_range = options.range == Some(true);
+      let tokens = match range.filter(|_| supports_range) {
+         Some(range) => {
+            let params = SemanticTokensRangeParams {
+               text_document: TextDocumentIdentifier { uri },
+               range,
+               work_done_progress_params: Default::default(),
+               partial_result_params: Default::default(),
+            };
+            client.text_document_semantic_tokens_range(params).await?
+         }
+         None => {
+            let delta = match &options.full {
+               Some(SemanticTokensFullOptions::Delta { delta }) => *delta == Some(true),
+               Some(SemanticTokensFullOptions::Bool(true)) => false,
+               _ => return Ok(Vec::new()),
+            };
+            client
+               .text_document_semantic_tokens_full(uri, delta)
+               .await?
+         }
+      };
+      Ok(semantic_tokens::decode(&tokens, &options.legend))
+   }
+
+   pub async fn goto(
+      &self,
+      kind: GotoKind,
+      file_path: &str,
+      line: u32,
+      character: u32,
+   ) -> Result<Vec<LspLocation>> {
+      let client = self
+         .get_client_for_file(file_path)
+         .context("No LSP client for this file")?;
+
+      let params = GotoDefinitionParams {
+         text_document_position_params: position_params(file_path, line, character)?,
+         work_done_progress_params: Default::default(),
+         partial_result_params: Default::default(),
+      };
+
+      let response = match kind {
+         GotoKind::Definition => client.text_document_definition(params).await?,
+         GotoKind::TypeDefinition => client.text_document_type_definition(params).await?,
+         GotoKind::Implementation => client.text_document_implementation(params).await?,
+         GotoKind::Declaration => client.text_document_declaration(params).await?,
+      };
+
+      let mut locations = location::from_goto_response(response);
+      location::attach_previews(&mut locations);
+      Ok(locations)
+   }
+
+   pub async fn get_references(
+      &self,
+      file_path: &str,
+      line: u32,
+      character: u32,
+      include_declaration: bool,
+   ) -> Result<Vec<LspLocation>> {
+      let client = self
+         .get_client_for_file(file_path)
+         .context("No LSP client for this file")?;
+
+      let params = ReferenceParams {
+         text_document_position: position_params(file_path, line, character)?,
+         work_done_progress_params: Default::default(),
+         partial_result_params: Default::default(),
+         context: ReferenceContext {
+            include_declaration,
+         },
+      };
+
+      let response = client.text_document_references(params).await?;
+      let mut locations = location::from_locations(response.unwrap_or_default());
+      location::attach_previews(&mut locations);
+      Ok(locations)
+   }
+
+   /// Check that the symbol at a position can be renamed. Returns `None` when it cannot.
+   pub async fn prepare_rename(
+      &self,
+      file_path: &str,
+      line: u32,
+      character: u32,
+   ) -> Result<Option<PrepareRenameResult>> {
+      let client = self
+         .get_client_for_file(file_path)
+         .context("No LSP client for this file")?;
+
+      if !client.supports_prepare_rename() {
+         return Ok(Some(PrepareRenameResult {
+            range: None,
+            placeholder: None,
+         }));
+      }
+
+      let params = position_params(file_path, line, character)?;
+      let result = match client.text_document_prepare_rename(params).await? {
+         None => None,
+         Some(PrepareRenameResponse::Range(range)) => Some(PrepareRenameResult {
+            range: Some(range),
+            placeholder: None,
+         }),
+         Some(PrepareRenameResponse::RangeWithPlaceholder { range, placeholder }) => {
+            Some(PrepareRenameResult {
+               range: Some(range),
+               placeholder: Some(placeholder),
+            })
+         }
+         Some(PrepareRenameResponse::DefaultBehavior { default_behavior }) => default_behavior
+            .then_some(PrepareRenameResult {
+               range: None,
+               placeholder: None,
+            }),
+      };
+      Ok(result)
+   }
+
+   /// Rename the symbol at a position and apply the resulting edit. Edits to `open_files` are
+   /// returned for the editor to apply to its buffers.
+   pub async fn rename(
+      &self,
+      file_path: &str,
+      line: u32,
+      character: u32,
+      new_name: String,
+      open_files: Vec<PathBuf>,
+   ) -> Result<AppliedWorkspaceEdit> {
+      let client = self
+         .get_client_for_file(file_path)
+         .context("No LSP client for this file")?;
+
+      let params = RenameParams {
+         text_document_position: position_params(file_path, line, character)?,
+         new_name,
+         work_done_progress_params: Default::default(),
+      };
+
+      let edit = client
+         .text_document_rename(params)
+         .await?
+         .context("Symbol cannot be renamed")?;
+      WorkspaceEditApplier::new(open_files).apply(edit)
+   }
+
+   /// Code actions for a range, with the diagnostics touching it as context. `only` limits the
+   /// kinds returned, e.g. `source.fixAll`.
+   pub async fn get_code_actions(
+      &self,
+      file_path: &str,
+      range: Range,
+      only: Option<Vec<String>>,
+   ) -> Result<Vec<LspCodeAction>> {
+      let client = self
+         .get_client_for_file(file_path)
+         .context("No LSP client for this file")?;
+
+      let uri = transport::editor_uri(file_path)?;
+      let diagnostics = code_action::diagnostics_in_range(&client.diagnostics(&uri), range);
+      let params = CodeActionParams {
+         text_document: TextDocumentIdentifier { uri },
+         range,
+         context: CodeActionContext {
+            diagnostics,
+            only: only.map(|kinds| kinds.into_iter().map(CodeActionKind::from).collect()),
+            trigger_kind: Some(CodeActionTriggerKind::INVOKED),
+         },
+         work_done_progress_params: Default::default(),
+         partial_result_params: Default::default(),
+      };
+
+      let response = client.text_document_code_action(params).await?;
+      Ok(response
+         .unwrap_or_default()
+         .into_iter()
+         .map(LspCodeAction::from)
+         .collect())
+   }
+
+   /// Fill in the edit of an action the server left for `codeAction/resolve`.
+   pub async fn resolve_code_action(
+      &self,
+      file_path: &str,
+      action: LspCodeAction,
+   ) -> Result<LspCodeAction> {
+      let client = self
+         .get_client_for_file(file_path)
+         .context("No LSP client for this file")?;
+
+      if !action.needs_resolve() || !client.supports_code_action_resolve() {
+         return Ok(action);
+      }
+      let resolved = client.code_action_resolve(action.into()).await?;
+      Ok(resolved.into())
+   }
+
+   pub async fn execute_command(&self, file_path: &str, command: Command) -> Result<Option<Value>> {
+      let client = self
+         .get_client_for_file(file_path)
+         .context("No LSP client for this file")?;
+
+      let params = ExecuteCommandParams {
+         command: command.command,
+         arguments: command.arguments.unwrap_or_default(),
+         work_done_progress_params: Default::default(),
+      };
+      client.workspace_execute_command(params).await
+   }
+
+   /// Resolve an action if needed, apply its edit and then run its command, as the protocol
+   /// orders them.
+   pub async fn apply_code_action(
+      &self,
+      file_path: &str,
+      action: LspCodeAction,
+      open_files: Vec<PathBuf>,
+   ) -> Result<AppliedWorkspaceEdit> {
+      if let Some(reason) = &action.disabled_reason {
+         bail!("Code action '{}' is disabled: {}", action.title, reason);
+      }
+
+      let action = self.resolve_code_action(file_path, action).await?;
+      let applied = match action.edit {
+         Some(edit) => WorkspaceEditApplier::new(open_files).apply(edit)?,
+         None => AppliedWorkspaceEdit::default(),
+      };
+      if let Some(command) = action.command {
+         self.execute_command(file_path, command).await?;
+      }
+      Ok(applied)
+   }
+
+   /// Outline of a document from its language server, or from its syntax tree when no server
+   /// provides one. `content` is the editor buffer; the file is read from disk without it.
+   pub async fn document_symbols(
+      &self,
+      file_path: &str,
+      content: Option<String>,
+   ) -> Result<Vec<OutlineSymbol>> {
+      if let Some(client) = self.get_client_for_file(file_path)
+         && client.supports_document_symbols()
+      {
+         let params = DocumentSymbolParams {
+            text_document: TextDocumentIdentifier {
+               uri: transport::editor_uri(file_path)?,
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+         };
+         match client.text_document_document_symbol(params).await {
+            Ok(response) => return Ok(symbols::from_document_symbol_response(response)),
+            Err(e) => log::warn!("[LSP] Document symbols failed, using syntax tree: {}", e),
+         }
+      }
+
+      let content = syntax_source(file_path, content)?;
+      symbols::tree_sitter_outline(&content, file_extension(file_path))
+   }
+
+   /// Search symbols across every running server that supports it.
+   pub async fn workspace_symbols(&self, query: &str) -> Result<Vec<LspWorkspaceSymbol>> {
+      let clients: Vec<(String, LspClient)> = self
+         .workspace_clients
+         .lock()
+         .unwrap()
+         .values()
+         .filter(|instance| instance.client.supports_workspace_symbols())
+         .map(|instance| (instance.server_name.clone(), instance.client.clone()))
+         .collect();
+
+      let mut results = Vec::new();
+      for (server_name, client) in clients {
+         let params = WorkspaceSymbolParams {
+            query: query.to_string(),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+         };
+         match client.workspace_symbol(params).await {
+            Ok(response) => results.extend(symbols::from_workspace_symbol_response(response)),
+            Err(e) => log::warn!(
+               "[LSP] Workspace symbols from '{}' failed: {}",
+               server_name,
+               e
+            ),
+         }
+      }
+      Ok(results)
+   }
+
+   pub async fn code_lenses(&self, file_path: &str) -> Result<Vec<CodeLens>> {
+      let client = self
+         .get_client_for_file(file_path)
+         .context("No LSP client for this file")?;
+      if !client.supports_code_lens() {
+         return Ok(Vec::new());
+      }
+
+      let params = CodeLensParams {
+         text_document: TextDocumentIdentifier {
+            uri: transport::editor_uri(file_path)?,
+         },
+         work_done_progress_params: Default::default(),
+         partial_result_params: Default::default(),
+      };
+      Ok(client
+         .text_document_code_lens(params)
+         .await?
+         .unwrap_or_default())
+   }
+
+   /// Fill in the command of a lens the server left for `codeLens/resolve`.
+   pub async fn resolve_code_lens(&self, file_path: &str, lens: CodeLens) -> Result<CodeLens> {
+      let client = self
+         .get_client_for_file(file_path)
+         .context("No LSP client for this file")?;
+
+      if lens.command.is_some() || !client.supports_code_lens_resolve() {
+         return Ok(lens);
+      }
+      client.code_lens_resolve(lens).await
+   }
+
+   /// Other occurrences of the symbol at a position in the same document.
+   pub async fn document_highlights(
+      &self,
+      file_path: &str,
+      line: u32,
+      character: u32,
+   ) -> Result<Vec<DocumentHighlight>> {
+      let client = self
+         .get_client_for_file(file_path)
+         .context("No LSP client for this file")?;
+      if !client.supports_document_highlight() {
+         return Ok(Vec::new());
+      }
+
+      let params = DocumentHighlightParams {
+         text_document_position_params: position_params(file_path, line, character)?,
+         work_done_progress_params: Default::default(),
+         partial_result_params: Default::default(),
+      };
+      Ok(client
+         .text_document_document_highlight(params)
+         .await?
+         .unwrap_or_default())
+   }
+
+   /// Folding ranges from the language server, or from the syntax tree when no server provides
+   /// them. `content` is the editor buffer; the file is read from disk without it.
+   pub async fn folding_ranges(
+      &self,
+      file_path: &str,
+      content: Option<String>,
+   ) -> Result<Vec<FoldingRange>> {
+      if let Some(client) = self.get_client_for_file(file_path)
+         && client.supports_folding_range()
+      {
+         let params = FoldingRangeParams {
+            text_document: TextDocumentIdentifier {
+               uri: transport::editor_uri(file_path)?,
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+         };
+         match client.text_document_folding_range(params).await {
+            Ok(ranges) => return Ok(ranges.unwrap_or_default()),
+            Err(e) => log::warn!("[LSP] Folding ranges failed, using syntax tree: {}", e),
+         }
+      }
+
+      let content = syntax_source(file_path, content)?;
+      ranges::tree_sitter_folding_ranges(&content, file_extension(file_path))
+   }
+
+   /// For each position, the ranges to grow a selection through, innermost first. Falls back to
+   /// the syntax tree like `folding_ranges`.
+   pub async fn selection_ranges(
+      &self,
+      file_path: &str,
+      positions: Vec<Position>,
+      content: Option<String>,
+   ) -> Result<Vec<SelectionRange>> {
+      if let Some(client) = self.get_client_for_file(file_path)
+         && client.supports_selection_range()
+      {
+         let params = SelectionRangeParams {
+            text_document: TextDocumentIdentifier {
+               uri: transport::editor_uri(file_path)?,
+            },
+            positions: positions.clone(),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+         };
+         match client.text_document_selection_range(params).await {
+            Ok(Some(ranges)) => return Ok(ranges),
+            Ok(None) => {}
+            Err(e) => log::warn!("[LSP] Selection ranges failed, using syntax tree: {}", e),
+         }
+      }
+
+      let content = syntax_source(file_path, content)?;
+      ranges::tree_sitter_selection_ranges(&content, file_extension(file_path), &positions)
+   }
+
+   /// The call or type hierarchy roots at a position, expanded `depth` levels deep.
+   pub async fn prepare_hierarchy(
+      &self,
+      file_path: &str,
+      line: u32,
+      character: u32,
+      direction: HierarchyDirection,
+      depth: usize,
+   ) -> Result<Vec<HierarchyNode>> {
+      let client = self
+         .get_client_for_file(file_path)
+         .context("No LSP client for this file")?;
+      let text_document_position_params = position_params(file_path, line, character)?;
+
+      let items: Vec<HierarchyItem> = if direction.is_call() {
+         if !client.supports_call_hierarchy() {
+            return Ok(Vec::new());
+         }
+         let params = CallHierarchyPrepareParams {
+            text_document_position_params,
+            work_done_progress_params: Default::default(),
+         };
+         let items = client.text_document_prepare_call_hierarchy(params).await?;
+         items.into_iter().flatten().map(Into::into).collect()
+      } else {
+         let params = TypeHierarchyPrepareParams {
+            text_document_position_params,
+            work_done_progress_params: Default::default(),
+         };
+         let items = client.text_document_prepare_type_hierarchy(params).await?;
+         items.into_iter().flatten().map(Into::into).collect()
+      };
+
+      let mut roots: Vec<HierarchyNode> = items.into_iter().map(HierarchyNode::new).collect();
+      hierarchy::expand(&mut roots, &[], depth, |item| {
+         hierarchy_children(&client, direction, item)
+      })
+      .await?;
+      Ok(roots)
+   }
+
+   /// Expand a node the frontend left collapsed. `ancestors` are the items on its path from
+   /// the root, so cycles back into them are marked instead of expanded.
+   pub async fn expand_hierarchy(
+      &self,
+      file_path: &str,
+      item: HierarchyItem,
+      direction: HierarchyDirection,
+      depth: usize,
+      ancestors: Vec<HierarchyItem>,
+   ) -> Result<Vec<HierarchyNode>> {
+      let client = self
+         .get_client_for_file(file_path)
+         .context("No LSP client for this file")?;
+
+      let mut root = [HierarchyNode::new(item)];
+      hierarchy::expand(&mut root, &ancestors, depth.max(1), |item| {
+         hierarchy_children(&client, direction, item)
+      })
+      .await?;
+      let [root] = root;
+      Ok(root.children.unwrap_or_default())
+   }
+
+   /// Pull diagnostics for a file from servers that support it, returning everything the
+   /// file's server reported, pulled or published.
+   pub async fn pull_diagnostics(&self, file_path: &str) -> Result<Vec<Diagnostic>> {
+      let client = self
+         .get_client_for_file(file_path)
+         .context("No LSP client for this file")?;
+      let uri = transport::editor_uri(file_path)?;
+      if client.diagnostic_options().is_some() {
+         client.text_document_diagnostic(uri.clone()).await?;
+      }
+      Ok(client.diagnostics(&uri))
+   }
+
+   /// Pull workspace-wide diagnostics from every running server that offers them.
+   pub async fn workspace_diagnostics(&self) -> Result<()> {
+      let clients: Vec<(String, LspClient)> = self
+         .workspace_clients
+         .lock()
+         .unwrap()
+         .values()
+         .filter(|instance| {
+            instance
+               .client
+               .diagnostic_options()
+               .is_some_and(|options| options.workspace_diagnostics)
+         })
+         .map(|instance| (instance.server_name.clone(), instance.client.clone()))
+         .collect();
+
+      for (server_name, client) in clients {
+         if let Err(e) = client.workspace_diagnostic().await {
+            log::warn!(
+               "[LSP] Workspace diagnostics from '{}' failed: {}",
+               server_name,
+               e
+            );
+         }
+      }
+      Ok(())
+   }
+
+   /// Pass the user's answer to a `window/showMessageRequest` back to the server that asked.
+   pub fn respond_to_message_request(
+      &self,
+      request_id: &str,
+      action: Option<MessageActionItem>,
+   ) -> Result<()> {
+      let clients = self.workspace_clients.lock().unwrap();
+      for instance in clients.values() {
+         if instance
+            .client
+            .respond_to_message_request(request_id, action.clone())?
+         {
+            return Ok(());
+         }
+      }
+      bail!("No pending message request {}", request_id)
+   }
+
    pub fn notify_document_open(&self, file_path: &str, content: String) -> Result<()> {
       let path = PathBuf::from(file_path);
       let _extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
@@ -408,8 +1552,7 @@ impl LspManager {
 
       let params = DidOpenTextDocumentParams {
          text_document: TextDocumentItem {
-            uri: Url::from_file_path(file_path)
-               .map_err(|_| anyhow::anyhow!("Invalid file path"))?,
+            uri: transport::editor_uri(file_path)?,
             language_id: self.get_language_id_for_file(file_path),
             version: 1,
             text: content,
@@ -419,10 +1562,11 @@ impl LspManager {
       client.text_document_did_open(params)
    }
 
+   /// Forward edits to the server. `changes` may be range edits or a single full-text change.
    pub fn notify_document_change(
       &self,
       file_path: &str,
-      content: String,
+      content_changes: Vec<TextDocumentContentChangeEvent>,
       version: i32,
    ) -> Result<()> {
       let path = PathBuf::from(file_path);
@@ -434,15 +1578,10 @@ impl LspManager {
 
       let params = DidChangeTextDocumentParams {
          text_document: VersionedTextDocumentIdentifier {
-            uri: Url::from_file_path(file_path)
-               .map_err(|_| anyhow::anyhow!("Invalid file path"))?,
+            uri: transport::editor_uri(file_path)?,
             version,
          },
-         content_changes: vec![TextDocumentContentChangeEvent {
-            range: None,
-            range_length: None,
-            text: content,
-         }],
+         content_changes,
       };
 
       client.text_document_did_change(params)
@@ -458,8 +1597,7 @@ impl LspManager {
 
       let params = DidCloseTextDocumentParams {
          text_document: TextDocumentIdentifier {
-            uri: Url::from_file_path(file_path)
-               .map_err(|_| anyhow::anyhow!("Invalid file path"))?,
+            uri: transport::editor_uri(file_path)?,
          },
       };
 
@@ -474,7 +1612,7 @@ impl LspManager {
             server_name,
             workspace
          );
-         let _ = instance.child.kill();
+         let _ = instance.process.kill();
       }
    }
 
@@ -495,26 +1633,191 @@ impl LspManager {
                instance.server_name,
                workspace_path
             );
-            instance.child.kill()?;
+            instance.process.kill()?;
+            self.clear_diagnostics(&instance);
          }
       }
 
       Ok(())
    }
 
+   /// Stop the servers running on the host of an SSH connection that is being closed.
+   pub fn shutdown_connection(&self, connection_id: &str) {
+      let mut clients = self.workspace_clients.lock().unwrap();
+      let keys_to_remove: Vec<_> = clients
+         .keys()
+         .filter(|(ws, _)| transport::connection_id(ws) == Some(connection_id))
+         .cloned()
+         .collect();
+
+      for key in keys_to_remove {
+         if let Some(mut instance) = clients.remove(&key) {
+            log::info!(
+               "Shutting down LSP '{}' on SSH connection '{}'",
+               instance.server_name,
+               connection_id
+            );
+            let _ = instance.process.kill();
+            self.clear_diagnostics(&instance);
+         }
+      }
+   }
+
+   /// Forget what a stopped server reported about its folders.
+   fn clear_diagnostics(&self, instance: &LspInstance) {
+      for folder in &instance.folders {
+         self
+            .diagnostics
+            .clear(&instance.server_name, Some(folder.as_path()));
+      }
+   }
+
    fn get_language_id_for_file(&self, file_path: &str) -> String {
       let path = PathBuf::from(file_path);
       let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
 
-      match extension {
-         "ts" => "typescript",
+      // Servers covering several languages need the id of the specific one
+      let language_id = match extension {
+         "ts" | "mts" | "cts" => "typescript",
          "tsx" => "typescriptreact",
          "js" | "mjs" | "cjs" => "javascript",
          "jsx" => "javascriptreact",
-         "json" => "json",
-         _ => "plaintext",
-      }
-      .to_string()
+         "c" | "h" => "c",
+         "m" => "objective-c",
+         "mm" => "objective-cpp",
+         "jsonc" => "jsonc",
+         _ => {
+            return self
+               .registry
+               .lock()
+               .unwrap()
+               .find_server_for_file(&path)
+               .map_or_else(|| "plaintext".to_string(), |s| s.language_id.clone());
+         }
+      };
+      language_id.to_string()
+   }
+}
+
+fn position_params(
+   file_path: &str,
+   line: u32,
+   character: u32,
+) -> Result<TextDocumentPositionParams> {
+   Ok(TextDocumentPositionParams {
+      text_document: TextDocumentIdentifier {
+         uri: transport::editor_uri(file_path)?,
+      },
+      position: Position { line, character },
+   })
+}
+
+/// The text to parse for syntax tree fallbacks: the editor buffer if given, else the file.
+fn syntax_source(file_path: &str, content: Option<String>) -> Result<String> {
+   match content {
+      Some(content) => Ok(content),
+      None => transport::read_editor_file(file_path)
+         .with_context(|| format!("Failed to read {}", file_path)),
+   }
+}
+
+fn file_extension(file_path: &str) -> &str {
+   Path::new(file_path)
+      .extension()
+      .and_then(|ext| ext.to_str())
+      .unwrap_or("")
+}
+
+/// One level of a hierarchy below `item`.
+async fn hierarchy_children(
+   client: &LspClient,
+   direction: HierarchyDirection,
+   item: HierarchyItem,
+) -> Result<Vec<HierarchyNode>> {
+   let children = match direction {
+      HierarchyDirection::IncomingCalls => client
+         .call_hierarchy_incoming_calls(CallHierarchyIncomingCallsParams {
+            item: item.into(),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+         })
+         .await?
+         .into_iter()
+         .flatten()
+         .map(Into::into)
+         .collect(),
+      HierarchyDirection::OutgoingCalls => client
+         .call_hierarchy_outgoing_calls(CallHierarchyOutgoingCallsParams {
+            item: item.into(),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+         })
+         .await?
+         .into_iter()
+         .flatten()
+         .map(Into::into)
+         .collect(),
+      HierarchyDirection::Supertypes => client
+         .type_hierarchy_supertypes(TypeHierarchySupertypesParams {
+            item: item.into(),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+         })
+         .await?
+         .into_iter()
+         .flatten()
+         .map(|item| HierarchyNode::new(item.into()))
+         .collect(),
+      HierarchyDirection::Subtypes => client
+         .type_hierarchy_subtypes(TypeHierarchySubtypesParams {
+            item: item.into(),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+         })
+         .await?
+         .into_iter()
+         .flatten()
+         .map(|item| HierarchyNode::new(item.into()))
+         .collect(),
+   };
+   Ok(children)
+}
+
+fn to_workspace_folders(roots: &[WorkspaceRoot]) -> Vec<WorkspaceFolder> {
+   roots
+      .iter()
+      .filter_map(|root| {
+         Some(WorkspaceFolder {
+            uri: transport::editor_dir_uri(&root.path).ok()?,
+            name: root.name.clone(),
+         })
+      })
+      .collect()
+}
+
+/// Marks a server as being installed until dropped, even if the install is cancelled.
+struct InstallingGuard<'a> {
+   installing: &'a Mutex<HashSet<String>>,
+   server_name: String,
+}
+
+impl<'a> InstallingGuard<'a> {
+   /// Returns `None` if the server is already being installed.
+   fn new(installing: &'a Mutex<HashSet<String>>, server_name: &str) -> Option<Self> {
+      installing
+         .lock()
+         .unwrap()
+         .insert(server_name.to_string())
+         .then(|| Self {
+            installing,
+            server_name: server_name.to_string(),
+         })
+   }
+}
+
+impl Drop for InstallingGuard<'_> {
+   fn drop(&mut self) {
+      self.installing.lock().unwrap().remove(&self.server_name);
    }
 }
 
@@ -1,7 +1,21 @@
+pub mod capabilities;
 pub mod client;
+pub mod code_action;
 pub mod config;
+mod dispatcher;
+pub mod hierarchy;
+pub mod installer;
+pub mod location;
 pub mod manager;
+pub mod ranges;
+pub mod semantic_tokens;
+pub mod snippet;
+pub mod supervisor;
+pub mod symbols;
+pub mod traffic;
+pub mod transport;
 pub mod types;
 pub mod utils;
+pub mod workspace_edit;
 
 pub use manager::LspManager;
@@ -0,0 +1,232 @@
+use super::symbols::{line_starts, lsp_position, parse};
+use anyhow::Result;
+use lsp_types::{FoldingRange, FoldingRangeKind, Position, Range, SelectionRange};
+use std::collections::BTreeMap;
+use tree_sitter::Node;
+
+/// Folding ranges built from the syntax tree: every node spanning several lines folds, keeping
+/// a closing line such as `}` or `end` visible, and runs of line comments fold together. Only
+/// the outermost range starting on a line is kept, as editors fold by line.
+pub fn tree_sitter_folding_ranges(
+   content: &str,
+   file_extension: &str,
+) -> Result<Vec<FoldingRange>> {
+   let (_, tree) = parse(content, file_extension)?;
+   let mut folding = Folding {
+      content,
+      line_starts: line_starts(content),
+      ranges: BTreeMap::new(),
+   };
+   folding.collect(tree.root_node());
+   Ok(folding.ranges.into_values().collect())
+}
+
+struct Folding<'a> {
+   content: &'a str,
+   line_starts: Vec<usize>,
+   /// By start line.
+   ranges: BTreeMap<usize, FoldingRange>,
+}
+
+impl Folding<'_> {
+   fn collect(&mut self, node: Node) {
+      let mut cursor = node.walk();
+      // First and last line of the comments seen since the last other node
+      let mut comments: Option<(usize, usize)> = None;
+      for child in node.named_children(&mut cursor) {
+         if child.kind().contains("comment") {
+            let (start, end) = (child.start_position().row, self.last_folded_line(child));
+            match &mut comments {
+               Some((_, run_end)) if start <= *run_end + 1 => *run_end = end.max(*run_end),
+               _ => {
+                  if let Some((start, end)) = comments.replace((start, end)) {
+                     self.add(start, end, Some(FoldingRangeKind::Comment));
+                  }
+               }
+            }
+            continue;
+         }
+         if let Some((start, end)) = comments.take() {
+            self.add(start, end, Some(FoldingRangeKind::Comment));
+         }
+         self.add(
+            child.start_position().row,
+            self.last_folded_line(child),
+            None,
+         );
+         self.collect(child);
+      }
+      if let Some((start, end)) = comments {
+         self.add(start, end, Some(FoldingRangeKind::Comment));
+      }
+   }
+
+   fn add(&mut self, start: usize, end: usize, kind: Option<FoldingRangeKind>) {
+      if end <= start {
+         return;
+      }
+      self.ranges.entry(start).or_insert(FoldingRange {
+         start_line: start as u32,
+         start_character: None,
+         end_line: end as u32,
+         end_character: None,
+         kind,
+         collapsed_text: None,
+      });
+   }
+
+   /// The last line hidden when a node is folded.
+   fn last_folded_line(&self, node: Node) -> usize {
+      let end = node.end_position();
+      // Nodes that swallow their trailing newline end at the start of the next line
+      if end.column == 0 {
+         return end.row.saturating_sub(1);
+      }
+      let last_line = self
+         .line_starts
+         .get(end.row)
+         .and_then(|start| self.content.get(*start..node.end_byte()))
+         .unwrap_or_default()
+         .trim_start();
+      let closes = last_line.starts_with(['}', ')', ']'])
+         || last_line.starts_with("</")
+         || matches!(last_line, "end" | "fi" | "done" | "esac");
+      if closes && end.row > node.start_position().row {
+         end.row - 1
+      } else {
+         end.row
+      }
+   }
+}
+
+/// Selection ranges built from the syntax tree: for each position, the ranges of the node
+/// under it and of each of its ancestors, innermost first.
+pub fn tree_sitter_selection_ranges(
+   content: &str,
+   file_extension: &str,
+   positions: &[Position],
+) -> Result<Vec<SelectionRange>> {
+   let (_, tree) = parse(content, file_extension)?;
+   let line_starts = line_starts(content);
+   let range = |node: Node| {
+      Range::new(
+         lsp_position(content, &line_starts, node.start_position()),
+         lsp_position(content, &line_starts, node.end_position()),
+      )
+   };
+
+   Ok(positions
+      .iter()
+      .map(|position| {
+         let offset = byte_offset(content, &line_starts, *position);
+         let mut ranges: Vec<Range> = Vec::new();
+         let mut node = tree.root_node().descendant_for_byte_range(offset, offset);
+         while let Some(current) = node {
+            let current_range = range(current);
+            if ranges.last() != Some(&current_range) {
+               ranges.push(current_range);
+            }
+            node = current.parent();
+         }
+
+         ranges
+            .into_iter()
+            .rev()
+            .fold(None, |parent, range| {
+               Some(SelectionRange {
+                  range,
+                  parent: parent.map(Box::new),
+               })
+            })
+            .unwrap_or_else(|| SelectionRange {
+               range: Range::new(*position, *position),
+               parent: None,
+            })
+      })
+      .collect())
+}
+
+/// Byte offset of an LSP position, clamped to the end of its line.
+fn byte_offset(content: &str, line_starts: &[usize], position: Position) -> usize {
+   let Some(&start) = line_starts.get(position.line as usize) else {
+      return content.len();
+   };
+   let line = content[start..].lines().next().unwrap_or_default();
+   let mut units = 0;
+   for (offset, ch) in line.char_indices() {
+      if units >= position.character as usize {
+         return start + offset;
+      }
+      units += ch.len_utf16();
+   }
+   start + line.len()
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_folding_keeps_closing_lines_visible() {
+      let content =
+         "/// Docs\n/// more\nfn main() {\n   let v = vec![\n      1,\n   ];\n}\n\nfn one() {}\n";
+      let ranges = tree_sitter_folding_ranges(content, "rs").unwrap();
+      let lines: Vec<_> = ranges
+         .iter()
+         .map(|range| (range.start_line, range.end_line, range.kind.clone()))
+         .collect();
+      assert_eq!(
+         lines,
+         vec![
+            (0, 1, Some(FoldingRangeKind::Comment)),
+            (2, 5, None),
+            (3, 4, None),
+         ]
+      );
+
+      let ranges =
+         tree_sitter_folding_ranges("def run():\n    a = 1\n    return a\n", "py").unwrap();
+      assert_eq!((ranges[0].start_line, ranges[0].end_line), (0, 2));
+
+      // Nodes on the first line that start with a closing bracket do not fold
+      assert!(
+         tree_sitter_folding_ranges("}\nfn a() {}", "rs")
+            .unwrap()
+            .is_empty()
+      );
+      assert!(
+         tree_sitter_folding_ranges("})();", "js")
+            .unwrap()
+            .is_empty()
+      );
+   }
+
+   #[test]
+   fn test_selection_ranges_expand_through_ancestors() {
+      let content = "fn main() {\n   let é = foo(bar);\n}\n";
+      let ranges =
+         tree_sitter_selection_ranges(content, "rs", &[Position::new(1, 15), Position::new(9, 0)])
+            .unwrap();
+
+      let mut chain = Vec::new();
+      let mut current = Some(&ranges[0]);
+      while let Some(selection) = current {
+         chain.push(selection.range);
+         current = selection.parent.as_deref();
+      }
+      // `bar`, `(bar)`, `foo(bar)`, the statement, the block, the function, the file
+      assert_eq!(
+         chain[0],
+         Range::new(Position::new(1, 15), Position::new(1, 18))
+      );
+      assert_eq!(
+         chain[2],
+         Range::new(Position::new(1, 11), Position::new(1, 19))
+      );
+      assert_eq!(chain.last().unwrap().start, Position::new(0, 0));
+      assert!(chain.windows(2).all(|pair| pair[0] != pair[1]));
+
+      // Positions past the end select from the end of the file outwards
+      assert_eq!(ranges[1].range.end, Position::new(3, 0));
+   }
+}
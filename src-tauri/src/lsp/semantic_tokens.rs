@@ -0,0 +1,221 @@
+use crate::commands::tokens::map_highlight_to_class;
+use lsp_types::{Position, Range, SemanticToken, SemanticTokensEdit, SemanticTokensLegend};
+use serde::Serialize;
+
+/// A semantic token at an absolute range, classified like the tree-sitter tokens it overlays.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct LspSemanticToken {
+   pub range: Range,
+   pub token_type: String,
+   pub class_name: String,
+   /// The server's own type name from its legend, e.g. `enumMember`.
+   pub semantic_type: String,
+   pub modifiers: Vec<String>,
+}
+
+/// Resolve the relative encoding into absolute ranges. Tokens whose type has no tree-sitter
+/// equivalent are dropped so they do not override the syntax highlighting.
+pub fn decode(tokens: &[SemanticToken], legend: &SemanticTokensLegend) -> Vec<LspSemanticToken> {
+   let mut line = 0;
+   let mut start = 0;
+   let mut decoded = Vec::with_capacity(tokens.len());
+
+   for token in tokens {
+      if token.delta_line > 0 {
+         line += token.delta_line;
+         start = token.delta_start;
+      } else {
+         start += token.delta_start;
+      }
+
+      let Some(semantic_type) = legend.token_types.get(token.token_type as usize) else {
+         continue;
+      };
+      let modifiers: Vec<String> = legend
+         .token_modifiers
+         .iter()
+         .enumerate()
+         .filter(|(bit, _)| *bit < 32 && token.token_modifiers_bitset & (1 << bit) != 0)
+         .map(|(_, modifier)| modifier.as_str().to_string())
+         .collect();
+      let Some(highlight) = highlight_name(semantic_type.as_str(), &modifiers) else {
+         continue;
+      };
+      let (token_type, class_name) = map_highlight_to_class(highlight);
+
+      decoded.push(LspSemanticToken {
+         range: Range::new(
+            Position::new(line, start),
+            Position::new(line, start + token.length),
+         ),
+         token_type: token_type.to_string(),
+         class_name: class_name.to_string(),
+         semantic_type: semantic_type.as_str().to_string(),
+         modifiers,
+      });
+   }
+   decoded
+}
+
+/// Tree-sitter highlight name closest to a standard semantic token type.
+fn highlight_name(semantic_type: &str, modifiers: &[String]) -> Option<&'static str> {
+   let readonly = modifiers.iter().any(|modifier| modifier == "readonly");
+   let highlight = match semantic_type {
+      "namespace" | "type" | "class" | "enum" | "interface" | "struct" | "typeParameter" => "type",
+      "variable" if readonly => "constant",
+      "variable" => "variable",
+      "parameter" => "variable.parameter",
+      "property" | "event" => "property",
+      "enumMember" => "constant",
+      "function" | "macro" | "decorator" => "function",
+      "method" => "function.method",
+      "keyword" | "modifier" => "keyword",
+      "comment" => "comment",
+      "string" | "regexp" => "string",
+      "number" => "number",
+      "operator" => "operator",
+      _ => return None,
+   };
+   Some(highlight)
+}
+
+/// Apply a `semanticTokens/full/delta` response. Edit offsets count integers of the flat
+/// encoding, five per token, and all refer to the previous result.
+pub fn apply_edits(
+   tokens: &[SemanticToken],
+   mut edits: Vec<SemanticTokensEdit>,
+) -> Vec<SemanticToken> {
+   let mut data: Vec<u32> = tokens
+      .iter()
+      .flat_map(|token| {
+         [
+            token.delta_line,
+            token.delta_start,
+            token.length,
+            token.token_type,
+            token.token_modifiers_bitset,
+         ]
+      })
+      .collect();
+
+   edits.sort_by_key(|edit| edit.start);
+   for edit in edits.into_iter().rev() {
+      let start = (edit.start as usize).min(data.len());
+      let end = (start + edit.delete_count as usize).min(data.len());
+      let inserted = edit.data.unwrap_or_default().into_iter().flat_map(|token| {
+         [
+            token.delta_line,
+            token.delta_start,
+            token.length,
+            token.token_type,
+            token.token_modifiers_bitset,
+         ]
+      });
+      data.splice(start..end, inserted);
+   }
+
+   data
+      .chunks_exact(5)
+      .map(|chunk| SemanticToken {
+         delta_line: chunk[0],
+         delta_start: chunk[1],
+         length: chunk[2],
+         token_type: chunk[3],
+         token_modifiers_bitset: chunk[4],
+      })
+      .collect()
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use lsp_types::{SemanticTokenModifier, SemanticTokenType};
+
+   fn token(
+      delta_line: u32,
+      delta_start: u32,
+      length: u32,
+      token_type: u32,
+      modifiers: u32,
+   ) -> SemanticToken {
+      SemanticToken {
+         delta_line,
+         delta_start,
+         length,
+         token_type,
+         token_modifiers_bitset: modifiers,
+      }
+   }
+
+   #[test]
+   fn test_decode_relative_tokens() {
+      let legend = SemanticTokensLegend {
+         token_types: vec![
+            SemanticTokenType::FUNCTION,
+            SemanticTokenType::VARIABLE,
+            SemanticTokenType::new("lifetime"),
+         ],
+         token_modifiers: vec![
+            SemanticTokenModifier::DECLARATION,
+            SemanticTokenModifier::READONLY,
+         ],
+      };
+      let tokens = [
+         token(2, 4, 3, 0, 0b01),
+         token(0, 6, 1, 1, 0b10),
+         token(0, 3, 2, 2, 0),
+         token(1, 1, 5, 1, 0),
+      ];
+
+      let decoded = decode(&tokens, &legend);
+      assert_eq!(decoded.len(), 3);
+      assert_eq!(
+         decoded[0].range,
+         Range::new(Position::new(2, 4), Position::new(2, 7))
+      );
+      assert_eq!(
+         (
+            decoded[0].token_type.as_str(),
+            decoded[0].class_name.as_str()
+         ),
+         ("function", "token-function")
+      );
+      assert_eq!(decoded[0].modifiers, vec!["declaration"]);
+      assert_eq!(decoded[1].range.start, Position::new(2, 10));
+      assert_eq!(decoded[1].token_type, "constant");
+      // The unknown type is skipped but still moves the position along
+      assert_eq!(decoded[2].range.start, Position::new(3, 1));
+      assert_eq!(decoded[2].token_type, "identifier");
+   }
+
+   #[test]
+   fn test_apply_delta_edits() {
+      let tokens = vec![
+         token(0, 0, 1, 0, 0),
+         token(1, 0, 2, 0, 0),
+         token(1, 0, 3, 0, 0),
+      ];
+      let edits = vec![
+         SemanticTokensEdit {
+            start: 10,
+            delete_count: 5,
+            data: None,
+         },
+         SemanticTokensEdit {
+            start: 5,
+            delete_count: 0,
+            data: Some(vec![token(0, 4, 9, 1, 0)]),
+         },
+      ];
+
+      let updated = apply_edits(&tokens, edits);
+      assert_eq!(
+         updated,
+         vec![
+            token(0, 0, 1, 0, 0),
+            token(0, 4, 9, 1, 0),
+            token(1, 0, 2, 0, 0)
+         ]
+      );
+   }
+}
@@ -0,0 +1,791 @@
+use chrono::Local;
+use regex::{Captures, Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::{
+   collections::{BTreeMap, HashMap},
+   path::PathBuf,
+};
+
+/// What snippet variables such as `$TM_FILENAME` resolve to. Variables whose source is missing
+/// resolve to nothing, so their default is used.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SnippetContext {
+   pub file_path: Option<PathBuf>,
+   pub workspace_path: Option<PathBuf>,
+   pub selected_text: Option<String>,
+   pub current_line: Option<String>,
+   pub current_word: Option<String>,
+   /// Zero-based line of the cursor.
+   pub line: Option<u32>,
+   pub clipboard: Option<String>,
+}
+
+/// A snippet as plain text plus the places the cursor visits, in order.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExpandedSnippet {
+   pub text: String,
+   /// Ascending by index with the final cursor position `$0` last.
+   pub tabstops: Vec<SnippetTabstop>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SnippetTabstop {
+   pub index: u32,
+   /// Every occurrence of the tabstop; later ones mirror what is typed into the first.
+   pub ranges: Vec<SnippetRange>,
+   /// Values offered for a `${1|one,two|}` choice, the first of which is inserted.
+   pub choices: Option<Vec<String>>,
+}
+
+/// Offsets into the expanded text, in UTF-16 code units like editor positions.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SnippetRange {
+   pub start: usize,
+   pub end: usize,
+}
+
+/// Expand a snippet in the LSP snippet syntax. Malformed parts are inserted as written.
+pub fn expand(snippet: &str, context: &SnippetContext) -> ExpandedSnippet {
+   let nodes = SnippetParser {
+      chars: snippet.chars().collect(),
+      pos: 0,
+   }
+   .parse_any(false);
+
+   let mut defaults = HashMap::new();
+   collect_defaults(&nodes, &mut defaults);
+   let mut renderer = Renderer {
+      context,
+      defaults: &defaults,
+      text: String::new(),
+      offset: 0,
+      tabstops: BTreeMap::new(),
+      next_index: max_index(&nodes).saturating_add(1),
+      rendering: Vec::new(),
+   };
+   renderer.render(&nodes, true);
+
+   let end = renderer.offset;
+   let mut tabstops = renderer.tabstops;
+   let final_stop = tabstops.remove(&0).unwrap_or(SnippetTabstop {
+      index: 0,
+      ranges: vec![SnippetRange { start: end, end }],
+      choices: None,
+   });
+   let mut tabstops: Vec<SnippetTabstop> = tabstops.into_values().collect();
+   tabstops.push(final_stop);
+   ExpandedSnippet {
+      text: renderer.text,
+      tabstops,
+   }
+}
+
+#[derive(Debug)]
+enum Node {
+   Text(String),
+   /// `$1`, `${1:placeholder}` or `${1|one,two|}`.
+   Tabstop {
+      index: u32,
+      children: Vec<Node>,
+      choices: Option<Vec<String>>,
+   },
+   /// `$NAME`, `${NAME:default}` or `${NAME/regex/format/options}`.
+   Variable {
+      name: String,
+      default: Option<Vec<Node>>,
+      transform: Option<Transform>,
+   },
+}
+
+#[derive(Debug)]
+struct Transform {
+   regex: Regex,
+   format: Vec<FormatItem>,
+   global: bool,
+}
+
+#[derive(Debug)]
+enum FormatItem {
+   Text(String),
+   /// `$1` or `${1}`.
+   Group(usize),
+   /// `${1:/upcase}` and the other case changes.
+   Case(usize, String),
+   /// `${1:+if}`, `${1:-else}` or `${1:?if:else}`, depending on whether the group matched.
+   Conditional(usize, String, String),
+}
+
+struct SnippetParser {
+   chars: Vec<char>,
+   pos: usize,
+}
+
+impl SnippetParser {
+   /// Nodes up to the end, or up to the `}` closing a placeholder when `nested`.
+   fn parse_any(&mut self, nested: bool) -> Vec<Node> {
+      let mut nodes = Vec::new();
+      let mut text = String::new();
+      while let Some(c) = self.peek() {
+         match c {
+            '}' if nested => break,
+            '\\' => {
+               self.pos += 1;
+               match self.peek() {
+                  Some(escaped @ ('$' | '}' | '\\')) => {
+                     self.pos += 1;
+                     text.push(escaped);
+                  }
+                  _ => text.push('\\'),
+               }
+            }
+            '$' => {
+               let start = self.pos;
+               match self.parse_dollar() {
+                  Some(node) => {
+                     if !text.is_empty() {
+                        nodes.push(Node::Text(std::mem::take(&mut text)));
+                     }
+                     nodes.push(node);
+                  }
+                  None => {
+                     self.pos = start + 1;
+                     text.push('$');
+                  }
+               }
+            }
+            _ => {
+               self.pos += 1;
+               text.push(c);
+            }
+         }
+      }
+      if !text.is_empty() {
+         nodes.push(Node::Text(text));
+      }
+      nodes
+   }
+
+   fn parse_dollar(&mut self) -> Option<Node> {
+      self.eat('$');
+      if let Some(index) = self.parse_int() {
+         return Some(tabstop(index, Vec::new(), None));
+      }
+      if let Some(name) = self.parse_var_name() {
+         return Some(Node::Variable {
+            name,
+            default: None,
+            transform: None,
+         });
+      }
+      if !self.eat('{') {
+         return None;
+      }
+
+      if let Some(index) = self.parse_int() {
+         return match self.next()? {
+            '}' => Some(tabstop(index, Vec::new(), None)),
+            ':' => {
+               let children = self.parse_any(true);
+               self.eat('}').then(|| tabstop(index, children, None))
+            }
+            '|' => {
+               let choices = self.parse_choices()?;
+               Some(tabstop(index, Vec::new(), Some(choices)))
+            }
+            // Transforms on tabstops apply as the user leaves them, which is the editor's part
+            '/' => {
+               self.parse_transform()?;
+               Some(tabstop(index, Vec::new(), None))
+            }
+            _ => None,
+         };
+      }
+
+      let name = self.parse_var_name()?;
+      let (default, transform) = match self.next()? {
+         '}' => (None, None),
+         ':' => {
+            let default = self.parse_any(true);
+            if !self.eat('}') {
+               return None;
+            }
+            (Some(default), None)
+         }
+         '/' => (None, Some(self.parse_transform()?)),
+         _ => return None,
+      };
+      Some(Node::Variable {
+         name,
+         default,
+         transform,
+      })
+   }
+
+   /// `one,two|}` after the opening `${1|`.
+   fn parse_choices(&mut self) -> Option<Vec<String>> {
+      let mut choices = Vec::new();
+      let mut choice = String::new();
+      loop {
+         match self.next()? {
+            '\\' => match self.next()? {
+               escaped @ ('$' | '}' | '\\' | ',' | '|') => choice.push(escaped),
+               other => {
+                  choice.push('\\');
+                  choice.push(other);
+               }
+            },
+            ',' => choices.push(std::mem::take(&mut choice)),
+            '|' => {
+               choices.push(choice);
+               return self.eat('}').then_some(choices);
+            }
+            c => choice.push(c),
+         }
+      }
+   }
+
+   /// `regex/format/options}` after the first `/`.
+   fn parse_transform(&mut self) -> Option<Transform> {
+      let mut pattern = String::new();
+      loop {
+         match self.next()? {
+            '/' => break,
+            '\\' if self.peek() == Some('/') => {
+               self.pos += 1;
+               pattern.push('/');
+            }
+            '\\' => {
+               pattern.push('\\');
+               pattern.push(self.next()?);
+            }
+            c => pattern.push(c),
+         }
+      }
+
+      let mut format = Vec::new();
+      let mut text = String::new();
+      loop {
+         match self.next()? {
+            '/' => break,
+            '\\' => text.push(self.next()?),
+            '$' => {
+               let item = self.parse_format_item()?;
+               if !text.is_empty() {
+                  format.push(FormatItem::Text(std::mem::take(&mut text)));
+               }
+               format.push(item);
+            }
+            c => text.push(c),
+         }
+      }
+      if !text.is_empty() {
+         format.push(FormatItem::Text(text));
+      }
+
+      let mut options = String::new();
+      loop {
+         match self.next()? {
+            '}' => break,
+            c => options.push(c),
+         }
+      }
+      let regex = RegexBuilder::new(&pattern)
+         .case_insensitive(options.contains('i'))
+         .multi_line(options.contains('m'))
+         .build()
+         .ok()?;
+      Some(Transform {
+         regex,
+         format,
+         global: options.contains('g'),
+      })
+   }
+
+   /// What follows a `$` in a transform's format.
+   fn parse_format_item(&mut self) -> Option<FormatItem> {
+      if let Some(group) = self.parse_int() {
+         return Some(FormatItem::Group(group as usize));
+      }
+      if !self.eat('{') {
+         return None;
+      }
+      let group = self.parse_int()? as usize;
+      match self.next()? {
+         '}' => return Some(FormatItem::Group(group)),
+         ':' => {}
+         _ => return None,
+      }
+      let item = match self.peek()? {
+         '/' => {
+            self.pos += 1;
+            FormatItem::Case(group, self.parse_format_text(&['}'])?)
+         }
+         '+' => {
+            self.pos += 1;
+            FormatItem::Conditional(group, self.parse_format_text(&['}'])?, String::new())
+         }
+         '?' => {
+            self.pos += 1;
+            let if_text = self.parse_format_text(&[':'])?;
+            self.pos += 1;
+            FormatItem::Conditional(group, if_text, self.parse_format_text(&['}'])?)
+         }
+         '-' => {
+            self.pos += 1;
+            FormatItem::Conditional(group, String::new(), self.parse_format_text(&['}'])?)
+         }
+         _ => FormatItem::Conditional(group, String::new(), self.parse_format_text(&['}'])?),
+      };
+      self.eat('}').then_some(item)
+   }
+
+   /// Text up to one of `stops`, which is left unconsumed.
+   fn parse_format_text(&mut self, stops: &[char]) -> Option<String> {
+      let mut text = String::new();
+      loop {
+         match self.peek()? {
+            c if stops.contains(&c) => return Some(text),
+            '\\' => {
+               self.pos += 1;
+               text.push(self.next()?);
+            }
+            c => {
+               self.pos += 1;
+               text.push(c);
+            }
+         }
+      }
+   }
+
+   fn parse_int(&mut self) -> Option<u32> {
+      let start = self.pos;
+      while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+         self.pos += 1;
+      }
+      let digits: String = self.chars[start..self.pos].iter().collect();
+      let value = digits.parse().ok();
+      if value.is_none() {
+         self.pos = start;
+      }
+      value
+   }
+
+   fn parse_var_name(&mut self) -> Option<String> {
+      if !self
+         .peek()
+         .is_some_and(|c| c == '_' || c.is_ascii_alphabetic())
+      {
+         return None;
+      }
+      let start = self.pos;
+      while self
+         .peek()
+         .is_some_and(|c| c == '_' || c.is_ascii_alphanumeric())
+      {
+         self.pos += 1;
+      }
+      Some(self.chars[start..self.pos].iter().collect())
+   }
+
+   fn peek(&self) -> Option<char> {
+      self.chars.get(self.pos).copied()
+   }
+
+   fn next(&mut self) -> Option<char> {
+      let c = self.peek()?;
+      self.pos += 1;
+      Some(c)
+   }
+
+   fn eat(&mut self, expected: char) -> bool {
+      let matches = self.peek() == Some(expected);
+      if matches {
+         self.pos += 1;
+      }
+      matches
+   }
+}
+
+fn tabstop(index: u32, children: Vec<Node>, choices: Option<Vec<String>>) -> Node {
+   Node::Tabstop {
+      index,
+      children,
+      choices,
+   }
+}
+
+/// The first placeholder text or choice of each tabstop, which bare occurrences mirror.
+fn collect_defaults<'n>(nodes: &'n [Node], defaults: &mut HashMap<u32, &'n Node>) {
+   for node in nodes {
+      match node {
+         Node::Tabstop {
+            index,
+            children,
+            choices,
+         } => {
+            if !children.is_empty() || choices.is_some() {
+               defaults.entry(*index).or_insert(node);
+            }
+            collect_defaults(children, defaults);
+         }
+         Node::Variable {
+            default: Some(default),
+            ..
+         } => collect_defaults(default, defaults),
+         _ => {}
+      }
+   }
+}
+
+fn max_index(nodes: &[Node]) -> u32 {
+   nodes
+      .iter()
+      .map(|node| match node {
+         Node::Tabstop {
+            index, children, ..
+         } => (*index).max(max_index(children)),
+         Node::Variable {
+            default: Some(default),
+            ..
+         } => max_index(default),
+         _ => 0,
+      })
+      .max()
+      .unwrap_or(0)
+}
+
+struct Renderer<'a> {
+   context: &'a SnippetContext,
+   defaults: &'a HashMap<u32, &'a Node>,
+   text: String,
+   /// Length of `text` in UTF-16 code units.
+   offset: usize,
+   tabstops: BTreeMap<u32, SnippetTabstop>,
+   /// Unknown variables become placeholders numbered after the snippet's own.
+   next_index: u32,
+   /// Tabstops whose content is being rendered, so one mirroring itself like `${1:a$1}` is left
+   /// empty instead of expanding forever.
+   rendering: Vec<u32>,
+}
+
+impl Renderer<'_> {
+   /// Append nodes to the text, recording where tabstops land only when `record` is set, so
+   /// mirrored text does not produce tabstops of its own.
+   fn render(&mut self, nodes: &[Node], record: bool) {
+      for node in nodes {
+         match node {
+            Node::Text(text) => self.push(text),
+            Node::Tabstop {
+               index,
+               children,
+               choices,
+            } => {
+               let start = self.offset;
+               if let Some(first) = choices.as_ref().and_then(|choices| choices.first()) {
+                  self.push(first);
+               } else if !self.rendering.contains(index) {
+                  self.rendering.push(*index);
+                  if !children.is_empty() {
+                     self.render(children, record);
+                  } else if let Some(Node::Tabstop {
+                     children, choices, ..
+                  }) = self.defaults.get(index).copied()
+                  {
+                     match choices.as_ref().and_then(|choices| choices.first()) {
+                        Some(first) => self.push(first),
+                        None => self.render(children, false),
+                     }
+                  }
+                  self.rendering.pop();
+               }
+               if record {
+                  self.record(*index, start, choices.clone());
+               }
+            }
+            Node::Variable {
+               name,
+               default,
+               transform,
+            } => match variable(self.context, name) {
+               Some(value) if !value.is_empty() => match transform {
+                  Some(transform) => self.push(&transform.apply(&value)),
+                  None => self.push(&value),
+               },
+               Some(_) => {
+                  if let Some(default) = default {
+                     self.render(default, record);
+                  }
+               }
+               // Unknown variables are inserted by name as a placeholder to fill in
+               None => {
+                  let start = self.offset;
+                  match default {
+                     Some(default) => self.render(default, record),
+                     None => self.push(name),
+                  }
+                  if record {
+                     let index = self.next_index;
+                     self.next_index = self.next_index.saturating_add(1);
+                     self.record(index, start, None);
+                  }
+               }
+            },
+         }
+      }
+   }
+
+   fn push(&mut self, text: &str) {
+      self.text.push_str(text);
+      self.offset += text.encode_utf16().count();
+   }
+
+   fn record(&mut self, index: u32, start: usize, choices: Option<Vec<String>>) {
+      let tabstop = self.tabstops.entry(index).or_insert(SnippetTabstop {
+         index,
+         ranges: Vec::new(),
+         choices: None,
+      });
+      tabstop.ranges.push(SnippetRange {
+         start,
+         end: self.offset,
+      });
+      if tabstop.choices.is_none() {
+         tabstop.choices = choices;
+      }
+   }
+}
+
+/// The value of a variable, empty when its source is missing, or `None` for unknown names.
+fn variable(context: &SnippetContext, name: &str) -> Option<String> {
+   let path_text = |path: Option<PathBuf>| {
+      path
+         .map(|path| path.to_string_lossy().into_owned())
+         .unwrap_or_default()
+   };
+   let file_name = |stem: bool| {
+      let file_path = context.file_path.as_ref()?;
+      let name = if stem {
+         file_path.file_stem()
+      } else {
+         file_path.file_name()
+      };
+      Some(name?.to_string_lossy().into_owned())
+   };
+   let now = Local::now();
+   let date = |format: &str| now.format(format).to_string();
+
+   let value = match name {
+      "TM_FILENAME" => file_name(false).unwrap_or_default(),
+      "TM_FILENAME_BASE" => file_name(true).unwrap_or_default(),
+      "TM_FILEPATH" => path_text(context.file_path.clone()),
+      "TM_DIRECTORY" => path_text(
+         context
+            .file_path
+            .as_ref()
+            .and_then(|path| path.parent())
+            .map(PathBuf::from),
+      ),
+      "RELATIVE_FILEPATH" => path_text(context.file_path.as_ref().map(|file_path| {
+         match &context.workspace_path {
+            Some(workspace) => file_path
+               .strip_prefix(workspace)
+               .unwrap_or(file_path)
+               .to_path_buf(),
+            None => file_path.clone(),
+         }
+      })),
+      "WORKSPACE_FOLDER" => path_text(context.workspace_path.clone()),
+      "WORKSPACE_NAME" => context
+         .workspace_path
+         .as_ref()
+         .and_then(|path| path.file_name())
+         .map(|name| name.to_string_lossy().into_owned())
+         .unwrap_or_default(),
+      "TM_SELECTED_TEXT" => context.selected_text.clone().unwrap_or_default(),
+      "TM_CURRENT_LINE" => context.current_line.clone().unwrap_or_default(),
+      "TM_CURRENT_WORD" => context.current_word.clone().unwrap_or_default(),
+      "TM_LINE_INDEX" => context
+         .line
+         .map(|line| line.to_string())
+         .unwrap_or_default(),
+      "TM_LINE_NUMBER" => context
+         .line
+         .map(|line| (line + 1).to_string())
+         .unwrap_or_default(),
+      "CLIPBOARD" => context.clipboard.clone().unwrap_or_default(),
+      "CURRENT_YEAR" => date("%Y"),
+      "CURRENT_YEAR_SHORT" => date("%y"),
+      "CURRENT_MONTH" => date("%m"),
+      "CURRENT_MONTH_NAME" => date("%B"),
+      "CURRENT_MONTH_NAME_SHORT" => date("%b"),
+      "CURRENT_DATE" => date("%d"),
+      "CURRENT_DAY_NAME" => date("%A"),
+      "CURRENT_DAY_NAME_SHORT" => date("%a"),
+      "CURRENT_HOUR" => date("%H"),
+      "CURRENT_MINUTE" => date("%M"),
+      "CURRENT_SECOND" => date("%S"),
+      "CURRENT_SECONDS_UNIX" => now.timestamp().to_string(),
+      "RANDOM" => format!("{:06}", uuid::Uuid::new_v4().as_u128() % 1_000_000),
+      "RANDOM_HEX" => format!("{:06x}", uuid::Uuid::new_v4().as_u128() & 0xff_ffff),
+      "UUID" => uuid::Uuid::new_v4().to_string(),
+      _ => return None,
+   };
+   Some(value)
+}
+
+impl Transform {
+   fn apply(&self, value: &str) -> String {
+      let mut result = String::new();
+      let mut last = 0;
+      for captures in self.regex.captures_iter(value) {
+         let matched = captures.get(0).unwrap();
+         result.push_str(&value[last..matched.start()]);
+         for item in &self.format {
+            result.push_str(&format_item(item, &captures));
+         }
+         last = matched.end();
+         if !self.global {
+            break;
+         }
+      }
+      result.push_str(&value[last..]);
+      result
+   }
+}
+
+fn format_item(item: &FormatItem, captures: &Captures) -> String {
+   let group = |index: &usize| captures.get(*index).map(|m| m.as_str()).unwrap_or("");
+   match item {
+      FormatItem::Text(text) => text.clone(),
+      FormatItem::Group(index) => group(index).to_string(),
+      FormatItem::Case(index, case) => {
+         let value = group(index);
+         match case.as_str() {
+            "upcase" => value.to_uppercase(),
+            "downcase" => value.to_lowercase(),
+            "capitalize" => capitalize(value),
+            "pascalcase" => words(value).map(capitalize).collect(),
+            "camelcase" => words(value)
+               .enumerate()
+               .map(|(i, word)| {
+                  if i == 0 {
+                     word.to_lowercase()
+                  } else {
+                     capitalize(word)
+                  }
+               })
+               .collect(),
+            _ => value.to_string(),
+         }
+      }
+      FormatItem::Conditional(index, if_text, else_text) => {
+         if group(index).is_empty() {
+            else_text.clone()
+         } else {
+            if_text.clone()
+         }
+      }
+   }
+}
+
+fn words(value: &str) -> impl Iterator<Item = &str> {
+   value
+      .split(|c: char| !c.is_alphanumeric())
+      .filter(|word| !word.is_empty())
+}
+
+fn capitalize(value: &str) -> String {
+   let mut chars = value.chars();
+   match chars.next() {
+      Some(first) => first.to_uppercase().chain(chars).collect(),
+      None => String::new(),
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn ranges(snippet: &ExpandedSnippet) -> Vec<(u32, Vec<(usize, usize)>)> {
+      snippet
+         .tabstops
+         .iter()
+         .map(|tabstop| {
+            (
+               tabstop.index,
+               tabstop
+                  .ranges
+                  .iter()
+                  .map(|range| (range.start, range.end))
+                  .collect(),
+            )
+         })
+         .collect()
+   }
+
+   #[test]
+   fn test_tabstops_placeholders_and_choices() {
+      let snippet = expand(
+         "fn ${1:name}(${2:arg: ${3:i32}}) -> ${4|u8,u16|} {\n\t$0\n} // $1 \\$5 ${6",
+         &SnippetContext::default(),
+      );
+      assert_eq!(
+         snippet.text,
+         "fn name(arg: i32) -> u8 {\n\t\n} // name $5 ${6"
+      );
+      assert_eq!(
+         ranges(&snippet),
+         vec![
+            (1, vec![(3, 7), (33, 37)]),
+            (2, vec![(8, 16)]),
+            (3, vec![(13, 16)]),
+            (4, vec![(21, 23)]),
+            (0, vec![(27, 27)]),
+         ]
+      );
+      assert_eq!(
+         snippet.tabstops[3].choices,
+         Some(vec!["u8".to_string(), "u16".to_string()])
+      );
+
+      // Without `$0` the cursor ends up after the snippet; UTF-16 offsets count `é` once
+      let snippet = expand("é${1}", &SnippetContext::default());
+      assert_eq!(ranges(&snippet), vec![(1, vec![(1, 1)]), (0, vec![(1, 1)])]);
+
+      // Placeholders that mirror themselves, directly or through another, stop expanding
+      let snippet = expand("${1:a$1} ${2:$3} ${3:b$2}", &SnippetContext::default());
+      assert_eq!(snippet.text, "a b b");
+      assert_eq!(
+         expand("$4294967295 $UNKNOWN", &SnippetContext::default()).text,
+         " UNKNOWN"
+      );
+   }
+
+   #[test]
+   fn test_variables_and_transforms() {
+      let context = SnippetContext {
+         file_path: Some(PathBuf::from("/project/src/user_profile.rs")),
+         workspace_path: Some(PathBuf::from("/project")),
+         line: Some(4),
+         ..Default::default()
+      };
+      let snippet = expand(
+         "$TM_FILENAME ${RELATIVE_FILEPATH} ${TM_FILENAME_BASE/(.*)/${1:/pascalcase}/} \
+          ${TM_SELECTED_TEXT:none} $TM_LINE_NUMBER ${TM_FILENAME/(rs)|(ts)/${1:+rust}${2:?ts:}/}",
+         &context,
+      );
+      assert_eq!(
+         snippet.text,
+         "user_profile.rs src/user_profile.rs UserProfile none 5 user_profile.rust"
+      );
+
+      // Unknown variables become placeholders after the snippet's own tabstops
+      let snippet = expand("${1:a} $UNKNOWN", &context);
+      assert_eq!(snippet.text, "a UNKNOWN");
+      assert_eq!(
+         ranges(&snippet),
+         vec![(1, vec![(0, 1)]), (2, vec![(2, 9)]), (0, vec![(9, 9)])]
+      );
+   }
+}
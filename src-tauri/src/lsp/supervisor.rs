@@ -0,0 +1,94 @@
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Restarts allowed in a row before a crashing server is given up on.
+pub const MAX_RESTARTS: u32 = 5;
+const INITIAL_DELAY: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(30);
+/// A server that stayed up this long is considered healthy again.
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LspServerStatus {
+   Running,
+   Crashed,
+   Restarting,
+   Failed,
+}
+
+/// Payload of `lsp://status`.
+#[derive(Debug, Clone, Serialize)]
+pub struct LspStatusEvent {
+   pub server_name: String,
+   pub workspace_path: String,
+   pub status: LspServerStatus,
+   /// Restarts attempted since the server last ran stably.
+   pub restart_attempt: u32,
+   pub message: Option<String>,
+}
+
+/// Exponential backoff between restarts of a crashing server.
+#[derive(Debug)]
+pub struct RestartPolicy {
+   attempts: u32,
+   started_at: Instant,
+}
+
+impl Default for RestartPolicy {
+   fn default() -> Self {
+      Self {
+         attempts: 0,
+         started_at: Instant::now(),
+      }
+   }
+}
+
+impl RestartPolicy {
+   /// Record that the server came up.
+   pub fn started(&mut self) {
+      self.started_at = Instant::now();
+   }
+
+   pub fn attempts(&self) -> u32 {
+      self.attempts
+   }
+
+   /// How long to wait before the next restart, or `None` once the server has crashed too often
+   /// without recovering.
+   pub fn next_delay(&mut self) -> Option<Duration> {
+      if self.started_at.elapsed() >= STABLE_AFTER {
+         self.attempts = 0;
+      }
+      if self.attempts >= MAX_RESTARTS {
+         return None;
+      }
+
+      let delay = INITIAL_DELAY
+         .saturating_mul(2u32.saturating_pow(self.attempts))
+         .min(MAX_DELAY);
+      self.attempts += 1;
+      Some(delay)
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_restart_backoff() {
+      let mut policy = RestartPolicy::default();
+      let delays: Vec<_> = std::iter::from_fn(|| policy.next_delay()).collect();
+      assert_eq!(delays.len(), MAX_RESTARTS as usize);
+      assert_eq!(delays[0], INITIAL_DELAY);
+      assert_eq!(delays[1], INITIAL_DELAY * 2);
+      assert!(delays.windows(2).all(|pair| pair[0] <= pair[1]));
+      assert!(delays.iter().all(|delay| *delay <= MAX_DELAY));
+
+      // A server that ran stably gets a fresh set of attempts
+      policy.started_at = Instant::now() - STABLE_AFTER;
+      assert_eq!(policy.next_delay(), Some(INITIAL_DELAY));
+      assert_eq!(policy.attempts(), 1);
+   }
+}
@@ -0,0 +1,515 @@
+use super::location::LspLocation;
+use crate::commands::tokens::{get_language, language_for_extension};
+use anyhow::{Context, Result};
+use lsp_types::{
+   DocumentSymbol, DocumentSymbolResponse, OneOf, Position, Range, SymbolInformation, SymbolKind,
+   WorkspaceSymbolResponse,
+};
+use serde::Serialize;
+use tree_sitter::{Node, Parser, Point, Tree};
+
+/// One entry of a document outline.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct OutlineSymbol {
+   pub name: String,
+   /// Extra text shown next to the name, e.g. a signature.
+   pub detail: Option<String>,
+   pub kind: SymbolKind,
+   /// The whole declaration, e.g. a function including its body.
+   pub range: Range,
+   /// The part to reveal when the symbol is picked, usually its name.
+   pub selection_range: Range,
+   pub children: Vec<OutlineSymbol>,
+}
+
+impl From<DocumentSymbol> for OutlineSymbol {
+   fn from(symbol: DocumentSymbol) -> Self {
+      Self {
+         name: symbol.name,
+         detail: symbol.detail,
+         kind: symbol.kind,
+         range: symbol.range,
+         selection_range: symbol.selection_range,
+         children: symbol
+            .children
+            .unwrap_or_default()
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+      }
+   }
+}
+
+/// A match for a workspace symbol search.
+#[derive(Debug, Clone, Serialize)]
+pub struct LspWorkspaceSymbol {
+   pub name: String,
+   pub kind: SymbolKind,
+   pub container_name: Option<String>,
+   pub location: LspLocation,
+}
+
+impl From<SymbolInformation> for LspWorkspaceSymbol {
+   fn from(symbol: SymbolInformation) -> Self {
+      Self {
+         name: symbol.name,
+         kind: symbol.kind,
+         container_name: symbol.container_name,
+         location: LspLocation::new(symbol.location.uri, symbol.location.range, None),
+      }
+   }
+}
+
+/// Outline from a `textDocument/documentSymbol` response. Flat responses are nested by range
+/// so both shapes produce the same tree.
+pub fn from_document_symbol_response(
+   response: Option<DocumentSymbolResponse>,
+) -> Vec<OutlineSymbol> {
+   match response {
+      None => Vec::new(),
+      Some(DocumentSymbolResponse::Nested(symbols)) => {
+         symbols.into_iter().map(Into::into).collect()
+      }
+      Some(DocumentSymbolResponse::Flat(symbols)) => nest_by_range(
+         symbols
+            .into_iter()
+            .map(|symbol| OutlineSymbol {
+               name: symbol.name,
+               detail: None,
+               kind: symbol.kind,
+               range: symbol.location.range,
+               selection_range: symbol.location.range,
+               children: Vec::new(),
+            })
+            .collect(),
+      ),
+   }
+}
+
+pub fn from_workspace_symbol_response(
+   response: Option<WorkspaceSymbolResponse>,
+) -> Vec<LspWorkspaceSymbol> {
+   match response {
+      None => Vec::new(),
+      Some(WorkspaceSymbolResponse::Flat(symbols)) => symbols.into_iter().map(Into::into).collect(),
+      Some(WorkspaceSymbolResponse::Nested(symbols)) => symbols
+         .into_iter()
+         .map(|symbol| {
+            // Servers may leave the range to `workspaceSymbol/resolve`; the file start will do
+            let (uri, range) = match symbol.location {
+               OneOf::Left(location) => (location.uri, location.range),
+               OneOf::Right(location) => (location.uri, Range::default()),
+            };
+            LspWorkspaceSymbol {
+               name: symbol.name,
+               kind: symbol.kind,
+               container_name: symbol.container_name,
+               location: LspLocation::new(uri, range, None),
+            }
+         })
+         .collect(),
+   }
+}
+
+/// Nest symbols whose range lies inside another's.
+fn nest_by_range(mut symbols: Vec<OutlineSymbol>) -> Vec<OutlineSymbol> {
+   // Outer symbols sort before the symbols they contain
+   symbols.sort_by(|a, b| (a.range.start, b.range.end).cmp(&(b.range.start, a.range.end)));
+
+   let mut roots: Vec<OutlineSymbol> = Vec::new();
+   let mut stack: Vec<OutlineSymbol> = Vec::new();
+   for symbol in symbols {
+      while let Some(parent) = stack.last() {
+         if parent.range.start <= symbol.range.start && symbol.range.end <= parent.range.end {
+            break;
+         }
+         let done = stack.pop().unwrap();
+         match stack.last_mut() {
+            Some(grandparent) => grandparent.children.push(done),
+            None => roots.push(done),
+         }
+      }
+      stack.push(symbol);
+   }
+   while let Some(done) = stack.pop() {
+      match stack.last_mut() {
+         Some(parent) => parent.children.push(done),
+         None => roots.push(done),
+      }
+   }
+   roots
+}
+
+/// Outline built from the syntax tree, for files no language server covers.
+pub fn tree_sitter_outline(content: &str, file_extension: &str) -> Result<Vec<OutlineSymbol>> {
+   let (language, tree) = parse(content, file_extension)?;
+   let outline = Outline {
+      language,
+      content,
+      line_starts: line_starts(content),
+   };
+   let mut symbols = Vec::new();
+   outline.collect(tree.root_node(), None, &mut symbols);
+   Ok(symbols)
+}
+
+/// Parse a document with the grammar for its extension, returning the language name too.
+pub(super) fn parse(content: &str, file_extension: &str) -> Result<(&'static str, Tree)> {
+   let language = language_for_extension(file_extension)
+      .with_context(|| format!("No grammar for .{} files", file_extension))?;
+   let mut parser = Parser::new();
+   parser.set_language(&get_language(language)?)?;
+   let tree = parser
+      .parse(content, None)
+      .context("Failed to parse document")?;
+   Ok((language, tree))
+}
+
+struct Outline<'a> {
+   language: &'a str,
+   content: &'a str,
+   line_starts: Vec<usize>,
+}
+
+impl Outline<'_> {
+   fn collect(&self, node: Node, parent: Option<SymbolKind>, symbols: &mut Vec<OutlineSymbol>) {
+      let mut cursor = node.walk();
+      for child in node.named_children(&mut cursor) {
+         let Some((mut kind, name, name_node)) = self.entry(child) else {
+            self.collect(child, parent, symbols);
+            continue;
+         };
+         if kind == SymbolKind::FUNCTION
+            && parent.is_some_and(|parent| {
+               matches!(
+                  parent,
+                  SymbolKind::CLASS
+                     | SymbolKind::STRUCT
+                     | SymbolKind::INTERFACE
+                     | SymbolKind::OBJECT
+               )
+            })
+         {
+            kind = SymbolKind::METHOD;
+         }
+
+         let mut symbol = OutlineSymbol {
+            name,
+            detail: None,
+            kind,
+            range: self.range(child),
+            selection_range: self.range(name_node),
+            children: Vec::new(),
+         };
+         self.collect(child, Some(kind), &mut symbol.children);
+         symbols.push(symbol);
+      }
+   }
+
+   /// Kind, name and name node of a node that belongs in the outline.
+   fn entry<'t>(&self, node: Node<'t>) -> Option<(SymbolKind, String, Node<'t>)> {
+      let named = |kind: SymbolKind| {
+         let name = node.child_by_field_name("name")?;
+         Some((kind, self.text(name), name))
+      };
+      let with_body = |kind: SymbolKind| node.child_by_field_name("body").and_then(|_| named(kind));
+
+      match (self.language, node.kind()) {
+         ("rust", "function_item" | "function_signature_item" | "macro_definition") => {
+            named(SymbolKind::FUNCTION)
+         }
+         ("rust", "struct_item" | "union_item") => named(SymbolKind::STRUCT),
+         ("rust", "enum_item") => named(SymbolKind::ENUM),
+         ("rust", "enum_variant") => named(SymbolKind::ENUM_MEMBER),
+         ("rust", "trait_item") => named(SymbolKind::INTERFACE),
+         ("rust", "mod_item") => named(SymbolKind::MODULE),
+         ("rust", "const_item" | "static_item") => named(SymbolKind::CONSTANT),
+         ("rust", "type_item") => named(SymbolKind::TYPE_PARAMETER),
+         ("rust", "field_declaration") => named(SymbolKind::FIELD),
+         ("rust", "impl_item") => {
+            let type_node = node.child_by_field_name("type")?;
+            let name = match node.child_by_field_name("trait") {
+               Some(trait_node) => format!(
+                  "impl {} for {}",
+                  self.text(trait_node),
+                  self.text(type_node)
+               ),
+               None => format!("impl {}", self.text(type_node)),
+            };
+            Some((SymbolKind::OBJECT, name, type_node))
+         }
+
+         (
+            "javascript" | "typescript" | "tsx",
+            "function_declaration" | "generator_function_declaration",
+         ) => named(SymbolKind::FUNCTION),
+         (
+            "javascript" | "typescript" | "tsx",
+            "class_declaration" | "abstract_class_declaration",
+         ) => named(SymbolKind::CLASS),
+         (
+            "javascript" | "typescript" | "tsx",
+            "method_definition" | "method_signature" | "abstract_method_signature",
+         ) => named(SymbolKind::METHOD),
+         ("typescript" | "tsx", "interface_declaration") => named(SymbolKind::INTERFACE),
+         ("typescript" | "tsx", "enum_declaration") => named(SymbolKind::ENUM),
+         ("typescript" | "tsx", "type_alias_declaration") => named(SymbolKind::TYPE_PARAMETER),
+         ("typescript" | "tsx", "internal_module" | "module") => named(SymbolKind::NAMESPACE),
+         ("javascript" | "typescript" | "tsx", "variable_declarator") => {
+            let value = node.child_by_field_name("value")?;
+            let is_function = matches!(
+               value.kind(),
+               "arrow_function" | "function_expression" | "function" | "generator_function"
+            );
+            if is_function {
+               named(SymbolKind::FUNCTION)
+            } else {
+               None
+            }
+         }
+
+         ("python", "function_definition") => named(SymbolKind::FUNCTION),
+         ("python", "class_definition") => named(SymbolKind::CLASS),
+
+         ("go", "function_declaration") => named(SymbolKind::FUNCTION),
+         ("go", "method_declaration") => named(SymbolKind::METHOD),
+         ("go", "type_spec") => {
+            let kind = match node.child_by_field_name("type")?.kind() {
+               "struct_type" => SymbolKind::STRUCT,
+               "interface_type" => SymbolKind::INTERFACE,
+               _ => SymbolKind::TYPE_PARAMETER,
+            };
+            named(kind)
+         }
+
+         ("ruby", "method" | "singleton_method") => named(SymbolKind::METHOD),
+         ("ruby", "class") => named(SymbolKind::CLASS),
+         ("ruby", "module") => named(SymbolKind::MODULE),
+
+         ("java", "class_declaration") => named(SymbolKind::CLASS),
+         ("java", "record_declaration") => named(SymbolKind::STRUCT),
+         ("java", "interface_declaration" | "annotation_type_declaration") => {
+            named(SymbolKind::INTERFACE)
+         }
+         ("java", "enum_declaration") => named(SymbolKind::ENUM),
+         ("java", "enum_constant") => named(SymbolKind::ENUM_MEMBER),
+         ("java", "method_declaration") => named(SymbolKind::METHOD),
+         ("java", "constructor_declaration") => named(SymbolKind::CONSTRUCTOR),
+
+         ("c" | "cpp", "function_definition") => {
+            let name = innermost_declarator(node.child_by_field_name("declarator")?);
+            Some((SymbolKind::FUNCTION, self.text(name), name))
+         }
+         ("c" | "cpp", "struct_specifier" | "union_specifier") => with_body(SymbolKind::STRUCT),
+         ("c" | "cpp", "enum_specifier") => with_body(SymbolKind::ENUM),
+         ("cpp", "class_specifier") => with_body(SymbolKind::CLASS),
+         ("cpp", "namespace_definition") => named(SymbolKind::NAMESPACE),
+
+         ("php", "class_declaration") => named(SymbolKind::CLASS),
+         ("php", "interface_declaration" | "trait_declaration") => named(SymbolKind::INTERFACE),
+         ("php", "enum_declaration") => named(SymbolKind::ENUM),
+         ("php", "function_definition") => named(SymbolKind::FUNCTION),
+         ("php", "method_declaration") => named(SymbolKind::METHOD),
+
+         ("bash", "function_definition") => named(SymbolKind::FUNCTION),
+
+         ("css", "rule_set") => {
+            let selectors = first_child_of_kind(node, "selectors")?;
+            Some((SymbolKind::CLASS, self.text(selectors), selectors))
+         }
+
+         ("html", "element" | "script_element" | "style_element") => {
+            let start_tag = first_child_of_kind(node, "start_tag")?;
+            let tag_name = first_child_of_kind(start_tag, "tag_name")?;
+            Some((SymbolKind::FIELD, self.text(tag_name), tag_name))
+         }
+
+         ("markdown", "section") => {
+            let heading = node
+               .named_child(0)
+               .filter(|heading| matches!(heading.kind(), "atx_heading" | "setext_heading"))?;
+            let name = self
+               .text(heading)
+               .trim_start_matches('#')
+               .trim()
+               .to_string();
+            Some((SymbolKind::STRING, name, heading))
+         }
+
+         ("json", "pair") => {
+            let key = node.child_by_field_name("key")?;
+            let kind = node
+               .child_by_field_name("value")
+               .map_or(SymbolKind::PROPERTY, |value| value_kind(value.kind()));
+            Some((kind, self.text(key).trim_matches('"').to_string(), key))
+         }
+         ("yaml", "block_mapping_pair" | "flow_pair") => {
+            let key = node.child_by_field_name("key")?;
+            Some((SymbolKind::PROPERTY, self.text(key), key))
+         }
+         ("toml", "table" | "table_array_element") => {
+            let key = node.named_child(0)?;
+            Some((SymbolKind::MODULE, self.text(key), key))
+         }
+         ("toml", "pair") => {
+            let key = node.named_child(0)?;
+            Some((SymbolKind::PROPERTY, self.text(key), key))
+         }
+
+         _ => None,
+      }
+   }
+
+   /// First line of a node's text; names never span lines in the outline.
+   fn text(&self, node: Node) -> String {
+      self.content[node.byte_range()]
+         .lines()
+         .next()
+         .unwrap_or_default()
+         .trim()
+         .to_string()
+   }
+
+   fn range(&self, node: Node) -> Range {
+      Range::new(
+         self.position(node.start_position()),
+         self.position(node.end_position()),
+      )
+   }
+
+   fn position(&self, point: Point) -> Position {
+      lsp_position(self.content, &self.line_starts, point)
+   }
+}
+
+/// Tree-sitter columns count bytes, LSP characters count UTF-16 code units.
+pub(super) fn lsp_position(content: &str, line_starts: &[usize], point: Point) -> Position {
+   let character = line_starts
+      .get(point.row)
+      .and_then(|start| content.get(*start..start + point.column))
+      .map_or(point.column, |prefix| prefix.encode_utf16().count());
+   Position::new(point.row as u32, character as u32)
+}
+
+pub(super) fn line_starts(content: &str) -> Vec<usize> {
+   std::iter::once(0)
+      .chain(content.match_indices('\n').map(|(offset, _)| offset + 1))
+      .collect()
+}
+
+fn first_child_of_kind<'t>(node: Node<'t>, kind: &str) -> Option<Node<'t>> {
+   let mut cursor = node.walk();
+   node
+      .named_children(&mut cursor)
+      .find(|child| child.kind() == kind)
+}
+
+/// The identifier inside nested C declarators such as `*(*name)(int)`.
+fn innermost_declarator(mut node: Node) -> Node {
+   while let Some(inner) = node.child_by_field_name("declarator") {
+      node = inner;
+   }
+   node
+}
+
+fn value_kind(node_kind: &str) -> SymbolKind {
+   match node_kind {
+      "object" => SymbolKind::MODULE,
+      "array" => SymbolKind::ARRAY,
+      "string" => SymbolKind::STRING,
+      "number" => SymbolKind::NUMBER,
+      "true" | "false" => SymbolKind::BOOLEAN,
+      "null" => SymbolKind::NULL,
+      _ => SymbolKind::PROPERTY,
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use lsp_types::{Location, Url};
+
+   fn names(symbols: &[OutlineSymbol]) -> Vec<(&str, SymbolKind)> {
+      symbols
+         .iter()
+         .map(|symbol| (symbol.name.as_str(), symbol.kind))
+         .collect()
+   }
+
+   #[test]
+   fn test_rust_outline_nests_items() {
+      let content =
+         "struct Point {\n   x: i32,\n}\n\nimpl Point {\n   fn norm(&self) -> i32 {\n      fn \
+          helper() {}\n      0\n   }\n}\n";
+      let outline = tree_sitter_outline(content, "rs").unwrap();
+
+      assert_eq!(
+         names(&outline),
+         vec![
+            ("Point", SymbolKind::STRUCT),
+            ("impl Point", SymbolKind::OBJECT)
+         ]
+      );
+      assert_eq!(names(&outline[0].children), vec![("x", SymbolKind::FIELD)]);
+      let norm = &outline[1].children[0];
+      assert_eq!(
+         (norm.name.as_str(), norm.kind),
+         ("norm", SymbolKind::METHOD)
+      );
+      assert_eq!(
+         names(&norm.children),
+         vec![("helper", SymbolKind::FUNCTION)]
+      );
+      assert_eq!(norm.selection_range.start, Position::new(5, 6));
+      assert_eq!(norm.range.end, Position::new(8, 4));
+   }
+
+   #[test]
+   fn test_outline_covers_markup_languages() {
+      let outline = tree_sitter_outline("# Intro\ntext\n## Usage\n", "md").unwrap();
+      assert_eq!(names(&outline), vec![("Intro", SymbolKind::STRING)]);
+      assert_eq!(
+         names(&outline[0].children),
+         vec![("Usage", SymbolKind::STRING)]
+      );
+
+      let outline = tree_sitter_outline(r#"{"name": "é", "deps": {"a": 1}}"#, "json").unwrap();
+      assert_eq!(
+         names(&outline),
+         vec![("name", SymbolKind::STRING), ("deps", SymbolKind::MODULE)]
+      );
+      assert_eq!(outline[1].selection_range.start.character, 14);
+      assert_eq!(names(&outline[1].children), vec![("a", SymbolKind::NUMBER)]);
+   }
+
+   #[test]
+   fn test_flat_symbols_are_nested_by_range() {
+      let symbol = |name: &str, start: u32, end: u32| {
+         #[allow(deprecated)]
+         SymbolInformation {
+            name: name.to_string(),
+            kind: SymbolKind::FUNCTION,
+            tags: None,
+            deprecated: None,
+            location: Location::new(
+               Url::parse("file:///main.py").unwrap(),
+               Range::new(Position::new(start, 0), Position::new(end, 0)),
+            ),
+            container_name: None,
+         }
+      };
+      let response = DocumentSymbolResponse::Flat(vec![
+         symbol("method", 2, 3),
+         symbol("Class", 1, 5),
+         symbol("other", 6, 7),
+         symbol("nested", 2, 2),
+      ]);
+
+      let outline = from_document_symbol_response(Some(response));
+      assert_eq!(outline.len(), 2);
+      assert_eq!(outline[0].name, "Class");
+      assert_eq!(outline[0].children[0].name, "method");
+      assert_eq!(outline[0].children[0].children[0].name, "nested");
+      assert_eq!(outline[1].name, "other");
+   }
+}
@@ -0,0 +1,414 @@
+use serde::{Deserialize, Serialize, de::IgnoredAny};
+use serde_json::{Value, json};
+use std::{
+   borrow::Cow,
+   collections::{HashMap, VecDeque},
+   path::Path,
+   sync::{Arc, Mutex},
+   time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tauri::{AppHandle, Emitter};
+
+/// Messages kept per server instance before the oldest are dropped.
+pub const MAX_MESSAGES: usize = 2000;
+/// Payload bytes kept per server instance, so a burst of full-text syncs cannot pile up.
+pub const MAX_BYTES: usize = 32 * 1024 * 1024;
+/// Requests still unanswered after this long no longer get a latency.
+const PENDING_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrafficDirection {
+   /// From the editor to the server.
+   Send,
+   /// From the server to the editor.
+   Receive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrafficKind {
+   Request,
+   Response,
+   Notification,
+}
+
+/// One JSON-RPC message between the editor and a server.
+#[derive(Debug, Clone, Serialize)]
+pub struct TrafficEntry {
+   /// Increases across all servers, so the frontend can tell which entries it has seen.
+   pub seq: u64,
+   /// The workspace of the server instance, as several can run the same server.
+   pub workspace_path: String,
+   pub server_name: String,
+   pub direction: TrafficDirection,
+   pub kind: TrafficKind,
+   /// Responses carry the method of the request they answer, when it was seen.
+   pub method: Option<String>,
+   pub id: Option<Value>,
+   /// Milliseconds since the Unix epoch.
+   pub timestamp: u64,
+   /// For responses, milliseconds since the request went the other way.
+   pub latency_ms: Option<u64>,
+   /// Bytes of the JSON body.
+   pub size: usize,
+   pub is_error: bool,
+   /// The message itself, left out of the live stream and of listings unless asked for.
+   #[serde(skip_serializing_if = "Option::is_none")]
+   pub message: Option<Value>,
+}
+
+impl TrafficEntry {
+   /// A line in the JSON trace format of `vscode-jsonrpc`, which LSP trace viewers read.
+   fn trace_line(&self) -> Value {
+      let direction = match self.direction {
+         TrafficDirection::Send => "send",
+         TrafficDirection::Receive => "receive",
+      };
+      let kind = match self.kind {
+         TrafficKind::Request => "request",
+         TrafficKind::Response => "response",
+         TrafficKind::Notification => "notification",
+      };
+      json!({
+         "isLSPMessage": true,
+         "type": format!("{}-{}", direction, kind),
+         "message": self.message,
+         "timestamp": self.timestamp,
+      })
+   }
+}
+
+/// The fields of a JSON-RPC message that describe it. Reading only these skips building the
+/// whole message, which is parsed again only when asked for.
+#[derive(Deserialize)]
+struct Envelope<'a> {
+   #[serde(default)]
+   id: Option<Value>,
+   #[serde(default, borrow)]
+   method: Option<Cow<'a, str>>,
+   #[serde(default)]
+   error: Option<IgnoredAny>,
+}
+
+/// An entry without its message, and the JSON body the message is parsed from when needed.
+struct Recorded {
+   entry: TrafficEntry,
+   body: String,
+}
+
+impl Recorded {
+   fn entry(&self, include_message: bool) -> TrafficEntry {
+      let mut entry = self.entry.clone();
+      if include_message {
+         entry.message = serde_json::from_str(&self.body).ok();
+      }
+      entry
+   }
+}
+
+#[derive(Default)]
+struct ServerTraffic {
+   entries: VecDeque<Recorded>,
+   bytes: usize,
+   /// Requests awaiting a response, by the direction they went and their id.
+   pending: HashMap<(TrafficDirection, String), (String, Instant)>,
+}
+
+impl ServerTraffic {
+   fn push(&mut self, recorded: Recorded) {
+      self.bytes += recorded.entry.size;
+      self.entries.push_back(recorded);
+      while self.entries.len() > MAX_MESSAGES || (self.bytes > MAX_BYTES && self.entries.len() > 1)
+      {
+         if let Some(dropped) = self.entries.pop_front() {
+            self.bytes -= dropped.entry.size;
+         }
+      }
+   }
+}
+
+#[derive(Default)]
+struct TrafficState {
+   /// By workspace path and server name.
+   servers: HashMap<(String, String), ServerTraffic>,
+   next_seq: u64,
+   streaming: bool,
+}
+
+/// The most recent messages exchanged with each server instance. Entries are also sent as
+/// `lsp://traffic` events while streaming is on.
+#[derive(Clone)]
+pub struct LspTraffic {
+   state: Arc<Mutex<TrafficState>>,
+   app_handle: Option<AppHandle>,
+}
+
+impl LspTraffic {
+   pub fn new(app_handle: Option<AppHandle>) -> Self {
+      Self {
+         state: Arc::default(),
+         app_handle,
+      }
+   }
+
+   /// A recorder for the server `server_name` started in `workspace_path`.
+   pub fn recorder(&self, workspace_path: &Path, server_name: &str) -> TrafficRecorder {
+      TrafficRecorder {
+         traffic: self.clone(),
+         workspace_path: workspace_path.to_string_lossy().to_string(),
+         server_name: server_name.to_string(),
+      }
+   }
+
+   /// Record a message body as it crossed the wire. Bodies that are not JSON-RPC are ignored.
+   fn record(
+      &self,
+      workspace_path: &str,
+      server_name: &str,
+      direction: TrafficDirection,
+      body: &str,
+   ) {
+      let Ok(envelope) = serde_json::from_str::<Envelope>(body) else {
+         return;
+      };
+      let method = envelope.method.as_deref();
+      let id = envelope.id.filter(|id| !id.is_null());
+      let kind = match (&id, method) {
+         (Some(_), Some(_)) => TrafficKind::Request,
+         (Some(_), None) => TrafficKind::Response,
+         _ => TrafficKind::Notification,
+      };
+
+      let event = {
+         let mut state = self.state.lock().unwrap();
+         state.next_seq += 1;
+         let seq = state.next_seq;
+         let streaming = state.streaming;
+         let traffic = state
+            .servers
+            .entry((workspace_path.to_string(), server_name.to_string()))
+            .or_default();
+
+         let now = Instant::now();
+         let (method, latency_ms) = match (kind, &id) {
+            (TrafficKind::Request, Some(id)) => {
+               if traffic.pending.len() >= MAX_MESSAGES {
+                  traffic
+                     .pending
+                     .retain(|_, (_, sent)| now.duration_since(*sent) < PENDING_TIMEOUT);
+               }
+               let method = method.unwrap_or_default().to_string();
+               traffic
+                  .pending
+                  .insert((direction, id.to_string()), (method.clone(), now));
+               (Some(method), None)
+            }
+            (TrafficKind::Response, Some(id)) => {
+               // Answers go the opposite way of the request
+               let request_direction = match direction {
+                  TrafficDirection::Send => TrafficDirection::Receive,
+                  TrafficDirection::Receive => TrafficDirection::Send,
+               };
+               match traffic.pending.remove(&(request_direction, id.to_string())) {
+                  Some((method, sent)) => (
+                     Some(method),
+                     Some(now.duration_since(sent).as_millis() as u64),
+                  ),
+                  None => (None, None),
+               }
+            }
+            _ => (method.map(str::to_string), None),
+         };
+
+         let entry = TrafficEntry {
+            seq,
+            workspace_path: workspace_path.to_string(),
+            server_name: server_name.to_string(),
+            direction,
+            kind,
+            method,
+            id,
+            timestamp: SystemTime::now()
+               .duration_since(UNIX_EPOCH)
+               .map(|elapsed| elapsed.as_millis() as u64)
+               .unwrap_or_default(),
+            latency_ms,
+            size: body.len(),
+            is_error: envelope.error.is_some(),
+            message: None,
+         };
+         let event = streaming.then(|| entry.clone());
+         traffic.push(Recorded {
+            entry,
+            body: body.to_string(),
+         });
+         event
+      };
+
+      if let Some(event) = event
+         && let Some(app_handle) = &self.app_handle
+      {
+         let _ = app_handle.emit("lsp://traffic", &event);
+      }
+   }
+
+   /// Recorded messages, oldest first, for one server or all of them.
+   pub fn entries(&self, server_name: Option<&str>, include_messages: bool) -> Vec<TrafficEntry> {
+      let state = self.state.lock().unwrap();
+      let mut entries: Vec<TrafficEntry> = state
+         .servers
+         .iter()
+         .filter(|((_, name), _)| server_name.is_none_or(|server_name| server_name == name))
+         .flat_map(|(_, traffic)| traffic.entries.iter())
+         .map(|recorded| recorded.entry(include_messages))
+         .collect();
+      entries.sort_by_key(|entry| entry.seq);
+      entries
+   }
+
+   pub fn clear(&self, server_name: Option<&str>) {
+      let mut state = self.state.lock().unwrap();
+      match server_name {
+         Some(server_name) => state.servers.retain(|(_, name), _| name != server_name),
+         None => state.servers.clear(),
+      }
+   }
+
+   /// Turn the `lsp://traffic` events on or off. Messages are recorded either way.
+   pub fn set_streaming(&self, enabled: bool) {
+      self.state.lock().unwrap().streaming = enabled;
+   }
+
+   /// The recorded messages as JSON lines in the `vscode-jsonrpc` trace format.
+   pub fn export_jsonl(&self, server_name: Option<&str>) -> String {
+      self
+         .entries(server_name, true)
+         .iter()
+         .map(|entry| format!("{}\n", entry.trace_line()))
+         .collect()
+   }
+}
+
+/// Records the messages of one server instance into the shared [`LspTraffic`].
+#[derive(Clone)]
+pub struct TrafficRecorder {
+   traffic: LspTraffic,
+   workspace_path: String,
+   server_name: String,
+}
+
+impl TrafficRecorder {
+   /// Record a message body as it crossed the wire.
+   pub fn record(&self, direction: TrafficDirection, body: &str) {
+      self
+         .traffic
+         .record(&self.workspace_path, &self.server_name, direction, body);
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn record(traffic: &LspTraffic, direction: TrafficDirection, message: Value) {
+      traffic
+         .recorder(Path::new("/project"), "rust-analyzer")
+         .record(direction, &message.to_string());
+   }
+
+   #[test]
+   fn test_requests_are_paired_with_responses() {
+      let traffic = LspTraffic::new(None);
+      record(
+         &traffic,
+         TrafficDirection::Send,
+         json!({ "jsonrpc": "2.0", "id": 1, "method": "textDocument/hover", "params": {} }),
+      );
+      record(
+         &traffic,
+         TrafficDirection::Receive,
+         json!({ "jsonrpc": "2.0", "method": "window/logMessage", "params": {} }),
+      );
+      record(
+         &traffic,
+         TrafficDirection::Receive,
+         json!({ "jsonrpc": "2.0", "id": 1, "result": null }),
+      );
+      // Another instance of the server answering the same id is not a response to ours
+      traffic
+         .recorder(Path::new("/other"), "rust-analyzer")
+         .record(
+            TrafficDirection::Receive,
+            r#"{"jsonrpc":"2.0","id":1,"result":null}"#,
+         );
+      // The server's own request ids may clash with ours
+      record(
+         &traffic,
+         TrafficDirection::Receive,
+         json!({ "jsonrpc": "2.0", "id": 1, "method": "workspace/configuration" }),
+      );
+      record(
+         &traffic,
+         TrafficDirection::Send,
+         json!({ "jsonrpc": "2.0", "id": 1, "error": { "code": -32601, "message": "" } }),
+      );
+
+      let entries = traffic.entries(Some("rust-analyzer"), false);
+      assert_eq!(entries.len(), 6);
+      assert_eq!(entries[3].workspace_path, "/other");
+      assert_eq!(entries[3].latency_ms, None);
+      let summary: Vec<_> = entries
+         .iter()
+         .filter(|entry| entry.workspace_path == "/project")
+         .map(|entry| {
+            (
+               entry.kind,
+               entry.method.as_deref(),
+               entry.latency_ms.is_some(),
+            )
+         })
+         .collect();
+      assert_eq!(
+         summary,
+         vec![
+            (TrafficKind::Request, Some("textDocument/hover"), false),
+            (TrafficKind::Notification, Some("window/logMessage"), false),
+            (TrafficKind::Response, Some("textDocument/hover"), true),
+            (TrafficKind::Request, Some("workspace/configuration"), false),
+            (TrafficKind::Response, Some("workspace/configuration"), true),
+         ]
+      );
+      assert!(entries[5].is_error);
+      assert!(entries.iter().all(|entry| entry.message.is_none()));
+      assert!(traffic.entries(Some("gopls"), false).is_empty());
+
+      let lines: Vec<Value> = traffic
+         .export_jsonl(None)
+         .lines()
+         .map(|line| serde_json::from_str(line).unwrap())
+         .collect();
+      assert_eq!(lines.len(), 6);
+      assert_eq!(lines[0]["type"], "send-request");
+      assert_eq!(lines[2]["type"], "receive-response");
+      assert_eq!(lines[0]["message"]["method"], "textDocument/hover");
+   }
+
+   #[test]
+   fn test_buffer_drops_oldest_messages() {
+      let traffic = LspTraffic::new(None);
+      for version in 0..MAX_MESSAGES + 5 {
+         record(
+            &traffic,
+            TrafficDirection::Send,
+            json!({ "method": "textDocument/didChange", "params": { "version": version } }),
+         );
+      }
+      let entries = traffic.entries(None, true);
+      assert_eq!(entries.len(), MAX_MESSAGES);
+      assert_eq!(entries[0].message.as_ref().unwrap()["params"]["version"], 5);
+
+      traffic.clear(Some("rust-analyzer"));
+      assert!(traffic.entries(None, false).is_empty());
+   }
+}
@@ -0,0 +1,548 @@
+use crate::ssh;
+use anyhow::{Context, Result};
+use crossbeam_channel::{Receiver, Sender, TryRecvError, unbounded};
+use lsp_types::Url;
+use serde_json::Value;
+use ssh2::Channel;
+use std::{
+   io::{self, Read, Write},
+   path::{Path, PathBuf},
+   process::{Child, Command, Stdio},
+   sync::{
+      Arc, Mutex,
+      atomic::{AtomicBool, Ordering},
+   },
+   thread,
+   time::Duration,
+};
+
+/// Scheme of the paths the editor gives files on SSH hosts: `remote://<connection id>/<path>`.
+const REMOTE_SCHEME: &str = "remote";
+const REMOTE_PREFIX: &str = "remote://";
+const SERVER_PREFIX: &str = "file:///";
+/// How long the SSH pump waits when there is nothing to send or receive.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Where a language server runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LspTransport {
+   /// A child process of the editor.
+   Local,
+   /// A process on the host of an SSH connection, started in `root` over an exec channel.
+   Ssh {
+      connection_id: String,
+      root: PathBuf,
+   },
+}
+
+impl LspTransport {
+   /// The transport for a server started at `workspace_path`, which is remote when it is an
+   /// editor path like `remote://<connection id>/home/me/project`.
+   pub fn for_workspace(workspace_path: &Path) -> Self {
+      match split_remote(workspace_path) {
+         Some((connection_id, root)) => Self::Ssh {
+            connection_id: connection_id.to_string(),
+            root: PathBuf::from(root),
+         },
+         None => Self::Local,
+      }
+   }
+
+   /// How URIs differ between the editor and a server on another host.
+   pub fn remote_uris(&self) -> Option<RemoteUris> {
+      match self {
+         Self::Local => None,
+         Self::Ssh { connection_id, .. } => Some(RemoteUris::new(connection_id)),
+      }
+   }
+
+   pub fn spawn(&self, command: &Path, args: &[String]) -> Result<ServerProcess> {
+      match self {
+         Self::Local => spawn_local(command, args),
+         Self::Ssh {
+            connection_id,
+            root,
+         } => spawn_ssh(connection_id, root, command, args),
+      }
+   }
+}
+
+/// The pipes to a started server and a handle to stop it.
+pub struct ServerProcess {
+   pub stdin: Box<dyn Write + Send>,
+   pub stdout: Box<dyn Read + Send>,
+   pub stderr: Box<dyn Read + Send>,
+   pub handle: ServerHandle,
+}
+
+pub enum ServerHandle {
+   Local(Child),
+   Ssh(SshProcess),
+}
+
+impl ServerHandle {
+   pub fn kill(&mut self) -> io::Result<()> {
+      match self {
+         Self::Local(child) => child.kill(),
+         Self::Ssh(process) => {
+            process.stop.store(true, Ordering::SeqCst);
+            Ok(())
+         }
+      }
+   }
+
+   /// How the server exited, or `None` while it is still running.
+   pub fn exit_status(&mut self) -> Option<String> {
+      match self {
+         Self::Local(child) => child.try_wait().ok().flatten().map(|s| s.to_string()),
+         Self::Ssh(process) => {
+            let code = (*process.exit_code.lock().unwrap())?;
+            Some(format!("exit status: {}", code))
+         }
+      }
+   }
+}
+
+/// A server on an SSH host, kept running by a pump thread until it exits or is stopped.
+pub struct SshProcess {
+   stop: Arc<AtomicBool>,
+   exit_code: Arc<Mutex<Option<i32>>>,
+}
+
+fn spawn_local(command: &Path, args: &[String]) -> Result<ServerProcess> {
+   let mut child = Command::new(command)
+      .args(args)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped())
+      .spawn()
+      .context("Failed to spawn LSP server")?;
+
+   log::info!("Language server process started with PID: {:?}", child.id());
+
+   Ok(ServerProcess {
+      stdin: Box::new(child.stdin.take().context("Failed to get stdin")?),
+      stdout: Box::new(child.stdout.take().context("Failed to get stdout")?),
+      stderr: Box::new(child.stderr.take().context("Failed to get stderr")?),
+      handle: ServerHandle::Local(child),
+   })
+}
+
+fn spawn_ssh(
+   connection_id: &str,
+   root: &Path,
+   command: &Path,
+   args: &[String],
+) -> Result<ServerProcess> {
+   let command_line = std::iter::once(command.to_string_lossy().into_owned())
+      .chain(args.iter().cloned())
+      .map(|arg| shell_quote(&arg))
+      .collect::<Vec<_>>()
+      .join(" ");
+   let script = format!(
+      "cd {} && exec {}",
+      shell_quote(&root.to_string_lossy()),
+      command_line
+   );
+   // A login shell, so servers installed on the PATH set up in the user's profile are found
+   let channel = ssh::open_exec_channel(connection_id, &format!("sh -lc {}", shell_quote(&script)))
+      .map_err(anyhow::Error::msg)
+      .context("Failed to start remote LSP server")?;
+
+   log::info!(
+      "Language server started on SSH connection '{}': {}",
+      connection_id,
+      script
+   );
+
+   let (stdin_tx, stdin_rx) = unbounded();
+   let (stdout_tx, stdout_rx) = unbounded();
+   let (stderr_tx, stderr_rx) = unbounded();
+   let stop = Arc::new(AtomicBool::new(false));
+   let exit_code = Arc::new(Mutex::new(None));
+
+   let pump = SshPump {
+      channel,
+      stdin: stdin_rx,
+      stdout: stdout_tx,
+      stderr: stderr_tx,
+      stop: Arc::clone(&stop),
+      exit_code: Arc::clone(&exit_code),
+   };
+   thread::spawn(move || pump.run());
+
+   Ok(ServerProcess {
+      stdin: Box::new(ChunkWriter(stdin_tx)),
+      stdout: Box::new(ChunkReader::new(stdout_rx)),
+      stderr: Box::new(ChunkReader::new(stderr_rx)),
+      handle: ServerHandle::Ssh(SshProcess { stop, exit_code }),
+   })
+}
+
+/// Moves data between an exec channel and the client's pipes. libssh2 holds the session lock
+/// for the whole of a blocking read, which would keep requests from being written while the
+/// server is quiet, so a single thread polls both directions on the non-blocking session
+/// instead.
+struct SshPump {
+   channel: Channel,
+   stdin: Receiver<Vec<u8>>,
+   stdout: Sender<Vec<u8>>,
+   stderr: Sender<Vec<u8>>,
+   stop: Arc<AtomicBool>,
+   exit_code: Arc<Mutex<Option<i32>>>,
+}
+
+impl SshPump {
+   fn run(mut self) {
+      if let Err(e) = self.pump() {
+         log::error!("Remote LSP channel failed: {}", e);
+      }
+
+      // The session is shared with other servers on the host, so only the channel is closed
+      let _ = ssh::retry_while_blocked(|| self.channel.close());
+      let _ = ssh::retry_while_blocked(|| self.channel.wait_close());
+      let code = self.channel.exit_status().unwrap_or(-1);
+      *self.exit_code.lock().unwrap() = Some(code);
+   }
+
+   /// Runs until the server closes its output, the client goes away or the server is stopped.
+   fn pump(&mut self) -> io::Result<()> {
+      let mut outgoing = Vec::new();
+      let mut buffer = vec![0u8; 16 * 1024];
+      let mut stderr = self.channel.stderr();
+
+      while !self.stop.load(Ordering::SeqCst) {
+         let mut idle = true;
+
+         loop {
+            match self.stdin.try_recv() {
+               Ok(data) => outgoing.extend(data),
+               Err(TryRecvError::Empty) => break,
+               Err(TryRecvError::Disconnected) => return Ok(()),
+            }
+         }
+         if !outgoing.is_empty() {
+            match self.channel.write(&outgoing) {
+               Ok(written) => {
+                  outgoing.drain(..written);
+                  idle = false;
+               }
+               Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+               Err(e) => return Err(e),
+            }
+         }
+
+         match self.channel.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(read) => {
+               if self.stdout.send(buffer[..read].to_vec()).is_err() {
+                  return Ok(());
+               }
+               idle = false;
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e),
+         }
+
+         match stderr.read(&mut buffer) {
+            Ok(read) if read > 0 => {
+               let _ = self.stderr.send(buffer[..read].to_vec());
+               idle = false;
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e),
+         }
+
+         if idle {
+            thread::sleep(POLL_INTERVAL);
+         }
+      }
+      Ok(())
+   }
+}
+
+/// Reads the chunks the pump receives, in order, until the pump stops.
+struct ChunkReader {
+   chunks: Receiver<Vec<u8>>,
+   chunk: Vec<u8>,
+   offset: usize,
+}
+
+impl ChunkReader {
+   fn new(chunks: Receiver<Vec<u8>>) -> Self {
+      Self {
+         chunks,
+         chunk: Vec::new(),
+         offset: 0,
+      }
+   }
+}
+
+impl Read for ChunkReader {
+   fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+      while self.offset == self.chunk.len() {
+         match self.chunks.recv() {
+            Ok(chunk) => {
+               self.chunk = chunk;
+               self.offset = 0;
+            }
+            Err(_) => return Ok(0),
+         }
+      }
+      let len = buf.len().min(self.chunk.len() - self.offset);
+      buf[..len].copy_from_slice(&self.chunk[self.offset..self.offset + len]);
+      self.offset += len;
+      Ok(len)
+   }
+}
+
+/// Hands written data to the pump.
+struct ChunkWriter(Sender<Vec<u8>>);
+
+impl Write for ChunkWriter {
+   fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+      self
+         .0
+         .send(buf.to_vec())
+         .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+      Ok(buf.len())
+   }
+
+   fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+   }
+}
+
+fn shell_quote(arg: &str) -> String {
+   format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// Rewrites URIs between the editor's `remote://<connection id>/<path>` form and the
+/// `file:///<path>` form a server on the remote host understands.
+#[derive(Debug, Clone)]
+pub struct RemoteUris {
+   editor_prefix: String,
+}
+
+impl RemoteUris {
+   pub fn new(connection_id: &str) -> Self {
+      Self {
+         editor_prefix: format!("{}{}/", REMOTE_PREFIX, connection_id),
+      }
+   }
+
+   pub fn to_server(&self, message: &mut Value) {
+      rewrite_prefix(message, &self.editor_prefix, SERVER_PREFIX);
+   }
+
+   pub fn to_editor(&self, message: &mut Value) {
+      rewrite_prefix(message, SERVER_PREFIX, &self.editor_prefix);
+   }
+
+   /// Rewrite a message already framed with its `Content-Length` header.
+   pub fn framed_to_server(&self, framed: String) -> String {
+      let Some((_, body)) = framed.split_once("\r\n\r\n") else {
+         return framed;
+      };
+      let Ok(mut message) = serde_json::from_str::<Value>(body) else {
+         return framed;
+      };
+      self.to_server(&mut message);
+      let body = message.to_string();
+      format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+   }
+}
+
+/// Fields whose string value is a document or folder URI.
+const URI_FIELDS: &[&str] = &[
+   "uri",
+   "targetUri",
+   "oldUri",
+   "newUri",
+   "rootUri",
+   "scopeUri",
+   "baseUri",
+   "target",
+];
+/// Fields holding a map keyed by URI, like the `changes` of a workspace edit.
+const URI_KEYED_FIELDS: &[&str] = &["changes", "relatedDocuments"];
+
+/// Swap `from` for `to` at the start of every URI in a message. Only URI fields are rewritten,
+/// so document text, edits and markdown that merely mention a path are left alone.
+fn rewrite_prefix(value: &mut Value, from: &str, to: &str) {
+   let rewrite = |s: &str| s.strip_prefix(from).map(|rest| format!("{}{}", to, rest));
+   match value {
+      Value::Array(items) => items
+         .iter_mut()
+         .for_each(|item| rewrite_prefix(item, from, to)),
+      Value::Object(map) => {
+         for (key, value) in map.iter_mut() {
+            match value {
+               Value::String(s) if URI_FIELDS.contains(&key.as_str()) => {
+                  if let Some(rewritten) = rewrite(s) {
+                     *s = rewritten;
+                  }
+               }
+               Value::Object(entries) if URI_KEYED_FIELDS.contains(&key.as_str()) => {
+                  *entries = std::mem::take(entries)
+                     .into_iter()
+                     .map(|(uri, mut value)| {
+                        rewrite_prefix(&mut value, from, to);
+                        (rewrite(&uri).unwrap_or(uri), value)
+                     })
+                     .collect();
+               }
+               _ => rewrite_prefix(value, from, to),
+            }
+         }
+      }
+      _ => {}
+   }
+}
+
+/// The connection id and remote path of an editor path such as `remote://<id>/home/me`.
+fn split_remote(path: &Path) -> Option<(&str, &str)> {
+   let rest = path.to_str()?.strip_prefix(REMOTE_PREFIX)?;
+   match rest.find('/') {
+      Some(slash) => Some((&rest[..slash], &rest[slash..])),
+      None => Some((rest, "/")),
+   }
+}
+
+/// The SSH connection a remote editor path belongs to.
+pub fn connection_id(path: &Path) -> Option<&str> {
+   split_remote(path).map(|(connection_id, _)| connection_id)
+}
+
+pub fn is_remote(path: &Path) -> bool {
+   split_remote(path).is_some()
+}
+
+/// Read a file by its editor path, over the SSH connection for remote paths.
+pub fn read_editor_file(path: impl AsRef<Path>) -> Result<String> {
+   let path = path.as_ref();
+   match split_remote(path) {
+      Some((connection_id, remote_path)) => {
+         ssh::read_file(connection_id, remote_path).map_err(anyhow::Error::msg)
+      }
+      None => std::fs::read_to_string(path).map_err(Into::into),
+   }
+}
+
+/// URI of a file path from the editor: `file://` for local files and the path itself for
+/// remote ones.
+pub fn editor_uri(path: impl AsRef<Path>) -> Result<Url> {
+   let path = path.as_ref();
+   if is_remote(path) {
+      Url::parse(&path.to_string_lossy()).context("Invalid remote path")
+   } else {
+      Url::from_file_path(path).map_err(|_| anyhow::anyhow!("Invalid file path"))
+   }
+}
+
+/// Like [`editor_uri`], for directories.
+pub fn editor_dir_uri(path: impl AsRef<Path>) -> Result<Url> {
+   let path = path.as_ref();
+   if is_remote(path) {
+      let path = path.to_string_lossy();
+      Url::parse(&format!("{}/", path.trim_end_matches('/'))).context("Invalid remote path")
+   } else {
+      Url::from_directory_path(path).map_err(|_| anyhow::anyhow!("Invalid directory path"))
+   }
+}
+
+/// The editor path of a URI, the inverse of [`editor_uri`].
+pub fn editor_path(uri: &Url) -> Option<PathBuf> {
+   if uri.scheme() != REMOTE_SCHEME {
+      return uri.to_file_path().ok();
+   }
+   // Decode the path the way file URIs are
+   let mut file = Url::parse(SERVER_PREFIX).ok()?;
+   file.set_path(uri.path());
+   let path = file.to_file_path().ok()?;
+   Some(PathBuf::from(format!(
+      "{}{}{}",
+      REMOTE_PREFIX,
+      uri.host_str()?,
+      path.display()
+   )))
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use serde_json::json;
+
+   #[test]
+   fn test_remote_uris_are_rewritten_both_ways() {
+      let path = Path::new("remote://devbox/home/me/my project/src/main.rs");
+      let uri = editor_uri(path).unwrap();
+      assert_eq!(
+         uri.as_str(),
+         "remote://devbox/home/me/my%20project/src/main.rs"
+      );
+      assert_eq!(editor_path(&uri).as_deref(), Some(path));
+      assert_eq!(connection_id(path), Some("devbox"));
+      assert_eq!(
+         LspTransport::for_workspace(Path::new("remote://devbox/home/me")),
+         LspTransport::Ssh {
+            connection_id: "devbox".to_string(),
+            root: PathBuf::from("/home/me"),
+         }
+      );
+      assert_eq!(
+         LspTransport::for_workspace(Path::new("/home/me")),
+         LspTransport::Local
+      );
+
+      let uris = RemoteUris::new("devbox");
+      let mut request = json!({
+         "textDocument": { "uri": uri },
+         "text": "remote://devbox/home/me/a.rs",
+      });
+      uris.to_server(&mut request);
+      assert_eq!(
+         request["textDocument"]["uri"],
+         "file:///home/me/my%20project/src/main.rs"
+      );
+      assert_eq!(request["text"], "remote://devbox/home/me/a.rs");
+
+      let mut edit = json!({
+         "changes": {
+            "file:///home/me/lib.rs": [{ "newText": "file:///home/me/kept.rs" }],
+         },
+         "documentChanges": [{ "textDocument": { "uri": "file:///home/me/lib.rs" } }],
+      });
+      uris.to_editor(&mut edit);
+      assert_eq!(
+         edit["changes"]["remote://devbox/home/me/lib.rs"][0]["newText"],
+         "file:///home/me/kept.rs"
+      );
+      assert_eq!(
+         edit["documentChanges"][0]["textDocument"]["uri"],
+         "remote://devbox/home/me/lib.rs"
+      );
+
+      let body = json!({ "uri": "remote://devbox/a.rs" }).to_string();
+      let framed = uris.framed_to_server(format!("Content-Length: {}\r\n\r\n{}", body.len(), body));
+      assert_eq!(
+         framed,
+         "Content-Length: 22\r\n\r\n{\"uri\":\"file:///a.rs\"}"
+      );
+   }
+
+   #[test]
+   fn test_chunk_pipes_preserve_order_and_close() {
+      let (tx, rx) = unbounded();
+      let mut writer = ChunkWriter(tx);
+      writer.write_all(b"Content-").unwrap();
+      writer.write_all(b"").unwrap();
+      writer.write_all(b"Length: 2\r\n\r\n{}").unwrap();
+      drop(writer);
+
+      let mut read = String::new();
+      ChunkReader::new(rx).read_to_string(&mut read).unwrap();
+      assert_eq!(read, "Content-Length: 2\r\n\r\n{}");
+   }
+}
@@ -84,3 +84,22 @@ pub fn find_in_path(binary_name: &str) -> Option<PathBuf> {
       None
    }
 }
+
+/// Directories where toolchains commonly install language servers outside `PATH`.
+pub fn tool_dirs() -> Vec<PathBuf> {
+   let Some(home) = dirs::home_dir() else {
+      return Vec::new();
+   };
+   vec![
+      home.join(".cargo/bin"),
+      home.join("go/bin"),
+      home.join(".local/bin"),
+   ]
+}
+
+pub fn find_in_dirs(binary_name: &str, dirs: &[PathBuf]) -> Option<PathBuf> {
+   dirs
+      .iter()
+      .map(|dir| dir.join(binary_name))
+      .find(|path| path.is_file())
+}
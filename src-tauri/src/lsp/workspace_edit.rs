@@ -0,0 +1,588 @@
+use super::transport;
+use crate::commands::replace::write_atomically;
+use anyhow::{Context, Result, bail};
+use lsp_types::{
+   DocumentChangeOperation, DocumentChanges, OneOf, Position, ResourceOp,
+   TextDocumentContentChangeEvent, TextDocumentEdit, TextEdit, Url, WorkspaceEdit,
+};
+use serde::Serialize;
+use std::{
+   collections::HashSet,
+   fs,
+   path::{Path, PathBuf},
+};
+
+/// Edits for a file open in the editor. The buffer may hold unsaved changes, so these are left
+/// for the frontend to apply instead of being written to disk.
+#[derive(Debug, Clone, Serialize)]
+pub struct BufferEdit {
+   pub file_path: String,
+   pub edits: Vec<TextEdit>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileRename {
+   pub old_path: String,
+   pub new_path: String,
+}
+
+/// Outcome of applying a `WorkspaceEdit`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AppliedWorkspaceEdit {
+   pub modified_files: Vec<String>,
+   pub created_files: Vec<String>,
+   pub renamed_files: Vec<FileRename>,
+   pub deleted_files: Vec<String>,
+   /// Edits for open buffers, to be applied in order.
+   pub buffer_edits: Vec<BufferEdit>,
+}
+
+/// A step already taken on disk, kept so a failure can undo everything before it.
+enum JournalEntry {
+   Wrote {
+      path: PathBuf,
+      original: Option<Vec<u8>>,
+   },
+   Renamed {
+      from: PathBuf,
+      to: PathBuf,
+   },
+   CreatedDir {
+      path: PathBuf,
+   },
+   /// Deleted paths are moved aside and only removed once the whole edit has succeeded.
+   Deleted {
+      path: PathBuf,
+      backup: PathBuf,
+   },
+}
+
+/// Applies a `WorkspaceEdit` to disk, all or nothing. Files in `open_files` are not written;
+/// their edits are returned in `buffer_edits` instead. Resource operations still happen on disk
+/// and open files that are renamed keep receiving buffer edits under their new path. Remote
+/// files can only be edited while open, as nothing here writes to a remote host.
+pub struct WorkspaceEditApplier {
+   open_files: HashSet<PathBuf>,
+   journal: Vec<JournalEntry>,
+   result: AppliedWorkspaceEdit,
+}
+
+impl WorkspaceEditApplier {
+   pub fn new(open_files: impl IntoIterator<Item = PathBuf>) -> Self {
+      Self {
+         open_files: open_files.into_iter().collect(),
+         journal: Vec::new(),
+         result: AppliedWorkspaceEdit::default(),
+      }
+   }
+
+   pub fn apply(mut self, edit: WorkspaceEdit) -> Result<AppliedWorkspaceEdit> {
+      match self.apply_all(edit) {
+         Ok(()) => {
+            self.commit();
+            Ok(self.result)
+         }
+         Err(e) => {
+            self.rollback();
+            Err(e)
+         }
+      }
+   }
+
+   fn apply_all(&mut self, edit: WorkspaceEdit) -> Result<()> {
+      // `documentChanges` supersedes `changes` when a server sends both
+      match edit.document_changes {
+         Some(DocumentChanges::Edits(edits)) => {
+            for edit in edits {
+               self.apply_document_edit(edit)?;
+            }
+         }
+         Some(DocumentChanges::Operations(operations)) => {
+            for operation in operations {
+               match operation {
+                  DocumentChangeOperation::Edit(edit) => self.apply_document_edit(edit)?,
+                  DocumentChangeOperation::Op(op) => self.apply_resource_op(op)?,
+               }
+            }
+         }
+         None => {
+            let mut changes: Vec<_> = edit.changes.unwrap_or_default().into_iter().collect();
+            changes.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+            for (uri, edits) in changes {
+               self.apply_text_edits(&uri, edits)?;
+            }
+         }
+      }
+      Ok(())
+   }
+
+   fn apply_document_edit(&mut self, edit: TextDocumentEdit) -> Result<()> {
+      let edits = edit
+         .edits
+         .into_iter()
+         .map(|edit| match edit {
+            OneOf::Left(edit) => edit,
+            OneOf::Right(annotated) => annotated.text_edit,
+         })
+         .collect();
+      self.apply_text_edits(&edit.text_document.uri, edits)
+   }
+
+   fn apply_text_edits(&mut self, uri: &Url, edits: Vec<TextEdit>) -> Result<()> {
+      let path = to_path(uri)?;
+      if self.open_files.contains(&path) {
+         self.result.buffer_edits.push(BufferEdit {
+            file_path: path.to_string_lossy().to_string(),
+            edits,
+         });
+         return Ok(());
+      }
+      let path = local_path(path)?;
+
+      let original =
+         fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+      let content = String::from_utf8(original.clone())
+         .with_context(|| format!("{} is not valid UTF-8", path.display()))?;
+      let updated = apply_text_edits(&content, edits)
+         .with_context(|| format!("Invalid edits for {}", path.display()))?;
+      self.write(&path, updated.as_bytes(), Some(original))?;
+
+      let file_path = path.to_string_lossy().to_string();
+      if !self.result.modified_files.contains(&file_path) {
+         self.result.modified_files.push(file_path);
+      }
+      Ok(())
+   }
+
+   fn apply_resource_op(&mut self, op: ResourceOp) -> Result<()> {
+      match op {
+         ResourceOp::Create(create) => {
+            let path = local_path(to_path(&create.uri)?)?;
+            let overwrite = create.options.as_ref().and_then(|o| o.overwrite) == Some(true);
+            let ignore_if_exists =
+               create.options.as_ref().and_then(|o| o.ignore_if_exists) == Some(true);
+            if path.exists() {
+               if !overwrite {
+                  if ignore_if_exists {
+                     return Ok(());
+                  }
+                  bail!("Cannot create {}: it already exists", path.display());
+               }
+               self.delete(&path)?;
+            }
+
+            self.create_parent_dirs(&path)?;
+            self.write(&path, b"", None)?;
+            self
+               .result
+               .created_files
+               .push(path.to_string_lossy().to_string());
+         }
+         ResourceOp::Rename(rename) => {
+            let from = local_path(to_path(&rename.old_uri)?)?;
+            let to = local_path(to_path(&rename.new_uri)?)?;
+            let overwrite = rename.options.as_ref().and_then(|o| o.overwrite) == Some(true);
+            let ignore_if_exists =
+               rename.options.as_ref().and_then(|o| o.ignore_if_exists) == Some(true);
+            if !from.exists() {
+               bail!("Cannot rename {}: it does not exist", from.display());
+            }
+            if to.exists() {
+               if !overwrite {
+                  if ignore_if_exists {
+                     return Ok(());
+                  }
+                  bail!("Cannot rename to {}: it already exists", to.display());
+               }
+               self.delete(&to)?;
+            }
+
+            self.create_parent_dirs(&to)?;
+            fs::rename(&from, &to).with_context(|| {
+               format!("Failed to rename {} to {}", from.display(), to.display())
+            })?;
+            self.journal.push(JournalEntry::Renamed {
+               from: from.clone(),
+               to: to.clone(),
+            });
+
+            if self.open_files.remove(&from) {
+               self.open_files.insert(to.clone());
+            }
+            self.result.renamed_files.push(FileRename {
+               old_path: from.to_string_lossy().to_string(),
+               new_path: to.to_string_lossy().to_string(),
+            });
+         }
+         ResourceOp::Delete(delete) => {
+            let path = local_path(to_path(&delete.uri)?)?;
+            let recursive = delete.options.as_ref().and_then(|o| o.recursive) == Some(true);
+            let ignore_if_not_exists =
+               delete.options.as_ref().and_then(|o| o.ignore_if_not_exists) == Some(true);
+            if !path.exists() {
+               if ignore_if_not_exists {
+                  return Ok(());
+               }
+               bail!("Cannot delete {}: it does not exist", path.display());
+            }
+            if path.is_dir() && !recursive && fs::read_dir(&path)?.next().is_some() {
+               bail!("Cannot delete {}: directory is not empty", path.display());
+            }
+
+            self.delete(&path)?;
+            self
+               .result
+               .deleted_files
+               .push(path.to_string_lossy().to_string());
+         }
+      }
+      Ok(())
+   }
+
+   fn write(&mut self, path: &Path, content: &[u8], original: Option<Vec<u8>>) -> Result<()> {
+      write_atomically(path, content)
+         .with_context(|| format!("Failed to write {}", path.display()))?;
+      self.journal.push(JournalEntry::Wrote {
+         path: path.to_path_buf(),
+         original,
+      });
+      Ok(())
+   }
+
+   /// Create the missing parent directories of `path`, outermost first, so a rollback can remove
+   /// them again.
+   fn create_parent_dirs(&mut self, path: &Path) -> Result<()> {
+      let missing: Vec<_> = path
+         .ancestors()
+         .skip(1)
+         .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+         .collect();
+      for dir in missing.into_iter().rev() {
+         fs::create_dir(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+         self.journal.push(JournalEntry::CreatedDir {
+            path: dir.to_path_buf(),
+         });
+      }
+      Ok(())
+   }
+
+   fn delete(&mut self, path: &Path) -> Result<()> {
+      let file_name = path
+         .file_name()
+         .context("Cannot delete a root directory")?
+         .to_string_lossy();
+      let backup = path.with_file_name(format!(
+         ".{}.deleted-{}",
+         file_name,
+         uuid::Uuid::new_v4().simple()
+      ));
+      fs::rename(path, &backup).with_context(|| format!("Failed to delete {}", path.display()))?;
+      self.journal.push(JournalEntry::Deleted {
+         path: path.to_path_buf(),
+         backup,
+      });
+      Ok(())
+   }
+
+   fn commit(&mut self) {
+      for entry in self.journal.drain(..) {
+         if let JournalEntry::Deleted { backup, .. } = entry {
+            let removed = if backup.is_dir() {
+               fs::remove_dir_all(&backup)
+            } else {
+               fs::remove_file(&backup)
+            };
+            if let Err(e) = removed {
+               log::warn!("[LSP] Failed to remove {}: {}", backup.display(), e);
+            }
+         }
+      }
+   }
+
+   fn rollback(&mut self) {
+      while let Some(entry) = self.journal.pop() {
+         let undone = match &entry {
+            JournalEntry::Wrote {
+               path,
+               original: Some(original),
+            } => write_atomically(path, original),
+            JournalEntry::Wrote {
+               path,
+               original: None,
+            } => fs::remove_file(path),
+            JournalEntry::Renamed { from, to } => fs::rename(to, from),
+            JournalEntry::CreatedDir { path } => fs::remove_dir(path),
+            JournalEntry::Deleted { path, backup } => fs::rename(backup, path),
+         };
+         if let Err(e) = undone {
+            log::error!("[LSP] Failed to roll back workspace edit: {}", e);
+         }
+      }
+   }
+}
+
+fn to_path(uri: &Url) -> Result<PathBuf> {
+   transport::editor_path(uri)
+      .ok_or_else(|| anyhow::anyhow!("Unsupported URI in workspace edit: {}", uri))
+}
+
+/// Reject remote paths, which the local file system would take for a relative `remote:` dir.
+fn local_path(path: PathBuf) -> Result<PathBuf> {
+   if transport::is_remote(&path) {
+      bail!(
+         "Cannot apply edits to {}: remote files must be open in the editor",
+         path.display()
+      );
+   }
+   Ok(path)
+}
+
+/// Apply LSP text edits, whose positions all refer to the original `content`.
+pub fn apply_text_edits(content: &str, edits: Vec<TextEdit>) -> Result<String> {
+   let mut ranges = edits
+      .into_iter()
+      .map(|edit| {
+         let start = offset_at(content, edit.range.start);
+         let end = offset_at(content, edit.range.end);
+         if start > end {
+            bail!("Edit range ends before it starts");
+         }
+         Ok((start, end, edit.new_text))
+      })
+      .collect::<Result<Vec<_>>>()?;
+
+   // Stable sort keeps edits inserted at the same position in the order given
+   ranges.sort_by_key(|(start, end, _)| (*start, *end));
+   if ranges.windows(2).any(|pair| pair[0].1 > pair[1].0) {
+      bail!("Overlapping edits");
+   }
+
+   let mut result = String::with_capacity(content.len());
+   let mut last = 0;
+   for (start, end, new_text) in ranges {
+      result.push_str(&content[last..start]);
+      result.push_str(&new_text);
+      last = end;
+   }
+   result.push_str(&content[last..]);
+   Ok(result)
+}
+
+/// Apply `didChange` content changes in order. Each change is relative to the text left by the
+/// previous one, and a change without a range replaces the whole document.
+pub fn apply_content_changes(content: &str, changes: &[TextDocumentContentChangeEvent]) -> String {
+   let mut content = content.to_string();
+   for change in changes {
+      match change.range {
+         Some(range) => {
+            let start = offset_at(&content, range.start);
+            let end = offset_at(&content, range.end).max(start);
+            content.replace_range(start..end, &change.text);
+         }
+         None => content.clone_from(&change.text),
+      }
+   }
+   content
+}
+
+/// Byte offset of an LSP position, whose character is counted in UTF-16 code units. Positions
+/// past the end of a line or of the document are clamped, as the protocol requires.
+pub fn offset_at(content: &str, position: Position) -> usize {
+   let mut line_start = 0;
+   for _ in 0..position.line {
+      match content[line_start..].find('\n') {
+         Some(newline) => line_start += newline + 1,
+         None => return content.len(),
+      }
+   }
+
+   let line_end = content[line_start..]
+      .find('\n')
+      .map_or(content.len(), |newline| line_start + newline);
+   let line = content[line_start..line_end].trim_end_matches('\r');
+
+   let mut units = 0;
+   for (offset, ch) in line.char_indices() {
+      if units >= position.character as usize {
+         return line_start + offset;
+      }
+      units += ch.len_utf16();
+   }
+   line_start + line.len()
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use lsp_types::{CreateFile, OptionalVersionedTextDocumentIdentifier, Range, RenameFile};
+   use std::collections::HashMap;
+
+   fn edit(line: u32, start: u32, end: u32, text: &str) -> TextEdit {
+      TextEdit::new(
+         Range::new(Position::new(line, start), Position::new(line, end)),
+         text.to_string(),
+      )
+   }
+
+   fn uri(path: &Path) -> Url {
+      Url::from_file_path(path).unwrap()
+   }
+
+   #[test]
+   fn test_text_edits_use_utf16_positions() {
+      let content = "let é = 1;\nlet 😀x = é;\n";
+      let edits = vec![edit(0, 4, 5, "a"), edit(1, 4, 7, "b"), edit(1, 10, 11, "a")];
+      assert_eq!(
+         apply_text_edits(content, edits).unwrap(),
+         "let a = 1;\nlet b = a;\n"
+      );
+      assert!(apply_text_edits(content, vec![edit(0, 0, 5, ""), edit(0, 4, 6, "")]).is_err());
+   }
+
+   #[test]
+   fn test_content_changes_apply_in_sequence() {
+      let change = |range: Option<Range>, text: &str| TextDocumentContentChangeEvent {
+         range,
+         range_length: None,
+         text: text.to_string(),
+      };
+      let at = |line, start, end| {
+         Some(Range::new(
+            Position::new(line, start),
+            Position::new(line, end),
+         ))
+      };
+
+      let changes = vec![
+         change(at(0, 4, 5), "ab"),
+         change(at(0, 6, 6), "c"),
+         change(at(1, 0, 3), ""),
+      ];
+      assert_eq!(
+         apply_content_changes("let é = 1;\nfoo\n", &changes),
+         "let abc = 1;\n\n"
+      );
+      assert_eq!(apply_content_changes("old", &[change(None, "new")]), "new");
+   }
+
+   #[test]
+   fn test_open_files_receive_buffer_edits() {
+      let dir = tempfile::tempdir().unwrap();
+      let (open, closed) = (dir.path().join("open.ts"), dir.path().join("closed.ts"));
+      fs::write(&open, "foo()").unwrap();
+      fs::write(&closed, "foo()").unwrap();
+
+      let edit = WorkspaceEdit {
+         changes: Some(HashMap::from([
+            (uri(&open), vec![edit(0, 0, 3, "bar")]),
+            (uri(&closed), vec![edit(0, 0, 3, "bar")]),
+         ])),
+         ..Default::default()
+      };
+      let result = WorkspaceEditApplier::new([open.clone()])
+         .apply(edit)
+         .unwrap();
+
+      assert_eq!(fs::read_to_string(&open).unwrap(), "foo()");
+      assert_eq!(fs::read_to_string(&closed).unwrap(), "bar()");
+      assert_eq!(result.buffer_edits.len(), 1);
+      assert_eq!(result.buffer_edits[0].file_path, open.to_string_lossy());
+   }
+
+   #[test]
+   fn test_failed_edit_rolls_back_every_change() {
+      let dir = tempfile::tempdir().unwrap();
+      let (old, new) = (dir.path().join("old.rs"), dir.path().join("new.rs"));
+      let created = dir.path().join("nested/dir/created.rs");
+      fs::write(&old, "fn old() {}").unwrap();
+
+      let edit_document = |path: &Path| {
+         DocumentChangeOperation::Edit(TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier {
+               uri: uri(path),
+               version: None,
+            },
+            edits: vec![OneOf::Left(edit(0, 3, 6, "renamed"))],
+         })
+      };
+      let operations = vec![
+         DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+            uri: uri(&created),
+            options: None,
+            annotation_id: None,
+         })),
+         edit_document(&old),
+         DocumentChangeOperation::Op(ResourceOp::Rename(RenameFile {
+            old_uri: uri(&old),
+            new_uri: uri(&new),
+            options: None,
+            annotation_id: None,
+         })),
+         edit_document(&dir.path().join("missing.rs")),
+      ];
+      let edit = WorkspaceEdit {
+         document_changes: Some(DocumentChanges::Operations(operations.clone())),
+         ..Default::default()
+      };
+
+      assert!(WorkspaceEditApplier::new([]).apply(edit).is_err());
+      assert_eq!(fs::read_to_string(&old).unwrap(), "fn old() {}");
+      assert!(!new.exists());
+      assert!(!created.exists());
+      assert!(!dir.path().join("nested").exists());
+
+      let edit = WorkspaceEdit {
+         document_changes: Some(DocumentChanges::Operations(operations[..3].to_vec())),
+         ..Default::default()
+      };
+      let result = WorkspaceEditApplier::new([]).apply(edit).unwrap();
+      assert_eq!(fs::read_to_string(&new).unwrap(), "fn renamed() {}");
+      assert!(created.exists());
+      assert_eq!(result.renamed_files.len(), 1);
+   }
+
+   #[test]
+   fn test_remote_files_are_never_written_locally() {
+      let dir = tempfile::tempdir().unwrap();
+      let local = dir.path().join("local.rs");
+      fs::write(&local, "foo()").unwrap();
+      let open = Url::parse("remote://devbox/home/me/open.rs").unwrap();
+      let closed = Url::parse("remote://devbox/home/me/closed.rs").unwrap();
+
+      let edit = |uris: &[&Url]| WorkspaceEdit {
+         changes: Some(
+            uris
+               .iter()
+               .map(|uri| ((*uri).clone(), vec![edit(0, 0, 3, "bar")]))
+               .collect(),
+         ),
+         ..Default::default()
+      };
+      let applier =
+         || WorkspaceEditApplier::new([PathBuf::from("remote://devbox/home/me/open.rs")]);
+
+      let result = applier().apply(edit(&[&open])).unwrap();
+      assert_eq!(result.buffer_edits.len(), 1);
+      assert_eq!(
+         result.buffer_edits[0].file_path,
+         "remote://devbox/home/me/open.rs"
+      );
+
+      let error = applier().apply(edit(&[&uri(&local), &closed])).unwrap_err();
+      assert!(error.to_string().contains("remote files must be open"));
+      assert_eq!(fs::read_to_string(&local).unwrap(), "foo()");
+
+      let create = WorkspaceEdit {
+         document_changes: Some(DocumentChanges::Operations(vec![
+            DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+               uri: closed.clone(),
+               options: None,
+               annotation_id: None,
+            })),
+         ])),
+         ..Default::default()
+      };
+      assert!(applier().apply(create).is_err());
+      assert!(!Path::new("remote:").exists());
+   }
+}
@@ -3,9 +3,14 @@
 
 use claude_bridge::ClaudeCodeBridge;
 use commands::*;
+use diagnostics::DiagnosticsStore;
+use file_finder::FileFinderManager;
 use file_watcher::FileWatcher;
+use frecency::FrecencyStore;
 use log::{debug, info};
 use lsp::LspManager;
+use search_index::SearchIndexManager;
+use search_session::SearchSessionManager;
 use ssh::{ssh_connect, ssh_disconnect, ssh_disconnect_only, ssh_write_file};
 use std::sync::Arc;
 use tauri::{Emitter, Manager};
@@ -15,16 +20,26 @@ use terminal::{
    TerminalManager, close_terminal, create_terminal, get_shells, terminal_resize, terminal_write,
 };
 use tokio::sync::Mutex;
+use workspace::WorkspaceManager;
 
 mod claude_bridge;
 mod commands;
+mod diagnostics;
 mod extensions;
+mod file_finder;
 mod file_watcher;
+mod frecency;
 mod logger;
 mod lsp;
 mod menu;
+mod search_index;
+mod search_session;
 mod ssh;
 mod terminal;
+#[cfg(test)]
+mod test_utils;
+mod workspace;
+mod workspace_ignore;
 
 fn main() {
    tauri::Builder::default()
@@ -59,6 +74,9 @@ fn main() {
 
          log::info!("Starting app!");
 
+         // Set up the workspace roots
+         app.manage(Arc::new(WorkspaceManager::new()));
+
          // Set up the file watcher
          app.manage(Arc::new(FileWatcher::new(app.handle().clone())));
 
@@ -66,8 +84,24 @@ fn main() {
          let claude_bridge = Arc::new(Mutex::new(ClaudeCodeBridge::new(app.handle().clone())));
          app.manage(claude_bridge.clone());
 
+         // Set up frecency history for ranking
+         app.manage(Arc::new(FrecencyStore::new(app.handle().clone())));
+
+         // Set up file finder sessions
+         app.manage(Arc::new(FileFinderManager::new(app.handle().clone())));
+
+         // Set up streaming search sessions
+         app.manage(Arc::new(SearchSessionManager::new(app.handle().clone())));
+
+         // Set up persistent search indexes
+         app.manage(Arc::new(SearchIndexManager::new(app.handle().clone())));
+
+         // Set up the diagnostics store, shared by language servers and other sources
+         let diagnostics = DiagnosticsStore::new(Some(app.handle().clone()));
+         app.manage(diagnostics.clone());
+
          // Set up LSP manager
-         app.manage(LspManager::new(app.handle().clone()));
+         app.manage(LspManager::new(app.handle().clone(), diagnostics));
 
          // Set up theme cache
          app.manage(theme::ThemeCache::new(std::collections::HashMap::new()));
@@ -261,6 +295,7 @@ fn main() {
          rename_file,
          // Git commands
          git_status,
+         git_workspace_status,
          git_add,
          git_reset,
          git_commit,
@@ -308,6 +343,10 @@ fn main() {
          start_watching,
          stop_watching,
          set_project_root,
+         workspace_get_roots,
+         workspace_set_roots,
+         workspace_add_root,
+         workspace_remove_root,
          // Terminal commands
          create_terminal,
          terminal_write,
@@ -355,11 +394,55 @@ fn main() {
          lsp_start_for_file,
          lsp_stop_for_file,
          lsp_get_completions,
+         lsp_resolve_completion,
+         lsp_expand_snippet,
          lsp_get_hover,
+         lsp_goto_definition,
+         lsp_goto_type_definition,
+         lsp_goto_implementation,
+         lsp_goto_declaration,
+         lsp_find_references,
+         lsp_prepare_rename,
+         lsp_rename,
+         lsp_get_code_actions,
+         lsp_resolve_code_action,
+         lsp_apply_code_action,
+         lsp_execute_command,
+         lsp_signature_help,
+         lsp_inlay_hints,
+         lsp_semantic_tokens,
+         lsp_document_symbols,
+         lsp_workspace_symbols,
+         lsp_code_lenses,
+         lsp_resolve_code_lens,
+         lsp_document_highlights,
+         lsp_folding_ranges,
+         lsp_selection_ranges,
+         lsp_prepare_hierarchy,
+         lsp_expand_hierarchy,
+         lsp_pull_diagnostics,
+         lsp_workspace_diagnostics,
+         lsp_respond_message_request,
          lsp_document_open,
          lsp_document_change,
          lsp_document_close,
          lsp_is_language_supported,
+         lsp_list_servers,
+         lsp_reload_config,
+         lsp_get_config_path,
+         lsp_install_server,
+         lsp_uninstall_server,
+         lsp_list_installed_servers,
+         lsp_check_server_updates,
+         lsp_get_traffic,
+         lsp_clear_traffic,
+         lsp_set_traffic_streaming,
+         lsp_export_traffic,
+         // Diagnostics commands
+         diagnostics_query,
+         diagnostics_counts,
+         diagnostics_set,
+         diagnostics_clear,
          // Extension commands
          download_extension,
          install_extension,
@@ -373,8 +456,20 @@ fn main() {
          // Fuzzy matching commands
          fuzzy_match,
          filter_completions,
+         frecency_record,
+         file_finder_open,
+         file_finder_query,
+         file_finder_close,
          // Search commands
          search_files_content,
+         search_start,
+         search_cancel,
+         search_index_enable,
+         search_index_disable,
+         search_index_status,
+         // Replace commands
+         replace_plan,
+         replace_apply,
          // Format commands
          format_code,
          // CLI commands
@@ -0,0 +1,459 @@
+mod trigram;
+
+use crate::{commands::search::SearchFilesRequest, workspace_ignore::WorkspaceIgnore};
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use std::{
+   collections::{HashMap, HashSet},
+   path::{Path, PathBuf},
+   sync::{
+      Arc, Mutex, RwLock,
+      atomic::{AtomicBool, AtomicUsize, Ordering},
+   },
+   thread,
+   time::UNIX_EPOCH,
+};
+use tauri::{AppHandle, Emitter, Manager};
+pub use trigram::{TrigramIndex, required_literals};
+
+/// Incremental updates are persisted after this many file changes.
+const SAVE_EVERY_CHANGES: usize = 500;
+/// Share of tombstoned entries above which the index is compacted after a build.
+const COMPACT_THRESHOLD: f64 = 0.25;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexState {
+   Missing,
+   Building,
+   Ready,
+   Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchIndexStatus {
+   pub root_path: String,
+   pub state: IndexState,
+   pub files_indexed: usize,
+   pub error: Option<String>,
+}
+
+/// The trigram index for one workspace root, with the bookkeeping needed to keep it in sync
+/// with disk while it is being built.
+pub struct IndexedWorkspace {
+   root: PathBuf,
+   index_path: PathBuf,
+   ignore: WorkspaceIgnore,
+   state: Mutex<(IndexState, Option<String>)>,
+   index: RwLock<TrigramIndex>,
+   /// Paths changed while the index was building, applied once it is ready.
+   pending: Mutex<HashSet<PathBuf>>,
+   unsaved_changes: AtomicUsize,
+   closed: AtomicBool,
+   /// `(running, rerun)`: whether a build thread is active, and whether it should run again
+   /// because the ignore rules changed while it was walking.
+   build_control: Mutex<(bool, bool)>,
+}
+
+impl IndexedWorkspace {
+   pub fn new(root: impl Into<PathBuf>, index_path: impl Into<PathBuf>) -> Self {
+      let root = root.into();
+      Self {
+         ignore: WorkspaceIgnore::new(root.clone(), false),
+         index: RwLock::new(TrigramIndex::new(root.clone())),
+         root,
+         index_path: index_path.into(),
+         state: Mutex::new((IndexState::Building, None)),
+         pending: Mutex::new(HashSet::new()),
+         unsaved_changes: AtomicUsize::new(0),
+         closed: AtomicBool::new(false),
+         build_control: Mutex::new((false, false)),
+      }
+   }
+
+   pub fn status(&self) -> SearchIndexStatus {
+      let (state, error) = self.state.lock().unwrap().clone();
+      SearchIndexStatus {
+         root_path: self.root.to_string_lossy().to_string(),
+         state,
+         files_indexed: self.index.read().unwrap().len(),
+         error,
+      }
+   }
+
+   /// Load the persisted index, reconcile it with the files on disk, then apply any changes
+   /// that arrived in the meantime. Only files whose size or modification time changed since
+   /// the last save are re-read.
+   pub fn build(&self) -> Result<()> {
+      self.set_state(IndexState::Building, None);
+
+      let mut index = match TrigramIndex::load(&self.index_path) {
+         Ok(index) if index.root() == self.root => index,
+         Ok(_) => TrigramIndex::new(self.root.clone()),
+         Err(e) => {
+            if self.index_path.exists() {
+               log::warn!(
+                  "[SearchIndex] Discarding unreadable index {:?}: {}",
+                  self.index_path,
+                  e
+               );
+            }
+            TrigramIndex::new(self.root.clone())
+         }
+      };
+
+      let mut seen = HashSet::new();
+      for entry in self.ignore.walk_builder(|_| true).build().flatten() {
+         if self.closed.load(Ordering::Relaxed) {
+            bail!("Index build cancelled");
+         }
+         if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+         }
+
+         let path = entry.path();
+         seen.insert(path.to_path_buf());
+         let Ok(metadata) = entry.metadata() else {
+            continue;
+         };
+         let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+         if !index.is_current(path, modified, metadata.len()) {
+            index.update_file(path);
+         }
+      }
+      index.retain_files(&seen);
+      if index.garbage_ratio() > COMPACT_THRESHOLD {
+         index.compact();
+      }
+
+      // Holding the pending lock while switching to ready ensures no change is queued after
+      // the queue has been drained
+      let mut pending = self.pending.lock().unwrap();
+      for path in pending.drain() {
+         self.apply_change(&mut index, &path);
+      }
+      *self.index.write().unwrap() = index;
+      self.set_state(IndexState::Ready, None);
+      drop(pending);
+
+      self.save()
+   }
+
+   /// Update the index for a changed, created or deleted path. Returns `true` when an ignore
+   /// file changed and the index needs a full rebuild.
+   pub fn handle_change(&self, path: &Path) -> bool {
+      if !path.starts_with(&self.root) {
+         return false;
+      }
+      if self.ignore.invalidate(path) {
+         return true;
+      }
+
+      {
+         let mut pending = self.pending.lock().unwrap();
+         match self.state.lock().unwrap().0 {
+            IndexState::Ready => {}
+            IndexState::Building => {
+               pending.insert(path.to_path_buf());
+               return false;
+            }
+            IndexState::Missing | IndexState::Failed => return false,
+         }
+      }
+
+      self.apply_change(&mut self.index.write().unwrap(), path);
+      if self.unsaved_changes.fetch_add(1, Ordering::Relaxed) + 1 >= SAVE_EVERY_CHANGES
+         && let Err(e) = self.save()
+      {
+         log::warn!("[SearchIndex] Failed to save index: {}", e);
+      }
+      false
+   }
+
+   /// Candidate files for `request`, or `None` when the index cannot answer it and the caller
+   /// should scan the workspace instead.
+   pub fn candidates(&self, request: &SearchFilesRequest) -> Option<Vec<PathBuf>> {
+      if self.state.lock().unwrap().0 != IndexState::Ready
+         || request.include_ignored.unwrap_or(false)
+         || Path::new(&request.root_path) != self.root
+      {
+         return None;
+      }
+
+      let literals = required_literals(&request.query, request.use_regex.unwrap_or(false));
+      self
+         .index
+         .read()
+         .unwrap()
+         .candidates(&literals, request.case_sensitive.unwrap_or(false))
+   }
+
+   pub fn close(&self) {
+      self.closed.store(true, Ordering::Relaxed);
+   }
+
+   fn apply_change(&self, index: &mut TrigramIndex, path: &Path) {
+      if path == self.root {
+         // Every change below the root reports its own path
+      } else if path.is_dir() {
+         // A directory moved into place may not report events for its children
+         for entry in self
+            .ignore
+            .walk_builder_from(path, |_| true)
+            .build()
+            .flatten()
+         {
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+               continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+               continue;
+            };
+            let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+            if !index.is_current(entry.path(), modified, metadata.len()) {
+               index.update_file(entry.path());
+            }
+         }
+      } else if path.is_file() && !self.ignore.is_ignored(path, false) {
+         index.update_file(path);
+      } else {
+         index.remove_file(path);
+         index.remove_prefix(path);
+      }
+   }
+
+   fn save(&self) -> Result<()> {
+      self.unsaved_changes.store(0, Ordering::Relaxed);
+      self.index.read().unwrap().save(&self.index_path)
+   }
+
+   fn set_state(&self, state: IndexState, error: Option<String>) {
+      *self.state.lock().unwrap() = (state, error);
+   }
+}
+
+/// Optional per-workspace trigram indexes, persisted under the app data directory and kept
+/// fresh from file watcher events. Status changes are emitted as `search-index://status`.
+pub struct SearchIndexManager {
+   app_handle: AppHandle,
+   workspaces: Arc<Mutex<HashMap<PathBuf, Arc<IndexedWorkspace>>>>,
+}
+
+impl SearchIndexManager {
+   pub fn new(app_handle: AppHandle) -> Self {
+      Self {
+         app_handle,
+         workspaces: Arc::new(Mutex::new(HashMap::new())),
+      }
+   }
+
+   /// Start indexing `root` in the background, reusing the index saved by a previous session.
+   pub fn enable(&self, root: &str) -> Result<SearchIndexStatus> {
+      let root = PathBuf::from(root);
+      if !root.is_dir() {
+         bail!("Root path does not exist");
+      }
+
+      if let Some(workspace) = self.workspaces.lock().unwrap().get(&root) {
+         return Ok(workspace.status());
+      }
+
+      let workspace = Arc::new(IndexedWorkspace::new(root.clone(), self.index_path(&root)?));
+      self
+         .workspaces
+         .lock()
+         .unwrap()
+         .insert(root, workspace.clone());
+      self.spawn_build(workspace.clone());
+
+      Ok(workspace.status())
+   }
+
+   /// Stop maintaining the index for `root`, optionally deleting it from disk.
+   pub fn disable(&self, root: &str, delete: bool) -> Result<()> {
+      let root = PathBuf::from(root);
+      if let Some(workspace) = self.workspaces.lock().unwrap().remove(&root) {
+         workspace.close();
+      }
+      if delete {
+         let index_path = self.index_path(&root)?;
+         if index_path.exists() {
+            std::fs::remove_file(index_path)?;
+         }
+      }
+      Ok(())
+   }
+
+   pub fn status(&self, root: &str) -> SearchIndexStatus {
+      match self.workspaces.lock().unwrap().get(Path::new(root)) {
+         Some(workspace) => workspace.status(),
+         None => SearchIndexStatus {
+            root_path: root.to_string(),
+            state: IndexState::Missing,
+            files_indexed: 0,
+            error: None,
+         },
+      }
+   }
+
+   pub fn candidates(&self, request: &SearchFilesRequest) -> Option<Vec<PathBuf>> {
+      let workspace = self
+         .workspaces
+         .lock()
+         .unwrap()
+         .get(Path::new(&request.root_path))
+         .cloned()?;
+      workspace.candidates(request)
+   }
+
+   /// Feed a file watcher event into every index containing `path`.
+   pub fn handle_file_change(&self, path: &Path) {
+      let workspaces: Vec<Arc<IndexedWorkspace>> =
+         self.workspaces.lock().unwrap().values().cloned().collect();
+
+      for workspace in workspaces {
+         if workspace.handle_change(path) {
+            log::info!(
+               "[SearchIndex] Ignore rules changed, rebuilding index for {:?}",
+               workspace.root
+            );
+            self.spawn_build(workspace);
+         }
+      }
+   }
+
+   fn spawn_build(&self, workspace: Arc<IndexedWorkspace>) {
+      {
+         let mut control = workspace.build_control.lock().unwrap();
+         if control.0 {
+            control.1 = true;
+            return;
+         }
+         *control = (true, false);
+      }
+
+      let app_handle = self.app_handle.clone();
+      thread::spawn(move || {
+         loop {
+            let _ = app_handle.emit("search-index://status", workspace.status());
+            if let Err(e) = workspace.build() {
+               log::warn!(
+                  "[SearchIndex] Failed to build index for {:?}: {}",
+                  workspace.root,
+                  e
+               );
+               workspace.set_state(IndexState::Failed, Some(e.to_string()));
+            }
+            let _ = app_handle.emit("search-index://status", workspace.status());
+
+            let mut control = workspace.build_control.lock().unwrap();
+            if !control.1 || workspace.closed.load(Ordering::Relaxed) {
+               *control = (false, false);
+               break;
+            }
+            control.1 = false;
+         }
+      });
+   }
+
+   fn index_path(&self, root: &Path) -> Result<PathBuf> {
+      let app_data_dir = self
+         .app_handle
+         .path()
+         .app_data_dir()
+         .context("Failed to get app data directory")?;
+      let file_name = format!("{}.idx", sha256::digest(root.to_string_lossy().as_bytes()));
+      Ok(app_data_dir.join("search-index").join(file_name))
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use std::fs;
+
+   fn request(root: &Path, query: &str) -> SearchFilesRequest {
+      SearchFilesRequest {
+         root_path: root.to_string_lossy().to_string(),
+         root_paths: None,
+         query: query.to_string(),
+         case_sensitive: None,
+         max_results: None,
+         use_regex: None,
+         whole_word: None,
+         include_globs: None,
+         exclude_globs: None,
+         include_ignored: None,
+         max_matches_per_file: None,
+         max_file_size: None,
+      }
+   }
+
+   fn candidate_names(workspace: &IndexedWorkspace, query: &str) -> Vec<String> {
+      let mut names: Vec<String> = workspace
+         .candidates(&request(&workspace.root, query))
+         .unwrap()
+         .iter()
+         .map(|p| {
+            p.strip_prefix(&workspace.root)
+               .unwrap()
+               .to_string_lossy()
+               .replace('\\', "/")
+         })
+         .collect();
+      names.sort();
+      names
+   }
+
+   #[test]
+   fn test_build_respects_ignores_and_tracks_changes() {
+      let dir = tempfile::tempdir().unwrap();
+      let root = dir.path().join("workspace");
+      fs::create_dir_all(root.join("out")).unwrap();
+      fs::write(root.join(".gitignore"), "out/\n").unwrap();
+      fs::write(root.join("main.rs"), "needle").unwrap();
+      fs::write(root.join("out/bundle.js"), "needle").unwrap();
+
+      let index_path = dir.path().join("index.idx");
+      let workspace = IndexedWorkspace::new(root.clone(), index_path.clone());
+      assert!(workspace.candidates(&request(&root, "needle")).is_none());
+      workspace.build().unwrap();
+      assert_eq!(workspace.status().state, IndexState::Ready);
+      assert_eq!(candidate_names(&workspace, "needle"), vec!["main.rs"]);
+
+      fs::create_dir_all(root.join("src/nested")).unwrap();
+      fs::write(root.join("src/nested/lib.rs"), "needle").unwrap();
+      assert!(!workspace.handle_change(&root.join("src")));
+      fs::remove_file(root.join("main.rs")).unwrap();
+      assert!(!workspace.handle_change(&root.join("main.rs")));
+      assert_eq!(
+         candidate_names(&workspace, "needle"),
+         vec!["src/nested/lib.rs"]
+      );
+
+      fs::write(root.join(".gitignore"), "").unwrap();
+      assert!(workspace.handle_change(&root.join(".gitignore")));
+
+      // A fresh workspace reuses the saved index and picks up the new ignore rules
+      let reloaded = IndexedWorkspace::new(root.clone(), index_path);
+      reloaded.build().unwrap();
+      assert_eq!(
+         candidate_names(&reloaded, "needle"),
+         vec!["out/bundle.js", "src/nested/lib.rs"]
+      );
+   }
+
+   #[test]
+   fn test_changes_during_build_are_queued() {
+      let dir = tempfile::tempdir().unwrap();
+      let workspace = IndexedWorkspace::new(dir.path(), dir.path().join("index.idx"));
+
+      fs::write(dir.path().join("late.txt"), "needle").unwrap();
+      assert!(!workspace.handle_change(&dir.path().join("late.txt")));
+      assert_eq!(workspace.pending.lock().unwrap().len(), 1);
+
+      workspace.build().unwrap();
+      assert!(workspace.pending.lock().unwrap().is_empty());
+      assert_eq!(candidate_names(&workspace, "needle"), vec!["late.txt"]);
+   }
+}
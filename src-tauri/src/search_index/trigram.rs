@@ -0,0 +1,574 @@
+use anyhow::{Context, Result, bail};
+use regex_syntax::hir::{Hir, HirKind};
+use std::{
+   collections::{HashMap, HashSet},
+   fs,
+   io::{BufReader, BufWriter, Read, Write},
+   path::{Path, PathBuf},
+   time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const MAGIC: &[u8; 8] = b"ATHASIDX";
+const FORMAT_VERSION: u32 = 1;
+
+/// Files larger than this are not indexed and are always returned as candidates.
+pub const MAX_INDEXED_FILE_SIZE: u64 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileState {
+   Indexed,
+   /// Too large to index; must always be searched.
+   Unindexed,
+   /// Binary or unreadable; can never match.
+   Skipped,
+   /// Removed or superseded by a newer entry. Its postings are dropped on compaction.
+   Deleted,
+}
+
+#[derive(Debug, Clone)]
+struct IndexedFile {
+   /// Path relative to the index root.
+   path: PathBuf,
+   modified: SystemTime,
+   size: u64,
+   state: FileState,
+}
+
+/// Mapping from lowercased byte trigrams to the files containing them.
+///
+/// File ids are only ever appended, so every posting list stays sorted. Updating a file marks
+/// its previous entry deleted and appends a new one.
+pub struct TrigramIndex {
+   root: PathBuf,
+   files: Vec<IndexedFile>,
+   by_path: HashMap<PathBuf, u32>,
+   postings: HashMap<u32, Vec<u32>>,
+}
+
+impl TrigramIndex {
+   pub fn new(root: impl Into<PathBuf>) -> Self {
+      Self {
+         root: root.into(),
+         files: Vec::new(),
+         by_path: HashMap::new(),
+         postings: HashMap::new(),
+      }
+   }
+
+   pub fn root(&self) -> &Path {
+      &self.root
+   }
+
+   /// Number of live files in the index.
+   pub fn len(&self) -> usize {
+      self.by_path.len()
+   }
+
+   /// Whether the entry for `path` matches the file's current size and modification time.
+   pub fn is_current(&self, path: &Path, modified: SystemTime, size: u64) -> bool {
+      self
+         .relative(path)
+         .and_then(|relative| self.by_path.get(relative))
+         .map(|&id| &self.files[id as usize])
+         .is_some_and(|file| file.modified == modified && file.size == size)
+   }
+
+   /// Index the current content of `path`, replacing any previous entry.
+   pub fn update_file(&mut self, path: &Path) {
+      let Some(relative) = self.relative(path).map(Path::to_path_buf) else {
+         return;
+      };
+      self.remove_relative(&relative);
+
+      let Ok(metadata) = fs::metadata(path) else {
+         return;
+      };
+      if !metadata.is_file() {
+         return;
+      }
+
+      let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+      let size = metadata.len();
+      let id = self.files.len() as u32;
+
+      let state = if size > MAX_INDEXED_FILE_SIZE {
+         FileState::Unindexed
+      } else {
+         match fs::read(path) {
+            Ok(content) if !content.contains(&0) => {
+               for trigram in content_trigrams(&content) {
+                  self.postings.entry(trigram).or_default().push(id);
+               }
+               FileState::Indexed
+            }
+            _ => FileState::Skipped,
+         }
+      };
+
+      self.files.push(IndexedFile {
+         path: relative.clone(),
+         modified,
+         size,
+         state,
+      });
+      self.by_path.insert(relative, id);
+   }
+
+   pub fn remove_file(&mut self, path: &Path) {
+      if let Some(relative) = self.relative(path).map(Path::to_path_buf) {
+         self.remove_relative(&relative);
+      }
+   }
+
+   /// Remove every file below the directory `path`.
+   pub fn remove_prefix(&mut self, path: &Path) {
+      let Some(prefix) = self.relative(path).map(Path::to_path_buf) else {
+         return;
+      };
+      let removed: Vec<PathBuf> = self
+         .by_path
+         .keys()
+         .filter(|relative| relative.starts_with(&prefix))
+         .cloned()
+         .collect();
+      for relative in removed {
+         self.remove_relative(&relative);
+      }
+   }
+
+   /// Remove every file not in `keep`, e.g. files deleted while the app was closed.
+   pub fn retain_files(&mut self, keep: &HashSet<PathBuf>) {
+      let removed: Vec<PathBuf> = self
+         .by_path
+         .keys()
+         .filter(|relative| !keep.contains(&self.root.join(relative)))
+         .cloned()
+         .collect();
+      for relative in removed {
+         self.remove_relative(&relative);
+      }
+   }
+
+   /// Absolute paths of files that may contain a match for all of `literals`, or `None` when
+   /// the literals are too short to narrow the search.
+   pub fn candidates(&self, literals: &[Vec<u8>], case_sensitive: bool) -> Option<Vec<PathBuf>> {
+      let trigrams: HashSet<u32> = literals
+         .iter()
+         .flat_map(|literal| query_trigrams(literal, case_sensitive))
+         .collect();
+      if trigrams.is_empty() {
+         return None;
+      }
+
+      let mut lists: Vec<&Vec<u32>> = Vec::with_capacity(trigrams.len());
+      for trigram in &trigrams {
+         match self.postings.get(trigram) {
+            Some(list) => lists.push(list),
+            None => {
+               lists.clear();
+               break;
+            }
+         }
+      }
+      // Intersect starting from the rarest trigram
+      lists.sort_by_key(|list| list.len());
+
+      let mut ids: Vec<u32> = lists.first().map(|list| list.to_vec()).unwrap_or_default();
+      for list in lists.iter().skip(1) {
+         ids.retain(|id| list.binary_search(id).is_ok());
+         if ids.is_empty() {
+            break;
+         }
+      }
+
+      let live = ids
+         .into_iter()
+         .map(|id| &self.files[id as usize])
+         .filter(|file| file.state == FileState::Indexed);
+      let unindexed = self
+         .files
+         .iter()
+         .filter(|file| file.state == FileState::Unindexed);
+
+      let mut paths: Vec<PathBuf> = live
+         .chain(unindexed)
+         .map(|file| self.root.join(&file.path))
+         .collect();
+      paths.sort();
+      Some(paths)
+   }
+
+   /// Share of file entries that are tombstones.
+   pub fn garbage_ratio(&self) -> f64 {
+      if self.files.is_empty() {
+         return 0.0;
+      }
+      let deleted = self.files.len() - self.by_path.len();
+      deleted as f64 / self.files.len() as f64
+   }
+
+   /// Drop deleted entries and renumber the remaining files.
+   pub fn compact(&mut self) {
+      let mut remap: Vec<Option<u32>> = vec![None; self.files.len()];
+      let mut files = Vec::with_capacity(self.by_path.len());
+      for (old_id, file) in self.files.drain(..).enumerate() {
+         if file.state != FileState::Deleted {
+            remap[old_id] = Some(files.len() as u32);
+            files.push(file);
+         }
+      }
+
+      for list in self.postings.values_mut() {
+         *list = list.iter().filter_map(|&id| remap[id as usize]).collect();
+      }
+      self.postings.retain(|_, list| !list.is_empty());
+      self.by_path = files
+         .iter()
+         .enumerate()
+         .map(|(id, file)| (file.path.clone(), id as u32))
+         .collect();
+      self.files = files;
+   }
+
+   pub fn save(&self, path: &Path) -> Result<()> {
+      if let Some(parent) = path.parent() {
+         fs::create_dir_all(parent)?;
+      }
+
+      // Write to a sibling file first so a crash never leaves a truncated index behind
+      let temp_path = path.with_extension("tmp");
+      {
+         let mut writer = BufWriter::new(fs::File::create(&temp_path)?);
+         writer.write_all(MAGIC)?;
+         write_u32(&mut writer, FORMAT_VERSION)?;
+         write_str(&mut writer, &self.root.to_string_lossy())?;
+
+         write_u32(&mut writer, self.files.len() as u32)?;
+         for file in &self.files {
+            let modified = file.modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+            write_str(&mut writer, &file.path.to_string_lossy())?;
+            write_u64(&mut writer, modified.as_secs())?;
+            write_u32(&mut writer, modified.subsec_nanos())?;
+            write_u64(&mut writer, file.size)?;
+            writer.write_all(&[match file.state {
+               FileState::Indexed => 0,
+               FileState::Unindexed => 1,
+               FileState::Skipped => 2,
+               FileState::Deleted => 3,
+            }])?;
+         }
+
+         write_u32(&mut writer, self.postings.len() as u32)?;
+         for (trigram, list) in &self.postings {
+            write_u32(&mut writer, *trigram)?;
+            write_u32(&mut writer, list.len() as u32)?;
+            for id in list {
+               write_u32(&mut writer, *id)?;
+            }
+         }
+         writer.flush()?;
+      }
+
+      fs::rename(&temp_path, path)?;
+      Ok(())
+   }
+
+   pub fn load(path: &Path) -> Result<Self> {
+      let mut reader = BufReader::new(fs::File::open(path)?);
+
+      let mut magic = [0u8; 8];
+      reader.read_exact(&mut magic)?;
+      if &magic != MAGIC {
+         bail!("Not a search index file");
+      }
+      let version = read_u32(&mut reader)?;
+      if version != FORMAT_VERSION {
+         bail!("Unsupported search index version {}", version);
+      }
+
+      let mut index = Self::new(read_str(&mut reader)?);
+
+      let file_count = read_u32(&mut reader)?;
+      for id in 0..file_count {
+         let path = PathBuf::from(read_str(&mut reader)?);
+         let secs = read_u64(&mut reader)?;
+         let nanos = read_u32(&mut reader)?;
+         let size = read_u64(&mut reader)?;
+         let mut state = [0u8; 1];
+         reader.read_exact(&mut state)?;
+         let state = match state[0] {
+            0 => FileState::Indexed,
+            1 => FileState::Unindexed,
+            2 => FileState::Skipped,
+            3 => FileState::Deleted,
+            other => bail!("Invalid file state {} in search index", other),
+         };
+
+         if state != FileState::Deleted {
+            index.by_path.insert(path.clone(), id);
+         }
+         index.files.push(IndexedFile {
+            path,
+            modified: UNIX_EPOCH + Duration::new(secs, nanos),
+            size,
+            state,
+         });
+      }
+
+      let posting_count = read_u32(&mut reader)?;
+      for _ in 0..posting_count {
+         let trigram = read_u32(&mut reader)?;
+         let len = read_u32(&mut reader)?;
+         let mut list = Vec::with_capacity(len as usize);
+         for _ in 0..len {
+            let id = read_u32(&mut reader)?;
+            if id >= file_count {
+               bail!("Corrupt search index posting");
+            }
+            list.push(id);
+         }
+         index.postings.insert(trigram, list);
+      }
+
+      Ok(index)
+   }
+
+   fn relative<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+      path.strip_prefix(&self.root).ok()
+   }
+
+   fn remove_relative(&mut self, relative: &Path) {
+      if let Some(id) = self.by_path.remove(relative) {
+         self.files[id as usize].state = FileState::Deleted;
+      }
+   }
+}
+
+fn pack(window: &[u8]) -> u32 {
+   (window[0] as u32) << 16 | (window[1] as u32) << 8 | window[2] as u32
+}
+
+/// Distinct trigrams of `content` after ASCII lowercasing. Trigrams spanning a line break are
+/// skipped since search never matches across lines.
+fn content_trigrams(content: &[u8]) -> HashSet<u32> {
+   let lowered = content.to_ascii_lowercase();
+   lowered
+      .windows(3)
+      .filter(|window| !window.contains(&b'\n'))
+      .map(pack)
+      .collect()
+}
+
+/// Trigrams every matching line must contain. Case-insensitive queries skip windows whose
+/// bytes could match different bytes under Unicode case folding.
+fn query_trigrams(literal: &[u8], case_sensitive: bool) -> Vec<u32> {
+   let lowered = literal.to_ascii_lowercase();
+   lowered
+      .windows(3)
+      .filter(|window| !window.contains(&b'\n'))
+      .filter(|window| {
+         // `k` and `s` also fold to the Kelvin sign and long s
+         case_sensitive
+            || window
+               .iter()
+               .all(|&b| b.is_ascii() && b != b'k' && b != b's')
+      })
+      .map(pack)
+      .collect()
+}
+
+/// Literal substrings that every match of `query` must contain.
+pub fn required_literals(query: &str, use_regex: bool) -> Vec<Vec<u8>> {
+   if !use_regex {
+      return vec![query.as_bytes().to_vec()];
+   }
+
+   let Ok(hir) = regex_syntax::Parser::new().parse(query) else {
+      return Vec::new();
+   };
+   let mut literals = Vec::new();
+   collect_required_literals(&hir, &mut literals);
+   literals
+}
+
+fn collect_required_literals(hir: &Hir, out: &mut Vec<Vec<u8>>) {
+   match hir.kind() {
+      HirKind::Literal(literal) => out.push(literal.0.to_vec()),
+      HirKind::Capture(capture) => collect_required_literals(&capture.sub, out),
+      HirKind::Repetition(repetition) if repetition.min >= 1 => {
+         collect_required_literals(&repetition.sub, out)
+      }
+      HirKind::Concat(subs) => {
+         let mut run: Vec<u8> = Vec::new();
+         for sub in subs {
+            match sub.kind() {
+               HirKind::Literal(literal) => run.extend_from_slice(&literal.0),
+               // Zero-width assertions keep the surrounding literals adjacent
+               HirKind::Look(_) => {}
+               _ => {
+                  if !run.is_empty() {
+                     out.push(std::mem::take(&mut run));
+                  }
+                  collect_required_literals(sub, out);
+               }
+            }
+         }
+         if !run.is_empty() {
+            out.push(run);
+         }
+      }
+      // Alternations, classes and optional repetitions guarantee no particular literal
+      _ => {}
+   }
+}
+
+fn write_u32(writer: &mut impl Write, value: u32) -> Result<()> {
+   writer.write_all(&value.to_le_bytes())?;
+   Ok(())
+}
+
+fn write_u64(writer: &mut impl Write, value: u64) -> Result<()> {
+   writer.write_all(&value.to_le_bytes())?;
+   Ok(())
+}
+
+fn write_str(writer: &mut impl Write, value: &str) -> Result<()> {
+   write_u32(writer, value.len() as u32)?;
+   writer.write_all(value.as_bytes())?;
+   Ok(())
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32> {
+   let mut bytes = [0u8; 4];
+   reader.read_exact(&mut bytes)?;
+   Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64> {
+   let mut bytes = [0u8; 8];
+   reader.read_exact(&mut bytes)?;
+   Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_str(reader: &mut impl Read) -> Result<String> {
+   let len = read_u32(reader)? as usize;
+   let mut bytes = vec![0u8; len];
+   reader.read_exact(&mut bytes)?;
+   String::from_utf8(bytes).context("Invalid path in search index")
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn index_with(files: &[(&str, &str)]) -> (tempfile::TempDir, TrigramIndex) {
+      let dir = tempfile::tempdir().unwrap();
+      let mut index = TrigramIndex::new(dir.path());
+      for (name, content) in files {
+         let path = dir.path().join(name);
+         fs::write(&path, content).unwrap();
+         index.update_file(&path);
+      }
+      (dir, index)
+   }
+
+   fn names(paths: Option<Vec<PathBuf>>) -> Vec<String> {
+      paths
+         .unwrap()
+         .iter()
+         .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+         .collect()
+   }
+
+   #[test]
+   fn test_candidates_narrow_to_files_with_all_trigrams() {
+      let (_dir, index) = index_with(&[
+         ("a.rs", "fn parse_request() {}"),
+         ("b.rs", "fn PARSE() {}\nrequest"),
+         ("c.rs", "fn render() {}"),
+         ("d.bin", "parse\0"),
+      ]);
+
+      assert_eq!(
+         names(index.candidates(&required_literals("parse", false), false)),
+         vec!["a.rs", "b.rs"]
+      );
+      // Trigrams spanning lines are not indexed, so b.rs cannot match the whole phrase
+      assert_eq!(
+         names(index.candidates(&required_literals("parse_request", false), true)),
+         vec!["a.rs"]
+      );
+      assert!(names(index.candidates(&required_literals("missing", false), true)).is_empty());
+      assert!(
+         index
+            .candidates(&required_literals("fn", false), true)
+            .is_none()
+      );
+   }
+
+   #[test]
+   fn test_update_and_remove_replace_entries() {
+      let (dir, mut index) = index_with(&[("a.txt", "alpha"), ("b.txt", "beta")]);
+      let a = dir.path().join("a.txt");
+
+      fs::write(&a, "gamma").unwrap();
+      index.update_file(&a);
+      index.remove_file(&dir.path().join("b.txt"));
+
+      let literals = |query: &str| required_literals(query, false);
+      assert!(names(index.candidates(&literals("alpha"), true)).is_empty());
+      assert!(names(index.candidates(&literals("beta"), true)).is_empty());
+      assert_eq!(
+         names(index.candidates(&literals("gamma"), true)),
+         vec!["a.txt"]
+      );
+      assert_eq!(index.len(), 1);
+      assert!(index.garbage_ratio() > 0.5);
+
+      index.compact();
+      assert_eq!(index.garbage_ratio(), 0.0);
+      assert_eq!(
+         names(index.candidates(&literals("gamma"), true)),
+         vec!["a.txt"]
+      );
+   }
+
+   #[test]
+   fn test_save_and_load_roundtrip() {
+      let (dir, index) = index_with(&[("a.txt", "hello world"), ("b.txt", "goodbye")]);
+      let index_path = dir.path().join("index").join("test.idx");
+      index.save(&index_path).unwrap();
+
+      let loaded = TrigramIndex::load(&index_path).unwrap();
+      let a = dir.path().join("a.txt");
+      let metadata = fs::metadata(&a).unwrap();
+
+      assert_eq!(loaded.root(), dir.path());
+      assert_eq!(loaded.len(), 2);
+      assert!(loaded.is_current(&a, metadata.modified().unwrap(), metadata.len()));
+      assert_eq!(
+         names(loaded.candidates(&required_literals("world", false), true)),
+         vec!["a.txt"]
+      );
+
+      fs::write(&index_path, b"garbage").unwrap();
+      assert!(TrigramIndex::load(&index_path).is_err());
+   }
+
+   #[test]
+   fn test_required_literals_from_regex() {
+      let literals = |query: &str| -> Vec<String> {
+         required_literals(query, true)
+            .into_iter()
+            .map(|l| String::from_utf8(l).unwrap())
+            .collect()
+      };
+
+      assert_eq!(literals(r"foo\d+bar"), vec!["foo", "bar"]);
+      assert_eq!(literals(r"\bhello\b"), vec!["hello"]);
+      assert_eq!(literals(r"(abc)+x?"), vec!["abc"]);
+      assert!(literals(r"foo|bar").is_empty());
+      assert!(literals(r"(unclosed").is_empty());
+      assert_eq!(required_literals("a.b", false), vec![b"a.b".to_vec()]);
+   }
+}
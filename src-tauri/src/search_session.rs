@@ -0,0 +1,391 @@
+use crate::commands::search::{
+   FileSearchOutcome, FileSearchResult, SearchFilesRequest, SearchLimits, SearchMatcher,
+   search_file, search_walker,
+};
+use anyhow::{Result, anyhow, bail};
+use crossbeam_channel::RecvTimeoutError;
+use ignore::WalkState;
+use serde::Serialize;
+use std::{
+   collections::HashMap,
+   sync::{
+      Arc, Mutex,
+      atomic::{AtomicBool, Ordering},
+   },
+   thread,
+   time::{Duration, Instant},
+};
+use tauri::{AppHandle, Emitter};
+use uuid::Uuid;
+
+/// How long results are held back before being emitted as a batch.
+const BATCH_INTERVAL: Duration = Duration::from_millis(100);
+const BATCH_SIZE: usize = 50;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResultsEvent {
+   pub session_id: String,
+   pub results: Vec<FileSearchResult>,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct SearchProgressEvent {
+   pub session_id: String,
+   pub files_scanned: usize,
+   /// Files skipped for being too large, binary or unreadable.
+   pub files_skipped: usize,
+   pub files_matched: usize,
+   pub total_matches: usize,
+   /// Set once `max_results` files have matched and the walk stopped early.
+   pub truncated: bool,
+   pub cancelled: bool,
+   pub done: bool,
+}
+
+pub enum SearchEvent {
+   Results(SearchResultsEvent),
+   Progress(SearchProgressEvent),
+}
+
+enum WorkerMessage {
+   Scanned,
+   Skipped,
+   Matched(FileSearchResult),
+}
+
+/// Runs searches on background threads and streams their results as `search://results`,
+/// `search://progress` and `search://complete` events.
+pub struct SearchSessionManager {
+   app_handle: AppHandle,
+   sessions: Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>,
+}
+
+impl SearchSessionManager {
+   pub fn new(app_handle: AppHandle) -> Self {
+      Self {
+         app_handle,
+         sessions: Arc::new(Mutex::new(HashMap::new())),
+      }
+   }
+
+   /// Validate the request and start searching. Returns the session id used in events.
+   pub fn start(&self, request: SearchFilesRequest) -> Result<String> {
+      if request.query.is_empty() {
+         bail!("Search query is empty");
+      }
+
+      // Surface pattern and glob errors to the caller instead of as an empty result stream
+      SearchMatcher::from_request(&request).map_err(|e| anyhow!(e))?;
+      for root_request in request.per_root() {
+         search_walker(&root_request).map_err(|e| anyhow!(e))?;
+      }
+
+      let session_id = Uuid::new_v4().to_string();
+      let cancelled = Arc::new(AtomicBool::new(false));
+      self
+         .sessions
+         .lock()
+         .unwrap()
+         .insert(session_id.clone(), cancelled.clone());
+
+      let app_handle = self.app_handle.clone();
+      let sessions = self.sessions.clone();
+      let id = session_id.clone();
+      thread::spawn(move || {
+         run_search(&id, &request, &cancelled, |event| {
+            let _ = match event {
+               SearchEvent::Results(results) => app_handle.emit("search://results", results),
+               SearchEvent::Progress(progress) if progress.done => {
+                  app_handle.emit("search://complete", progress)
+               }
+               SearchEvent::Progress(progress) => app_handle.emit("search://progress", progress),
+            };
+         });
+         sessions.lock().unwrap().remove(&id);
+      });
+
+      Ok(session_id)
+   }
+
+   /// Cancel a running session. Unknown ids are ignored, since the session may have finished
+   /// between its last event and the cancel request.
+   pub fn cancel(&self, session_id: &str) {
+      if let Some(cancelled) = self.sessions.lock().unwrap().remove(session_id) {
+         cancelled.store(true, Ordering::Relaxed);
+      }
+   }
+}
+
+/// Walk each request root in parallel and report results through `on_event` until the walks
+/// finish, `max_results` files have matched, or `cancelled` is set. The final event is always a
+/// progress event with `done` set.
+pub fn run_search<F>(
+   session_id: &str,
+   request: &SearchFilesRequest,
+   cancelled: &AtomicBool,
+   mut on_event: F,
+) where
+   F: FnMut(SearchEvent),
+{
+   let mut progress = SearchProgressEvent {
+      session_id: session_id.to_string(),
+      ..Default::default()
+   };
+
+   match SearchMatcher::from_request(request) {
+      Ok(matcher) => {
+         let limit_reached = AtomicBool::new(false);
+         for root_request in request.per_root() {
+            if cancelled.load(Ordering::Relaxed) || limit_reached.load(Ordering::Relaxed) {
+               break;
+            }
+            let search = RootSearch {
+               session_id,
+               request: &root_request,
+               matcher: &matcher,
+               cancelled,
+               limit_reached: &limit_reached,
+            };
+            search.run(&mut progress, &mut on_event);
+         }
+         if cancelled.load(Ordering::Relaxed) {
+            progress.cancelled = true;
+         }
+      }
+      Err(e) => {
+         log::warn!("[Search] Session {} failed to start: {}", session_id, e);
+      }
+   }
+
+   progress.done = true;
+   on_event(SearchEvent::Progress(progress));
+}
+
+/// The walk of a single root within a session. Progress accumulates across roots.
+struct RootSearch<'a> {
+   session_id: &'a str,
+   request: &'a SearchFilesRequest,
+   matcher: &'a SearchMatcher,
+   cancelled: &'a AtomicBool,
+   limit_reached: &'a AtomicBool,
+}
+
+impl RootSearch<'_> {
+   fn run<F>(&self, progress: &mut SearchProgressEvent, on_event: &mut F)
+   where
+      F: FnMut(SearchEvent),
+   {
+      let (walker, filter) = match search_walker(self.request) {
+         Ok(walker) => walker,
+         Err(e) => {
+            log::warn!(
+               "[Search] Session {} skipped root {}: {}",
+               self.session_id,
+               self.request.root_path,
+               e
+            );
+            return;
+         }
+      };
+
+      let limits = SearchLimits::from_request(self.request);
+      let max_results = self.request.max_results.unwrap_or(100);
+      let (sender, receiver) = crossbeam_channel::unbounded::<WorkerMessage>();
+
+      thread::scope(|scope| {
+         let (matcher, filter, cancelled, limit_reached) =
+            (self.matcher, &filter, self.cancelled, self.limit_reached);
+         scope.spawn(move || {
+            walker.build_parallel().run(|| {
+               let sender = sender.clone();
+               Box::new(move |entry| {
+                  if cancelled.load(Ordering::Relaxed) || limit_reached.load(Ordering::Relaxed) {
+                     return WalkState::Quit;
+                  }
+
+                  let Ok(entry) = entry else {
+                     return WalkState::Continue;
+                  };
+                  if !entry.file_type().is_some_and(|t| t.is_file())
+                     || !filter.accepts(entry.path())
+                  {
+                     return WalkState::Continue;
+                  }
+
+                  let message = match search_file(entry.path(), matcher, limits) {
+                     FileSearchOutcome::Matched(result) => WorkerMessage::Matched(result),
+                     FileSearchOutcome::NoMatches => WorkerMessage::Scanned,
+                     FileSearchOutcome::Skipped => WorkerMessage::Skipped,
+                  };
+                  if sender.send(message).is_err() {
+                     return WalkState::Quit;
+                  }
+                  WalkState::Continue
+               })
+            });
+         });
+
+         let mut batch: Vec<FileSearchResult> = Vec::new();
+         let mut last_flush = Instant::now();
+
+         loop {
+            let disconnected = match receiver.recv_timeout(BATCH_INTERVAL) {
+               Ok(WorkerMessage::Scanned) => {
+                  progress.files_scanned += 1;
+                  false
+               }
+               Ok(WorkerMessage::Skipped) => {
+                  progress.files_skipped += 1;
+                  false
+               }
+               Ok(WorkerMessage::Matched(result)) => {
+                  progress.files_scanned += 1;
+                  // Workers may finish a few files after the limit is hit; drop those
+                  if progress.files_matched < max_results {
+                     progress.files_matched += 1;
+                     progress.total_matches += result.total_matches;
+                     batch.push(result);
+                     if progress.files_matched >= max_results {
+                        progress.truncated = true;
+                        limit_reached.store(true, Ordering::Relaxed);
+                     }
+                  }
+                  false
+               }
+               Err(RecvTimeoutError::Timeout) => false,
+               Err(RecvTimeoutError::Disconnected) => true,
+            };
+
+            if cancelled.load(Ordering::Relaxed) {
+               // Stop reporting as soon as the session is cancelled; workers quit on their own
+               break;
+            }
+
+            if disconnected || batch.len() >= BATCH_SIZE || last_flush.elapsed() >= BATCH_INTERVAL {
+               if !batch.is_empty() {
+                  on_event(SearchEvent::Results(SearchResultsEvent {
+                     session_id: self.session_id.to_string(),
+                     results: std::mem::take(&mut batch),
+                  }));
+               }
+               on_event(SearchEvent::Progress(progress.clone()));
+               last_flush = Instant::now();
+            }
+
+            if disconnected {
+               break;
+            }
+         }
+      });
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use std::fs;
+
+   fn request(root: &std::path::Path, query: &str) -> SearchFilesRequest {
+      SearchFilesRequest {
+         root_path: root.to_string_lossy().to_string(),
+         root_paths: None,
+         query: query.to_string(),
+         case_sensitive: None,
+         max_results: None,
+         use_regex: None,
+         whole_word: None,
+         include_globs: None,
+         exclude_globs: None,
+         include_ignored: None,
+         max_matches_per_file: None,
+         max_file_size: None,
+      }
+   }
+
+   fn collect(
+      request: &SearchFilesRequest,
+      cancelled: &AtomicBool,
+   ) -> (Vec<String>, SearchProgressEvent) {
+      let mut files = Vec::new();
+      let mut last = None;
+      run_search("test", request, cancelled, |event| match event {
+         SearchEvent::Results(event) => {
+            files.extend(event.results.into_iter().map(|r| r.file_path));
+         }
+         SearchEvent::Progress(event) => {
+            assert!(last.as_ref().is_none_or(|p: &SearchProgressEvent| !p.done));
+            last = Some(event);
+         }
+      });
+      files.sort();
+      (files, last.unwrap())
+   }
+
+   #[test]
+   fn test_streams_all_results_and_progress() {
+      let dir = tempfile::tempdir().unwrap();
+      for i in 0..120 {
+         let content = if i % 3 == 0 { "needle" } else { "hay" };
+         fs::write(dir.path().join(format!("{i}.txt")), content).unwrap();
+      }
+      fs::write(dir.path().join("big.txt"), "needle ".repeat(100)).unwrap();
+
+      let mut req = request(dir.path(), "needle");
+      req.max_results = Some(1000);
+      req.max_file_size = Some(100);
+      let (files, progress) = collect(&req, &AtomicBool::new(false));
+
+      assert_eq!(files.len(), 40);
+      assert!(progress.done);
+      assert!(!progress.truncated && !progress.cancelled);
+      assert_eq!(progress.files_matched, 40);
+      assert_eq!(progress.files_scanned, 120);
+      assert_eq!(progress.files_skipped, 1);
+   }
+
+   #[test]
+   fn test_stops_at_max_results() {
+      let dir = tempfile::tempdir().unwrap();
+      for i in 0..50 {
+         fs::write(dir.path().join(format!("{i}.txt")), "needle").unwrap();
+      }
+
+      let mut req = request(dir.path(), "needle");
+      req.max_results = Some(5);
+      let (files, progress) = collect(&req, &AtomicBool::new(false));
+
+      assert_eq!(files.len(), 5);
+      assert!(progress.truncated);
+      assert_eq!(progress.files_matched, 5);
+   }
+
+   #[test]
+   fn test_searches_every_root() {
+      let (api, web) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+      fs::write(api.path().join("a.txt"), "needle").unwrap();
+      fs::write(web.path().join("b.txt"), "needle").unwrap();
+      fs::write(web.path().join("c.txt"), "hay").unwrap();
+
+      let mut req = request(api.path(), "needle");
+      req.root_paths = Some(vec![
+         api.path().to_string_lossy().to_string(),
+         web.path().to_string_lossy().to_string(),
+      ]);
+      let (files, progress) = collect(&req, &AtomicBool::new(false));
+
+      assert_eq!(files.len(), 2);
+      assert_eq!(progress.files_scanned, 3);
+      assert_eq!(progress.files_matched, 2);
+   }
+
+   #[test]
+   fn test_cancelled_session_reports_nothing() {
+      let dir = tempfile::tempdir().unwrap();
+      fs::write(dir.path().join("a.txt"), "needle").unwrap();
+
+      let (files, progress) = collect(&request(dir.path(), "needle"), &AtomicBool::new(true));
+
+      assert!(files.is_empty());
+      assert!(progress.cancelled && progress.done);
+   }
+}
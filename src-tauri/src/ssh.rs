@@ -1,5 +1,6 @@
+use crate::lsp::LspManager;
 use serde::{Deserialize, Serialize};
-use ssh2::{Session, Sftp};
+use ssh2::{Channel, Session, Sftp};
 use std::{
    collections::HashMap,
    env, fs,
@@ -7,6 +8,8 @@ use std::{
    net::TcpStream,
    path::Path,
    sync::{Arc, Mutex},
+   thread,
+   time::Duration,
 };
 use tauri::{Manager, command};
 
@@ -25,8 +28,14 @@ type ConnectionStorage = Arc<Mutex<HashMap<String, (Session, Option<Sftp>)>>>;
 
 lazy_static::lazy_static! {
     static ref CONNECTIONS: ConnectionStorage = Arc::new(Mutex::new(HashMap::new()));
+    /// A second session per connection for language servers, opened while the credentials are
+    /// at hand. It is kept non-blocking, as every server polls its own channel on it.
+    static ref LSP_SESSIONS: Mutex<HashMap<String, Session>> = Mutex::new(HashMap::new());
 }
 
+/// How long to wait before retrying an operation on a non-blocking session.
+const RETRY_INTERVAL: Duration = Duration::from_millis(5);
+
 #[derive(Debug, Clone)]
 struct SshConfig {
    hostname: Option<String>,
@@ -194,6 +203,98 @@ pub fn create_ssh_session(
    Ok(sess)
 }
 
+/// Run `command` on the host of a connection, on the session set aside for language servers.
+/// libssh2 serializes every call on a session, so a long-running program such as a language
+/// server would otherwise stall file operations on the connection. The session is
+/// non-blocking; see [`retry_while_blocked`].
+pub fn open_exec_channel(connection_id: &str, command: &str) -> Result<Channel, String> {
+   let session = LSP_SESSIONS
+      .lock()
+      .map_err(|e| format!("Failed to lock connections: {}", e))?
+      .get(connection_id)
+      .cloned()
+      .ok_or("Language servers cannot run on this connection")?;
+
+   let mut channel = retry_while_blocked(|| session.channel_session())
+      .map_err(|e| format!("Failed to create channel: {}", e))?;
+   retry_while_blocked(|| channel.exec(command))
+      .map_err(|e| format!("Failed to execute command: {}", e))?;
+
+   Ok(channel)
+}
+
+/// Run an operation on a non-blocking session until it no longer would block.
+pub fn retry_while_blocked<T>(
+   mut operation: impl FnMut() -> Result<T, ssh2::Error>,
+) -> std::io::Result<T> {
+   loop {
+      match operation().map_err(std::io::Error::from) {
+         Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => thread::sleep(RETRY_INTERVAL),
+         result => return result,
+      }
+   }
+}
+
+/// Read a text file on the host of a connection, over SFTP when the connection has it.
+pub fn read_file(connection_id: &str, file_path: &str) -> Result<String, String> {
+   let connections = CONNECTIONS
+      .lock()
+      .map_err(|e| format!("Failed to lock connections: {}", e))?;
+   let (session, sftp_opt) = connections
+      .get(connection_id)
+      .ok_or("Connection not found")?;
+
+   let mut content = String::new();
+   if let Some(sftp) = sftp_opt {
+      sftp
+         .open(Path::new(file_path))
+         .map_err(|e| format!("Failed to open file: {}", e))?
+         .read_to_string(&mut content)
+         .map_err(|e| format!("Failed to read file: {}", e))?;
+   } else {
+      let mut channel = session
+         .channel_session()
+         .map_err(|e| format!("Failed to create channel: {}", e))?;
+      channel
+         .exec(&format!("cat '{}'", file_path.replace('\'', r"'\''")))
+         .map_err(|e| format!("Failed to execute command: {}", e))?;
+      channel
+         .read_to_string(&mut content)
+         .map_err(|e| format!("Failed to read file: {}", e))?;
+      channel.wait_close().ok();
+      if channel.exit_status().ok() != Some(0) {
+         return Err(format!("Failed to read {}", file_path));
+      }
+   }
+   Ok(content)
+}
+
+/// Close a connection and stop the language servers running on its host.
+fn close_connection(app: &tauri::AppHandle, connection_id: &str) -> Result<(), String> {
+   if let Some(lsp_manager) = app.try_state::<LspManager>() {
+      lsp_manager.shutdown_connection(connection_id);
+   }
+   let lsp_session = LSP_SESSIONS
+      .lock()
+      .map_err(|e| format!("Failed to lock connections: {}", e))?
+      .remove(connection_id);
+   if let Some(session) = lsp_session {
+      let _ = retry_while_blocked(|| session.disconnect(None, "Disconnecting", None));
+   }
+
+   let mut connections = CONNECTIONS
+      .lock()
+      .map_err(|e| format!("Failed to lock connections: {}", e))?;
+   if let Some((session, sftp_opt)) = connections.remove(connection_id) {
+      // Explicitly close SFTP handle before disconnecting session
+      if let Some(sftp) = sftp_opt {
+         drop(sftp);
+      }
+      let _ = session.disconnect(None, "Disconnecting", None);
+   }
+   Ok(())
+}
+
 #[command]
 pub async fn ssh_connect(
    connection_id: String,
@@ -222,6 +323,17 @@ pub async fn ssh_connect(
       None
    };
 
+   // Without it the connection still works, only language servers cannot run on the host
+   let lsp_session = create_ssh_session(
+      &host,
+      port,
+      &username,
+      password.as_deref(),
+      key_path.as_deref(),
+   )
+   .inspect(|session| session.set_blocking(false))
+   .inspect_err(|e| log::warn!("Failed to open a session for language servers: {}", e))
+   .ok();
    let connection = SshConnection {
       id: connection_id.clone(),
       name: format!("{}@{}", username, host),
@@ -236,7 +348,13 @@ pub async fn ssh_connect(
       let mut connections = CONNECTIONS
          .lock()
          .map_err(|e| format!("Failed to lock connections: {}", e))?;
-      connections.insert(connection_id, (session, sftp));
+      connections.insert(connection_id.clone(), (session, sftp));
+   }
+   if let Some(session) = lsp_session {
+      LSP_SESSIONS
+         .lock()
+         .map_err(|e| format!("Failed to lock connections: {}", e))?
+         .insert(connection_id, session);
    }
 
    Ok(connection)
@@ -244,16 +362,7 @@ pub async fn ssh_connect(
 
 #[command]
 pub async fn ssh_disconnect(app: tauri::AppHandle, connection_id: String) -> Result<(), String> {
-   let mut connections = CONNECTIONS
-      .lock()
-      .map_err(|e| format!("Failed to lock connections: {}", e))?;
-   if let Some((session, sftp_opt)) = connections.remove(&connection_id) {
-      // Explicitly close SFTP handle before disconnecting session
-      if let Some(sftp) = sftp_opt {
-         drop(sftp);
-      }
-      let _ = session.disconnect(None, "Disconnecting", None);
-   }
+   close_connection(&app, &connection_id)?;
 
    // Close the remote window if it exists
    let window_label = format!("remote-{}", connection_id);
@@ -265,19 +374,11 @@ pub async fn ssh_disconnect(app: tauri::AppHandle, connection_id: String) -> Res
 }
 
 #[command]
-pub async fn ssh_disconnect_only(connection_id: String) -> Result<(), String> {
-   let mut connections = CONNECTIONS
-      .lock()
-      .map_err(|e| format!("Failed to lock connections: {}", e))?;
-   if let Some((session, sftp_opt)) = connections.remove(&connection_id) {
-      // Explicitly close SFTP handle before disconnecting session
-      if let Some(sftp) = sftp_opt {
-         drop(sftp);
-      }
-      let _ = session.disconnect(None, "Disconnecting", None);
-   }
-
-   Ok(())
+pub async fn ssh_disconnect_only(
+   app: tauri::AppHandle,
+   connection_id: String,
+) -> Result<(), String> {
+   close_connection(&app, &connection_id)
 }
 
 #[command]
@@ -0,0 +1,17 @@
+use std::fs;
+use tempfile::TempDir;
+
+/// A temporary directory holding `files`, given as relative path and content. The directory
+/// name is not hidden, so walkers that skip dotfiles still see its contents.
+pub fn create_tree(files: &[(&str, &str)]) -> TempDir {
+   let dir = tempfile::Builder::new()
+      .prefix("athas-test")
+      .tempdir()
+      .unwrap();
+   for (relative, content) in files {
+      let path = dir.path().join(relative);
+      fs::create_dir_all(path.parent().unwrap()).unwrap();
+      fs::write(path, content).unwrap();
+   }
+   dir
+}
@@ -0,0 +1,187 @@
+use crate::commands::search::SearchFilesRequest;
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+   collections::HashSet,
+   path::{Path, PathBuf},
+   sync::Mutex,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WorkspaceRoot {
+   pub name: String,
+   pub path: String,
+}
+
+impl WorkspaceRoot {
+   /// A root named after its folder.
+   pub fn from_path(path: &str) -> Self {
+      let name = Path::new(path)
+         .file_name()
+         .map(|name| name.to_string_lossy().to_string())
+         .unwrap_or_else(|| path.to_string());
+      Self {
+         name,
+         path: path.to_string(),
+      }
+   }
+}
+
+/// Roots added and removed by a change to the workspace.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WorkspaceRootsChange {
+   pub roots: Vec<WorkspaceRoot>,
+   pub added: Vec<WorkspaceRoot>,
+   pub removed: Vec<WorkspaceRoot>,
+}
+
+/// The folders open in the window. Most workspaces have a single root; multi-root workspaces
+/// open several sibling folders side by side, each with a unique name.
+pub struct WorkspaceManager {
+   roots: Mutex<Vec<WorkspaceRoot>>,
+}
+
+impl WorkspaceManager {
+   pub fn new() -> Self {
+      Self {
+         roots: Mutex::new(Vec::new()),
+      }
+   }
+
+   pub fn roots(&self) -> Vec<WorkspaceRoot> {
+      self.roots.lock().unwrap().clone()
+   }
+
+   /// Replace every root. Roots are validated as a whole, so an invalid entry leaves the
+   /// workspace unchanged.
+   pub fn set_roots(&self, roots: Vec<WorkspaceRoot>) -> Result<WorkspaceRootsChange> {
+      self.update(|current| {
+         *current = roots;
+         Ok(())
+      })
+   }
+
+   pub fn add_root(&self, root: WorkspaceRoot) -> Result<WorkspaceRootsChange> {
+      self.update(|roots| {
+         roots.push(root);
+         Ok(())
+      })
+   }
+
+   pub fn remove_root(&self, name: &str) -> Result<WorkspaceRootsChange> {
+      self.update(|roots| {
+         let before = roots.len();
+         roots.retain(|root| root.name != name);
+         if roots.len() == before {
+            bail!("No workspace root named '{}'", name);
+         }
+         Ok(())
+      })
+   }
+
+   /// Edit a copy of the roots and keep it if it is valid, all under one lock so concurrent
+   /// edits cannot overwrite each other.
+   fn update(
+      &self,
+      edit: impl FnOnce(&mut Vec<WorkspaceRoot>) -> Result<()>,
+   ) -> Result<WorkspaceRootsChange> {
+      let mut current = self.roots.lock().unwrap();
+      let mut roots = current.clone();
+      edit(&mut roots)?;
+      validate_roots(&roots)?;
+
+      let change = WorkspaceRootsChange {
+         added: roots
+            .iter()
+            .filter(|root| !current.contains(root))
+            .cloned()
+            .collect(),
+         removed: current
+            .iter()
+            .filter(|root| !roots.contains(root))
+            .cloned()
+            .collect(),
+         roots: roots.clone(),
+      };
+      *current = roots;
+      Ok(change)
+   }
+
+   /// Search every root when the request does not name any.
+   pub fn scope_search(&self, mut request: SearchFilesRequest) -> SearchFilesRequest {
+      if request.root_path.is_empty() && request.root_paths.as_ref().is_none_or(Vec::is_empty) {
+         request.root_paths = Some(self.roots().into_iter().map(|root| root.path).collect());
+      }
+      request
+   }
+}
+
+fn validate_roots(roots: &[WorkspaceRoot]) -> Result<()> {
+   let mut names = HashSet::new();
+   let mut paths: HashSet<PathBuf> = HashSet::new();
+   for root in roots {
+      if root.name.trim().is_empty() {
+         bail!("Workspace root name cannot be empty");
+      }
+      if !names.insert(root.name.as_str()) {
+         bail!("Duplicate workspace root name '{}'", root.name);
+      }
+      if !Path::new(&root.path).is_dir() {
+         bail!("Workspace root does not exist: {}", root.path);
+      }
+      if !paths.insert(PathBuf::from(&root.path)) {
+         bail!("Folder is already a workspace root: {}", root.path);
+      }
+   }
+   Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn root(name: &str, path: &Path) -> WorkspaceRoot {
+      WorkspaceRoot {
+         name: name.to_string(),
+         path: path.to_string_lossy().to_string(),
+      }
+   }
+
+   #[test]
+   fn test_set_roots_reports_changes() {
+      let dir = tempfile::tempdir().unwrap();
+      let (api, web) = (dir.path().join("api"), dir.path().join("web"));
+      std::fs::create_dir_all(&api).unwrap();
+      std::fs::create_dir_all(&web).unwrap();
+      let workspace = WorkspaceManager::new();
+
+      let change = workspace.set_roots(vec![root("api", &api)]).unwrap();
+      assert_eq!(change.added, vec![root("api", &api)]);
+
+      let change = workspace.add_root(root("web", &web)).unwrap();
+      assert_eq!(change.added, vec![root("web", &web)]);
+      assert!(change.removed.is_empty());
+      assert_eq!(workspace.roots().len(), 2);
+
+      let change = workspace.remove_root("api").unwrap();
+      assert_eq!(change.removed, vec![root("api", &api)]);
+      assert!(workspace.remove_root("api").is_err());
+   }
+
+   #[test]
+   fn test_invalid_roots_are_rejected() {
+      let dir = tempfile::tempdir().unwrap();
+      let workspace = WorkspaceManager::new();
+
+      let duplicate_name = vec![root("a", dir.path()), root("a", &dir.path().join(".."))];
+      assert!(workspace.set_roots(duplicate_name).is_err());
+      let duplicate_path = vec![root("a", dir.path()), root("b", dir.path())];
+      assert!(workspace.set_roots(duplicate_path).is_err());
+      assert!(
+         workspace
+            .set_roots(vec![root("missing", &dir.path().join("missing"))])
+            .is_err()
+      );
+      assert!(workspace.roots().is_empty());
+   }
+}
@@ -0,0 +1,380 @@
+use ignore::{
+   DirEntry, Match, WalkBuilder,
+   gitignore::{Gitignore, GitignoreBuilder},
+};
+use std::{
+   collections::HashMap,
+   path::{Path, PathBuf},
+   sync::{Arc, Mutex},
+};
+
+/// Per-workspace ignore file, read with the same syntax as `.gitignore`.
+pub const ATHAS_IGNORE_FILENAME: &str = ".athasignore";
+
+/// Ignore files consulted in each directory, highest precedence first.
+const IGNORE_FILENAMES: [&str; 3] = [ATHAS_IGNORE_FILENAME, ".ignore", ".gitignore"];
+
+/// Ignore rules for a workspace root.
+///
+/// Honors nested `.gitignore`, `.ignore` and `.athasignore` files, `.git/info/exclude` and the
+/// user's global gitignore. Ignore files above the enclosing repository, or above the root when
+/// it is not in one, are not read. The `.git` directory itself is always skipped, even when
+/// ignored files are requested.
+pub struct WorkspaceIgnore {
+   root: PathBuf,
+   repo_root: Option<PathBuf>,
+   include_ignored: bool,
+   rules: Arc<IgnoreRules>,
+}
+
+/// The ignore files that apply below `top`, shared with walkers.
+struct IgnoreRules {
+   /// Directory whose ignore files still apply to the root; the enclosing repository root when
+   /// the root is nested inside one.
+   top: PathBuf,
+   /// Repository exclude file and global gitignore, consulted after per-directory files.
+   base: Mutex<Arc<Vec<Gitignore>>>,
+   directory_matchers: Mutex<HashMap<PathBuf, Arc<Vec<Gitignore>>>>,
+}
+
+impl WorkspaceIgnore {
+   pub fn new(root: impl Into<PathBuf>, include_ignored: bool) -> Self {
+      let root = root.into();
+      let repo_root = root
+         .ancestors()
+         .find(|dir| dir.join(".git").exists())
+         .map(Path::to_path_buf);
+
+      let rules = Arc::new(IgnoreRules {
+         top: repo_root.clone().unwrap_or_else(|| root.clone()),
+         base: Mutex::new(Arc::new(build_base_matchers(repo_root.as_deref()))),
+         directory_matchers: Mutex::new(HashMap::new()),
+      });
+
+      Self {
+         root,
+         repo_root,
+         include_ignored,
+         rules,
+      }
+   }
+
+   /// Walker over the workspace that applies these rules. `filter` can prune additional entries.
+   pub fn walk_builder<F>(&self, filter: F) -> WalkBuilder
+   where
+      F: Fn(&DirEntry) -> bool + Send + Sync + 'static,
+   {
+      self.walk_builder_from(&self.root, filter)
+   }
+
+   /// Like `walk_builder`, starting from a directory inside the workspace. Ignore files in the
+   /// directories between the repository root and `start` still apply.
+   pub fn walk_builder_from<F>(&self, start: &Path, filter: F) -> WalkBuilder
+   where
+      F: Fn(&DirEntry) -> bool + Send + Sync + 'static,
+   {
+      let respect = !self.include_ignored;
+      // The walker would read ignore files all the way up to the filesystem root, so it only
+      // reads those from `start` down and the rest are matched here
+      let rules = (respect && start != self.rules.top).then(|| self.rules.clone());
+      let mut builder = WalkBuilder::new(start);
+      builder
+         .hidden(false)
+         .follow_links(false)
+         .parents(false)
+         .require_git(false)
+         .git_ignore(respect)
+         .git_global(respect)
+         .git_exclude(respect)
+         .ignore(respect)
+         .filter_entry(move |entry| {
+            entry.file_name() != ".git"
+               && rules.as_ref().is_none_or(|rules| {
+                  let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                  !rules.matches(entry.path(), is_dir)
+               })
+               && filter(entry)
+         });
+      if respect {
+         builder.add_custom_ignore_filename(ATHAS_IGNORE_FILENAME);
+      }
+      builder
+   }
+
+   /// Whether `path` or any of its parent directories below the root is ignored.
+   pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+      let Ok(relative) = path.strip_prefix(&self.root) else {
+         return false;
+      };
+      if relative.components().any(|c| c.as_os_str() == ".git") {
+         return true;
+      }
+      if self.include_ignored {
+         return false;
+      }
+
+      let component_count = relative.components().count();
+      let mut current = self.root.clone();
+      for (index, component) in relative.components().enumerate() {
+         current.push(component);
+         let component_is_dir = index + 1 < component_count || is_dir;
+         if self.rules.matches(&current, component_is_dir) {
+            return true;
+         }
+      }
+
+      false
+   }
+
+   /// Drop cached rules when an ignore file changes on disk. Returns whether `path` was one.
+   pub fn invalidate(&self, path: &Path) -> bool {
+      if !is_ignore_file(path) {
+         return false;
+      }
+      if path.ends_with(git_exclude_path()) {
+         *self.rules.base.lock().unwrap() =
+            Arc::new(build_base_matchers(self.repo_root.as_deref()));
+      } else if let Some(dir) = path.parent() {
+         self.rules.directory_matchers.lock().unwrap().remove(dir);
+      }
+      true
+   }
+}
+
+impl IgnoreRules {
+   fn matches(&self, path: &Path, is_dir: bool) -> bool {
+      // Deeper ignore files take precedence over shallower ones
+      for dir in path
+         .ancestors()
+         .skip(1)
+         .take_while(|dir| dir.starts_with(&self.top))
+      {
+         for matcher in self.matchers_for(dir).iter() {
+            match matcher.matched(path, is_dir) {
+               Match::Ignore(_) => return true,
+               Match::Whitelist(_) => return false,
+               Match::None => {}
+            }
+         }
+      }
+
+      let base = self.base.lock().unwrap().clone();
+      for matcher in base.iter() {
+         match matcher.matched(path, is_dir) {
+            Match::Ignore(_) => return true,
+            Match::Whitelist(_) => return false,
+            Match::None => {}
+         }
+      }
+
+      false
+   }
+
+   fn matchers_for(&self, dir: &Path) -> Arc<Vec<Gitignore>> {
+      let mut cache = self.directory_matchers.lock().unwrap();
+      if let Some(matchers) = cache.get(dir) {
+         return matchers.clone();
+      }
+
+      let matchers: Vec<Gitignore> = IGNORE_FILENAMES
+         .iter()
+         .map(|name| dir.join(name))
+         .filter(|path| path.is_file())
+         .filter_map(|path| {
+            let mut builder = GitignoreBuilder::new(dir);
+            if let Some(err) = builder.add(&path) {
+               log::debug!("[WorkspaceIgnore] Error reading {:?}: {}", path, err);
+            }
+            builder.build().ok()
+         })
+         .collect();
+
+      let matchers = Arc::new(matchers);
+      cache.insert(dir.to_path_buf(), matchers.clone());
+      matchers
+   }
+}
+
+fn git_exclude_path() -> PathBuf {
+   Path::new(".git").join("info").join("exclude")
+}
+
+fn build_base_matchers(repo_root: Option<&Path>) -> Vec<Gitignore> {
+   let mut matchers = Vec::new();
+
+   if let Some(repo_root) = repo_root {
+      // Patterns in the exclude file are relative to the repository root, not `.git/info`
+      let exclude_path = repo_root.join(git_exclude_path());
+      if exclude_path.is_file() {
+         let mut builder = GitignoreBuilder::new(repo_root);
+         if let Some(err) = builder.add(&exclude_path) {
+            log::debug!("[WorkspaceIgnore] Error reading git exclude file: {}", err);
+         }
+         if let Ok(exclude) = builder.build() {
+            matchers.push(exclude);
+         }
+      }
+   }
+
+   let (global, err) = Gitignore::global();
+   if let Some(err) = err {
+      log::debug!("[WorkspaceIgnore] Error reading global gitignore: {}", err);
+   }
+   matchers.push(global);
+
+   matchers
+}
+
+/// Whether `path` is a file whose contents affect ignore rules.
+pub fn is_ignore_file(path: &Path) -> bool {
+   path
+      .file_name()
+      .and_then(|name| name.to_str())
+      .is_some_and(|name| IGNORE_FILENAMES.contains(&name))
+      || path.ends_with(git_exclude_path())
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::test_utils::create_tree;
+   use std::fs;
+   use tempfile::TempDir;
+
+   fn walked_files(dir: &TempDir, ignore: &WorkspaceIgnore) -> Vec<String> {
+      let mut files: Vec<String> = ignore
+         .walk_builder(|_| true)
+         .build()
+         .flatten()
+         .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
+         .map(|e| {
+            e.path()
+               .strip_prefix(dir.path())
+               .unwrap()
+               .to_string_lossy()
+               .replace('\\', "/")
+         })
+         .collect();
+      files.sort();
+      files
+   }
+
+   #[test]
+   fn test_nested_ignore_files() {
+      let dir = create_tree(&[
+         (".git/HEAD", "ref: refs/heads/main"),
+         (".git/info/exclude", "secret.txt\n"),
+         (".gitignore", "out/\n*.log\n"),
+         (".athasignore", "fixtures/\n"),
+         (".env", "KEY=1"),
+         ("app.log", ""),
+         ("secret.txt", ""),
+         ("out/bundle.js", ""),
+         ("fixtures/data.json", ""),
+         ("src/.ignore", "generated.rs\n"),
+         ("src/.gitignore", "!keep.log\n"),
+         ("src/generated.rs", ""),
+         ("src/keep.log", ""),
+         ("src/lib.rs", ""),
+      ]);
+      let ignore = WorkspaceIgnore::new(dir.path(), false);
+
+      assert_eq!(
+         walked_files(&dir, &ignore),
+         vec![
+            ".athasignore",
+            ".env",
+            ".gitignore",
+            "src/.gitignore",
+            "src/.ignore",
+            "src/keep.log",
+            "src/lib.rs",
+         ]
+      );
+
+      let root = dir.path();
+      assert!(ignore.is_ignored(&root.join("app.log"), false));
+      assert!(ignore.is_ignored(&root.join("secret.txt"), false));
+      assert!(ignore.is_ignored(&root.join("out/bundle.js"), false));
+      assert!(ignore.is_ignored(&root.join("fixtures/data.json"), false));
+      assert!(ignore.is_ignored(&root.join("src/generated.rs"), false));
+      assert!(ignore.is_ignored(&root.join(".git/HEAD"), false));
+      assert!(!ignore.is_ignored(&root.join("src/keep.log"), false));
+      assert!(!ignore.is_ignored(&root.join("src/lib.rs"), false));
+      assert!(!ignore.is_ignored(&root.join(".env"), false));
+   }
+
+   #[test]
+   fn test_ignore_files_above_the_repository_do_not_apply() {
+      let dir = create_tree(&[
+         (".gitignore", "*.log\n"),
+         (".ignore", "*.tmp\n"),
+         ("repo/.git/HEAD", "ref: refs/heads/main"),
+         ("repo/.gitignore", "generated/\n"),
+         ("repo/app.log", ""),
+         ("repo/a.tmp", ""),
+         ("repo/src/debug.log", ""),
+         ("repo/src/generated/x.rs", ""),
+         ("repo/src/lib.rs", ""),
+         ("plain/a.log", ""),
+      ]);
+
+      let ignore = WorkspaceIgnore::new(dir.path().join("repo"), false);
+      assert_eq!(
+         walked_files(&dir, &ignore),
+         vec![
+            "repo/.gitignore",
+            "repo/a.tmp",
+            "repo/app.log",
+            "repo/src/debug.log",
+            "repo/src/lib.rs",
+         ]
+      );
+
+      // A workspace nested in a repository still follows the repository's ignore files
+      let ignore = WorkspaceIgnore::new(dir.path().join("repo/src"), false);
+      assert_eq!(
+         walked_files(&dir, &ignore),
+         vec!["repo/src/debug.log", "repo/src/lib.rs"]
+      );
+      assert!(ignore.is_ignored(&dir.path().join("repo/src/generated/x.rs"), false));
+
+      let ignore = WorkspaceIgnore::new(dir.path().join("plain"), false);
+      assert_eq!(walked_files(&dir, &ignore), vec!["plain/a.log"]);
+      assert!(!ignore.is_ignored(&dir.path().join("plain/a.log"), false));
+   }
+
+   #[test]
+   fn test_include_ignored_still_skips_git_directory() {
+      let dir = create_tree(&[
+         (".git/HEAD", "ref: refs/heads/main"),
+         (".gitignore", "out/\n"),
+         ("out/bundle.js", ""),
+      ]);
+      let ignore = WorkspaceIgnore::new(dir.path(), true);
+
+      assert_eq!(
+         walked_files(&dir, &ignore),
+         vec![".gitignore", "out/bundle.js"]
+      );
+      assert!(!ignore.is_ignored(&dir.path().join("out/bundle.js"), false));
+      assert!(ignore.is_ignored(&dir.path().join(".git/HEAD"), false));
+   }
+
+   #[test]
+   fn test_invalidate_rereads_changed_ignore_file() {
+      let dir = create_tree(&[(".gitignore", "a.txt\n"), ("a.txt", ""), ("b.txt", "")]);
+      let ignore = WorkspaceIgnore::new(dir.path(), false);
+      let gitignore = dir.path().join(".gitignore");
+
+      assert!(ignore.is_ignored(&dir.path().join("a.txt"), false));
+      assert!(!ignore.is_ignored(&dir.path().join("b.txt"), false));
+
+      fs::write(&gitignore, "b.txt\n").unwrap();
+      assert!(ignore.invalidate(&gitignore));
+      assert!(!ignore.invalidate(&dir.path().join("a.txt")));
+
+      assert!(!ignore.is_ignored(&dir.path().join("a.txt"), false));
+      assert!(ignore.is_ignored(&dir.path().join("b.txt"), false));
+   }
+}
@@ -1,4 +1,5 @@
 import { ArrowUp, GitBranch, GitCommit, RefreshCw } from "lucide-react";
+import type { CommitResult } from "@/features/version-control/git/controllers/git";
 import type { Action } from "../models/action.types";
 
 interface GitActionsParams {
@@ -12,7 +13,7 @@ interface GitActionsParams {
   gitOperations: {
     stageAllFiles: (path: string) => Promise<boolean>;
     unstageAllFiles: (path: string) => Promise<boolean>;
-    commitChanges: (path: string, message: string) => Promise<boolean>;
+    commitChanges: (path: string, message: string) => Promise<CommitResult>;
     pushChanges: (path: string) => Promise<boolean>;
     pullChanges: (path: string) => Promise<boolean>;
     fetchChanges: (path: string) => Promise<boolean>;
@@ -108,12 +109,12 @@ export const createGitActions = (params: GitActionsParams): Action[] => {
           return;
         }
         try {
-          const success = await gitOperations.commitChanges(rootFolderPath, message);
-          if (success) {
+          const result = await gitOperations.commitChanges(rootFolderPath, message);
+          if (result.success) {
             showToast({ message: "Changes committed successfully", type: "success" });
             window.dispatchEvent(new Event("refresh-git-data"));
           } else {
-            showToast({ message: "Failed to commit changes", type: "error" });
+            showToast({ message: `Failed to commit changes: ${result.error}`, type: "error" });
           }
         } catch (error) {
           showToast({ message: `Error: ${error}`, type: "error" });
@@ -0,0 +1,66 @@
+import { invoke } from "@tauri-apps/api/core";
+import type { Diagnostic, DiagnosticSeverity } from "vscode-languageserver-protocol";
+
+export interface DiagnosticCounts {
+  errors: number;
+  warnings: number;
+  information: number;
+  hints: number;
+}
+
+/** Every criterion left out matches everything. */
+export interface DiagnosticsFilter {
+  /** A file, or a directory to match every file below. */
+  path?: string;
+  /** The least severe level to include, e.g. warnings to get errors and warnings. */
+  min_severity?: DiagnosticSeverity;
+  /** Producers, such as a language server name or `eslint`. */
+  sources?: string[];
+  code?: string;
+  /** Case-insensitive text the message must contain. */
+  message?: string;
+}
+
+/** The diagnostics one source reported for one file. */
+export interface FileDiagnostics {
+  uri: string;
+  file_path: string | null;
+  source: string;
+  diagnostics: Diagnostic[];
+  counts: DiagnosticCounts;
+}
+
+/** Payload of `diagnostics://changed`. */
+export interface DiagnosticsChangedEvent {
+  uri: string;
+  file_path: string | null;
+  source: string;
+  counts: DiagnosticCounts;
+  /** Counts across every file, for the status bar. */
+  totals: DiagnosticCounts;
+}
+
+export async function diagnosticsQuery(filter?: DiagnosticsFilter): Promise<FileDiagnostics[]> {
+  return invoke<FileDiagnostics[]>("diagnostics_query", { filter });
+}
+
+export async function diagnosticsCounts(filter?: DiagnosticsFilter): Promise<DiagnosticCounts> {
+  return invoke<DiagnosticCounts>("diagnostics_counts", { filter });
+}
+
+/**
+ * Reports diagnostics from a linter or task output. They replace what `source` reported for the
+ * file before.
+ */
+export async function diagnosticsSet(
+  source: string,
+  filePath: string,
+  diagnostics: Diagnostic[],
+): Promise<void> {
+  return invoke<void>("diagnostics_set", { source, filePath, diagnostics });
+}
+
+/** Drops what `source` reported, everywhere or below `path`. */
+export async function diagnosticsClear(source: string, path?: string): Promise<void> {
+  return invoke<void>("diagnostics_clear", { source, path });
+}
@@ -1,9 +1,29 @@
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import type {
+  CodeLens,
+  Command,
+  LogMessageParams,
+  MessageActionItem,
+  ProgressParams,
+  ShowMessageParams,
+  ShowMessageRequestParams,
+  SignatureHelp,
   CompletionItem,
+  Diagnostic,
+  DocumentHighlight,
+  FoldingRange,
   Hover,
+  InlayHint,
+  Position,
   PublishDiagnosticsParams,
+  Range,
+  SelectionRange,
+  SymbolKind,
+  SymbolTag,
+  TextDocumentContentChangeEvent,
+  TextEdit,
+  WorkspaceEdit,
 } from "vscode-languageserver-protocol";
 import {
   convertLSPDiagnostic,
@@ -15,6 +35,212 @@ export interface LspError {
   message: string;
 }
 
+export interface LspLocation {
+  uri: string;
+  file_path: string | null;
+  range: Range;
+  target_range: Range | null;
+  preview: string | null;
+}
+
+/** A semantic token at an absolute range, classified like the tree-sitter `Token`s. */
+export interface LspSemanticToken {
+  range: Range;
+  token_type: string;
+  class_name: string;
+  semantic_type: string;
+  modifiers: string[];
+}
+
+/** One entry of a document outline, from the language server or the syntax tree. */
+export interface OutlineSymbol {
+  name: string;
+  detail: string | null;
+  kind: SymbolKind;
+  range: Range;
+  selection_range: Range;
+  children: OutlineSymbol[];
+}
+
+export interface LspWorkspaceSymbol {
+  name: string;
+  kind: SymbolKind;
+  container_name: string | null;
+  location: LspLocation;
+}
+
+export type HierarchyDirection = "incoming_calls" | "outgoing_calls" | "supertypes" | "subtypes";
+
+/** A call or type hierarchy item. Pass it back unchanged to expand it. */
+export interface HierarchyItem {
+  name: string;
+  kind: SymbolKind;
+  tags: SymbolTag[];
+  detail: string | null;
+  uri: string;
+  range: Range;
+  selection_range: Range;
+  data: unknown;
+}
+
+export interface HierarchyNode {
+  item: HierarchyItem;
+  call_ranges: Range[];
+  /** null until expanded. */
+  children: HierarchyNode[] | null;
+  /** Already shown elsewhere in the tree, e.g. a recursive call, so it is not expanded. */
+  repeated: boolean;
+}
+
+export interface PrepareRenameResult {
+  /** Missing when the server cannot tell; rename the word at the cursor. */
+  range: Range | null;
+  placeholder: string | null;
+}
+
+export interface AppliedWorkspaceEdit {
+  modified_files: string[];
+  created_files: string[];
+  renamed_files: { old_path: string; new_path: string }[];
+  deleted_files: string[];
+  /** Edits for open buffers, to apply in order. Their files were not written. */
+  buffer_edits: { file_path: string; edits: TextEdit[] }[];
+}
+
+/** Values for snippet variables such as `$TM_FILENAME`; missing ones expand to their default. */
+export interface SnippetContext {
+  file_path?: string;
+  workspace_path?: string;
+  selected_text?: string;
+  current_line?: string;
+  current_word?: string;
+  /** Zero-based line of the cursor. */
+  line?: number;
+  clipboard?: string;
+}
+
+/** Offsets are UTF-16 code units into `text`. `$0` is always the last tabstop. */
+export interface ExpandedSnippet {
+  text: string;
+  tabstops: {
+    index: number;
+    ranges: { start: number; end: number }[];
+    choices: string[] | null;
+  }[];
+}
+
+/** A code action or bare command. Pass it back unchanged to resolve or apply it. */
+export interface LspCodeAction {
+  title: string;
+  kind: string | null;
+  is_preferred: boolean;
+  disabled_reason: string | null;
+  diagnostics: Diagnostic[];
+  edit: WorkspaceEdit | null;
+  command: Command | null;
+  data: unknown | null;
+}
+
+/**
+ * Payloads of `lsp://progress`, `lsp://show-message` and `lsp://log-message`, tagged with the
+ * server that sent them.
+ */
+export type LspServerEvent<T> = T & { server_name: string };
+
+/** Payload of `lsp://show-message-request`; answer it with `respondToMessageRequest`. */
+export type LspMessageRequestEvent = LspServerEvent<ShowMessageRequestParams> & {
+  request_id: string;
+};
+
+export type LspProgressEvent = LspServerEvent<ProgressParams>;
+export type LspShowMessageEvent = LspServerEvent<ShowMessageParams>;
+export type LspLogMessageEvent = LspServerEvent<LogMessageParams>;
+
+/** Payload of `lsp://apply-edit`, sent after a server-initiated edit was written to disk. */
+export type LspApplyEditEvent = LspServerEvent<{
+  label: string | null;
+  result: AppliedWorkspaceEdit;
+}>;
+
+/** Payload of `lsp://status`, sent as a server starts, crashes and is restarted. */
+export interface LspStatusEvent {
+  server_name: string;
+  workspace_path: string;
+  status: "running" | "crashed" | "restarting" | "failed";
+  restart_attempt: number;
+  message: string | null;
+}
+
+export type GotoKind = "definition" | "type_definition" | "implementation" | "declaration";
+
+export interface LspServerConfig {
+  name: string;
+  language_id: string;
+  command: string;
+  args: string[];
+  file_extensions: string[];
+  root_markers: string[];
+  initialization_options: unknown | null;
+  /** Answers the server's `workspace/configuration` requests. */
+  settings: unknown | null;
+  install_hint: string | null;
+  /** Set when the editor can install the server itself. */
+  install: LspInstallSource | null;
+}
+
+export type LspInstallSource =
+  | { type: "npm"; packages: string[]; bin: string }
+  | { type: "github_release"; repo: string; asset: string; binary: string }
+  | { type: "go"; module: string; bin: string };
+
+export interface InstalledServer {
+  name: string;
+  version: string;
+  source: LspInstallSource;
+  /** The executable, relative to the server's install directory. */
+  binary: string;
+  installed_at: string;
+}
+
+export interface LspServerUpdate {
+  name: string;
+  installed_version: string;
+  latest_version: string;
+}
+
+/** Payload of `lsp://install-progress`. */
+export interface LspInstallProgress {
+  server_name: string;
+  status:
+    | {
+        type: "resolving" | "downloading" | "verifying" | "extracting" | "installing" | "completed";
+      }
+    | { type: "failed"; error: string };
+  progress: number;
+  message: string;
+}
+
+/** A message exchanged with a server, as recorded by the traffic inspector. */
+export interface LspTrafficEntry {
+  seq: number;
+  /** The workspace of the server instance, as several can run the same server. */
+  workspace_path: string;
+  server_name: string;
+  direction: "send" | "receive";
+  kind: "request" | "response" | "notification";
+  /** Responses carry the method of the request they answer. */
+  method: string | null;
+  id: number | string | null;
+  /** Milliseconds since the Unix epoch. */
+  timestamp: number;
+  latency_ms: number | null;
+  /** Bytes of the JSON body. */
+  size: number;
+  is_error: boolean;
+  /** Only present when requested. */
+  message?: unknown;
+}
+
 export class LspClient {
   private static instance: LspClient | null = null;
   private activeLanguageServers = new Set<string>(); // workspace:language format
@@ -227,6 +453,20 @@ export class LspClient {
     }
   }
 
+  /** Fill in documentation and auto-import edits the server left out of the list. */
+  async resolveCompletion(filePath: string, item: CompletionItem): Promise<CompletionItem> {
+    try {
+      return await invoke<CompletionItem>("lsp_resolve_completion", { filePath, item });
+    } catch (error) {
+      logger.error("LSPClient", "LSP completion resolve error:", error);
+      return item;
+    }
+  }
+
+  async expandSnippet(snippet: string, context?: SnippetContext): Promise<ExpandedSnippet> {
+    return invoke<ExpandedSnippet>("lsp_expand_snippet", { snippet, context });
+  }
+
   async getHover(filePath: string, line: number, character: number): Promise<Hover | null> {
     try {
       return await invoke<Hover | null>("lsp_get_hover", {
@@ -240,6 +480,291 @@ export class LspClient {
     }
   }
 
+  async goto(
+    kind: GotoKind,
+    filePath: string,
+    line: number,
+    character: number,
+  ): Promise<LspLocation[]> {
+    try {
+      return await invoke<LspLocation[]>(`lsp_goto_${kind}`, { filePath, line, character });
+    } catch (error) {
+      logger.error("LSPClient", `LSP ${kind} error:`, error);
+      return [];
+    }
+  }
+
+  async findReferences(
+    filePath: string,
+    line: number,
+    character: number,
+    includeDeclaration = true,
+  ): Promise<LspLocation[]> {
+    try {
+      return await invoke<LspLocation[]>("lsp_find_references", {
+        filePath,
+        line,
+        character,
+        includeDeclaration,
+      });
+    } catch (error) {
+      logger.error("LSPClient", "LSP references error:", error);
+      return [];
+    }
+  }
+
+  /** `triggerCharacter` is the character just typed when triggered automatically. */
+  async getSignatureHelp(
+    filePath: string,
+    line: number,
+    character: number,
+    triggerCharacter?: string,
+  ): Promise<SignatureHelp | null> {
+    try {
+      return await invoke<SignatureHelp | null>("lsp_signature_help", {
+        filePath,
+        line,
+        character,
+        triggerCharacter,
+      });
+    } catch (error) {
+      logger.error("LSPClient", "LSP signature help error:", error);
+      return null;
+    }
+  }
+
+  async getInlayHints(filePath: string, range: Range): Promise<InlayHint[]> {
+    try {
+      return await invoke<InlayHint[]>("lsp_inlay_hints", { filePath, range });
+    } catch (error) {
+      logger.error("LSPClient", "LSP inlay hints error:", error);
+      return [];
+    }
+  }
+
+  /** Tokens for the whole document, or only `range` when the server supports it. */
+  async getSemanticTokens(filePath: string, range?: Range): Promise<LspSemanticToken[]> {
+    try {
+      return await invoke<LspSemanticToken[]>("lsp_semantic_tokens", { filePath, range });
+    } catch (error) {
+      logger.error("LSPClient", "LSP semantic tokens error:", error);
+      return [];
+    }
+  }
+
+  /** `content` is the current buffer, used when the outline comes from the syntax tree. */
+  async documentSymbols(filePath: string, content?: string): Promise<OutlineSymbol[]> {
+    try {
+      return await invoke<OutlineSymbol[]>("lsp_document_symbols", { filePath, content });
+    } catch (error) {
+      logger.error("LSPClient", "LSP document symbols error:", error);
+      return [];
+    }
+  }
+
+  async workspaceSymbols(query: string): Promise<LspWorkspaceSymbol[]> {
+    try {
+      return await invoke<LspWorkspaceSymbol[]>("lsp_workspace_symbols", { query });
+    } catch (error) {
+      logger.error("LSPClient", "LSP workspace symbols error:", error);
+      return [];
+    }
+  }
+
+  async codeLenses(filePath: string): Promise<CodeLens[]> {
+    try {
+      return await invoke<CodeLens[]>("lsp_code_lenses", { filePath });
+    } catch (error) {
+      logger.error("LSPClient", "LSP code lens error:", error);
+      return [];
+    }
+  }
+
+  /** Fill in the command of a lens the server sent without one. */
+  async resolveCodeLens(filePath: string, lens: CodeLens): Promise<CodeLens> {
+    return invoke<CodeLens>("lsp_resolve_code_lens", { filePath, lens });
+  }
+
+  async documentHighlights(
+    filePath: string,
+    line: number,
+    character: number,
+  ): Promise<DocumentHighlight[]> {
+    try {
+      return await invoke<DocumentHighlight[]>("lsp_document_highlights", {
+        filePath,
+        line,
+        character,
+      });
+    } catch (error) {
+      logger.error("LSPClient", "LSP document highlight error:", error);
+      return [];
+    }
+  }
+
+  /** `content` is the current buffer, used when the ranges come from the syntax tree. */
+  async foldingRanges(filePath: string, content?: string): Promise<FoldingRange[]> {
+    try {
+      return await invoke<FoldingRange[]>("lsp_folding_ranges", { filePath, content });
+    } catch (error) {
+      logger.error("LSPClient", "LSP folding ranges error:", error);
+      return [];
+    }
+  }
+
+  /** One chain per position, innermost range first, linked through `parent`. */
+  async selectionRanges(
+    filePath: string,
+    positions: Position[],
+    content?: string,
+  ): Promise<SelectionRange[]> {
+    try {
+      return await invoke<SelectionRange[]>("lsp_selection_ranges", {
+        filePath,
+        positions,
+        content,
+      });
+    } catch (error) {
+      logger.error("LSPClient", "LSP selection ranges error:", error);
+      return [];
+    }
+  }
+
+  async prepareHierarchy(
+    filePath: string,
+    line: number,
+    character: number,
+    direction: HierarchyDirection,
+    depth?: number,
+  ): Promise<HierarchyNode[]> {
+    try {
+      return await invoke<HierarchyNode[]>("lsp_prepare_hierarchy", {
+        filePath,
+        line,
+        character,
+        direction,
+        depth,
+      });
+    } catch (error) {
+      logger.error("LSPClient", "LSP prepare hierarchy error:", error);
+      return [];
+    }
+  }
+
+  /** Children of a collapsed node. `ancestors` is its path from the root, used to stop at cycles. */
+  async expandHierarchy(
+    filePath: string,
+    item: HierarchyItem,
+    direction: HierarchyDirection,
+    ancestors: HierarchyItem[],
+    depth?: number,
+  ): Promise<HierarchyNode[]> {
+    try {
+      return await invoke<HierarchyNode[]>("lsp_expand_hierarchy", {
+        filePath,
+        item,
+        direction,
+        depth,
+        ancestors,
+      });
+    } catch (error) {
+      logger.error("LSPClient", "LSP expand hierarchy error:", error);
+      return [];
+    }
+  }
+
+  /** Pulls a file's diagnostics; servers without pull support return what they published. */
+  async pullDiagnostics(filePath: string): Promise<Diagnostic[]> {
+    try {
+      return await invoke<Diagnostic[]>("lsp_pull_diagnostics", { filePath });
+    } catch (error) {
+      logger.error("LSPClient", "LSP pull diagnostics error:", error);
+      return [];
+    }
+  }
+
+  /** Pulls workspace diagnostics into the backend store, see `diagnosticsQuery`. */
+  async workspaceDiagnostics(): Promise<void> {
+    try {
+      await invoke<void>("lsp_workspace_diagnostics");
+    } catch (error) {
+      logger.error("LSPClient", "LSP workspace diagnostics error:", error);
+    }
+  }
+
+  /** Returns null when the symbol at the position cannot be renamed. */
+  async prepareRename(
+    filePath: string,
+    line: number,
+    character: number,
+  ): Promise<PrepareRenameResult | null> {
+    try {
+      return await invoke<PrepareRenameResult | null>("lsp_prepare_rename", {
+        filePath,
+        line,
+        character,
+      });
+    } catch (error) {
+      logger.error("LSPClient", "LSP prepare rename error:", error);
+      return null;
+    }
+  }
+
+  /**
+   * Renames the symbol and writes the edit to closed files. Throws without changing any file
+   * when part of the edit cannot be applied.
+   */
+  async rename(
+    filePath: string,
+    line: number,
+    character: number,
+    newName: string,
+    openFiles: string[],
+  ): Promise<AppliedWorkspaceEdit> {
+    return invoke<AppliedWorkspaceEdit>("lsp_rename", {
+      filePath,
+      line,
+      character,
+      newName,
+      openFiles,
+    });
+  }
+
+  /** Pass `only`, e.g. `["source.fixAll"]`, to limit the kinds of actions returned. */
+  async getCodeActions(filePath: string, range: Range, only?: string[]): Promise<LspCodeAction[]> {
+    try {
+      return await invoke<LspCodeAction[]>("lsp_get_code_actions", { filePath, range, only });
+    } catch (error) {
+      logger.error("LSPClient", "LSP code actions error:", error);
+      return [];
+    }
+  }
+
+  async resolveCodeAction(filePath: string, action: LspCodeAction): Promise<LspCodeAction> {
+    return invoke<LspCodeAction>("lsp_resolve_code_action", { filePath, action });
+  }
+
+  /** Resolves the action if needed, applies its edit and runs its command. */
+  async applyCodeAction(
+    filePath: string,
+    action: LspCodeAction,
+    openFiles: string[],
+  ): Promise<AppliedWorkspaceEdit> {
+    return invoke<AppliedWorkspaceEdit>("lsp_apply_code_action", { filePath, action, openFiles });
+  }
+
+  async executeCommand(filePath: string, command: Command): Promise<unknown> {
+    return invoke<unknown>("lsp_execute_command", { filePath, command });
+  }
+
+  /** Pass `null` when the user dismissed the message. */
+  async respondToMessageRequest(
+    requestId: string,
+    action: MessageActionItem | null,
+  ): Promise<void> {
+    await invoke<void>("lsp_respond_message_request", { requestId, action });
+  }
+
   async notifyDocumentOpen(filePath: string, content: string): Promise<void> {
     try {
       logger.debug("LSPClient", `Opening document: ${filePath}`);
@@ -261,6 +786,26 @@ export class LspClient {
     }
   }
 
+  /**
+   * Send range edits instead of the whole document. Each change applies to the text left by the
+   * previous one; a change without a range replaces the document.
+   */
+  async notifyDocumentChanges(
+    filePath: string,
+    changes: TextDocumentContentChangeEvent[],
+    version: number,
+  ): Promise<void> {
+    try {
+      await invoke<void>("lsp_document_change", {
+        filePath,
+        changes,
+        version,
+      });
+    } catch (error) {
+      logger.error("LSPClient", "LSP document change error:", error);
+    }
+  }
+
   async notifyDocumentClose(filePath: string): Promise<void> {
     try {
       await invoke<void>("lsp_document_close", { filePath });
@@ -278,6 +823,54 @@ export class LspClient {
     }
   }
 
+  async listServers(): Promise<LspServerConfig[]> {
+    return invoke<LspServerConfig[]>("lsp_list_servers");
+  }
+
+  /** Re-reads the user's `lsp.json`; running servers pick up changes when restarted. */
+  async reloadConfig(): Promise<LspServerConfig[]> {
+    return invoke<LspServerConfig[]>("lsp_reload_config");
+  }
+
+  async getConfigPath(): Promise<string> {
+    return invoke<string>("lsp_get_config_path");
+  }
+
+  /** Installs the latest version of a server, or updates it. Listen to `lsp://install-progress`. */
+  async installServer(serverName: string): Promise<InstalledServer> {
+    return invoke<InstalledServer>("lsp_install_server", { serverName });
+  }
+
+  async uninstallServer(serverName: string): Promise<void> {
+    return invoke<void>("lsp_uninstall_server", { serverName });
+  }
+
+  async listInstalledServers(): Promise<InstalledServer[]> {
+    return invoke<InstalledServer[]>("lsp_list_installed_servers");
+  }
+
+  async checkServerUpdates(): Promise<LspServerUpdate[]> {
+    return invoke<LspServerUpdate[]>("lsp_check_server_updates");
+  }
+
+  async getTraffic(serverName?: string, includeMessages = false): Promise<LspTrafficEntry[]> {
+    return invoke<LspTrafficEntry[]>("lsp_get_traffic", { serverName, includeMessages });
+  }
+
+  async clearTraffic(serverName?: string): Promise<void> {
+    return invoke<void>("lsp_clear_traffic", { serverName });
+  }
+
+  /** While enabled, every message is also sent as an `lsp://traffic` event. */
+  async setTrafficStreaming(enabled: boolean): Promise<void> {
+    return invoke<void>("lsp_set_traffic_streaming", { enabled });
+  }
+
+  /** Writes the recorded messages as JSON lines for LSP trace viewers; returns the count. */
+  async exportTraffic(outputPath: string, serverName?: string): Promise<number> {
+    return invoke<number>("lsp_export_traffic", { serverName, outputPath });
+  }
+
   getActiveWorkspaces(): string[] {
     // Get unique workspace paths from all active language servers
     const workspaces = new Set<string>();
@@ -2,7 +2,13 @@ import type { CompletionItem } from "vscode-languageserver-protocol";
 import { create } from "zustand";
 import { EDITOR_CONSTANTS } from "@/features/editor/config/constants";
 import { logger } from "@/features/editor/utils/logger";
-import { detectCompletionContext, extractPrefix, filterCompletions } from "@/utils/fuzzy-matcher";
+import { useProjectStore } from "@/stores/project-store";
+import {
+  detectCompletionContext,
+  extractPrefix,
+  filterCompletions,
+  frecencyRecord,
+} from "@/utils/fuzzy-matcher";
 import { createSelectors } from "@/utils/zustand-selectors";
 import { useEditorUIStore } from "../stores/ui-store";
 
@@ -216,7 +222,6 @@ export const useLspStore = createSelectors(
           !getCompletions ||
           !filePath ||
           !isLanguageSupported?.(filePath) ||
-          filePath.startsWith("remote://") ||
           !editorRef.current
         ) {
           return;
@@ -259,6 +264,7 @@ export const useLspStore = createSelectors(
                 completions,
                 context_word: prefix,
                 context_type: context,
+                workspace_path: useProjectStore.getState().rootFolderPath,
               });
 
               if (filtered.length > 0) {
@@ -325,6 +331,7 @@ export const useLspStore = createSelectors(
                 completions,
                 context_word: prefix,
                 context_type: context,
+                workspace_path: useProjectStore.getState().rootFolderPath,
               });
 
               if (filtered.length > 0) {
@@ -370,6 +377,13 @@ export const useLspStore = createSelectors(
 
         completionActions.setIsLspCompletionVisible(false);
 
+        const { rootFolderPath } = useProjectStore.getState();
+        if (rootFolderPath) {
+          frecencyRecord(rootFolderPath, "completion", completion.label).catch((error) =>
+            logger.warn("Editor", "Failed to record completion frecency:", error),
+          );
+        }
+
         return { newValue, newCursorPos };
       },
 
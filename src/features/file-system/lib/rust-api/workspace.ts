@@ -0,0 +1,34 @@
+import { invoke } from "@tauri-apps/api/core";
+
+export interface WorkspaceRoot {
+  name: string;
+  path: string;
+}
+
+export interface WorkspaceRootsChange {
+  roots: WorkspaceRoot[];
+  added: WorkspaceRoot[];
+  removed: WorkspaceRoot[];
+}
+
+export async function workspaceGetRoots(): Promise<WorkspaceRoot[]> {
+  return invoke<WorkspaceRoot[]>("workspace_get_roots");
+}
+
+/**
+ * Replaces every workspace root. Each change is also emitted on `workspace://roots-changed`.
+ */
+export async function workspaceSetRoots(roots: WorkspaceRoot[]): Promise<WorkspaceRootsChange> {
+  return invoke<WorkspaceRootsChange>("workspace_set_roots", { roots });
+}
+
+export async function workspaceAddRoot(
+  path: string,
+  name?: string,
+): Promise<WorkspaceRootsChange> {
+  return invoke<WorkspaceRootsChange>("workspace_add_root", { path, name });
+}
+
+export async function workspaceRemoveRoot(name: string): Promise<WorkspaceRootsChange> {
+  return invoke<WorkspaceRootsChange>("workspace_remove_root", { name });
+}
@@ -1,10 +1,19 @@
 import { invoke } from "@tauri-apps/api/core";
+import type { GitDiffLine } from "@/features/version-control/git/types/git";
+
+export interface CaptureSpan {
+  index: number;
+  name: string | null;
+  column_start: number;
+  column_end: number;
+}
 
 export interface SearchMatch {
   line_number: number;
   line_content: string;
   column_start: number;
   column_end: number;
+  captures: CaptureSpan[];
 }
 
 export interface FileSearchResult {
@@ -14,12 +23,119 @@ export interface FileSearchResult {
 }
 
 export interface SearchFilesRequest {
+  /** Leave empty to search every workspace root. */
   root_path: string;
+  root_paths?: string[];
   query: string;
   case_sensitive?: boolean;
   max_results?: number;
+  use_regex?: boolean;
+  whole_word?: boolean;
+  include_globs?: string[];
+  exclude_globs?: string[];
+  include_ignored?: boolean;
+  max_matches_per_file?: number;
+  max_file_size?: number;
 }
 
 export async function searchFilesContent(request: SearchFilesRequest): Promise<FileSearchResult[]> {
   return invoke<FileSearchResult[]>("search_files_content", { request });
 }
+
+export interface ReplacePlanRequest extends SearchFilesRequest {
+  replacement: string;
+}
+
+export interface ReplaceEdit {
+  start: number;
+  end: number;
+  line_number: number;
+  column_start: number;
+  column_end: number;
+  original: string;
+  replacement: string;
+}
+
+export interface FileReplacePlan {
+  file_path: string;
+  content_hash: string;
+  edits: ReplaceEdit[];
+  preview: GitDiffLine[];
+}
+
+export interface ReplacePlan {
+  files: FileReplacePlan[];
+  total_edits: number;
+}
+
+export interface FileReplaceSelection {
+  file_path: string;
+  content_hash: string;
+  edits: ReplaceEdit[];
+}
+
+export interface ApplyReplaceResult {
+  files_changed: number;
+  edits_applied: number;
+}
+
+export async function replacePlan(request: ReplacePlanRequest): Promise<ReplacePlan> {
+  return invoke<ReplacePlan>("replace_plan", { request });
+}
+
+export async function replaceApply(files: FileReplaceSelection[]): Promise<ApplyReplaceResult> {
+  return invoke<ApplyReplaceResult>("replace_apply", { request: { files } });
+}
+
+export interface SearchResultsEvent {
+  session_id: string;
+  results: FileSearchResult[];
+}
+
+export interface SearchProgressEvent {
+  session_id: string;
+  files_scanned: number;
+  files_skipped: number;
+  files_matched: number;
+  total_matches: number;
+  truncated: boolean;
+  cancelled: boolean;
+  done: boolean;
+}
+
+/**
+ * Starts a streaming search. Results arrive on `search://results`, progress on
+ * `search://progress` and the final summary on `search://complete`.
+ */
+export async function searchStart(request: SearchFilesRequest): Promise<string> {
+  return invoke<string>("search_start", { request });
+}
+
+export async function searchCancel(sessionId: string): Promise<void> {
+  return invoke("search_cancel", { sessionId });
+}
+
+export type SearchIndexState = "missing" | "building" | "ready" | "failed";
+
+export interface SearchIndexStatus {
+  root_path: string;
+  state: SearchIndexState;
+  files_indexed: number;
+  error: string | null;
+}
+
+/**
+ * Builds or loads the persistent trigram index for a workspace. Status changes arrive on
+ * `search-index://status`; once ready, searches of that root only scan candidate files.
+ */
+export async function searchIndexEnable(rootPath: string): Promise<SearchIndexStatus> {
+  return invoke<SearchIndexStatus>("search_index_enable", { rootPath });
+}
+
+export async function searchIndexDisable(rootPath: string, deleteIndex = false): Promise<void> {
+  return invoke("search_index_disable", { rootPath, delete: deleteIndex });
+}
+
+export async function searchIndexStatus(rootPath: string): Promise<SearchIndexStatus> {
+  return invoke<SearchIndexStatus>("search_index_status", { rootPath });
+}
@@ -1,9 +1,14 @@
+import { listen } from "@tauri-apps/api/event";
 import { AlertCircle, GitCommit as GitCommitIcon, Send } from "lucide-react";
 import type React from "react";
-import { useState } from "react";
+import { useEffect, useState } from "react";
 import { commitChanges } from "@/features/version-control/git/controllers/git";
+import type { GitHookEvent } from "@/features/version-control/git/types/git";
 import { cn } from "@/utils/cn";
 
+/** Lines of hook output kept while a commit runs. */
+const MAX_HOOK_LINES = 200;
+
 interface GitCommitPanelProps {
   stagedFilesCount: number;
   repoPath?: string;
@@ -14,25 +19,66 @@ const GitCommitPanel = ({ stagedFilesCount, repoPath, onCommitSuccess }: GitComm
   const [commitMessage, setCommitMessage] = useState("");
   const [isCommitting, setIsCommitting] = useState(false);
   const [error, setError] = useState<string | null>(null);
+  const [noVerify, setNoVerify] = useState(false);
+  const [runningHook, setRunningHook] = useState<string | null>(null);
+  const [hookOutput, setHookOutput] = useState<string[]>([]);
+
+  // Show the progress of the repository's hooks while a commit runs them
+  useEffect(() => {
+    if (!repoPath) return;
+    let unlisten: (() => void) | null = null;
+
+    const setupHookListener = async () => {
+      try {
+        unlisten = await listen<GitHookEvent>("git://hook", (event) => {
+          const hookEvent = event.payload;
+          if (hookEvent.repo_path !== repoPath) return;
+          switch (hookEvent.type) {
+            case "started":
+              setRunningHook(hookEvent.hook);
+              break;
+            case "output":
+              setHookOutput((lines) => [...lines, hookEvent.line].slice(-MAX_HOOK_LINES));
+              break;
+            case "finished":
+              setRunningHook(null);
+              break;
+          }
+        });
+      } catch (error) {
+        console.error("Failed to set up git hook listener:", error);
+      }
+    };
+
+    setupHookListener();
+
+    return () => {
+      if (unlisten) {
+        unlisten();
+      }
+    };
+  }, [repoPath]);
 
   const handleCommit = async () => {
     if (!repoPath || !commitMessage.trim() || stagedFilesCount === 0) return;
 
     setIsCommitting(true);
     setError(null);
+    setHookOutput([]);
 
     try {
-      const success = await commitChanges(repoPath, commitMessage.trim());
-      if (success) {
+      const result = await commitChanges(repoPath, commitMessage.trim(), { noVerify });
+      if (result.success) {
         setCommitMessage("");
         onCommitSuccess?.();
       } else {
-        setError("Failed to commit changes");
+        setError(result.error);
       }
     } catch (error) {
       setError(error instanceof Error ? error.message : "Unknown error occurred");
     } finally {
       setIsCommitting(false);
+      setRunningHook(null);
     }
   };
 
@@ -62,15 +108,26 @@ const GitCommitPanel = ({ stagedFilesCount, repoPath, onCommitSuccess }: GitComm
         {error && (
           <div
             className={cn(
-              "mb-2 flex items-center gap-2 rounded border border-red-500 border-opacity-30",
+              "mb-2 flex items-start gap-2 rounded border border-red-500 border-opacity-30",
               "bg-red-900 bg-opacity-20 p-2 text-red-400 text-xs",
             )}
           >
-            <AlertCircle size={12} />
-            {error}
+            <AlertCircle size={12} className="mt-0.5 shrink-0" />
+            <span className="max-h-40 overflow-auto whitespace-pre-wrap break-words">{error}</span>
           </div>
         )}
 
+        {isCommitting && hookOutput.length > 0 && (
+          <pre
+            className={cn(
+              "mb-2 max-h-32 overflow-auto whitespace-pre-wrap break-words rounded border",
+              "border-border bg-primary-bg p-2 font-mono text-[10px] text-text-lighter",
+            )}
+          >
+            {hookOutput.join("\n")}
+          </pre>
+        )}
+
         <div className="space-y-2">
           <textarea
             value={commitMessage}
@@ -86,7 +143,22 @@ const GitCommitPanel = ({ stagedFilesCount, repoPath, onCommitSuccess }: GitComm
           />
 
           <div className="flex items-center justify-between">
-            <div className="text-[9px] text-text-lighter">Ctrl+Enter to commit</div>
+            <div className="flex items-center gap-3">
+              <div className="text-[9px] text-text-lighter">Ctrl+Enter to commit</div>
+              <label
+                className="flex cursor-pointer items-center gap-1 text-[9px] text-text-lighter"
+                title="Skip the pre-commit and commit-msg hooks (--no-verify)"
+              >
+                <input
+                  type="checkbox"
+                  checked={noVerify}
+                  onChange={(e) => setNoVerify(e.target.checked)}
+                  disabled={isCommitting}
+                  className="h-2.5 w-2.5"
+                />
+                Skip hooks
+              </label>
+            </div>
 
             <button
               onClick={handleCommit}
@@ -102,7 +174,7 @@ const GitCommitPanel = ({ stagedFilesCount, repoPath, onCommitSuccess }: GitComm
               {isCommitting ? (
                 <>
                   <div className="h-3 w-3 animate-spin rounded-full border border-white border-t-transparent"></div>
-                  Committing...
+                  {runningHook ? `Running ${runningHook}...` : "Committing..."}
                 </>
               ) : (
                 <>
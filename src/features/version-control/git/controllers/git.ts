@@ -8,6 +8,7 @@ import type {
   GitStash,
   GitStatus,
   GitTag,
+  WorkspaceRootGitStatus,
 } from "../types/git";
 import { gitDiffCache } from "./git-diff-cache";
 
@@ -21,6 +22,15 @@ export const getGitStatus = async (repoPath: string): Promise<GitStatus | null>
   }
 };
 
+export const getWorkspaceGitStatus = async (): Promise<WorkspaceRootGitStatus[]> => {
+  try {
+    return await tauriInvoke<WorkspaceRootGitStatus[]>("git_workspace_status");
+  } catch (error) {
+    console.error("Failed to get workspace git status:", error);
+    return [];
+  }
+};
+
 export const stageFile = async (repoPath: string, filePath: string): Promise<boolean> => {
   try {
     await tauriInvoke("git_add", { repoPath, filePath });
@@ -61,13 +71,23 @@ export const unstageAllFiles = async (repoPath: string): Promise<boolean> => {
   }
 };
 
-export const commitChanges = async (repoPath: string, message: string): Promise<boolean> => {
+export type CommitResult = { success: true } | { success: false; error: string };
+
+/**
+ * Runs the repository's commit hooks; `noVerify` skips `pre-commit` and `commit-msg`. Hook
+ * progress is sent as `git://hook` events, and a failed hook's output is in the error.
+ */
+export const commitChanges = async (
+  repoPath: string,
+  message: string,
+  options: { noVerify?: boolean } = {},
+): Promise<CommitResult> => {
   try {
-    await tauriInvoke("git_commit", { repoPath, message });
-    return true;
+    await tauriInvoke("git_commit", { repoPath, message, noVerify: options.noVerify });
+    return { success: true };
   } catch (error) {
     console.error("Failed to commit changes:", error);
-    return false;
+    return { success: false, error: String(error) };
   }
 };
 
@@ -11,6 +11,14 @@ export interface GitStatus {
   files: GitFile[];
 }
 
+export interface WorkspaceRootGitStatus {
+  root_name: string;
+  root_path: string;
+  repo_path: string | null;
+  status: GitStatus | null;
+  error: string | null;
+}
+
 export interface GitCommit {
   hash: string;
   message: string;
@@ -18,6 +26,24 @@ export interface GitCommit {
   date: string;
 }
 
+/** Payload of `git://hook`, sent while the hooks of a commit made from the app run. */
+export type GitHookEvent =
+  | { type: "started"; repo_path: string; hook: string }
+  | {
+      type: "output";
+      repo_path: string;
+      hook: string;
+      stream: "stdout" | "stderr";
+      line: string;
+    }
+  | {
+      type: "finished";
+      repo_path: string;
+      hook: string;
+      success: boolean;
+      exit_code: number | null;
+    };
+
 export interface GitDiffLine {
   line_type: "added" | "removed" | "context" | "header";
   content: string;
@@ -8,11 +8,37 @@ export interface FuzzyMatchItem {
   indices: number[];
 }
 
+export type FrecencyKind = "file" | "completion";
+
+export interface FrecencyScope {
+  workspace_path: string;
+  kind: FrecencyKind;
+}
+
+export interface ContextMultiplier {
+  kinds: number[];
+  matched: number;
+  other: number;
+}
+
+/**
+ * Ranking weights added to the fuzzy score. Omitted fields keep the backend defaults.
+ */
+export interface ScoringProfile {
+  kind_boosts?: Record<number, number>;
+  context_multipliers?: Record<string, ContextMultiplier>;
+  short_sort_text_boost?: number;
+  short_sort_text_len?: number;
+  frecency_weight?: number;
+}
+
 export interface FuzzyMatchRequest {
   pattern: string;
   items: string[];
   case_sensitive?: boolean;
   normalize?: boolean;
+  frecency?: FrecencyScope;
+  scoring_profile?: ScoringProfile;
 }
 
 export interface CompletionFilterRequest {
@@ -20,6 +46,8 @@ export interface CompletionFilterRequest {
   completions: CompletionItem[];
   context_word: string;
   context_type?: string;
+  workspace_path?: string;
+  scoring_profile?: ScoringProfile;
 }
 
 export interface FilteredCompletion {
@@ -44,6 +72,61 @@ export async function filterCompletions(
   return invoke<FilteredCompletion[]>("filter_completions", { request });
 }
 
+/**
+ * Remember that the user picked a file (workspace-relative path) or completion label, so
+ * later rankings in the same workspace favor it
+ */
+export async function frecencyRecord(
+  workspacePath: string,
+  kind: FrecencyKind,
+  key: string,
+): Promise<void> {
+  return invoke("frecency_record", { workspacePath, kind, key });
+}
+
+export interface FileFinderMatch {
+  path: string;
+  relative_path: string;
+  score: number;
+  indices: number[];
+}
+
+export interface FileFinderResults {
+  matches: FileFinderMatch[];
+  total_matches: number;
+  total_files: number;
+  indexing: boolean;
+}
+
+export interface FileFinderUpdatedEvent {
+  session_id: string;
+  total_files: number;
+  indexing: boolean;
+}
+
+/**
+ * Start a backend file finder session for a workspace. The backend walks the workspace and
+ * emits `file-finder://updated` while indexing and when watched files change.
+ */
+export async function fileFinderOpen(rootPath: string): Promise<string> {
+  return invoke<string>("file_finder_open", { rootPath });
+}
+
+/**
+ * Return the best matching workspace files, with highlight indices into `relative_path`
+ */
+export async function fileFinderQuery(
+  sessionId: string,
+  query: string,
+  limit?: number,
+): Promise<FileFinderResults> {
+  return invoke<FileFinderResults>("file_finder_query", { sessionId, query, limit });
+}
+
+export async function fileFinderClose(sessionId: string): Promise<void> {
+  return invoke("file_finder_close", { sessionId });
+}
+
 /**
  * Extract the current word being typed at the cursor position
  */